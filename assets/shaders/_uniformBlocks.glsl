@@ -0,0 +1,41 @@
+#pragma once
+
+// Uniform blocks shared by every shader, updated once per frame.
+// Their layouts have to match the `UniformBlock` implementations in Rust
+
+// Has to match `MAX_LIGHTS` in `ConeTracer`
+const uint MAX_LIGHTS = 8;
+
+struct Light {
+    vec3 position; // Not used by directional lights
+    vec3 direction; // Only for directional lights
+    vec3 color;
+    float intensity;
+    bool isDirectional;
+};
+
+struct ConeParameters {
+    float halfConeAngle;
+    float maxDistance;
+};
+
+// `CameraBlock`
+layout (std140, binding = 0) uniform Camera {
+    mat4 projection;
+    mat4 view;
+    vec3 eyePosition;
+};
+
+// `LightsBlock`
+layout (std140, binding = 1) uniform Lights {
+    Light lights[MAX_LIGHTS];
+    uint numberOfLights;
+};
+
+// `ConeSettings`
+layout (std140, binding = 2) uniform ConeSettings {
+    ConeParameters shadowConeParameters;
+    ConeParameters ambientOcclusionConeParameters;
+    ConeParameters diffuseConeParameters;
+    ConeParameters specularConeParameters;
+};
@@ -1,6 +1,7 @@
+#pragma once
+
 // Requires:
-// - uniform mat4 projection
-// - uniform mat4 view
+// - the `Camera` uniform block
 // - out vec4 frag_color
 
 // Takes up 8 lines -> 16 vertices
@@ -5,8 +5,7 @@ layout (line_strip, max_vertices = 30) out;
 
 out vec4 frag_nodeColor;
 
-uniform mat4 projection;
-uniform mat4 view;
+#include "assets/shaders/_uniformBlocks.glsl"
 uniform mat4 model;
 
 uniform vec3 color = vec3(1);
@@ -8,14 +8,13 @@ uniform layout(binding = 2, r32f) imageBuffer sampledColor;
 
 uniform layout(binding = 0, offset = 0) atomic_uint queriedNodesCounter;
 
+#include "assets/shaders/_uniformBlocks.glsl"
+
 // Parameters
-struct ConeParameters {
-    float halfConeAngle;
-    float maxDistance;
+// `ConeParameters`
+layout (std140, binding = 3) uniform DebugConeParameters {
+    ConeParameters parameters;
 };
-uniform ConeParameters parameters;
-uniform mat4 projection;
-uniform mat4 view;
 uniform uint voxelDimension;
 uniform uint maxOctreeLevel;
 uniform bool pointToLight;
@@ -111,14 +110,12 @@ in VertexData {
 
 out vec4 frag_color;
 
-uniform mat4 projection;
-uniform mat4 view;
+#include "assets/shaders/_uniformBlocks.glsl"
 
-struct ConeParameters {
-    float halfConeAngle;
-    float maxDistance;
+// `ConeParameters`
+layout (std140, binding = 3) uniform DebugConeParameters {
+    ConeParameters parameters;
 };
-uniform ConeParameters parameters;
 
 const float PI = 3.14159;
 
@@ -4,8 +4,7 @@
 
 layout (location = 0) in vec3 position;
 
-uniform mat4 projection;
-uniform mat4 view;
+#include "assets/shaders/_uniformBlocks.glsl"
 uniform mat4 model;
 
 mat4 canonizationMatrix = projection * view * model;
@@ -33,8 +32,7 @@ uniform usampler2D eyeViewMap;
 uniform sampler2D eyeViewMapNormals;
 uniform uint voxelDimension;
 
-uniform mat4 projection;
-uniform mat4 view;
+#include "assets/shaders/_uniformBlocks.glsl"
 
 #include "./_drawCone.glsl"
 #include "assets/shaders/octree/_helpers.glsl"
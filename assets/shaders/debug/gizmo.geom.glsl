@@ -7,8 +7,7 @@ in vec3 geom_position[];
 
 out vec4 frag_nodeColor;
 
-uniform mat4 projection;
-uniform mat4 view;
+#include "assets/shaders/_uniformBlocks.glsl"
 uniform mat4 model;
 
 uniform vec3 color;
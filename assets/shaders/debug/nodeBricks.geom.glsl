@@ -15,8 +15,7 @@ out flat vec4 frag_nodeColor;
 out vec3 frag_normal;
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+#include "assets/shaders/_uniformBlocks.glsl"
 
 uniform uint bricksToShow;
 
@@ -56,10 +55,10 @@ vec4 showProp(ivec3 coordinates, uint type) {
     if (type == BY_NONE) {
       return vec4(0); // Will be discarded
     } else if (type == BY_PHOTONS) {
-      vec3 normalizedCoordinates = normalizedFromIntCoordinates(coordinates, 384.0);
+      vec3 normalizedCoordinates = normalizedFromIntCoordinates(coordinates, float(BRICK_POOL_RESOLUTION));
       return getAnisotropicIrradiance(normalizedCoordinates, colorDirection);
     } else if (type == BY_COLOR) {
-      vec3 normalizedCoordinates = normalizedFromIntCoordinates(coordinates, 384.0);
+      vec3 normalizedCoordinates = normalizedFromIntCoordinates(coordinates, float(BRICK_POOL_RESOLUTION));
       return getAnisotropicColor(normalizedCoordinates, colorDirection);
     }
 }
@@ -9,8 +9,7 @@ in uint geom_nodeID[];
 out vec4 frag_nodeColor;
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+#include "assets/shaders/_uniformBlocks.glsl"
 
 uniform uint voxelDimension;
 
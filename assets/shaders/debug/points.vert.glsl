@@ -3,8 +3,7 @@
 layout(location = 0) in vec3 point;
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+#include "assets/shaders/_uniformBlocks.glsl"
 
 mat4 canonizationMatrix = projection * view * model;
 
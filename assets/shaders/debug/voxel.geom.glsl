@@ -12,8 +12,7 @@ out vec4 frag_nodeColor;
 #include "assets/shaders/octree/_drawCube.glsl"
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+#include "assets/shaders/_uniformBlocks.glsl"
 
 mat4 canonizationMatrix = projection * view * model;
 
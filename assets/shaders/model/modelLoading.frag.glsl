@@ -15,7 +15,6 @@ uniform vec3 materialDiffuse;
 
 struct Material {
     vec3 color;
-    float diffuse;
     float specular;
 };
 uniform Material material;
@@ -9,8 +9,7 @@ out vec3 Normal;
 
 uniform mat4 modelNormalizationMatrix;
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+#include "assets/shaders/_uniformBlocks.glsl"
 
 void main()
 {
@@ -9,7 +9,7 @@ in VS_OUT {
 
 const float MAGNITUDE = 0.1;
   
-uniform mat4 projection;
+#include "assets/shaders/_uniformBlocks.glsl"
 
 void GenerateLine(int index)
 {
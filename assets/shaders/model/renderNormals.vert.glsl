@@ -7,7 +7,7 @@ out VS_OUT {
     vec3 normal;
 } vs_out;
 
-uniform mat4 view;
+#include "assets/shaders/_uniformBlocks.glsl"
 uniform mat4 model;
 
 void main() {
@@ -1,3 +1,5 @@
+#pragma once
+
 // Requires:
 // - uniform sampler3D brickPoolColorsX
 // - uniform sampler3D brickPoolColorsXNeg
@@ -1,3 +1,5 @@
+#pragma once
+
 // Requires:
 // - uniform sampler3D brickPoolIrradianceX
 // - uniform sampler3D brickPoolIrradianceXNeg
@@ -1,4 +1,6 @@
-int brickPoolResolution = 384; // TODO: Get this from a uniform. No hardcoding.
+#pragma once
+
+int brickPoolResolution = BRICK_POOL_RESOLUTION;
 
 ivec3 calculateBrickCoordinates(int nodeID) {
     ivec3 coordinates = ivec3(0);
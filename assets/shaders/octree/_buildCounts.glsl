@@ -0,0 +1,21 @@
+#pragma once
+
+// Dependencies:
+// - uniform uimageBuffer (r32ui) buildCounts
+
+// Layout of `buildCounts`, has to match `BuildCounts`.
+// Indirect dispatches take four uints: the work groups in x, y and z and how many invocations they are for
+const int NODES_IN_LEVEL_DISPATCH = 0;
+const int CHILDREN_OF_PREVIOUS_LEVEL_DISPATCH = 4;
+const int VOXEL_FRAGMENTS_DISPATCH = 8;
+const int BORDER_VOXEL_FRAGMENTS_DISPATCH = 12;
+const int NEXT_FREE_NODE = 16;
+const int BORDER_VOXEL_FRAGMENTS = 17;
+
+// Start of the indirect dispatch running, set by `BuildCounts::dispatch`
+uniform uint indirectDispatch;
+
+// The extra invocations of the last group have to return
+uint dispatchedInvocations() {
+    return imageLoad(buildCounts, int(indirectDispatch) + 3).r;
+}
@@ -1,3 +1,5 @@
+#pragma once
+
 // Requires:
 // - uniform uint voxelDimension
 // - uniform uint maxOctreeLevel
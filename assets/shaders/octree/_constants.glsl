@@ -1,6 +1,9 @@
+#pragma once
+
 const int CHILDREN_PER_NODE = 8;
 const uint NODE_FLAG_VALUE = 0x80000000;
-const int WORKING_GROUP_SIZE = 64; // Working group size in x
+// WORKING_GROUP_SIZE, BRICK_SIZE, BRICK_POOL_RESOLUTION, VOXEL_DIMENSION and OCTREE_LEVELS
+// are defined from the config, see `Config::shader_defines`
 const uvec3 CHILD_OFFSETS[8] = {
     uvec3(0, 0, 0),
     uvec3(1, 0, 0),
@@ -0,0 +1,34 @@
+#pragma once
+
+// Dependencies:
+// - uniform uimageBuffer (r32ui) dirtyNodes
+
+const uint NODE_CLEAN = 0;
+const uint NODE_CHANGED = 1; // Its own values changed
+// Shares borders with or reads from a changed node.
+// They are flagged one axis at a time, with `NODE_NEXT_TO_CHANGED + axis`
+const uint NODE_NEXT_TO_CHANGED = 2;
+
+// Only dirty nodes get their bricks written.
+// Every node is dirty except when updating the light after one light moved
+bool isDirty(int nodeID) {
+    return imageLoad(dirtyNodes, nodeID).r != NODE_CLEAN;
+}
+
+// A border shared with a node that isn't dirty was already transferred,
+// so it keeps the value that node has
+vec4 keepCleanBorder(
+    vec4 finalValue,
+    vec4 borderValue,
+    bool isNodeDirty,
+    vec4 neighborBorderValue,
+    bool isNeighborDirty
+) {
+    if (!isNeighborDirty) {
+        return neighborBorderValue;
+    }
+    if (!isNodeDirty) {
+        return borderValue;
+    }
+    return finalValue;
+}
@@ -1,3 +1,5 @@
+#pragma once
+
 /// 5 vertices
 void createZPositiveFace(vec4 center, vec3 dimensions, mat4 canonizationMatrix, vec4 color) {
     vec4 position;
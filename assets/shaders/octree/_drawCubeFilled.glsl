@@ -1,3 +1,5 @@
+#pragma once
+
 // Requires:
 // out vec4 frag_nodeColor
 
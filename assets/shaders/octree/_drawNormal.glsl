@@ -1,3 +1,5 @@
+#pragma once
+
 // Requires:
 // - mat4 projection
 // - mat4 view
@@ -1,3 +1,5 @@
+#pragma once
+
 // requires
 // - uniform layout(binding = x, r32ui) readonly uimageBuffer levelStartIndices;
 // - uniform layout(binding = y, r32ui) readonly uimageBuffer borderLevelStartIndices;
@@ -1,3 +1,5 @@
+#pragma once
+
 const float fourThirds = 1.33333333;
 const float twoThirds = 0.6666666;
 
@@ -0,0 +1,16 @@
+#pragma once
+
+// Dispatches with more work groups than GL_MAX_COMPUTE_WORK_GROUP_COUNT are split in
+// several batches by `Shader::dispatch_invocations`, which sets where each one starts
+uniform uvec3 invocationOffset;
+
+// Replaces `gl_GlobalInvocationID`, counting from the first batch
+uvec3 globalInvocationID() {
+    return invocationOffset + gl_GlobalInvocationID;
+}
+
+// Index of the invocation in dispatches with one invocation per item, like per node.
+// Indirect dispatches can't be split in batches, so their groups past the maximum in x continue in y.
+// A macro because `gl_WorkGroupSize` is only declared after the `local_size` layout
+#define invocationIndex() \
+    (globalInvocationID().x + globalInvocationID().y * gl_NumWorkGroups.x * gl_WorkGroupSize.x)
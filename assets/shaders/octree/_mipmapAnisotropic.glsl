@@ -1,3 +1,5 @@
+#pragma once
+
 // Requires:
 // - _constants
 // - _brickCoordinates
@@ -1,3 +1,5 @@
+#pragma once
+
 // Requires:
 // - uniform image3D brickPoolValues
 // - uniform uimageBuffer nodePool
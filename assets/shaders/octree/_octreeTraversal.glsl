@@ -1,6 +1,9 @@
+#pragma once
+
+#include "./_constants.glsl"
+#include "./_traversalHelpers.glsl"
+
 // Dependencies:
-// - _constants
-// - _traversalHelpers
 // - uniform (r32ui) nodePool
 
 int traverseOctree(
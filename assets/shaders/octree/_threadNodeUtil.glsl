@@ -1,5 +1,9 @@
+#pragma once
+
+#include "./_constants.glsl"
+#include "./_invocation.glsl"
+
 // Dependencies:
-// - _constants
 // - uniform uimageBuffer (r32ui) levelStartIndices
 // - uniform uint octreeLevel
 
@@ -10,7 +14,7 @@ int getThreadNode() {
     int nextLevelStart = int(imageLoad(levelStartIndices, int(octreeLevel + 1)).r);
     memoryBarrier();
 
-    index = uint(levelStart) + uint(gl_GlobalInvocationID.x);
+    index = uint(levelStart) + invocationIndex();
 
     if (index >= uint(nextLevelStart)) {
         return NODE_NOT_FOUND;
@@ -1,3 +1,5 @@
+#pragma once
+
 bool withinSecondHalf(float min, float halfNodeSize, float coordinatePosition) {
   // TODO: Is this wrong?
   return coordinatePosition >= min + halfNodeSize;
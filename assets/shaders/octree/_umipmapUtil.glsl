@@ -1,3 +1,5 @@
+#pragma once
+
 // Requires:
 // - uniform uimage3D brickPoolPhotons
 // - uniform uimageBuffer nodePool
@@ -1,16 +1,17 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 
 layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
 
-uniform int firstNodeInLevel;
-uniform int firstFreeNode;
+uniform uint octreeLevel; // Of the nodes whose children are allocated
 
 uniform layout(binding = 0, r32ui) uimageBuffer nodePool;
-uniform layout(binding = 1, r32f) imageBuffer debug;
+uniform layout(binding = 1, r32ui) uimageBuffer buildCounts;
+uniform layout(binding = 2, r32ui) readonly uimageBuffer levelStartIndices;
 
-uniform layout(binding = 0, offset = 0) atomic_uint allocatedNodesCounter;
+#include "./_buildCounts.glsl"
 
 bool isNodeFlagged(uint node) {
     return (node & NODE_FLAG_VALUE) != 0;
@@ -18,15 +19,18 @@ bool isNodeFlagged(uint node) {
 
 void main()
 {
-    uint allocatedNodeIndex;
-    uint threadIndex = gl_GlobalInvocationID.x;
-    int parentNodeIndex = firstNodeInLevel * CHILDREN_PER_NODE + int(threadIndex);
+    uint threadIndex = invocationIndex();
+    if (threadIndex >= dispatchedInvocations()) {
+        return;
+    }
+
+    uint levelStart = imageLoad(levelStartIndices, int(octreeLevel)).r;
+    int parentNodeIndex = int(levelStart * CHILDREN_PER_NODE + threadIndex);
     uint parentNode = imageLoad(nodePool, parentNodeIndex).r;
-    imageStore(debug, int(parentNodeIndex), vec4(float(parentNode), 0, 0, 0));
 
     if (isNodeFlagged(parentNode)) {
-        allocatedNodeIndex = atomicCounterIncrement(allocatedNodesCounter);
-        allocatedNodeIndex += firstFreeNode;
+        // Nodes are allocated one after the other from the first free one
+        uint allocatedNodeIndex = imageAtomicAdd(buildCounts, NEXT_FREE_NODE, 1);
 
         imageStore(nodePool, parentNodeIndex, uvec4(allocatedNodeIndex, 0, 0, 0));
     }
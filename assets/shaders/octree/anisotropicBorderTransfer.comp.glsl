@@ -1,6 +1,7 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 #include "./_helpers.glsl"
 
 layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
@@ -8,6 +9,7 @@ layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) i
 uniform layout(binding = 0, r32ui) uimageBuffer nodePoolNeighbors; // Will have different axis
 uniform layout(binding = 1, rgba8) image3D brickPoolValues; // Will be different
 uniform layout(binding = 2, r32ui) uimageBuffer levelStartIndices;
+uniform layout(binding = 7, r32ui) readonly uimageBuffer dirtyNodes;
 
 struct Direction {
     int axis;
@@ -20,6 +22,7 @@ uniform uint octreeLevel;
 uniform uint voxelDimension;
 
 #include "./_threadNodeUtil.glsl"
+#include "./_dirtyNodes.glsl"
 #include "assets/shaders/octree/_brickCoordinates.glsl"
 
 void main() {
@@ -33,6 +36,12 @@ void main() {
     if (neighborAddress == 0) {
         return;
     }
+
+    bool isNodeDirty = isDirty(nodeAddress);
+    bool isNeighborDirty = isDirty(neighborAddress);
+    if (!isNodeDirty && !isNeighborDirty) {
+        return;
+    }
     
     ivec3 brickAddress = calculateBrickCoordinates(nodeAddress);
     ivec3 neighborBrickAddress = calculateBrickCoordinates(neighborAddress);
@@ -57,6 +66,7 @@ void main() {
                 } else {
                     finalValue = 0.5 * (borderValue + neighborBorderValue); // We average partial averages
                 }
+                finalValue = keepCleanBorder(finalValue, borderValue, isNodeDirty, neighborBorderValue, isNeighborDirty);
                 imageStore(brickPoolValues, brickAddress + offset, finalValue);
                 imageStore(brickPoolValues, neighborBrickAddress + neighborOffset, finalValue);
             }
@@ -79,6 +89,7 @@ void main() {
                 } else {
                     finalValue = 0.5 * (borderValue + neighborBorderValue); // We average partial averages
                 }
+                finalValue = keepCleanBorder(finalValue, borderValue, isNodeDirty, neighborBorderValue, isNeighborDirty);
                 imageStore(brickPoolValues, brickAddress + offset, finalValue);
                 imageStore(brickPoolValues, neighborBrickAddress + neighborOffset, finalValue);
             }
@@ -101,6 +112,7 @@ void main() {
                 } else {
                     finalValue = 0.5 * (borderValue + neighborBorderValue); // We average partial averages
                 }
+                finalValue = keepCleanBorder(finalValue, borderValue, isNodeDirty, neighborBorderValue, isNeighborDirty);
                 imageStore(brickPoolValues, brickAddress + offset, finalValue);
                 imageStore(brickPoolValues, neighborBrickAddress + neighborOffset, finalValue);
             }
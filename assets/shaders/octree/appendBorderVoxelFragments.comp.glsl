@@ -1,23 +1,21 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 
 layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
 
 uniform layout(binding = 0, rgb10_a2ui) writeonly uimageBuffer borderVoxelFragments;
 uniform layout(binding = 1, rgb10_a2ui) readonly uimageBuffer nodePositions;
 uniform layout(binding = 2, r32ui) readonly uimageBuffer nodePoolNeighbors[HALF_NEIGHBORS];
-uniform layout(binding = 5, r32f) imageBuffer debug;
-
-uniform layout(binding = 0, offset = 0) atomic_uint nextVoxelFragmentCounter;
+uniform layout(binding = 5, r32ui) readonly uimageBuffer levelStartIndices;
+uniform layout(binding = 6, r32ui) uimageBuffer buildCounts;
 
 uniform uint offsetTexture;
 uniform uint sideOffsetTexture;
 uniform uint octreeLevel;
 uniform uint maxOctreeLevel;
 uniform uint voxelDimension;
-uniform uint levelStart;
-uniform uint nextLevelStart;
 
 const ivec4 NEIGHBOR_OFFSETS[6] = {
     ivec4(2, 0, 0, 0),
@@ -42,18 +40,15 @@ const uint DIAGONAL_DIRECTION_MAPPING[6] = {
 };
 
 #include "./_helpers.glsl"
-#include "./_threadNodeUtilNoTexture.glsl"
+#include "./_threadNodeUtil.glsl"
+#include "./_buildCounts.glsl"
 
 void save(uvec4 borderVoxelFragmentPosition) {
     if (
         !isOutsideRange(borderVoxelFragmentPosition.xyz, ivec3(0), ivec3(voxelDimension - 1))
     ) {
-      uint nextVoxelFragment = atomicCounterIncrement(nextVoxelFragmentCounter);
+      uint nextVoxelFragment = imageAtomicAdd(buildCounts, BORDER_VOXEL_FRAGMENTS, 1);
       imageStore(borderVoxelFragments, int(nextVoxelFragment), borderVoxelFragmentPosition);
-
-      //imageStore(debug, int(nextVoxelFragment * 3 + 3), vec4(float(borderVoxelFragmentPosition.x), 0, 0, 0));
-      //imageStore(debug, int(nextVoxelFragment * 3 + 4), vec4(float(borderVoxelFragmentPosition.y), 0, 0, 0));
-      //imageStore(debug, int(nextVoxelFragment * 3 + 5), vec4(float(borderVoxelFragmentPosition.z), 0, 0, 0));
     }
 }
 
@@ -71,12 +66,8 @@ void main() {
     }
 
     ivec4 nodePosition = ivec4(imageLoad(nodePositions, nodeID));
-    //imageStore(debug, int(0), vec4(float(nodePosition.x), 0, 0, 0));
-    //imageStore(debug, int(1), vec4(float(nodePosition.y), 0, 0, 0));
-    //imageStore(debug, int(2), vec4(float(nodePosition.z), 0, 0, 0));
 
     uint baseNeighborID = imageLoad(nodePoolNeighbors[0], nodeID).r;
-    //imageStore(debug, int(0), vec4(float(baseNeighborID), 0, 0, 0));
 
     if (baseNeighborID == 0) {
         uvec4 borderVoxelBaseFragmentPosition = ivec4(nodePosition) + getNeighborOffset(offsetTexture);
@@ -1,6 +1,7 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 
 layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
 
@@ -12,7 +13,7 @@ uniform uint voxelDimension;
 #include "assets/shaders/octree/_brickCoordinates.glsl"
 
 void main() {
-    int nodeID = int(gl_GlobalInvocationID.x);
+    int nodeID = int(globalInvocationID().x);
     ivec3 brickCoordinates = calculateBrickCoordinates(nodeID);
 	vec4 clearValue = vec4(0);
     for (uint x = 0; x < 3; x++) {
@@ -0,0 +1,33 @@
+#version 460 core
+
+#include "./_constants.glsl"
+#include "./_invocation.glsl"
+
+layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
+
+uniform layout(binding = 0, r32ui) writeonly uimage3D brickPoolColorsRaw;
+uniform layout(binding = 1, rgba32f) writeonly image3D brickPoolNormals;
+uniform layout(binding = 2, r32ui) readonly uimageBuffer dirtyNodes;
+
+#include "./_dirtyNodes.glsl"
+#include "./_brickCoordinates.glsl"
+
+// Same as clearing the raw colors and normals before building the octree,
+// but only for the bricks of dirty nodes, so their voxel fragments can be written again
+void main() {
+    int nodeID = int(globalInvocationID().x);
+    if (!isDirty(nodeID)) {
+        return;
+    }
+
+    ivec3 brickCoordinates = calculateBrickCoordinates(nodeID);
+    for (int x = 0; x < 3; x++) {
+        for (int y = 0; y < 3; y++) {
+            for (int z = 0; z < 3; z++) {
+                ivec3 coordinates = brickCoordinates + ivec3(x, y, z);
+                imageStore(brickPoolColorsRaw, coordinates, uvec4(0));
+                imageStore(brickPoolNormals, coordinates, vec4(0));
+            }
+        }
+    }
+}
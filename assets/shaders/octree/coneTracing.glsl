@@ -25,38 +25,14 @@ in VertexData {
 
 uniform layout(binding = 0, r32ui) readonly uimageBuffer nodePool;
 
-struct PointLight {
-    vec3 position;
-    vec3 color;
-    float intensity;
-};
-
-struct DirectionalLight {
-    vec3 direction;
-    vec3 color;
-    float intensity;
-};
+#include "assets/shaders/_uniformBlocks.glsl"
 
 // Scalar attributes
 uniform uint voxelDimension;
 uniform uint maxOctreeLevel;
-uniform DirectionalLight directionalLight;
-uniform PointLight pointLight;
-uniform bool isDirectional;
 uniform float shininess;
 uniform float photonPower;
 uniform bool showIndirectLight;
-uniform vec3 eyePosition;
-
-// Cone parameters
-struct ConeParameters {
-    float halfConeAngle;
-    float maxDistance;
-};
-uniform ConeParameters shadowConeParameters;
-uniform ConeParameters ambientOcclusionConeParameters;
-uniform ConeParameters diffuseConeParameters;
-uniform ConeParameters specularConeParameters;
 
 // Boolean toggles
 uniform bool shouldShowColor;
@@ -94,6 +70,7 @@ const float PI = 3.14159;
 vec4 gatherIndirectLight(vec3 position, vec3 normal, vec3 tangent, ConeParameters parameters);
 vec4 gatherSpecularIndirectLight(vec3 position, vec3 eyeDirection, vec3 normal, ConeParameters parameters);
 float traceShadowCone(vec3 origin, vec3 direction, float targetDistance, ConeParameters parameters);
+vec3 calculateDirectLight(Light light, vec3 position, vec3 normal, float specularFactor);
 
 vec3 toVoxelSpace(vec3 positionWorldSpace) {
   return (positionWorldSpace + vec3(1)) / 2.0;
@@ -138,32 +115,12 @@ void main() {
         specularIndirectLight = specularFactor * gatherSpecularIndirectLight(positionVoxelSpace, eyeDirection, normal, specularConeParameters).rgb;
     }
 
-    // float h = normalize((lightDirection - view);
-    // float specular = pow(max(0.0, dot(normal, h)), shininess);
-    float visibility = 1.0;
-    vec3 lightVector;
-    float lightIntensity;
-    if (isDirectional) {
-        // We reverse the direction of the light since we care about the direction
-        // TOWARDS the light, not FROM it.
-        lightVector = -directionalLight.direction;
-        lightIntensity = directionalLight.intensity;
-    } else {
-        lightVector = toVoxelSpace(pointLight.position) - positionVoxelSpace;
-        lightIntensity = pointLight.intensity;
+    vec3 directLight = vec3(0);
+    if (shouldShowDirect) {
+        for (uint i = 0; i < numberOfLights; i++) {
+            directLight += calculateDirectLight(lights[i], positionVoxelSpace, normal, specularFactor);
+        }
     }
-    vec3 lightDirection = normalize(lightVector);
-    float lightDistance = length(lightVector);
-    visibility = traceShadowCone(positionVoxelSpace, lightDirection, lightDistance, shadowConeParameters);
-    float lightAngle = dot(normal, lightDirection);
-    float diffuse = max(lightAngle, 0.0);
-    // Parameters for the attenuation function
-    float c1 = 1.0;
-    float c2 = 0.09;
-    float c3 = 0.032;
-    float attenuation = c1 + c2 * lightDistance + c3 * lightDistance * lightDistance;
-    // TODO: This should be the diffuse factor, not 1 minus the specular
-    vec3 directLight = lightIntensity * (1 - specularFactor) / attenuation * vec3(diffuse);
 
     bool shouldShowOnlyColor = (
         !shouldShowDirect &&
@@ -175,7 +132,7 @@ void main() {
     vec4 finalImage = vec4(0);
 
     if (shouldShowDirect) {
-        finalImage += vec4(visibility * directLight * color.rgb, 1.0);
+        finalImage += vec4(directLight * color.rgb, 1.0);
     }
     if (shouldShowIndirect) {
         finalImage += vec4(indirectLight * color.rgb, 1.0);
@@ -195,6 +152,38 @@ void main() {
     outColor = vec4(finalImage.xyz, 1.0);
 }
 
+// Diffuse light arriving from `light`, already attenuated and shadowed
+vec3 calculateDirectLight(Light light, vec3 position, vec3 normal, float specularFactor) {
+    // float h = normalize((lightDirection - view);
+    // float specular = pow(max(0.0, dot(normal, h)), shininess);
+    vec3 lightDirection;
+    float lightDistance;
+    float attenuation;
+    if (light.isDirectional) {
+        // We reverse the direction of the light since we care about the direction
+        // TOWARDS the light, not FROM it.
+        lightDirection = normalize(-light.direction);
+        // The light is outside of the scene, so the shadow cone has to go
+        // through all of it. It isn't attenuated either.
+        lightDistance = sqrt(3.0);
+        attenuation = 1.0;
+    } else {
+        vec3 lightVector = toVoxelSpace(light.position) - position;
+        lightDirection = normalize(lightVector);
+        lightDistance = length(lightVector);
+        // Parameters for the attenuation function
+        float c1 = 1.0;
+        float c2 = 0.09;
+        float c3 = 0.032;
+        attenuation = c1 + c2 * lightDistance + c3 * lightDistance * lightDistance;
+    }
+    float visibility = traceShadowCone(position, lightDirection, lightDistance, shadowConeParameters);
+    float lightAngle = dot(normal, lightDirection);
+    float diffuse = max(lightAngle, 0.0);
+    // TODO: This should be the diffuse factor, not 1 minus the specular
+    return visibility * light.intensity * (1 - specularFactor) / attenuation * diffuse * light.color;
+}
+
 float traceShadowCone(vec3 origin, vec3 direction, float targetDistance, ConeParameters parameters) {
     // TODO: Possibly add a little bit in the direction of the normal
     float occlusion = coneTrace(origin, direction, parameters.halfConeAngle, targetDistance).a;
@@ -0,0 +1,43 @@
+#version 460 core
+
+#include "./_constants.glsl"
+#include "./_invocation.glsl"
+
+layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
+
+uniform layout(binding = 0, r32ui) uimageBuffer dirtyNodes;
+uniform layout(binding = 1, r32ui) readonly uimageBuffer levelStartIndices;
+uniform layout(binding = 2, r32ui) readonly uimageBuffer neighborsPositive;
+uniform layout(binding = 3, r32ui) readonly uimageBuffer neighborsNegative;
+
+uniform uint octreeLevel;
+uniform uint axis;
+
+#include "./_threadNodeUtil.glsl"
+#include "./_dirtyNodes.glsl"
+
+// Nodes flagged by this same pass have `NODE_NEXT_TO_CHANGED + axis` and don't spread
+bool shouldSpread(uint neighborID) {
+    if (neighborID == 0) {
+        return false;
+    }
+    uint neighborState = imageLoad(dirtyNodes, int(neighborID)).r;
+    return neighborState != NODE_CLEAN && neighborState < NODE_NEXT_TO_CHANGED + axis;
+}
+
+// Neighbors of changed nodes share border voxels with them and mipmap their children,
+// so they have to be written again too.
+// Running it once per axis also flags the diagonal neighbors, that share edges and corners
+void main() {
+    int nodeID = getThreadNode();
+    if (nodeID == NODE_NOT_FOUND || isDirty(nodeID)) {
+        return;
+    }
+
+    if (
+        shouldSpread(imageLoad(neighborsPositive, nodeID).r)
+        || shouldSpread(imageLoad(neighborsNegative, nodeID).r)
+    ) {
+        imageStore(dirtyNodes, nodeID, uvec4(NODE_NEXT_TO_CHANGED + axis, 0, 0, 0));
+    }
+}
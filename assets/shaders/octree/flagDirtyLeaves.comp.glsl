@@ -0,0 +1,35 @@
+#version 460 core
+
+#include "./_constants.glsl"
+#include "./_invocation.glsl"
+
+layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
+
+uniform layout(binding = 0, r32ui) uimageBuffer litNodes;
+uniform layout(binding = 1, r32ui) uimageBuffer dirtyNodes;
+uniform layout(binding = 2, r32ui) readonly uimageBuffer levelStartIndices;
+
+uniform uint octreeLevel;
+uniform uint lightMask; // Bit of the light in `litNodes`
+uniform bool shouldForgetLight;
+
+#include "./_threadNodeUtil.glsl"
+#include "./_dirtyNodes.glsl"
+
+// Leaves the light reaches change
+void main() {
+    int nodeID = getThreadNode();
+    if (nodeID == NODE_NOT_FOUND) {
+        return;
+    }
+
+    uint lights = imageLoad(litNodes, nodeID).r;
+    if ((lights & lightMask) == 0) {
+        return;
+    }
+
+    imageStore(dirtyNodes, nodeID, uvec4(NODE_CHANGED, 0, 0, 0));
+    if (shouldForgetLight) {
+        imageStore(litNodes, nodeID, uvec4(lights & ~lightMask, 0, 0, 0));
+    }
+}
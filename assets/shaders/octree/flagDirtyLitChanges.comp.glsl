@@ -0,0 +1,29 @@
+#version 460 core
+
+#include "./_constants.glsl"
+#include "./_invocation.glsl"
+
+layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
+
+uniform layout(binding = 0, r32ui) readonly uimageBuffer litNodes;
+uniform layout(binding = 1, r32ui) readonly uimageBuffer previousLitNodes;
+uniform layout(binding = 2, r32ui) uimageBuffer dirtyNodes;
+uniform layout(binding = 3, r32ui) readonly uimageBuffer levelStartIndices;
+
+uniform uint octreeLevel;
+
+#include "./_threadNodeUtil.glsl"
+#include "./_dirtyNodes.glsl"
+
+// Leaves a light reaches now and didn't before, or the other way around, change.
+// That's where the shadows of dynamic objects were and are
+void main() {
+    int nodeID = getThreadNode();
+    if (nodeID == NODE_NOT_FOUND) {
+        return;
+    }
+
+    if (imageLoad(litNodes, nodeID).r != imageLoad(previousLitNodes, nodeID).r) {
+        imageStore(dirtyNodes, nodeID, uvec4(NODE_CHANGED, 0, 0, 0));
+    }
+}
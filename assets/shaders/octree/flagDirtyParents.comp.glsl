@@ -0,0 +1,31 @@
+#version 460 core
+
+#include "./_constants.glsl"
+#include "./_invocation.glsl"
+
+layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
+
+uniform layout(binding = 0, r32ui) readonly uimageBuffer nodePool;
+uniform layout(binding = 1, r32ui) uimageBuffer dirtyNodes;
+uniform layout(binding = 2, r32ui) readonly uimageBuffer levelStartIndices;
+
+uniform uint octreeLevel;
+
+#include "./_threadNodeUtil.glsl"
+#include "./_dirtyNodes.glsl"
+
+// Nodes are mipmapped from their children, so they change if any child is dirty
+void main() {
+    int nodeID = getThreadNode();
+    if (nodeID == NODE_NOT_FOUND) {
+        return;
+    }
+
+    for (int i = 0; i < CHILDREN_PER_NODE; i++) {
+        int childID = int(imageLoad(nodePool, nodeID * CHILDREN_PER_NODE + i).r);
+        if (childID != 0 && isDirty(childID)) {
+            imageStore(dirtyNodes, nodeID, uvec4(NODE_CHANGED, 0, 0, 0));
+            return;
+        }
+    }
+}
@@ -0,0 +1,44 @@
+#version 460 core
+
+#include "./_constants.glsl"
+#include "./_invocation.glsl"
+#include "./_helpers.glsl"
+
+layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
+
+uniform layout(binding = 0, rgb10_a2ui) readonly uimageBuffer voxelPositions;
+uniform layout(binding = 1, r32ui) readonly uimageBuffer nodePool;
+uniform layout(binding = 2, r32ui) uimageBuffer dirtyNodes;
+
+uniform uint voxelDimension;
+uniform uint octreeLevel;
+uniform uint numberOfVoxelFragments;
+
+#include "./_traversalHelpers.glsl"
+#include "./_octreeTraversal.glsl"
+#include "./_dirtyNodes.glsl"
+
+// Leaves with voxel fragments of dynamic objects change when they move
+void main() {
+    const uint threadIndex = globalInvocationID().x;
+    if (threadIndex >= numberOfVoxelFragments) {
+        return;
+    }
+
+    uvec3 voxelPosition = imageLoad(voxelPositions, int(threadIndex)).xyz;
+    vec3 normalizedVoxelPosition = normalizedFromIntCoordinates(voxelPosition, float(voxelDimension));
+
+    float halfNodeSize;
+    vec3 nodeCoordinates;
+    int nodeID = traverseOctree(
+        normalizedVoxelPosition,
+        octreeLevel,
+        nodeCoordinates,
+        halfNodeSize
+    );
+    if (nodeID == NODE_NOT_FOUND) {
+        return;
+    }
+
+    imageStore(dirtyNodes, nodeID, uvec4(NODE_CHANGED, 0, 0, 0));
+}
@@ -1,25 +1,28 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 #include "./_helpers.glsl"
 
 layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
 
 uniform uint octreeLevel;
-uniform uint numberOfVoxelFragments;
 uniform uint voxelDimension;
 
 uniform layout(binding = 0, rgb10_a2ui) uimageBuffer voxelPositions;
 uniform layout(binding = 1, r32ui) uimageBuffer nodePool;
+uniform layout(binding = 2, r32ui) readonly uimageBuffer buildCounts;
+
+#include "./_buildCounts.glsl"
 
 #include "./_traversalHelpers.glsl"
 #include "./_octreeTraversal.glsl"
 
 void main()
 {
-    const uint threadIndex = gl_GlobalInvocationID.x;
+    const uint threadIndex = invocationIndex();
 
-    if (threadIndex >= numberOfVoxelFragments) {
+    if (threadIndex >= dispatchedInvocations()) {
         return;
     }
 
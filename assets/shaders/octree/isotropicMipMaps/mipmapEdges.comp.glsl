@@ -1,6 +1,7 @@
 #version 460 core
 
 #include "assets/shaders/octree/_constants.glsl"
+#include "assets/shaders/octree/_invocation.glsl"
 
 layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
 
@@ -1,6 +1,7 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 #include "./_helpers.glsl"
 
 layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
@@ -8,12 +9,14 @@ layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) i
 uniform layout(binding = 0, r32ui) uimageBuffer nodePoolNeighbors; // Will have different axis
 uniform layout(binding = 1, rgba8) image3D brickPoolValues; // Will be different
 uniform layout(binding = 2, r32ui) uimageBuffer levelStartIndices;
+uniform layout(binding = 7, r32ui) readonly uimageBuffer dirtyNodes;
 
 uniform uint axis;
 uniform uint octreeLevel;
 
 #include "./_brickCoordinates.glsl"
 #include "./_threadNodeUtil.glsl"
+#include "./_dirtyNodes.glsl"
 
 vec4 calculateBorderFinalValue(vec4 borderValue, vec4 neighborBorderValue) {
   // We don't want border nodes to affect the geometry nodes' color
@@ -39,6 +42,12 @@ void main() {
         return;
     }
 
+    bool isNodeDirty = isDirty(nodeAddress);
+    bool isNeighborDirty = isDirty(neighborAddress);
+    if (!isNodeDirty && !isNeighborDirty) {
+        return;
+    }
+
     ivec3 brickAddress = calculateBrickCoordinates(nodeAddress);
     ivec3 neighborBrickAddress = calculateBrickCoordinates(neighborAddress);
 
@@ -53,6 +62,7 @@ void main() {
                 memoryBarrier();
 
                 vec4 finalValue = calculateBorderFinalValue(borderValue, neighborBorderValue);
+                finalValue = keepCleanBorder(finalValue, borderValue, isNodeDirty, neighborBorderValue, isNeighborDirty);
                 imageStore(brickPoolValues, brickAddress + offset, finalValue);
                 imageStore(brickPoolValues, neighborBrickAddress + neighborOffset, finalValue);
             }
@@ -70,6 +80,7 @@ void main() {
                 memoryBarrier();
 
                 vec4 finalValue = calculateBorderFinalValue(borderValue, neighborBorderValue);
+                finalValue = keepCleanBorder(finalValue, borderValue, isNodeDirty, neighborBorderValue, isNeighborDirty);
                 imageStore(brickPoolValues, brickAddress + offset, finalValue);
                 imageStore(brickPoolValues, neighborBrickAddress + neighborOffset, finalValue);
             }
@@ -87,6 +98,7 @@ void main() {
                 memoryBarrier();
 
                 vec4 finalValue = calculateBorderFinalValue(borderValue, neighborBorderValue);
+                finalValue = keepCleanBorder(finalValue, borderValue, isNodeDirty, neighborBorderValue, isNeighborDirty);
                 imageStore(brickPoolValues, brickAddress + offset, finalValue);
                 imageStore(brickPoolValues, neighborBrickAddress + neighborOffset, finalValue);
             }
@@ -10,7 +10,9 @@ out VertexData {
 
 uniform mat4 model;
 uniform mat4 modelNormalizationMatrix;
+// Looks along the light direction from the center of the normalized scene
 uniform mat4 view;
+// Orthographic, fit to the whole scene so every voxel can be reached
 uniform mat4 projection;
 
 void main() {
@@ -1,6 +1,7 @@
 #version 460 core
 
 #include "assets/shaders/octree/_constants.glsl"
+#include "assets/shaders/octree/_invocation.glsl"
 
 layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
 
@@ -9,19 +10,21 @@ uniform layout(binding = 1, rgba8) writeonly image3D brickPoolValues;
 uniform layout(binding = 2, r32ui) uimageBuffer levelStartIndices;
 uniform layout(binding = 3, r32ui) uimageBuffer directionalNeighbors;
 uniform layout(binding = 4, rgba8) readonly image3D brickPoolValuesRead;
+uniform layout(binding = 7, r32ui) readonly uimageBuffer dirtyNodes;
 
 uniform uint octreeLevel;
 uniform uint voxelDimension;
 
 #include "assets/shaders/octree/_helpers.glsl"
 #include "assets/shaders/octree/_threadNodeUtil.glsl"
+#include "assets/shaders/octree/_dirtyNodes.glsl"
 #include "assets/shaders/octree/_brickCoordinates.glsl"
 #include "assets/shaders/octree/_mipmapAnisotropic.glsl"
 
 void main() {
     int nodeAddress = getThreadNode();
 
-    if (nodeAddress == NODE_NOT_FOUND) {
+    if (nodeAddress == NODE_NOT_FOUND || !isDirty(nodeAddress)) {
         return;
     }
 
@@ -1,6 +1,7 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 
 layout (local_size_x = 32, local_size_y = 32, local_size_z = 1) in;
 
@@ -20,7 +21,7 @@ uniform uint voxelDimension;
 void main() {
     uvec3 queryCoordinates = texelFetch(
         lightViewMap,
-        ivec2(gl_GlobalInvocationID.xy),
+        ivec2(globalInvocationID().xy),
         0
     ).xyz;
     if (queryCoordinates == uvec3(0)) {
@@ -1,6 +1,7 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 
 layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
 
@@ -10,17 +11,19 @@ uniform layout(binding = 1, rgb10_a2ui) uimageBuffer voxelPositions;
 uniform layout(binding = 2, r32ui) uimageBuffer nodePoolNeighborsPositive;
 uniform layout(binding = 3, r32ui) uimageBuffer nodePoolNeighborsNegative;
 uniform layout(binding = 4, rgb10_a2ui) readonly uimageBuffer nodePositions;
+uniform layout(binding = 5, r32ui) readonly uimageBuffer levelStartIndices;
 
 uniform int axis;
 uniform uint octreeLevel;
 uniform uint voxelDimension;
-uniform uint levelStart;
-uniform uint nextLevelStart;
+// Static nodes before it get a pointer back to the dynamic nodes next to them,
+// it's 0 when building the static nodes
+uniform uint firstDynamicNode;
 
 #include "./_traversalHelpers.glsl"
 #include "./_helpers.glsl"
 #include "./_octreeTraversal.glsl"
-#include "./_threadNodeUtilNoTexture.glsl"
+#include "./_threadNodeUtil.glsl"
 
 void main() {
     int nodeID = getThreadNode();
@@ -84,4 +87,12 @@ void main() {
 
     imageStore(nodePoolNeighborsPositive, nodeID, uvec4(neighborPositive));
     imageStore(nodePoolNeighborsNegative, nodeID, uvec4(neighborNegative));
+
+    // Static nodes were built before this one existed
+    if (neighborPositive != 0 && uint(neighborPositive) < firstDynamicNode) {
+      imageStore(nodePoolNeighborsNegative, neighborPositive, uvec4(nodeID));
+    }
+    if (neighborNegative != 0 && uint(neighborNegative) < firstDynamicNode) {
+      imageStore(nodePoolNeighborsPositive, neighborNegative, uvec4(nodeID));
+    }
 }
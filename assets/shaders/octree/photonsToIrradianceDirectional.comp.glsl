@@ -1,61 +1,59 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 
-layout (local_size_x = 32, local_size_y = 32, local_size_z = 1) in;
+layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
 
-uniform layout(binding = 0, rgba8) writeonly image3D brickPoolIrradiance;
-uniform layout(binding = 1, r32ui) readonly uimageBuffer nodePool;
+uniform layout(binding = 0, rgba8) image3D brickPoolIrradiance;
+uniform layout(binding = 1, rgb10_a2ui) readonly uimageBuffer nodePositions;
+uniform layout(binding = 2, r32ui) readonly uimageBuffer levelStartIndices;
+uniform layout(binding = 7, r32ui) readonly uimageBuffer dirtyNodes;
 
 uniform sampler3D brickPoolColors;
 uniform usampler3D brickPoolPhotons;
-uniform usampler2D lightViewMap;
 
 uniform uint voxelDimension;
 uniform uint octreeLevel;
 uniform float lightIntensity;
+uniform vec3 lightColor;
 
 #include "./_helpers.glsl"
-#include "./_traversalHelpers.glsl"
-#include "./_octreeTraversal.glsl"
+#include "./_threadNodeUtil.glsl"
+#include "./_dirtyNodes.glsl"
 #include "./_brickCoordinates.glsl"
 
+// One thread per leaf node instead of per light view map texel, so every voxel
+// adds this light's irradiance exactly once and lights can be accumulated
 void main() {
-    uvec3 queryCoordinates = texelFetch(
-        lightViewMap,
-        ivec2(gl_GlobalInvocationID.xy),
-        0
-    ).xyz;
-    if (queryCoordinates == uvec3(0)) {
-        return;
-    }
-    vec3 normalizedQueryCoordinates = normalizedFromIntCoordinates(queryCoordinates, float(voxelDimension));
-
-    float halfNodeSize;
-    vec3 nodeCoordinates;
-    int nodeID = traverseOctree(
-        normalizedQueryCoordinates,
-        octreeLevel,
-        nodeCoordinates,
-        halfNodeSize
-    );
-    if (nodeID == NODE_NOT_FOUND) {
+    int nodeID = getThreadNode();
+    if (nodeID == NODE_NOT_FOUND || !isDirty(nodeID)) {
         return;
     }
 
     ivec3 brickCoordinates = calculateBrickCoordinates(nodeID);
-    uint offset = calculateChildLocalID(nodeCoordinates, halfNodeSize, normalizedQueryCoordinates);
-    ivec3 brickOffset = 2 * ivec3(CHILD_OFFSETS[offset]);
-
-    vec4 voxelColor = texelFetch(brickPoolColors, brickCoordinates + brickOffset, 0);
-    uint numberOfPhotons = texelFetch(brickPoolPhotons, brickCoordinates + brickOffset, 0).r;
-
-    // TODO: Use also total photon hits here for the multiplier.
-    // Every octree level added separates the current surface touched by photons in
-    // 4 (2D section of a voxel is separated in 4 new voxels, each with a fourth of the amount of photons)
-    // float multiplier = numberOfPhotons * pow(4, octreeLevel) / float(262144); 
-    // float multiplier = clamp(float(numberOfPhotons), 0.0, 1.0);
-    vec4 irradiance = vec4(voxelColor.xyz * numberOfPhotons * lightIntensity, 1.0);
-
-    imageStore(brickPoolIrradiance, brickCoordinates + brickOffset, irradiance);
+    uvec3 nodePosition = imageLoad(nodePositions, nodeID).xyz;
+
+    for (uint offset = 0; offset < CHILDREN_PER_NODE; offset++) {
+        ivec3 brickOffset = 2 * ivec3(CHILD_OFFSETS[offset]);
+        uint numberOfPhotons = texelFetch(brickPoolPhotons, brickCoordinates + brickOffset, 0).r;
+        if (numberOfPhotons == 0) {
+            continue;
+        }
+        vec4 voxelColor = texelFetch(brickPoolColors, brickCoordinates + brickOffset, 0);
+
+        // TODO: Use also total photon hits here for the multiplier.
+        // Every octree level added separates the current surface touched by photons in
+        // 4 (2D section of a voxel is separated in 4 new voxels, each with a fourth of the amount of photons)
+        // float multiplier = numberOfPhotons * pow(4, octreeLevel) / float(262144); 
+        // float multiplier = clamp(float(numberOfPhotons), 0.0, 1.0);
+        vec3 irradiance = voxelColor.xyz * lightColor * lightIntensity * numberOfPhotons;
+
+        vec4 previousIrradiance = imageLoad(brickPoolIrradiance, brickCoordinates + brickOffset);
+        imageStore(
+            brickPoolIrradiance,
+            brickCoordinates + brickOffset,
+            vec4(previousIrradiance.rgb + irradiance, 1.0)
+        );
+    }
 }
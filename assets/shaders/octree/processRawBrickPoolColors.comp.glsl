@@ -1,6 +1,7 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 
 layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
 
@@ -8,16 +9,19 @@ uniform layout(binding = 0, r32ui) uimageBuffer levelStartIndices;
 uniform layout(binding = 1, r32ui) uimage3D brickPoolColorsRaw;
 uniform layout(binding = 2, rgba8) image3D brickPoolColors;
 
+uniform layout(binding = 3, r32ui) readonly uimageBuffer dirtyNodes;
+
 uniform uint octreeLevel;
 
 #include "./_helpers.glsl"
 #include "./_threadNodeUtil.glsl"
 #include "./_brickCoordinates.glsl"
+#include "./_dirtyNodes.glsl"
 
 void main() {
 	int nodeID = getThreadNode();
 
-	if (nodeID == NODE_NOT_FOUND) {
+	if (nodeID == NODE_NOT_FOUND || !isDirty(nodeID)) {
 		return;
 	}
 
@@ -0,0 +1,44 @@
+#version 460 core
+
+#include "./_constants.glsl"
+#include "./_invocation.glsl"
+
+layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
+
+uniform layout(binding = 0, rgba8) readonly image3D brickPoolAlpha;
+// X, -X, Y, -Y, Z, -Z
+uniform layout(binding = 1, rgba8) writeonly image3D brickPoolIrradianceXPositive;
+uniform layout(binding = 2, rgba8) writeonly image3D brickPoolIrradianceXNegative;
+uniform layout(binding = 3, rgba8) writeonly image3D brickPoolIrradianceYPositive;
+uniform layout(binding = 4, rgba8) writeonly image3D brickPoolIrradianceYNegative;
+uniform layout(binding = 5, rgba8) writeonly image3D brickPoolIrradianceZPositive;
+uniform layout(binding = 6, rgba8) writeonly image3D brickPoolIrradianceZNegative;
+uniform layout(binding = 7, r32ui) readonly uimageBuffer dirtyNodes;
+
+#include "./_dirtyNodes.glsl"
+#include "./_brickCoordinates.glsl"
+
+// Same as clearing the light and copying the alpha map to the irradiance,
+// but only for the bricks of dirty nodes
+void main() {
+    int nodeID = int(globalInvocationID().x);
+    if (!isDirty(nodeID)) {
+        return;
+    }
+
+    ivec3 brickCoordinates = calculateBrickCoordinates(nodeID);
+    vec4 clearValue = vec4(0);
+    for (int x = 0; x < 3; x++) {
+        for (int y = 0; y < 3; y++) {
+            for (int z = 0; z < 3; z++) {
+                ivec3 coordinates = brickCoordinates + ivec3(x, y, z);
+                imageStore(brickPoolIrradianceXPositive, coordinates, imageLoad(brickPoolAlpha, coordinates));
+                imageStore(brickPoolIrradianceXNegative, coordinates, clearValue);
+                imageStore(brickPoolIrradianceYPositive, coordinates, clearValue);
+                imageStore(brickPoolIrradianceYNegative, coordinates, clearValue);
+                imageStore(brickPoolIrradianceZPositive, coordinates, clearValue);
+                imageStore(brickPoolIrradianceZNegative, coordinates, clearValue);
+            }
+        }
+    }
+}
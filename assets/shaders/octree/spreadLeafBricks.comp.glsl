@@ -1,17 +1,20 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 
 layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
 
 uniform layout(binding = 0, rgba8) image3D brickPoolValues;
 uniform layout(binding = 1, r32ui) readonly uimageBuffer levelStartIndices;
+uniform layout(binding = 7, r32ui) readonly uimageBuffer dirtyNodes;
 
 uniform uint octreeLevel;
 uniform uint voxelDimension;
 
 #include "./_helpers.glsl"
 #include "./_threadNodeUtil.glsl"
+#include "./_dirtyNodes.glsl"
 #include "assets/shaders/octree/_brickCoordinates.glsl"
 
 vec4[8] loadVoxelValues(in ivec3 brickAddress) {
@@ -31,7 +34,7 @@ vec4[8] loadVoxelValues(in ivec3 brickAddress) {
 void main() {
     int nodeAddress = getThreadNode();
 
-    if (nodeAddress == NODE_NOT_FOUND) {
+    if (nodeAddress == NODE_NOT_FOUND || !isDirty(nodeAddress)) {
         return;
     }
 
@@ -1,6 +1,7 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 #include "./_helpers.glsl"
 
 layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
@@ -8,15 +9,21 @@ layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) i
 uniform layout(binding = 0, rgb10_a2ui) uimageBuffer voxelPositions;
 uniform layout(binding = 1, rgb10_a2ui) uimageBuffer nodePositions;
 uniform layout(binding = 2, r32ui) uimageBuffer nodePool;
+uniform layout(binding = 3, r32ui) readonly uimageBuffer buildCounts;
 
 uniform uint octreeLevel;
 uniform uint voxelDimension;
 
 #include "./_traversalHelpers.glsl"
 #include "./_octreeTraversal.glsl"
+#include "./_buildCounts.glsl"
 
 void main() {
-    uint threadIndex = gl_GlobalInvocationID.x;
+    uint threadIndex = invocationIndex();
+    if (threadIndex >= dispatchedInvocations()) {
+        return;
+    }
+
     uvec3 voxelFragmentPosition = imageLoad(voxelPositions, int(threadIndex)).xyz;
 
     float halfNodeSize;
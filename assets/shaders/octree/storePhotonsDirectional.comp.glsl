@@ -1,17 +1,20 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 
 layout (local_size_x = 32, local_size_y = 32, local_size_z = 1) in;
 
 uniform layout(binding = 0, r32ui) readonly uimageBuffer nodePool; // TODO: Could be a texture
 uniform layout(binding = 1, r32ui) uimage3D brickPoolPhotons;
-uniform layout(binding = 2, r32ui) uimageBuffer totalPhotonHits;
+uniform layout(binding = 3, r32ui) uimageBuffer litNodes;
 
+// The light view map covers the whole scene with parallel rays
 uniform usampler2D lightViewMap;
 
 uniform uint octreeLevel;
 uniform uint voxelDimension;
+uniform uint lightMask; // Bit of this light in `litNodes`
 
 #include "./_helpers.glsl"
 #include "./_traversalHelpers.glsl"
@@ -21,7 +24,7 @@ uniform uint voxelDimension;
 void main() {
     uvec3 queryCoordinates = texelFetch(
         lightViewMap,
-        ivec2(gl_GlobalInvocationID.xy),
+        ivec2(globalInvocationID().xy),
         0 // Mipmap level is always 0
     ).xyz;
 
@@ -42,6 +45,11 @@ void main() {
         return;
     }
 
+    // Remember which lights reach each leaf, so only those are updated when a light moves
+    if ((imageLoad(litNodes, nodeID).r & lightMask) == 0) {
+        imageAtomicOr(litNodes, nodeID, lightMask);
+    }
+
     ivec3 brickCoordinates = calculateBrickCoordinates(nodeID);
     uint offset = calculateChildLocalID(nodeCoordinates, halfNodeSize, normalizedQueryCoordinates);
     ivec3 brickOffset = 2 * ivec3(CHILD_OFFSETS[offset]);
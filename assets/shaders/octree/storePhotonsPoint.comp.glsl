@@ -1,6 +1,7 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 
 // We have 6 z threads in each work group so that they can process all faces
 // of the lightViewMap.
@@ -9,7 +10,7 @@ layout (local_size_x = 12, local_size_y = 12, local_size_z = 6) in;
 
 uniform layout(binding = 0, r32ui) readonly uimageBuffer nodePool; // TODO: Could be a texture
 uniform layout(binding = 1, r32ui) uimage3D brickPoolPhotons;
-uniform layout(binding = 2, r32ui) uimageBuffer totalPhotonHits;
+uniform layout(binding = 3, r32ui) uimageBuffer litNodes;
 
 // xy are the texture coordinates
 // z is the image index in the array
@@ -17,6 +18,7 @@ uniform usampler2DArray lightViewMap;
 
 uniform uint octreeLevel;
 uniform uint voxelDimension;
+uniform uint lightMask; // Bit of this light in `litNodes`
 
 #include "./_helpers.glsl"
 #include "./_traversalHelpers.glsl"
@@ -26,7 +28,7 @@ uniform uint voxelDimension;
 void main() {
     uvec3 queryCoordinates = texelFetch(
         lightViewMap,
-        ivec3(gl_GlobalInvocationID.xy, gl_LocalInvocationID.z),
+        ivec3(globalInvocationID().xy, gl_LocalInvocationID.z),
         0 // Mipmap level is always 0
     ).xyz;
 
@@ -47,6 +49,11 @@ void main() {
         return;
     }
 
+    // Remember which lights reach each leaf, so only those are updated when a light moves
+    if ((imageLoad(litNodes, nodeID).r & lightMask) == 0) {
+        imageAtomicOr(litNodes, nodeID, lightMask);
+    }
+
     ivec3 brickCoordinates = calculateBrickCoordinates(nodeID);
     uint offset = calculateChildLocalID(nodeCoordinates, halfNodeSize, normalizedQueryCoordinates);
     ivec3 brickOffset = 2 * ivec3(CHILD_OFFSETS[offset]);
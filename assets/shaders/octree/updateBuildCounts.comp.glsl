@@ -0,0 +1,42 @@
+#version 460 core
+
+#include "./_constants.glsl"
+
+// Only writes a few counts
+layout (local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+uniform layout(binding = 0, r32ui) uimageBuffer buildCounts;
+uniform layout(binding = 1, r32ui) uimageBuffer levelStartIndices;
+
+uniform uint octreeLevel;
+uniform uint maxWorkGroupCount;
+
+#include "./_buildCounts.glsl"
+
+void setDispatch(int dispatch, uint invocations) {
+    uint groups = (invocations + WORKING_GROUP_SIZE - 1) / WORKING_GROUP_SIZE;
+    // Groups past the maximum in x continue in y, see `invocationIndex`
+    uint groupsInX = min(groups, maxWorkGroupCount);
+    uint groupsInY = groupsInX == 0 ? 0 : (groups + groupsInX - 1) / groupsInX;
+
+    imageStore(buildCounts, dispatch, uvec4(groupsInX));
+    imageStore(buildCounts, dispatch + 1, uvec4(groupsInY));
+    imageStore(buildCounts, dispatch + 2, uvec4(1));
+    imageStore(buildCounts, dispatch + 3, uvec4(invocations));
+}
+
+void main() {
+    uint previousLevelStart = octreeLevel == 0 ? 0 : imageLoad(levelStartIndices, int(octreeLevel) - 1).r;
+    uint levelStart = imageLoad(levelStartIndices, int(octreeLevel)).r;
+    uint nextFreeNode = imageLoad(buildCounts, NEXT_FREE_NODE).r;
+
+    // Every node allocated after the start of this level belongs to it
+    imageStore(levelStartIndices, int(octreeLevel) + 1, uvec4(nextFreeNode));
+
+    setDispatch(NODES_IN_LEVEL_DISPATCH, nextFreeNode - levelStart);
+    setDispatch(
+        CHILDREN_OF_PREVIOUS_LEVEL_DISPATCH,
+        (levelStart - previousLevelStart) * CHILDREN_PER_NODE
+    );
+    setDispatch(BORDER_VOXEL_FRAGMENTS_DISPATCH, imageLoad(buildCounts, BORDER_VOXEL_FRAGMENTS).r);
+}
@@ -15,8 +15,7 @@ out VertexData {
 uniform mat3 normalMatrix;
 uniform mat4 modelNormalizationMatrix;
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+#include "assets/shaders/_uniformBlocks.glsl"
 
 void main() {
     gl_Position = projection * view * modelNormalizationMatrix * model * vec4(position, 1.0);
@@ -44,7 +43,6 @@ in VertexData {
 
 struct Material {
     vec3 color;
-    float diffuse;
     float specular;
 };
 uniform Material material;
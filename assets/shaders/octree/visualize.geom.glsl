@@ -11,8 +11,7 @@ in VertexData {
 out vec4 frag_nodeColor;
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+#include "assets/shaders/_uniformBlocks.glsl"
 
 #include "./_drawCube.glsl"
 
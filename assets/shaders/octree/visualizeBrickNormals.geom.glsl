@@ -10,8 +10,7 @@ in VertexData {
 } In[];
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+#include "assets/shaders/_uniformBlocks.glsl"
 
 uniform uint bricksToShow;
 
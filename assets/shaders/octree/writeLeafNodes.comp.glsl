@@ -1,6 +1,7 @@
 #version 460 core
 
 #include "./_constants.glsl"
+#include "./_invocation.glsl"
 #include "./_helpers.glsl"
 
 layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
@@ -11,14 +12,19 @@ uniform layout(binding = 2, r32ui) uimage3D brickPoolColorsRaw;
 uniform layout(binding = 3, r32ui) uimageBuffer nodePool;
 uniform layout(binding = 4, rgba32f) imageBuffer voxelNormals;
 uniform layout(binding = 5, rgba32f) image3D brickPoolNormals;
+uniform layout(binding = 6, r32ui) uimageBuffer dirtyNodes;
 
 uniform uint voxelDimension;
 uniform uint octreeLevel;
 uniform uint numberOfVoxelFragments;
+// Writes the voxel fragments of static objects again in the leaves dynamic objects left.
+// Otherwise the leaves written are flagged as changed
+uniform bool onlyDirtyLeaves;
 
 #include "./_traversalHelpers.glsl"
 #include "./_octreeTraversal.glsl"
 #include "assets/shaders/octree/_brickCoordinates.glsl"
+#include "./_dirtyNodes.glsl"
 
 void imageAtomicR32UIAvg(ivec3 coordinates, vec4 val) {
     val.rgb *= 255.0f; // Optimize following calculations
@@ -65,7 +71,7 @@ void storeInLeaf(
 
 void main() {
     // Get voxel attributes from voxel fragment list
-    const uint threadIndex = gl_GlobalInvocationID.x;
+    const uint threadIndex = globalInvocationID().x;
     if (threadIndex < numberOfVoxelFragments) {
         // We need to traverse the tree to get the node because we
         // need the voxel attributes (color, normal, etc)
@@ -87,6 +93,13 @@ void main() {
           halfNodeSize
         );
 
+        if (onlyDirtyLeaves && !isDirty(nodeID)) {
+            return;
+        }
+        if (!onlyDirtyLeaves) {
+            imageStore(dirtyNodes, nodeID, uvec4(NODE_CHANGED, 0, 0, 0));
+        }
+
         storeInLeaf(normalizedVoxelPosition, nodeID, voxelColor, halfNodeSize, nodeCoordinates, voxelNormal);
     }
 }
@@ -11,8 +11,7 @@ in vec4 geom_color[];
 in int geom_vertexID[];
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+#include "assets/shaders/_uniformBlocks.glsl"
 
 uniform float halfDimension; // TODO: Why is this half dimension?
 
@@ -4,8 +4,7 @@ out vec4 geom_position;
 out vec4 geom_color;
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+#include "assets/shaders/_uniformBlocks.glsl"
 
 uniform uint voxelDimension;
 
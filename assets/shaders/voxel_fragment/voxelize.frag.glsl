@@ -22,7 +22,6 @@ uniform layout(binding = 2, rgba32f) imageBuffer voxelNormals;
 
 struct Material {
     vec3 color;
-    float diffuse;
     float specular;
 };
 uniform Material material;
@@ -0,0 +1,26 @@
+use structopt::StructOpt;
+
+/// Command-line arguments we can pass in to the benchmarks runner
+#[derive(Debug, StructOpt)]
+#[structopt(name = "Options")]
+pub struct Options {
+    /// Folder with the results of a previous run to compare against, laid out like `benchmarks/`
+    #[structopt(long)]
+    pub baseline: Option<String>,
+
+    /// Compare the results already in `benchmarks/` against the baseline without running the benchmarks
+    #[structopt(long, requires = "baseline")]
+    pub compare_only: bool,
+
+    /// Relative increase of the median octree build time that counts as a regression
+    #[structopt(long, default_value = "0.1")]
+    pub build_time_threshold: f64,
+
+    /// Relative decrease of the FPS, from the median frame time, that counts as a regression
+    #[structopt(long, default_value = "0.1")]
+    pub fps_threshold: f64,
+
+    /// Maximum difference allowed per color channel between screenshots, from 0.0 to 1.0
+    #[structopt(long, default_value = "0.05")]
+    pub screenshot_tolerance: f32,
+}
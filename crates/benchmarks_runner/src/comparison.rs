@@ -0,0 +1,308 @@
+use std::fmt::Write;
+
+use image_comparison::compare_images;
+
+use crate::results::BenchmarkResults;
+
+/// How much worse a benchmark can get before it counts as a regression
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    /// Relative increase of the median octree build time
+    pub build_time: f64,
+    /// Relative decrease of the FPS
+    pub fps: f64,
+    /// Maximum difference per color channel between screenshots, from 0.0 to 1.0
+    pub screenshot_tolerance: f32,
+}
+
+/// A measurement in the baseline and in the current run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Change {
+    pub baseline: f64,
+    pub current: f64,
+    pub is_regression: bool,
+}
+
+impl Change {
+    /// Regresses when the current value is more than `threshold` times the baseline above it
+    fn higher_is_worse(baseline: f64, current: f64, threshold: f64) -> Self {
+        Self {
+            baseline,
+            current,
+            is_regression: current > baseline * (1.0 + threshold),
+        }
+    }
+
+    /// Regresses when the current value is more than `threshold` times the baseline below it
+    fn lower_is_worse(baseline: f64, current: f64, threshold: f64) -> Self {
+        Self {
+            baseline,
+            current,
+            is_regression: current < baseline * (1.0 - threshold),
+        }
+    }
+
+    pub fn relative(&self) -> f64 {
+        (self.current - self.baseline) / self.baseline
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScreenshotComparison {
+    Match,
+    Different {
+        different_pixels: u32,
+        total_pixels: u32,
+    },
+    /// The current one is missing, they have different dimensions or something else
+    /// that can't be compared pixel by pixel
+    Incomparable(String),
+    /// The baseline screenshot is missing, like for a new benchmark
+    Missing(String),
+}
+
+/// A benchmark of the current run compared against the baseline
+#[derive(Debug)]
+pub struct Comparison {
+    pub name: String,
+    /// `None` if the baseline or the current run have no results for this benchmark
+    pub build_time: Option<Change>,
+    pub fps: Option<Change>,
+    pub screenshot: ScreenshotComparison,
+    /// Why the current run has no results, like when the benchmark failed. It fails the comparison
+    pub missing_results: Option<String>,
+}
+
+impl Comparison {
+    pub fn is_regression(&self) -> bool {
+        self.missing_results.is_some()
+            || self.build_time.is_some_and(|change| change.is_regression)
+            || self.fps.is_some_and(|change| change.is_regression)
+            || matches!(
+                self.screenshot,
+                ScreenshotComparison::Different { .. } | ScreenshotComparison::Incomparable(_)
+            )
+    }
+}
+
+/// Compares the results and screenshot of the benchmark in `folder` against the ones in
+/// `baseline_folder`. The diff of the screenshots is saved in `folder/screenshot_diff.png`
+pub fn compare(
+    name: &str,
+    folder: &str,
+    baseline_folder: &str,
+    thresholds: Thresholds,
+) -> Comparison {
+    let results_path = format!("{folder}/results.json");
+    let results = BenchmarkResults::load(&results_path)
+        .map_err(|error| format!("couldn't load {results_path}: {error}"));
+    let baseline_results = BenchmarkResults::load(format!("{baseline_folder}/results.json"));
+    let (build_time, fps) = match (&results, baseline_results) {
+        (Ok(results), Ok(baseline_results)) => (
+            Some(Change::higher_is_worse(
+                baseline_results.octree_build.median,
+                results.octree_build.median,
+                thresholds.build_time,
+            )),
+            Some(Change::lower_is_worse(
+                baseline_results.fps(),
+                results.fps(),
+                thresholds.fps,
+            )),
+        ),
+        _ => (None, None),
+    };
+
+    Comparison {
+        name: name.to_string(),
+        build_time,
+        fps,
+        screenshot: compare_screenshots(folder, baseline_folder, thresholds.screenshot_tolerance),
+        missing_results: results.err(),
+    }
+}
+
+fn compare_screenshots(
+    folder: &str,
+    baseline_folder: &str,
+    tolerance: f32,
+) -> ScreenshotComparison {
+    let open = |path: &str| {
+        image::open(path)
+            .map(|image| image.to_rgba8())
+            .map_err(|error| format!("couldn't open {path}: {error}"))
+    };
+    let screenshot = match open(&format!("{folder}/screenshot.png")) {
+        Ok(screenshot) => screenshot,
+        Err(error) => return ScreenshotComparison::Incomparable(error),
+    };
+    let baseline_screenshot = match open(&format!("{baseline_folder}/screenshot.png")) {
+        Ok(screenshot) => screenshot,
+        Err(error) => return ScreenshotComparison::Missing(error),
+    };
+
+    match compare_images(&screenshot, &baseline_screenshot, tolerance) {
+        Ok(comparison) if comparison.images_match() => ScreenshotComparison::Match,
+        Ok(comparison) => {
+            comparison.save_diff_image(&format!("{folder}/screenshot_diff.png"));
+            ScreenshotComparison::Different {
+                different_pixels: comparison.different_pixels,
+                total_pixels: comparison.total_pixels,
+            }
+        }
+        Err(error) => ScreenshotComparison::Incomparable(error.to_string()),
+    }
+}
+
+/// Markdown table with a row per benchmark
+pub fn markdown_summary(comparisons: &[Comparison], baseline: &str) -> String {
+    let mut summary = String::new();
+    let regressions = comparisons
+        .iter()
+        .filter(|comparison| comparison.is_regression())
+        .count();
+    writeln!(summary, "# Benchmarks compared against `{baseline}`").unwrap();
+    writeln!(summary).unwrap();
+    writeln!(
+        summary,
+        "{regressions} of {} benchmarks regressed.",
+        comparisons.len()
+    )
+    .unwrap();
+    writeln!(summary).unwrap();
+    writeln!(
+        summary,
+        "| Benchmark | Octree build (ms) | FPS | Screenshot | Status |"
+    )
+    .unwrap();
+    writeln!(summary, "| --- | --- | --- | --- | --- |").unwrap();
+    for comparison in comparisons.iter() {
+        let screenshot = match &comparison.screenshot {
+            ScreenshotComparison::Match => "Matches".to_string(),
+            ScreenshotComparison::Different {
+                different_pixels,
+                total_pixels,
+            } => format!("{different_pixels} of {total_pixels} pixels differ"),
+            ScreenshotComparison::Incomparable(error) | ScreenshotComparison::Missing(error) => {
+                error.clone()
+            }
+        };
+        let status = if comparison.missing_results.is_some() {
+            "Missing results"
+        } else if comparison.is_regression() {
+            "Regression"
+        } else if comparison.build_time.is_none() {
+            "Not compared"
+        } else {
+            "Ok"
+        };
+        writeln!(
+            summary,
+            "| {} | {} | {} | {} | {} |",
+            comparison.name,
+            format_change(comparison.build_time),
+            format_change(comparison.fps),
+            screenshot,
+            status
+        )
+        .unwrap();
+    }
+    summary
+}
+
+fn format_change(change: Option<Change>) -> String {
+    match change {
+        Some(change) => format!(
+            "{:.2} → {:.2} ({:+.1}%){}",
+            change.baseline,
+            change.current,
+            change.relative() * 100.0,
+            if change.is_regression { " ⚠" } else { "" }
+        ),
+        None => "No results".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_time_regresses_above_threshold() {
+        assert!(!Change::higher_is_worse(100.0, 109.0, 0.1).is_regression);
+        assert!(Change::higher_is_worse(100.0, 111.0, 0.1).is_regression);
+        assert!(!Change::higher_is_worse(100.0, 50.0, 0.1).is_regression);
+    }
+
+    #[test]
+    fn fps_regresses_below_threshold() {
+        assert!(!Change::lower_is_worse(60.0, 55.0, 0.1).is_regression);
+        assert!(Change::lower_is_worse(60.0, 53.0, 0.1).is_regression);
+        assert!(!Change::lower_is_worse(60.0, 120.0, 0.1).is_regression);
+    }
+
+    #[test]
+    fn different_screenshots_are_a_regression() {
+        let comparison = Comparison {
+            name: "benchmark".to_string(),
+            build_time: None,
+            fps: None,
+            screenshot: ScreenshotComparison::Different {
+                different_pixels: 1,
+                total_pixels: 4,
+            },
+            missing_results: None,
+        };
+        assert!(comparison.is_regression());
+    }
+
+    #[test]
+    fn missing_baseline_is_not_a_regression() {
+        let comparison = Comparison {
+            name: "new benchmark".to_string(),
+            build_time: None,
+            fps: None,
+            screenshot: ScreenshotComparison::Missing("couldn't open screenshot.png".to_string()),
+            missing_results: None,
+        };
+        assert!(!comparison.is_regression());
+    }
+
+    #[test]
+    fn missing_current_results_are_a_regression() {
+        let comparison = Comparison {
+            name: "failed benchmark".to_string(),
+            build_time: None,
+            fps: None,
+            screenshot: ScreenshotComparison::Match,
+            missing_results: Some("couldn't load results.json".to_string()),
+        };
+        assert!(comparison.is_regression());
+    }
+
+    #[test]
+    fn summary_has_a_row_per_benchmark() {
+        let comparisons = vec![
+            Comparison {
+                name: "faster".to_string(),
+                build_time: Some(Change::higher_is_worse(100.0, 90.0, 0.1)),
+                fps: Some(Change::lower_is_worse(60.0, 60.0, 0.1)),
+                screenshot: ScreenshotComparison::Match,
+                missing_results: None,
+            },
+            Comparison {
+                name: "slower".to_string(),
+                build_time: Some(Change::higher_is_worse(100.0, 200.0, 0.1)),
+                fps: Some(Change::lower_is_worse(60.0, 60.0, 0.1)),
+                screenshot: ScreenshotComparison::Match,
+                missing_results: None,
+            },
+        ];
+        let summary = markdown_summary(&comparisons, "baseline");
+        assert!(summary.contains("1 of 2 benchmarks regressed."));
+        assert!(summary.contains(
+            "| faster | 100.00 → 90.00 (-10.0%) | 60.00 → 60.00 (+0.0%) | Matches | Ok |"
+        ));
+        assert!(summary.contains("| slower | 100.00 → 200.00 (+100.0%) ⚠ |"));
+    }
+}
@@ -1,8 +1,18 @@
-use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
+use std::fs::{self, File};
 use std::process::Command;
 
 use serde::Deserialize;
+use structopt::StructOpt;
+
+mod cli_arguments;
+mod comparison;
+mod results;
+mod statistics;
+
+use cli_arguments::Options;
+use comparison::{Comparison, Thresholds};
+use results::BenchmarkResults;
+use statistics::{FrameRate, Statistics};
 
 #[derive(Deserialize)]
 pub struct Benchmark {
@@ -11,6 +21,37 @@ pub struct Benchmark {
     pub preset: String,
     pub seconds_for_fps: u32,
     pub number_of_builds: u32,
+    /// Builds run before `number_of_builds` that don't count.
+    /// None of them compiles the shaders of the octree, the build before them does and isn't recorded
+    #[serde(default = "default_warmup_builds")]
+    pub warmup_builds: u32,
+    /// Frames at the start of the FPS run that don't count
+    #[serde(default = "default_warmup_frames")]
+    pub warmup_frames: u32,
+    /// Camera path in `camera_paths/` to follow while recording frame times
+    #[serde(default)]
+    pub camera_path: Option<String>,
+}
+
+fn default_warmup_builds() -> u32 {
+    1
+}
+
+fn default_warmup_frames() -> u32 {
+    60
+}
+
+impl Benchmark {
+    fn name(&self) -> String {
+        let name = format!(
+            "benchmarks/{}_{}_{}",
+            &self.config, &self.scene, &self.preset
+        );
+        match &self.camera_path {
+            Some(camera_path) => format!("{name}_{camera_path}"),
+            None => name,
+        }
+    }
 }
 
 /// Loads the benchmarks from `benchmarks.ron`
@@ -23,101 +64,271 @@ fn load_benchmarks() -> Vec<Benchmark> {
 }
 
 fn main() {
+    let options = Options::from_args();
     let benchmarks = load_benchmarks();
 
-    // Proceso de correr benchmarks:
-    // 0) Loopear por cada una de las benchmarks definidas.
-    // 1) Crear un archivo específico para esta benchmark para guardar resultados.
-    // 2) Cargar config, scene y preset de acá en lugar de las flags.
-    // 3) Construir el octree e inyectar luz `number_of_builds` veces, promediar y guardar.
-    // 4) Seguir la ejecución del programa con la última build del octree.
-    // 5) Sacar una foto de la escena y guardar.
-    // 6) Contar FPS por `seconds_for_fps` segundos, promediar y guardar.
+    let mut failed_benchmarks = Vec::new();
+    if !options.compare_only {
+        failed_benchmarks = run_benchmarks(&benchmarks);
+    }
+
+    let mut regressions = Vec::new();
+    if let Some(baseline) = &options.baseline {
+        let thresholds = Thresholds {
+            build_time: options.build_time_threshold,
+            fps: options.fps_threshold,
+            screenshot_tolerance: options.screenshot_tolerance,
+        };
+        regressions = compare_benchmarks(&benchmarks, baseline, thresholds);
+    }
+
+    if !failed_benchmarks.is_empty() {
+        eprintln!("Failed benchmarks: {}", failed_benchmarks.join(", "));
+    }
+    if !regressions.is_empty() {
+        eprintln!("Regressed benchmarks: {}", regressions.join(", "));
+    }
+    if !failed_benchmarks.is_empty() || !regressions.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Compares every benchmark against the one in `baseline` with the same name, writes the summary
+/// to `benchmarks/comparison.md` and returns the names of the benchmarks that regressed
+fn compare_benchmarks(
+    benchmarks: &[Benchmark],
+    baseline: &str,
+    thresholds: Thresholds,
+) -> Vec<String> {
+    let comparisons: Vec<Comparison> = benchmarks
+        .iter()
+        .map(|benchmark| {
+            let name = benchmark.name();
+            let folder_name = name.trim_start_matches("benchmarks/");
+            comparison::compare(
+                folder_name,
+                &name,
+                &format!("{baseline}/{folder_name}"),
+                thresholds,
+            )
+        })
+        .collect();
+
+    let summary = comparison::markdown_summary(&comparisons, baseline);
+    println!("{summary}");
+    fs::write("benchmarks/comparison.md", &summary).expect("Couldn't write comparison summary");
+
+    comparisons
+        .into_iter()
+        .filter(|comparison| comparison.is_regression())
+        .map(|comparison| comparison.name)
+        .collect()
+}
+
+/// Runs every benchmark and returns the names of the ones that failed
+fn run_benchmarks(benchmarks: &[Benchmark]) -> Vec<String> {
+    let git_commit = git_commit();
 
+    // For each benchmark:
+    // 1) Take a screenshot of the scene.
+    // 2) Record the time of every frame for `seconds_for_fps` seconds, following the camera path if there's one.
+    // 3) Build the octree and inject the light, then do it again `warmup_builds + number_of_builds` times.
+    // 4) Drop the warm-up samples, summarize the rest and write them to `results.json` and `results.csv`.
+    let mut failed_benchmarks = Vec::new();
     for benchmark in benchmarks.iter() {
-        let name = format!(
-            "benchmarks/{}_{}_{}",
-            &benchmark.config, &benchmark.scene, &benchmark.preset
-        );
+        let name = benchmark.name();
         println!("Running benchmark {}", name);
-
-        let _ = std::fs::create_dir(&name); // We don't handle error because it errors if folder already exists
-
-        // First take a screenshot
-        println!("Taking screenshot");
-        let _ = Command::new("cargo")
-            .arg("run")
-            .arg("--release")
-            .arg("--")
-            .arg("--config")
-            .arg(&benchmark.config)
-            .arg("--scene")
-            .arg(&benchmark.scene)
-            .arg("--preset")
-            .arg(&benchmark.preset)
-            .arg("--screenshot")
-            .output()
-            .expect("Failed to execute process!");
-
-        // Second get the average FPS
-        println!("Doing the FPS for {} seconds", benchmark.seconds_for_fps);
-        let _ = Command::new("cargo")
-            .arg("run")
-            .arg("--release")
-            .arg("--")
-            .arg("--config")
-            .arg(&benchmark.config)
-            .arg("--scene")
-            .arg(&benchmark.scene)
-            .arg("--preset")
-            .arg(&benchmark.preset)
-            .arg("--seconds-for-fps")
-            .arg(&benchmark.seconds_for_fps.to_string())
-            .output()
-            .expect("Failed to execute process!");
-
-        // Third get the average octree build time
-        println!("Starting to record the octree build time");
-        for index in 0..benchmark.number_of_builds {
-            println!("Execution number {}", index);
-            let _ = Command::new("cargo")
-                .arg("run")
-                .arg("--release")
-                .arg("--")
-                .arg("--config")
-                .arg(&benchmark.config)
-                .arg("--scene")
-                .arg(&benchmark.scene)
-                .arg("--preset")
-                .arg(&benchmark.preset)
-                .arg("--record-octree-build-time")
-                .output()
-                .expect("Failed to execute process!");
+        match run_benchmark(benchmark, git_commit.clone()) {
+            Ok(results) => {
+                results
+                    .write_json(format!("{name}/results.json"))
+                    .expect("Couldn't write results as JSON");
+                results
+                    .write_csv(format!("{name}/results.csv"))
+                    .expect("Couldn't write results as CSV");
+                println!(
+                    "Octree build: {:.2} ms, light injection: {:.2} ms, frame time: {:.2} ms (medians)",
+                    results.octree_build.median,
+                    results.light_injection.median,
+                    results.frame_time.median
+                );
+                println!(
+                    "Average FPS: {:.1}, 1% low: {:.1}, 0.1% low: {:.1}",
+                    results.frame_rate.average_fps,
+                    results.frame_rate.one_percent_low_fps,
+                    results.frame_rate.point_one_percent_low_fps
+                );
+            }
+            Err(error) => {
+                eprintln!("Benchmark {name} failed: {error}");
+                failed_benchmarks.push(name);
+            }
         }
+    }
+
+    failed_benchmarks
+}
+
+fn run_benchmark(
+    benchmark: &Benchmark,
+    git_commit: Option<String>,
+) -> Result<BenchmarkResults, String> {
+    let name = benchmark.name();
+    fs::create_dir_all(&name).map_err(|error| format!("couldn't create {name}: {error}"))?;
+    // Samples of previous runs would be mixed with the new ones, and results of a previous run
+    // would be compared as if they were this one's if it fails
+    for file_name in [
+        "results.json",
+        "results.csv",
+        "screenshot.png",
+        "screenshot_diff.png",
+        "build_times.csv",
+        "frame_times.txt",
+        "gpu_timings.json",
+        "gpu_timings.csv",
+    ] {
+        let _ = fs::remove_file(format!("{name}/{file_name}"));
+    }
+    let config_contents = fs::read_to_string(format!("{}.ron", benchmark.config))
+        .map_err(|error| format!("couldn't read config {}: {error}", benchmark.config))?;
+
+    println!("Taking screenshot");
+    // Also times the octree build, the light injection and the frame pass by pass, in `gpu_timings.json`.
+    // Not while recording frame times, where the timer queries would add their own overhead
+    run_cli(
+        benchmark,
+        &["--screenshot", "--profile", "--no-octree-cache"],
+    )?;
+
+    println!(
+        "Recording frame times for {} seconds",
+        benchmark.seconds_for_fps
+    );
+    run_cli(
+        benchmark,
+        &["--seconds-for-fps", &benchmark.seconds_for_fps.to_string()],
+    )?;
+    let frame_times = read_samples(&format!("{name}/frame_times.txt"), 1)?;
+    let frame_times = skip_warmup(frame_times, benchmark.warmup_frames, "frames")?;
+
+    let total_builds = benchmark.warmup_builds + benchmark.number_of_builds;
+    println!("Recording the octree build time of {total_builds} builds");
+    run_cli(
+        benchmark,
+        &[
+            "--record-octree-build-time",
+            "--number-of-builds",
+            &total_builds.to_string(),
+        ],
+    )?;
+    let build_times = read_samples(&format!("{name}/build_times.csv"), 2)?;
+    let build_times = skip_warmup(build_times, benchmark.warmup_builds, "builds")?;
+    if build_times.len() != benchmark.number_of_builds as usize {
+        return Err(format!(
+            "expected {} build times but found {}",
+            benchmark.number_of_builds,
+            build_times.len()
+        ));
+    }
+    let (octree_build_times, light_injection_times): (Vec<f64>, Vec<f64>) = build_times
+        .into_iter()
+        .map(|line| (line[0], line[1]))
+        .unzip();
+    let frame_times: Vec<f64> = frame_times.into_iter().map(|line| line[0]).collect();
+
+    Ok(BenchmarkResults {
+        name,
+        git_commit,
+        config: benchmark.config.clone(),
+        config_contents,
+        scene: benchmark.scene.clone(),
+        preset: benchmark.preset.clone(),
+        camera_path: benchmark.camera_path.clone(),
+        warmup_builds: benchmark.warmup_builds,
+        warmup_frames: benchmark.warmup_frames,
+        octree_build: summarize(&octree_build_times)?,
+        light_injection: summarize(&light_injection_times)?,
+        frame_time: summarize(&frame_times)?,
+        frame_rate: FrameRate::from_frame_times(&frame_times)
+            .ok_or_else(|| "no frame times were recorded".to_string())?,
+    })
+}
+
+/// Runs the application with the benchmark's config, scene, preset and camera path.
+/// The camera path is always passed because it's part of the name of the results folder
+fn run_cli(benchmark: &Benchmark, arguments: &[&str]) -> Result<(), String> {
+    let camera_path_arguments = benchmark
+        .camera_path
+        .iter()
+        .flat_map(|camera_path| ["--camera-path", camera_path.as_str()]);
+    let output = Command::new("cargo")
+        .arg("run")
+        .arg("--release")
+        .arg("--")
+        .arg("--config")
+        .arg(&benchmark.config)
+        .arg("--scene")
+        .arg(&benchmark.scene)
+        .arg("--preset")
+        .arg(&benchmark.preset)
+        .args(camera_path_arguments)
+        .args(arguments)
+        .output()
+        .map_err(|error| format!("couldn't execute process: {error}"))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let last_lines: Vec<&str> = stderr.lines().rev().take(10).collect();
+    let last_lines: Vec<&str> = last_lines.into_iter().rev().collect();
+    Err(format!(
+        "`{}` exited with {}:\n{}",
+        arguments.join(" "),
+        output.status,
+        last_lines.join("\n")
+    ))
+}
+
+/// Reads a file with one sample per line, made of `columns` comma-separated values.
+/// Lines that aren't, like headers, are skipped
+fn read_samples(file_name: &str, columns: usize) -> Result<Vec<Vec<f64>>, String> {
+    let contents = fs::read_to_string(file_name)
+        .map_err(|error| format!("couldn't read {file_name}: {error}"))?;
+    Ok(contents
+        .lines()
+        .filter_map(|line| {
+            line.split(',')
+                .map(|value| value.trim().parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>()
+                .ok()
+        })
+        .filter(|values| values.len() == columns)
+        .collect())
+}
+
+fn skip_warmup(samples: Vec<Vec<f64>>, warmup: u32, what: &str) -> Result<Vec<Vec<f64>>, String> {
+    if samples.len() <= warmup as usize {
+        return Err(format!(
+            "only {} {what} were recorded, {warmup} of them are warm-up",
+            samples.len()
+        ));
+    }
+    Ok(samples.into_iter().skip(warmup as usize).collect())
+}
+
+fn summarize(samples: &[f64]) -> Result<Statistics, String> {
+    Statistics::from_samples(samples).ok_or_else(|| "no samples were recorded".to_string())
+}
 
-        // Now that all the octree build times have been written, we can average them
-        println!("Averaging all octree build times");
-        let octree_build_times_file_name = format!("{name}/octree_build_time.txt");
-        let octree_build_times_file = File::open(&octree_build_times_file_name)
-            .expect("Couldn't open octree build times file");
-        let octree_build_times_sum = BufReader::new(octree_build_times_file)
-            .lines()
-            .map(|line| line.unwrap())
-            .map(|line| {
-                let build_time: f64 = line.parse().unwrap();
-                println!("Build time: {build_time}");
-                build_time
-            })
-            .sum::<f64>();
-        let octree_build_time_average = octree_build_times_sum / benchmark.number_of_builds as f64;
-        println!("Average: {octree_build_time_average}");
-        OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&octree_build_times_file_name)
-            .expect("Couldn't open octree build times file")
-            .write_all(octree_build_time_average.to_string().as_bytes())
-            .expect("Couldn't write to file");
+/// Commit of the working directory, with `-dirty` if it has uncommitted changes
+fn git_commit() -> Option<String> {
+    let output = Command::new("git")
+        .args(["describe", "--always", "--dirty", "--abbrev=40"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
     }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
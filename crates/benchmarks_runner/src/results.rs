@@ -0,0 +1,74 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::statistics::{FrameRate, Statistics};
+
+/// Everything measured by a benchmark, with what's needed to reproduce it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BenchmarkResults {
+    pub name: String,
+    /// `None` if it's not run from a git checkout
+    pub git_commit: Option<String>,
+    pub config: String,
+    /// Contents of the config file when the benchmark ran
+    pub config_contents: String,
+    pub scene: String,
+    pub preset: String,
+    /// Camera path followed while recording frame times, the preset camera stays still without one
+    pub camera_path: Option<String>,
+    pub warmup_builds: u32,
+    pub warmup_frames: u32,
+    pub octree_build: Statistics,
+    pub light_injection: Statistics,
+    pub frame_time: Statistics,
+    pub frame_rate: FrameRate,
+}
+
+impl BenchmarkResults {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        serde_json::from_reader(reader).map_err(io::Error::from)
+    }
+
+    /// FPS of the median frame time
+    pub fn fps(&self) -> f64 {
+        1000.0 / self.frame_time.median
+    }
+
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self).map_err(io::Error::from)
+    }
+
+    /// One row per measurement, the rest of the results are only in the JSON
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "measurement,samples,min_ms,median_ms,mean_ms,stddev_ms,p95_ms,p99_ms,p999_ms"
+        )?;
+        for (measurement, statistics) in [
+            ("octree_build", &self.octree_build),
+            ("light_injection", &self.light_injection),
+            ("frame_time", &self.frame_time),
+        ] {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{}",
+                measurement,
+                statistics.samples,
+                statistics.min,
+                statistics.median,
+                statistics.mean,
+                statistics.stddev,
+                statistics.p95,
+                statistics.p99,
+                statistics.p999,
+            )?;
+        }
+        writer.flush()
+    }
+}
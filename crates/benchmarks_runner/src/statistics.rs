@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+/// Summary of a set of samples, all in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Statistics {
+    pub samples: usize,
+    pub min: f64,
+    pub median: f64,
+    pub mean: f64,
+    /// Sample standard deviation, 0 with a single sample
+    pub stddev: f64,
+    /// 95th percentile, nearest rank
+    pub p95: f64,
+    /// 99th percentile, nearest rank
+    pub p99: f64,
+    /// 99.9th percentile, nearest rank
+    pub p999: f64,
+}
+
+impl Statistics {
+    /// Returns `None` if there are no samples
+    pub fn from_samples(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let count = sorted.len();
+
+        let mean = sorted.iter().sum::<f64>() / count as f64;
+        let stddev = if count > 1 {
+            let variance = sorted
+                .iter()
+                .map(|sample| (sample - mean).powi(2))
+                .sum::<f64>()
+                / (count - 1) as f64;
+            variance.sqrt()
+        } else {
+            0.0
+        };
+        // Both are the middle sample if there's an odd number of them
+        let median = (sorted[(count - 1) / 2] + sorted[count / 2]) / 2.0;
+
+        Some(Self {
+            samples: count,
+            min: sorted[0],
+            median,
+            mean,
+            stddev,
+            p95: percentile(&sorted, 0.95),
+            p99: percentile(&sorted, 0.99),
+            p999: percentile(&sorted, 0.999),
+        })
+    }
+}
+
+/// Nearest rank percentile of samples sorted in ascending order
+fn percentile(sorted: &[f64], percentile: f64) -> f64 {
+    let rank = (percentile * sorted.len() as f64).ceil() as usize;
+    sorted[rank.max(1) - 1]
+}
+
+/// Frame rate of a run from the time of each of its frames
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameRate {
+    /// Frames divided by the total time
+    pub average_fps: f64,
+    /// Average FPS of the slowest 1% of frames
+    pub one_percent_low_fps: f64,
+    /// Average FPS of the slowest 0.1% of frames
+    pub point_one_percent_low_fps: f64,
+}
+
+impl FrameRate {
+    /// Returns `None` if there are no frame times. Frame times are in milliseconds.
+    /// The lows always include at least the slowest frame
+    pub fn from_frame_times(frame_times: &[f64]) -> Option<Self> {
+        if frame_times.is_empty() {
+            return None;
+        }
+
+        let mut slowest_first = frame_times.to_vec();
+        slowest_first.sort_by(|a, b| b.total_cmp(a));
+        let average_fps_of_slowest = |fraction: f64| {
+            let frames = ((fraction * slowest_first.len() as f64).ceil() as usize).max(1);
+            fps(&slowest_first[..frames])
+        };
+
+        Some(Self {
+            average_fps: fps(&slowest_first),
+            one_percent_low_fps: average_fps_of_slowest(0.01),
+            point_one_percent_low_fps: average_fps_of_slowest(0.001),
+        })
+    }
+}
+
+fn fps(frame_times: &[f64]) -> f64 {
+    frame_times.len() as f64 * 1000.0 / frame_times.iter().sum::<f64>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_samples_have_no_statistics() {
+        assert_eq!(Statistics::from_samples(&[]), None);
+    }
+
+    #[test]
+    fn single_sample() {
+        let statistics = Statistics::from_samples(&[4.0]).unwrap();
+        assert_eq!(statistics.samples, 1);
+        assert_eq!(statistics.min, 4.0);
+        assert_eq!(statistics.median, 4.0);
+        assert_eq!(statistics.mean, 4.0);
+        assert_eq!(statistics.stddev, 0.0);
+        assert_eq!(statistics.p95, 4.0);
+        assert_eq!(statistics.p999, 4.0);
+    }
+
+    #[test]
+    fn unsorted_samples() {
+        let statistics = Statistics::from_samples(&[5.0, 1.0, 4.0, 2.0]).unwrap();
+        assert_eq!(statistics.min, 1.0);
+        assert_eq!(statistics.median, 3.0);
+        assert_eq!(statistics.mean, 3.0);
+        assert!((statistics.stddev - (10.0_f64 / 3.0).sqrt()).abs() < 1e-12);
+        assert_eq!(statistics.p95, 5.0);
+    }
+
+    #[test]
+    fn p95_is_nearest_rank() {
+        let samples: Vec<f64> = (1..=100).map(|sample| sample as f64).collect();
+        let statistics = Statistics::from_samples(&samples).unwrap();
+        assert_eq!(statistics.p95, 95.0);
+        assert_eq!(statistics.p99, 99.0);
+        assert_eq!(statistics.p999, 100.0);
+        assert_eq!(statistics.median, 50.5);
+    }
+
+    #[test]
+    fn no_frames_have_no_frame_rate() {
+        assert_eq!(FrameRate::from_frame_times(&[]), None);
+    }
+
+    #[test]
+    fn lows_are_the_slowest_frames() {
+        // 990 frames at 10 ms, 9 at 20 ms and one at 100 ms
+        let mut frame_times = vec![10.0; 990];
+        frame_times.extend([20.0; 9]);
+        frame_times.push(100.0);
+        let frame_rate = FrameRate::from_frame_times(&frame_times).unwrap();
+        assert!((frame_rate.average_fps - 1000.0 * 1000.0 / 10_180.0).abs() < 1e-9);
+        assert!((frame_rate.one_percent_low_fps - 10.0 * 1000.0 / 280.0).abs() < 1e-9);
+        assert!((frame_rate.point_one_percent_low_fps - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn lows_of_few_frames_are_the_slowest_frame() {
+        let frame_rate = FrameRate::from_frame_times(&[10.0, 50.0, 20.0]).unwrap();
+        assert_eq!(frame_rate.one_percent_low_fps, 20.0);
+        assert_eq!(frame_rate.point_one_percent_low_fps, 20.0);
+    }
+}
@@ -0,0 +1,78 @@
+use std::fs::File;
+
+use cgmath::{EuclideanSpace, Point3};
+use engine::prelude::*;
+use serde::Deserialize;
+
+/// Where the camera is at a point in time, with its rotation in degrees like in presets
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct CameraKeyframe {
+    /// Seconds since the start of the render loop
+    pub time: f32,
+    pub position: Point3<f32>,
+    pub pitch: f32,
+    pub yaw: f32,
+}
+
+/// Keyframes the camera moves through during the render loop, for FPS benchmarks that move
+/// through the scene. Everything is interpolated linearly, so angles don't wrap around:
+/// to turn from 350 to 10 degrees, go to 370 instead
+#[derive(Debug, Deserialize)]
+pub struct CameraPath {
+    keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraPath {
+    /// Camera at `time`, staying at the first or last keyframe outside of the path
+    pub fn keyframe_at(&self, time: f32) -> CameraKeyframe {
+        let next_index = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        if next_index == 0 {
+            return self.keyframes[0];
+        }
+        if next_index == self.keyframes.len() {
+            return self.keyframes[next_index - 1];
+        }
+
+        let previous = self.keyframes[next_index - 1];
+        let next = self.keyframes[next_index];
+        let amount = (time - previous.time) / (next.time - previous.time);
+        let lerp = |from: f32, to: f32| from + (to - from) * amount;
+        CameraKeyframe {
+            time,
+            position: Point3::from_vec(
+                previous.position.to_vec() + (next.position - previous.position) * amount,
+            ),
+            pitch: lerp(previous.pitch, next.pitch),
+            yaw: lerp(previous.yaw, next.yaw),
+        }
+    }
+
+    /// Moves `transform` to where the camera is at `time`
+    pub fn apply(&self, time: f32, transform: &mut Transform) {
+        let keyframe = self.keyframe_at(time);
+        transform.position = keyframe.position;
+        transform.set_rotation_x(keyframe.pitch);
+        transform.set_rotation_y(keyframe.yaw);
+    }
+}
+
+/// Loads the camera path in `camera_paths/<file_name>.ron`
+pub fn load_camera_path(file_name: &str) -> CameraPath {
+    let input_path = format!("camera_paths/{}.ron", file_name);
+    let file = File::open(&input_path).expect("Missing camera path file!");
+    let camera_path: CameraPath = ron::de::from_reader(file).expect("Camera path file malformed!");
+    assert!(
+        !camera_path.keyframes.is_empty(),
+        "Camera path {input_path} has no keyframes"
+    );
+    assert!(
+        camera_path
+            .keyframes
+            .windows(2)
+            .all(|keyframes| keyframes[0].time < keyframes[1].time),
+        "Keyframes of camera path {input_path} aren't sorted by time"
+    );
+    camera_path
+}
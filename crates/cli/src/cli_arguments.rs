@@ -1,3 +1,4 @@
+use engine::common::ContextBackend;
 use structopt::StructOpt;
 
 /// Command-line arguments we can pass in to the binary
@@ -20,20 +21,76 @@ pub struct Options {
     #[structopt(long)]
     pub screenshot: bool,
 
-    /// Count FPS for this many seconds and record the average
+    /// Count FPS for this many seconds and record the average,
+    /// along with the time of every frame in `frame_times.txt`. Disables vsync
     #[structopt(long)]
     pub seconds_for_fps: Option<u32>,
 
-    /// Record how much time it takes to build the octree
+    /// Camera path file to follow, loaded from `camera_paths/<name>.ron`.
+    /// The path starts with the render loop
+    #[structopt(long)]
+    pub camera_path: Option<String>,
+
+    /// Record how much time it takes to build the octree and inject the light in `build_times.csv`
     #[structopt(long)]
     pub record_octree_build_time: bool,
+
+    /// How many times to build the octree again and inject the light when recording the build time.
+    /// They reuse the textures and shaders of the first build, which isn't recorded.
+    /// A line is recorded for each
+    #[structopt(long, default_value = "1")]
+    pub number_of_builds: u32,
+
+    /// Time every GPU pass from the start and write the timings to
+    /// `gpu_timings.json` and `gpu_timings.csv` in the benchmark folder on exit
+    #[structopt(long)]
+    pub profile: bool,
+
+    /// Compile shaders again when their files change, also toggled in the Shaders menu
+    #[structopt(long)]
+    pub hot_reload: bool,
+
+    /// Always voxelize and build the octree instead of loading it from `cache/`
+    #[structopt(long)]
+    pub no_octree_cache: bool,
+
+    /// Run headless and compare the rendered frame to `screenshots/<preset>.png`
+    #[structopt(long)]
+    pub visual_tests: bool,
+
+    /// Overwrite the stored screenshot instead of comparing against it.
+    /// Only used with `--visual-tests`
+    #[structopt(long)]
+    pub update_screenshots: bool,
+
+    /// Maximum difference allowed per color channel in visual tests, from 0.0 to 1.0
+    #[structopt(long, default_value = "0.05")]
+    pub visual_tests_tolerance: f32,
+
+    /// How to create the OpenGL context: `glfw` (window) or `egl` (surfaceless, no display needed).
+    /// `egl` is always headless
+    #[structopt(long, default_value = "glfw")]
+    pub context: ContextBackend,
 }
 
 impl Options {
     pub fn get_name(&self) -> String {
-        format!(
+        let name = format!(
             "benchmarks/{}_{}_{}",
             &self.config, &self.scene, &self.preset
-        )
+        );
+        match &self.camera_path {
+            Some(camera_path) => format!("{name}_{camera_path}"),
+            None => name,
+        }
+    }
+
+    /// Whether the application should render a single frame offscreen and exit
+    pub fn is_headless(&self) -> bool {
+        self.screenshot
+            || self.record_octree_build_time
+            || self.seconds_for_fps.is_some()
+            || self.visual_tests
+            || self.context == ContextBackend::Egl
     }
 }
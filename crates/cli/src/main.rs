@@ -1,63 +1,71 @@
 //! The entrypoint to the VCT application
 
-use std::fs::{File, OpenOptions};
+use std::fs::OpenOptions;
 use std::io::Write;
-use std::time::Instant;
-use std::{collections::HashMap, sync::mpsc::Receiver};
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 extern crate c_str_macro;
 
-use c_str_macro::c_str;
 extern crate gl;
-use cgmath::{point3, vec2, vec3, Euler, Matrix4};
+use cgmath::{point3, vec2, vec3, Matrix4};
 use core::{
-    cone_tracing::{ConeTracer, DebugCone},
+    cone_tracing::{ConeSettings, ConeTracer, DebugCone, MAX_LIGHTS},
     config::Config as CoreConfig,
     menu::{
         submenus::{
-            ChildrenMenuInput, DiagnosticsMenuInput, NodeSearchMenuInput, PhotonsMenuInput,
-            SavePresetMenuInput,
+            AllNodesMenuInput, ChildrenMenuInput, DiagnosticsMenuInput, NodeSearchMenuInput,
+            PhotonsMenuInput, SavePresetMenuInput, SettingsMenuInput,
         },
         DebugNode, Menu, Preset,
     },
-    octree::{BrickAttribute, BricksToShow, Octree, OctreeDataType},
+    octree::{BrickAttribute, BricksToShow, Octree, OctreeDataType, VoxelData},
     voxelization,
     voxelization::visualize::RenderVoxelFragmentsShader,
 };
+use engine::common::{ContextBackend, ContextHandle};
+use engine::shader;
+use engine::prelude::*;
 use engine::ui::glfw;
 use engine::ui::Ui;
-use engine::{
-    prelude::*,
-    ui::glfw::{Glfw, WindowEvent},
-};
 use log::info;
 use structopt::StructOpt;
 
+mod camera_path;
 mod cli_arguments;
 use cli_arguments::Options;
+mod octree_cache;
 mod preset;
 mod scene;
+mod visual_tests;
 
 fn main() {
-    simple_logger::init().unwrap();
     let options = Options::from_args();
+    if options.context == ContextBackend::Egl {
+        // SAFETY: No other thread has been spawned yet
+        unsafe { engine::headless::set_mesa_overrides() };
+    }
+    simple_logger::init().unwrap();
     // NOTE: This is true if the binary was compiled in debug mode
     let debug = cfg!(debug_assertions);
-    let file = File::open(&format!("{}.ron", &options.config)).expect("Missing config file!");
-    let config: CoreConfig = ron::de::from_reader(file).expect("Config file malformed!");
+    let config =
+        CoreConfig::load(format!("{}.ron", &options.config)).expect("Couldn't load config file!");
     log::info!("Configuration used: {:#?}", config);
 
     let (viewport_width, viewport_height) = config.viewport_dimensions();
-    let (glfw, events) = unsafe {
-        common::setup_glfw(
+    let mut context = unsafe {
+        common::setup_context(
+            options.context,
             viewport_width,
             viewport_height,
             debug,
-            options.screenshot
-                || options.record_octree_build_time
-                || options.seconds_for_fps.is_some(), // Don't run headless
+            options.is_headless(),
         )
     };
+    // Frame times are capped at the refresh rate otherwise
+    if options.seconds_for_fps.is_some() {
+        context.set_vsync(false);
+    }
 
     let scene = scene::load_scene(&options.scene);
     let preset = preset::load_preset(&options.preset);
@@ -66,27 +74,37 @@ fn main() {
         config,
         scene,
         preset,
-        events,
         options,
     };
 
-    run_application(parameters, glfw);
+    match run_application(parameters, context) {
+        Ok(visual_tests_passed) => {
+            if !visual_tests_passed {
+                std::process::exit(1);
+            }
+        }
+        Err(error) => {
+            log::error!("{error}");
+            std::process::exit(1);
+        }
+    }
 }
 
 struct ApplicationParameters {
     config: CoreConfig,
     scene: Scene,
     preset: Preset,
-    events: Receiver<(f64, WindowEvent)>,
     options: Options,
 }
 
-fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
-    // Load configuration file and set it up in core
-    unsafe {
-        CoreConfig::initialize(parameters.config);
-    }
-    let config = CoreConfig::instance();
+/// Returns `false` only if visual tests were run and failed,
+/// and an error if a shader doesn't compile
+fn run_application(
+    parameters: ApplicationParameters,
+    mut context: ContextHandle,
+) -> Result<bool, ShaderError> {
+    profiler::set_enabled(parameters.options.profile);
+    shader::hot_reload::set_enabled(parameters.options.hot_reload);
     let scene = parameters.scene;
     let preset = parameters.preset;
 
@@ -94,21 +112,22 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
     let mut delta_time: f64;
     let mut last_frame: f64 = 0.0;
 
-    let (viewport_width, viewport_height) = config.viewport_dimensions();
+    let (viewport_width, viewport_height) = parameters.config.viewport_dimensions();
 
     // Camera setup
     let mut camera = preset.camera.clone();
+    unsafe { camera.transform.init()? };
     let mut first_mouse = true;
     let mut last_x: f32 = viewport_width as f32 / 2.0;
     let mut last_y: f32 = viewport_height as f32 / 2.0;
 
     // Static eye
-    let mut static_eye = Transform::default();
+    let mut static_eye = unsafe { Transform::new()? };
     static_eye.position = point3(0.0, 0.0, -2.0);
 
     // FPS variables
     let mut frame_count = 0;
-    let mut starting_time: f64 = glfw.get_time();
+    let mut starting_time: f64 = context.get_time();
     let mut elapsed_time: f64;
     let mut fps: f64 = 0.0;
 
@@ -117,92 +136,74 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
     };
 
     let mut menu = Menu::new(preset.clone());
+    // Settings saved in the preset take precedence over the ones in the config file
+    let mut config = match menu.get_data().11.settings {
+        Some(settings) => {
+            let config = parameters.config.with_octree_settings(settings);
+            log::info!("Configuration changed by the preset: {:#?}", config);
+            config
+        }
+        None => parameters.config,
+    };
 
     let render_model_shader = compile_shaders!(
         "assets/shaders/model/modelLoading.vert.glsl",
         "assets/shaders/model/modelLoading.frag.glsl",
         "assets/shaders/model/modelLoading.geom.glsl",
-    );
-    let mut cone_tracer = ConeTracer::init();
-    let mut cone_parameters = HashMap::new();
-    let mut debug_cone = unsafe { DebugCone::new() };
+    )?;
+    let mut cone_tracer = ConeTracer::init(&config)?;
+    let mut cone_settings = ConeSettings::default();
+    let camera_buffer = unsafe { UniformBuffer::<CameraBlock>::new() };
+    let mut debug_cone = unsafe { DebugCone::new(&config)? };
     let mut exposure: f32 = 0.0;
 
-    // Process scene
-    let (mut objects, mut light) = process_scene(scene);
+    // Benchmarks measure the build, so they never use the cache.
+    // Dynamic objects move, so the octree of their starting positions isn't worth keeping
+    let octree_cache_key = if parameters.options.no_octree_cache
+        || parameters.options.record_octree_build_time
+        || scene.objects.iter().any(|object| object.dynamic)
+    {
+        None
+    } else {
+        octree_cache::cache_key(&parameters.options, &scene)
+    };
 
-    let mut scene_aabb = Aabb::default();
-    for object in objects.iter_mut() {
-        let offset = vec3(
-            object.transform.position.x,
-            object.transform.position.y,
-            object.transform.position.z,
+    // Process scene
+    let (mut objects, mut lights) = process_scene(scene)?;
+    // Static objects first, their voxel fragments are the start of the voxel fragment list
+    objects.sort_by_key(|object| object.dynamic);
+    let first_dynamic_object = objects.partition_point(|object| !object.dynamic);
+    if lights.len() > MAX_LIGHTS {
+        log::warn!(
+            "Only the first {MAX_LIGHTS} of {} lights get direct lighting",
+            lights.len()
         );
-        let _rotation = Euler {
-            x: object.transform.rotation_x(),
-            y: object.transform.rotation_y(),
-            z: object.transform.rotation_z(),
-        };
-        scene_aabb.join(&object.model().aabb.offsetted(offset));
     }
-    let model_normalization_matrix = scene_aabb.normalization_matrix();
 
-    let (voxel_positions, number_of_voxel_fragments, voxel_colors, voxel_normals) =
-        unsafe { voxelization::build_voxel_fragment_list(&mut objects[..], &scene_aabb) };
-    info!(
-        "Number of voxel fragments: {}",
-        number_of_voxel_fragments.to_string()
-            .as_bytes()
-            .rchunks(3)
-            .rev()
-            .map(std::str::from_utf8)
-            .collect::<Result<Vec<&str>, _>>()
-            .unwrap()
-            .join("_")
-    );
-
-    let _instant_before_octree = Instant::now();
-    let mut octree = unsafe {
-        Octree::new(
-            voxel_positions.clone(),
-            number_of_voxel_fragments,
-            voxel_colors,
-            voxel_normals,
-        )
-    };
-    if parameters.options.record_octree_build_time {
-        let octree_build_time = _instant_before_octree.elapsed().as_millis().to_string();
-        let mut in_bytes = octree_build_time.as_bytes().to_vec();
-        let newline = b'\n';
-        in_bytes.push(newline);
-        let folder = parameters.options.get_name();
-        let file_name = format!("{folder}/octree_build_time.txt");
-        OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(&file_name)
-            .expect("Couldn't open record octree build time file")
-            .write(&in_bytes[..])
-            .expect("Couldn't append to record octree build time file");
+    let mut scene_aabb = Aabb::default();
+    for object in objects.iter_mut() {
+        scene_aabb.join(&object_aabb(object));
     }
-
-    let node_positions = unsafe {
-        helpers::get_values_from_texture_buffer(
-            octree.textures.node_positions.1,
-            octree.number_of_nodes(),
-            0_u32,
-        )
+    let mut model_normalization_matrix = scene_aabb.normalization_matrix();
+
+    let mut octree_build_time = None;
+    let cached_octree = octree_cache_key
+        .and_then(|key| unsafe { octree_cache::load(&config, &parameters.options, key) });
+    let mut octree = match cached_octree {
+        Some(octree) => octree,
+        None => {
+            let (octree, build_time) =
+                unsafe { build_octree(&config, &mut objects, first_dynamic_object, &scene_aabb)? };
+            octree_build_time = Some(build_time);
+            if let Some(key) = octree_cache_key {
+                unsafe { octree_cache::save(&parameters.options, key, &octree) };
+            }
+            octree
+        }
     };
+    let mut voxel_data = octree.geometry_data.voxel_data.clone();
 
-    let debug_nodes: Vec<DebugNode> = node_positions
-        .iter()
-        .enumerate()
-        .map(|(index, &node_position)| {
-            let position = helpers::r32ui_to_rgb10_a2ui(node_position);
-            let text = format!("({}, {}, {})", position.0, position.1, position.2);
-            DebugNode::new(index as u32, text)
-        })
-        .collect();
+    let mut debug_nodes = unsafe { get_debug_nodes(&octree) };
     let mut selected_debug_nodes: Vec<DebugNode> = Vec::new();
     let mut selected_debug_nodes_updated = false;
     let mut color_direction = vec3(1.0, 0.0, 0.0);
@@ -213,8 +214,38 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
     let mut photons: Vec<u32> = Vec::new();
     let mut children: Vec<u32> = Vec::new();
 
-    let mut light_maps = unsafe { octree.inject_light(&mut objects[..], &light, &scene_aabb) };
-    let quad = unsafe { Quad::new() };
+    let mut light_maps = unsafe { octree.inject_lights(&mut objects[..], &lights, &scene_aabb) };
+    if parameters.options.record_octree_build_time {
+        let octree_build_time =
+            octree_build_time.expect("The octree is always built when recording its build time");
+        // Not recorded, it includes compiling the octree shaders and allocating its textures
+        info!("First octree build took {octree_build_time:?}, startup work included");
+
+        for _ in 0..parameters.options.number_of_builds {
+            // Only the rebuild is timed, not the light injection still running on the GPU
+            unsafe { gl::Finish() };
+            let instant_before_octree = Instant::now();
+            unsafe {
+                profiler::begin_section("Octree build");
+                octree.rebuild();
+                profiler::end_section();
+                gl::Finish();
+            }
+            let octree_build_time = instant_before_octree.elapsed();
+            // The light timings only have stages while profiling, so the whole injection is timed here
+            let instant_before_light = Instant::now();
+            unsafe {
+                light_maps = octree.inject_lights(&mut objects[..], &lights, &scene_aabb);
+                gl::Finish();
+            }
+            append_build_time(
+                &parameters.options.get_name(),
+                octree_build_time,
+                instant_before_light.elapsed(),
+            );
+        }
+    }
+    let quad = unsafe { Quad::new()? };
     let camera_framebuffer = unsafe { GeometryFramebuffer::new() };
 
     let mut current_voxel_fragment_count: u32 = 0;
@@ -229,6 +260,7 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
     let mut should_show_final_image_quad = false;
 
     let mut should_move_light = false;
+    let mut should_move_object = false;
 
     let mut should_show_neighbors = false;
     let mut bricks_to_show = BricksToShow::default();
@@ -236,19 +268,22 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
     let mut should_show_debug_cone = false;
     let mut should_move_debug_cone = false;
 
-    let render_voxel_fragments_shader = RenderVoxelFragmentsShader::init(
-        voxel_positions.texture(),
-        voxel_colors.0,
-        number_of_voxel_fragments,
-    );
-    let render_border_voxel_fragments_shader = RenderVoxelFragmentsShader::init(
+    let mut render_voxel_fragments_shader = RenderVoxelFragmentsShader::init(
+        &config,
+        voxel_data.voxel_positions.texture(),
+        voxel_data.voxel_colors.0,
+        voxel_data.number_of_voxel_fragments,
+    )?;
+    let mut render_border_voxel_fragments_shader = RenderVoxelFragmentsShader::init(
+        &config,
         octree.border_data.voxel_data.voxel_positions.texture(),
         octree.border_data.voxel_data.voxel_colors.0,
         octree.border_data.voxel_data.number_of_voxel_fragments,
-    );
-    let render_depth_buffer_shader = compile_shaders!("assets/shaders/renderDepthQuad.glsl");
+    )?;
+    let render_depth_buffer_shader = compile_shaders!("assets/shaders/renderDepthQuad.glsl")?;
 
-    let photon_power = light.intensity() / (viewport_width * viewport_height) as f32;
+    let photon_power = lights.iter().map(Light::intensity).sum::<f32>()
+        / (viewport_width * viewport_height) as f32;
 
     // TODO: Theory. See if EGUI breaks the rendering in some way.
     // I remember it did some weird things with opacity, but maybe that was
@@ -256,25 +291,47 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
     let ui = Ui::instance();
 
     let mut fps_values = Vec::new();
+    let mut frame_times = Vec::new();
+    let mut visual_tests_passed = true;
 
-    // We create a camera from the view of the light.
+    // We create a camera from the view of the first light.
     let mut light_camera = Camera::default();
-    light_camera.transform = light.transform().clone();
+    if let Some(light) = lights.first() {
+        light_camera.transform = light.transform().clone();
+    }
 
     // The active camera is a reference to a camera.
     // All calculations are done with the active camera.
     // It can be switched at runtime. TODO: Not yet.
     let active_camera = &mut camera;
 
+    let camera_path = parameters
+        .options
+        .camera_path
+        .as_deref()
+        .map(camera_path::load_camera_path);
+    // Time of the first frame, where the camera path starts
+    let mut first_frame: Option<f64> = None;
+    // Set when new settings are applied from the menu or the scene grows,
+    // the octree is rebuilt with them
+    let mut config_to_apply: Option<(CoreConfig, Aabb)> = None;
+    // Why the last settings couldn't be applied, shown in the Settings window
+    let mut settings_error: Option<String> = None;
+
     // Render loop
     while !common::should_close_window() {
-        let current_frame = glfw.get_time();
+        let current_frame = context.get_time();
+        unsafe { shader::hot_reload::reload_changed() };
+        profiler::begin_section("Frame");
 
         frame_count += 1;
         delta_time = current_frame - last_frame;
         last_frame = current_frame;
 
         elapsed_time = current_frame - starting_time;
+        if parameters.options.seconds_for_fps.is_some() {
+            frame_times.push(delta_time * 1000.0);
+        }
 
         if elapsed_time > 1.0 {
             fps = frame_count as f64 / elapsed_time;
@@ -299,13 +356,23 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
             starting_time = current_frame;
         }
 
+        let first_frame = *first_frame.get_or_insert(current_frame);
+        if let Some(camera_path) = &camera_path {
+            camera_path.apply(
+                (current_frame - first_frame) as f32,
+                &mut active_camera.transform,
+            );
+        }
+
+        // Every pass this frame sees the camera from here
+        unsafe { camera_buffer.update(&active_camera.uniform_block()) };
+
         let geometry_buffers = unsafe {
+            let _timer = profiler::scope("Geometry buffer");
             active_camera.transform.take_photo(
                 &mut objects[..],
-                &active_camera.get_projection_matrix(),
                 &scene_aabb,
                 &camera_framebuffer,
-                0,
             )
         };
 
@@ -318,7 +385,7 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
             // gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
         }
 
-        for (_, event) in glfw::flush_messages(&parameters.events) {
+        for event in context.flush_events() {
             // Events
             if let glfw::WindowEvent::Key(glfw::Key::Escape, _, glfw::Action::Press, _) = event {
                 menu.toggle_showing(&mut last_x, &mut last_y);
@@ -335,6 +402,7 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
                 common::handle_show_model(&event, &mut show_model);
                 common::handle_show_voxel_fragment_list(&event, &mut show_voxel_fragment_list);
                 common::handle_light_movement(&event, &mut should_move_light);
+                common::handle_object_movement(&event, &mut should_move_object);
             } else {
                 menu.handle_event(event);
             }
@@ -346,17 +414,19 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
         if ui.is_showing() {
             menu.show_main_window();
             menu.render((
-                (),
+                AllNodesMenuInput::new(config.last_octree_level()),
                 NodeSearchMenuInput::new(&debug_nodes),
                 (),
                 ChildrenMenuInput::new(&children),
-                DiagnosticsMenuInput::new(fps),
+                DiagnosticsMenuInput::new(fps, octree.light_timings()),
                 (),
                 PhotonsMenuInput::new(&photons),
                 SavePresetMenuInput::new(&active_camera, menu.sub_menus.clone()), // TODO: Remove clone
                 (),
                 (),
                 (),
+                SettingsMenuInput::new(config, settings_error.as_deref()),
+                (),
             ));
             let outputs = menu.get_data();
 
@@ -394,28 +464,77 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
             // TODO: there is quite a bit of cloning here
             debug_cone.parameters = outputs.9.debug_cone_parameters.clone();
             debug_cone.point_to_light = outputs.9.point_to_light;
-            cone_parameters.insert(
-                "shadowConeParameters",
-                outputs.9.shadow_cone_parameters.clone(),
-            );
-            cone_parameters.insert(
-                "ambientOcclusionConeParameters",
-                outputs.9.ambient_occlusion_cone_parameters.clone(),
-            );
-            cone_parameters.insert(
-                "diffuseConeParameters",
-                outputs.9.diffuse_cone_parameters.clone(),
-            );
-            cone_parameters.insert(
-                "specularConeParameters",
-                outputs.9.specular_cone_parameters.clone(),
-            );
+            cone_settings = ConeSettings {
+                shadow: outputs.9.shadow_cone_parameters.clone(),
+                ambient_occlusion: outputs.9.ambient_occlusion_cone_parameters.clone(),
+                diffuse: outputs.9.diffuse_cone_parameters.clone(),
+                specular: outputs.9.specular_cone_parameters.clone(),
+            };
             // This one doesn't come from `get_data()` but is still relevant to `debug_cone`
             geometry_buffer_coordinates = menu.get_quad_coordinates();
 
+            // Settings
+            if outputs.11.should_apply {
+                config_to_apply = outputs
+                    .11
+                    .settings
+                    .map(|settings| (config.with_octree_settings(settings), scene_aabb));
+            }
+
             menu.is_picking = outputs.10.is_picking;
         }
 
+        if let Some((new_config, new_scene_aabb)) = config_to_apply.take() {
+            log::info!("Rebuilding the octree with configuration: {:#?}", new_config);
+            // The old octree stays until the new one is built, so it's kept if anything fails
+            let built = unsafe {
+                build_for_config(
+                    &new_config,
+                    &config,
+                    &mut objects,
+                    first_dynamic_object,
+                    &new_scene_aabb,
+                    &mut cone_tracer,
+                    &mut debug_cone,
+                )
+            };
+            match built {
+                Ok(new_octree) => {
+                    settings_error = None;
+                    config = new_config;
+                    // The scene may have grown to fit a dynamic object
+                    scene_aabb = new_scene_aabb;
+                    model_normalization_matrix = scene_aabb.normalization_matrix();
+                    unsafe {
+                        std::mem::replace(&mut octree, new_octree).delete();
+                        light_maps = octree.inject_lights(&mut objects[..], &lights, &scene_aabb);
+                        debug_nodes = get_debug_nodes(&octree);
+                    }
+                    voxel_data = octree.geometry_data.voxel_data.clone();
+                    // Node indices of the old octree mean nothing in the new one
+                    selected_debug_nodes.clear();
+                    photons.clear();
+                    children.clear();
+                    render_voxel_fragments_shader.set_voxel_fragments(
+                        &config,
+                        voxel_data.voxel_positions.texture(),
+                        voxel_data.voxel_colors.0,
+                        voxel_data.number_of_voxel_fragments,
+                    );
+                    render_border_voxel_fragments_shader.set_voxel_fragments(
+                        &config,
+                        octree.border_data.voxel_data.voxel_positions.texture(),
+                        octree.border_data.voxel_data.voxel_colors.0,
+                        octree.border_data.voxel_data.number_of_voxel_fragments,
+                    );
+                }
+                Err(error) => {
+                    log::error!("Couldn't rebuild the octree, keeping the current one: {error}");
+                    settings_error = Some(error.to_string());
+                }
+            }
+        }
+
         // This is for debugging
         if selected_debug_nodes_updated {
             selected_debug_nodes_updated = false;
@@ -440,22 +559,53 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
         }
 
         // Input
-        if !ui.is_showing() {
-            let transform = if should_move_light {
+        if !ui.is_showing() && should_move_object && first_dynamic_object < objects.len() {
+            // Only the first dynamic object can be moved
+            let transform = &mut objects[first_dynamic_object].transform;
+            let previous_position = transform.position;
+            unsafe {
+                common::process_movement_input(delta_time as f32, transform);
+            }
+            let has_moved = transform.position != previous_position;
+            let moved_object_aabb = object_aabb(&mut objects[first_dynamic_object]);
+            if has_moved && !scene_aabb.contains(&moved_object_aabb) {
+                // Voxels outside the scene would be clipped, so the octree is built again
+                // for a bigger one. With some room, so it isn't rebuilt every frame
+                let margin = (moved_object_aabb.max_vertex - moved_object_aabb.min_vertex) / 2.0;
+                let mut grown_scene_aabb = scene_aabb;
+                grown_scene_aabb.join(&Aabb {
+                    min_vertex: moved_object_aabb.min_vertex - margin,
+                    max_vertex: moved_object_aabb.max_vertex + margin,
+                });
+                config_to_apply = Some((config, grown_scene_aabb));
+            } else if has_moved {
                 unsafe {
-                    if frame_count % 60 == 0 {
-                        light_maps = // TODO: This takes too long, optimize
-                            octree.inject_light(
-                                &mut objects[..],
-                                &light,
-                                &scene_aabb,
-                                );
-                        light.transform_mut()
-                    } else {
-                        light.transform_mut()
-                    }
+                    profiler::begin_section("Octree build");
+                    let update = update_dynamic_objects(
+                        &mut octree,
+                        &mut objects[first_dynamic_object..],
+                        &scene_aabb,
+                    );
+                    profiler::end_section();
+                    update?;
+                    light_maps =
+                        octree.inject_lights_into_dirty_nodes(&mut objects[..], &lights, &scene_aabb);
                 }
-            } else if should_move_debug_cone {
+            }
+        } else if !ui.is_showing() && should_move_light && !lights.is_empty() {
+            // Only the first light can be moved
+            let transform = lights[0].transform_mut();
+            let previous_position = transform.position;
+            unsafe {
+                common::process_movement_input(delta_time as f32, transform);
+            }
+            if transform.position != previous_position {
+                unsafe {
+                    light_maps = octree.update_light(&mut objects[..], &lights, 0, &scene_aabb);
+                }
+            }
+        } else if !ui.is_showing() {
+            let transform = if should_move_debug_cone {
                 &mut debug_cone.transform
             } else {
                 &mut active_camera.transform
@@ -473,16 +623,16 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
             //     0.0001,
             //     10000.0,
             // );
-            let projection = active_camera.get_projection_matrix();
-            let view = active_camera.transform.get_view_matrix();
+            cone_tracer.update_blocks(&lights, &cone_settings);
+
             let mut model = Matrix4::<f32>::from_translation(vec3(0.0, 0.0, 0.0));
             model = model * Matrix4::from_scale(1.);
 
             if show_voxel_fragment_list {
                 if should_move_light {
-                    render_border_voxel_fragments_shader.run(&projection, &view, &model);
+                    render_border_voxel_fragments_shader.run(&model);
                 } else {
-                    render_voxel_fragments_shader.run(&projection, &view, &model);
+                    render_voxel_fragments_shader.run(&model);
                 }
             }
 
@@ -494,8 +644,6 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
             if show_octree {
                 octree.render(
                     &model,
-                    &view,
-                    &projection,
                     current_octree_level,
                     color_direction,
                     should_show_normals,
@@ -511,17 +659,15 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
                     .map(|node| node.index())
                     .collect(),
             );
-            octree.run_node_positions_shader(&projection, &view, &model);
+            octree.run_node_positions_shader(&model);
             octree.set_bricks_to_show(bricks_to_show);
 
             if should_show_neighbors {
-                octree.run_node_neighbors_shader(&projection, &view, &model);
+                octree.run_node_neighbors_shader(&model);
             }
 
             if bricks_to_show.at_least_one() {
                 octree.run_node_bricks_shader(
-                    &projection,
-                    &view,
                     &model,
                     color_direction,
                     brick_attribute,
@@ -532,8 +678,6 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
             if show_model {
                 // Render model normally
                 render_model_shader.use_program();
-                render_model_shader.set_mat4(c_str!("projection"), &projection);
-                render_model_shader.set_mat4(c_str!("view"), &view);
                 // Model and model normalization matrix get set in the draw call
                 for object in objects.iter_mut() {
                     object.draw(&render_model_shader, &model_normalization_matrix);
@@ -541,13 +685,10 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
             }
 
             cone_tracer.run(
-                &light,
                 &octree.textures,
                 &geometry_buffers,
-                light_maps,
+                &light_maps,
                 &quad,
-                &active_camera,
-                &cone_parameters,
                 if parameters.options.screenshot {
                     Some(parameters.options.get_name())
                 } else {
@@ -556,19 +697,26 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
                 exposure,
             );
 
+            if parameters.options.visual_tests {
+                visual_tests_passed = visual_tests::run_visual_test(
+                    &parameters.options,
+                    cone_tracer.processed_framebuffer(),
+                );
+            }
+
             if should_show_debug_cone {
                 debug_cone.run(
                     &octree.textures,
-                    &projection,
-                    &view,
                     &mut selected_debug_nodes,
                     &geometry_buffers,
                     &geometry_buffer_coordinates,
-                    &light,
+                    lights.first(),
                 );
             }
-            static_eye.draw_gizmo(&projection, &view);
-            light.draw_gizmo(&projection, &view);
+            static_eye.draw_gizmo();
+            for light in lights.iter() {
+                light.draw_gizmo();
+            }
             // quad.render(light_maps.1);
 
             // let quad_vao = quad.get_vao();
@@ -592,16 +740,21 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
         }
 
         ui.end_frame();
+        unsafe { profiler::end_section() };
 
         current_voxel_fragment_count =
-            (current_voxel_fragment_count + 10000).min(number_of_voxel_fragments);
+            (current_voxel_fragment_count + 10000).min(voxel_data.number_of_voxel_fragments);
 
         // Swap buffers and poll I/O events
         common::swap_buffers();
-        glfw.poll_events();
+        context.poll_events();
 
         if parameters.options.screenshot
+            || parameters.options.visual_tests
             || parameters.options.record_octree_build_time
+            // Nothing to look at without a window
+            || (parameters.options.context == ContextBackend::Egl
+                && parameters.options.seconds_for_fps.is_none())
             || parameters
                 .options
                 .seconds_for_fps
@@ -610,4 +763,198 @@ fn run_application(parameters: ApplicationParameters, mut glfw: Glfw) {
             break;
         }
     }
+
+    if parameters.options.seconds_for_fps.is_some() {
+        write_frame_times(&parameters.options.get_name(), &frame_times);
+    }
+    if parameters.options.profile {
+        write_gpu_timings(&parameters.options.get_name());
+    }
+
+    Ok(visual_tests_passed)
+}
+
+/// Appends the octree build time and the light injection time of this run to
+/// `build_times.csv` in `folder`, the benchmark runner reads them from there
+fn append_build_time(folder: &str, octree_build_time: Duration, light_injection_time: Duration) {
+    std::fs::create_dir_all(folder).expect("Couldn't create the benchmark folder");
+    let file_name = format!("{folder}/build_times.csv");
+    let is_new_file = !Path::new(&file_name).exists();
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&file_name)
+        .expect("Couldn't open build times file");
+    if is_new_file {
+        writeln!(file, "octree_build_ms,light_injection_ms")
+            .expect("Couldn't write to build times file");
+    }
+    writeln!(
+        file,
+        "{},{}",
+        octree_build_time.as_secs_f64() * 1000.0,
+        light_injection_time.as_secs_f64() * 1000.0
+    )
+    .expect("Couldn't append to build times file");
+}
+
+/// Writes the time of every frame in milliseconds to `frame_times.txt` in `folder`, one per line
+fn write_frame_times(folder: &str, frame_times: &[f64]) {
+    std::fs::create_dir_all(folder).expect("Couldn't create the benchmark folder");
+    let file_name = format!("{folder}/frame_times.txt");
+    let contents: String = frame_times
+        .iter()
+        .map(|frame_time| format!("{frame_time}\n"))
+        .collect();
+    std::fs::write(&file_name, contents).expect("Couldn't write frame times file");
+}
+
+/// Writes the timings of the profiler to `folder` as JSON and CSV
+fn write_gpu_timings(folder: &str) {
+    // The last runs may not have their results yet
+    unsafe { profiler::finish() };
+    std::fs::create_dir_all(folder).expect("Couldn't create the benchmark folder");
+    let json_file_name = format!("{folder}/gpu_timings.json");
+    profiler::write_json(&json_file_name).expect("Couldn't write the GPU timings as JSON");
+    let csv_file_name = format!("{folder}/gpu_timings.csv");
+    profiler::write_csv(&csv_file_name).expect("Couldn't write the GPU timings as CSV");
+    info!("GPU timings written to {json_file_name} and {csv_file_name}");
+}
+
+/// Voxelizes the objects and builds an octree with them, the static ones first.
+/// Returns the octree and the time it took to build it, voxelization of the static objects excluded
+unsafe fn build_octree(
+    config: &CoreConfig,
+    objects: &mut [Object],
+    first_dynamic_object: usize,
+    scene_aabb: &Aabb,
+) -> Result<(Octree, Duration), ShaderError> {
+    profiler::begin_section("Octree build");
+    let build = voxelize_and_build_octree(config, objects, first_dynamic_object, scene_aabb);
+    profiler::end_section();
+    let (octree, instant_before_octree) = build?;
+    // Commands only get queued otherwise, the time would be how long it took to issue them
+    gl::Finish();
+    Ok((octree, instant_before_octree.elapsed()))
+}
+
+/// The work of `build_octree` in its profiler section.
+/// Returns the octree and when its build started, after voxelizing the static objects
+unsafe fn voxelize_and_build_octree(
+    config: &CoreConfig,
+    objects: &mut [Object],
+    first_dynamic_object: usize,
+    scene_aabb: &Aabb,
+) -> Result<(Octree, Instant), ShaderError> {
+    let (voxel_positions, number_of_voxel_fragments, voxel_colors, voxel_normals) =
+        voxelization::build_voxel_fragment_list(
+            config,
+            &mut objects[..first_dynamic_object],
+            scene_aabb,
+        )?;
+    info!(
+        "Number of voxel fragments: {}",
+        number_of_voxel_fragments.to_string()
+            .as_bytes()
+            .rchunks(3)
+            .rev()
+            .map(std::str::from_utf8)
+            .collect::<Result<Vec<&str>, _>>()
+            .unwrap()
+            .join("_")
+    );
+
+    let instant_before_octree = Instant::now();
+    let mut octree = Octree::new(
+        config,
+        voxel_positions.clone(),
+        number_of_voxel_fragments,
+        voxel_colors,
+        voxel_normals,
+    )?;
+    if first_dynamic_object < objects.len() {
+        if let Err(error) =
+            update_dynamic_objects(&mut octree, &mut objects[first_dynamic_object..], scene_aabb)
+        {
+            octree.delete();
+            return Err(error);
+        }
+    }
+    Ok((octree, instant_before_octree))
+}
+
+/// Builds an octree for `new_config` and sets it on the cone tracers.
+/// If anything fails, what was built is freed and the cone tracers keep `old_config`
+#[allow(clippy::too_many_arguments)]
+unsafe fn build_for_config(
+    new_config: &CoreConfig,
+    old_config: &CoreConfig,
+    objects: &mut [Object],
+    first_dynamic_object: usize,
+    scene_aabb: &Aabb,
+    cone_tracer: &mut ConeTracer,
+    debug_cone: &mut DebugCone,
+) -> Result<Octree, ShaderError> {
+    let (octree, _) = build_octree(new_config, objects, first_dynamic_object, scene_aabb)?;
+    if let Err(error) = cone_tracer.set_config(new_config) {
+        octree.delete();
+        return Err(error);
+    }
+    if let Err(error) = debug_cone.set_config(new_config) {
+        octree.delete();
+        // Its shader compiled with the old config before, unless it was hot reloaded since
+        if let Err(restore_error) = cone_tracer.set_config(old_config) {
+            log::error!("Couldn't restore the cone tracer configuration: {restore_error}");
+        }
+        return Err(error);
+    }
+    Ok(octree)
+}
+
+/// A debug node per node of the octree, with its position as text
+unsafe fn get_debug_nodes(octree: &Octree) -> Vec<DebugNode> {
+    let node_positions = helpers::get_values_from_texture_buffer(
+        octree.textures.node_positions.1,
+        octree.number_of_nodes(),
+        0_u32,
+    );
+
+    node_positions
+        .iter()
+        .enumerate()
+        .map(|(index, &node_position)| {
+            let position = helpers::r32ui_to_rgb10_a2ui(node_position);
+            let text = format!("({}, {}, {})", position.0, position.1, position.2);
+            DebugNode::new(index as u32, text)
+        })
+        .collect()
+}
+
+/// Bounding box of `object` where it is now
+fn object_aabb(object: &mut Object) -> Aabb {
+    let position = object.transform.position;
+    object
+        .model()
+        .aabb
+        .offsetted(vec3(position.x, position.y, position.z))
+}
+
+/// Voxelizes the dynamic objects again and updates their nodes in the octree.
+/// Light has to be injected again afterwards, at least into the dirty nodes
+unsafe fn update_dynamic_objects(
+    octree: &mut Octree,
+    dynamic_objects: &mut [Object],
+    scene_aabb: &Aabb,
+) -> Result<(), ShaderError> {
+    let (voxel_positions, number_of_voxel_fragments, voxel_colors, voxel_normals) =
+        voxelization::build_voxel_fragment_list(octree.config(), dynamic_objects, scene_aabb)?;
+    log::debug!("Number of dynamic voxel fragments: {number_of_voxel_fragments}");
+    let dynamic_voxel_data = VoxelData {
+        voxel_positions,
+        number_of_voxel_fragments,
+        voxel_colors,
+        voxel_normals,
+    };
+    octree.update_dynamic_voxels(dynamic_voxel_data);
+    Ok(())
 }
@@ -0,0 +1,69 @@
+use std::{io, path::PathBuf};
+
+use core::{
+    config::Config,
+    octree::{self, Octree},
+};
+
+use engine::prelude::*;
+
+use crate::cli_arguments::Options;
+
+const CACHE_FOLDER: &str = "cache";
+
+/// Where the octree built for this config and scene is stored
+fn cache_path(options: &Options) -> String {
+    format!(
+        "{CACHE_FOLDER}/{}_{}.octree",
+        &options.config, &options.scene
+    )
+}
+
+/// Hash of everything the octree depends on: config, scene and model files, with the
+/// materials and textures of the models.
+/// `None` if some file couldn't be read, in which case the cache isn't used
+pub fn cache_key(options: &Options, scene: &Scene) -> Option<u64> {
+    let mut files = vec![
+        PathBuf::from(format!("{}.ron", &options.config)),
+        PathBuf::from(format!("scenes/{}.ron", &options.scene)),
+    ];
+    let model_files = scene
+        .models
+        .iter()
+        .map(|model| Model::files(&model.path))
+        .collect::<io::Result<Vec<_>>>();
+
+    match model_files.and_then(|model_files| {
+        files.extend(model_files.into_iter().flatten());
+        octree::cache_key(&files)
+    }) {
+        Ok(key) => Some(key),
+        Err(error) => {
+            log::warn!("Couldn't hash the scene files, not using the octree cache: {error}");
+            None
+        }
+    }
+}
+
+/// Returns the cached octree if there is one and it's still valid for `config`
+pub unsafe fn load(config: &Config, options: &Options, key: u64) -> Option<Octree> {
+    let path = cache_path(options);
+    match Octree::load(config, &path, key) {
+        Ok(octree) => {
+            log::info!("Octree loaded from cache {path}");
+            Some(octree)
+        }
+        Err(error) => {
+            log::info!("Not using octree cache {path}: {error}");
+            None
+        }
+    }
+}
+
+pub unsafe fn save(options: &Options, key: u64, octree: &Octree) {
+    let path = cache_path(options);
+    match octree.save(&path, key) {
+        Ok(()) => log::info!("Octree cached in {path}"),
+        Err(error) => log::warn!("Couldn't cache the octree in {path}: {error}"),
+    }
+}
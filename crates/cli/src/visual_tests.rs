@@ -0,0 +1,65 @@
+use std::path::Path;
+
+use engine::{framebuffer::ImageComparisonError, prelude::*};
+
+use crate::cli_arguments::Options;
+
+const SCREENSHOTS_FOLDER: &str = "screenshots";
+
+/// Compares the final image against the stored screenshot for the preset in use,
+/// or overwrites it if `--update-screenshots` was passed.
+/// Returns whether the visual test passed.
+pub fn run_visual_test(options: &Options, framebuffer: &Framebuffer<1>) -> bool {
+    if options.preset.is_empty() {
+        log::error!("Visual tests need a preset, the screenshot is named after it");
+        return false;
+    }
+
+    let screenshot_path = format!("{SCREENSHOTS_FOLDER}/{}.png", &options.preset);
+
+    if options.update_screenshots {
+        framebuffer.save_color_attachment_to_file(0, &screenshot_path);
+        log::info!("Screenshot updated: {screenshot_path}");
+        return true;
+    }
+
+    if !Path::new(&screenshot_path).exists() {
+        log::error!(
+            "No screenshot found in {screenshot_path}, run again with `--update-screenshots` to create it"
+        );
+        return false;
+    }
+
+    match framebuffer.compare_attachment_to_file(
+        0,
+        &screenshot_path,
+        options.visual_tests_tolerance,
+    ) {
+        Ok(comparison) if comparison.images_match() => {
+            log::info!("Visual test passed for preset {}", &options.preset);
+            true
+        }
+        Ok(comparison) => {
+            let diff_path = format!("{SCREENSHOTS_FOLDER}/diffs/{}.png", &options.preset);
+            comparison.save_diff_image(&diff_path);
+            log::error!(
+                "Visual test failed for preset {}: {} out of {} pixels differ. Diff saved in {diff_path}",
+                &options.preset,
+                comparison.different_pixels,
+                comparison.total_pixels,
+            );
+            false
+        }
+        Err(error @ ImageComparisonError::DifferentDimensions { .. }) => {
+            log::error!(
+                "Visual test failed for preset {}: {error}. Check the viewport dimensions of the config",
+                &options.preset
+            );
+            false
+        }
+        Err(error) => {
+            log::error!("Visual test failed for preset {}: {error}", &options.preset);
+            false
+        }
+    }
+}
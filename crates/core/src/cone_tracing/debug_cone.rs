@@ -3,7 +3,7 @@ use std::collections::HashSet;
 use std::fmt;
 
 use c_str_macro::c_str;
-use cgmath::{point3, vec3, Matrix4, Vector2, Vector3};
+use cgmath::{point3, vec3, Vector2, Vector3};
 use colored::{customcolors, Colorize};
 
 use gl::types::GLuint;
@@ -17,12 +17,14 @@ pub struct DebugCone {
     pub parameters: ConeParameters,
     pub point_to_light: bool,
     shader: Shader,
+    parameters_buffer: UniformBuffer<ConeParameters>,
     direction: Vector3<f32>,
     previous_values: HashSet<u32>,
     nodes_queried: BufferTexture,
     nodes_queried_counter: GLuint,
     sampled_colors_texture: BufferTexture,
     vao: GLuint,
+    config: Config,
 }
 
 pub struct VoxelData {
@@ -50,7 +52,8 @@ impl fmt::Display for VoxelData {
 }
 
 impl DebugCone {
-    pub unsafe fn new() -> Self {
+    pub unsafe fn new(config: &Config) -> Result<Self, ShaderError> {
+        let shader = Self::compile_shader(config)?;
         let mut vao = 0;
         gl::GenVertexArrays(1, &mut vao);
 
@@ -58,8 +61,9 @@ impl DebugCone {
         transform.movement_speed = 0.25;
         transform.position = point3(0.5, 0.5, 0.43);
 
-        Self {
-            shader: compile_shaders!("assets/shaders/debug/debugConeTracing.glsl", debug = true),
+        Ok(Self {
+            shader,
+            parameters_buffer: UniformBuffer::new(),
             transform,
             parameters: ConeParameters {
                 max_distance: 1.0,
@@ -82,18 +86,34 @@ impl DebugCone {
             nodes_queried_counter: helpers::generate_atomic_counter_buffer1(),
             point_to_light: false,
             vao,
-        }
+            config: *config,
+        })
+    }
+
+    /// Uses `config` from now on, for when the octree is rebuilt with it.
+    /// Keeps the previous one if the shader doesn't compile with it
+    pub fn set_config(&mut self, config: &Config) -> Result<(), ShaderError> {
+        let shader = Self::compile_shader(config)?;
+        unsafe { std::mem::replace(&mut self.shader, shader).delete() };
+        self.config = *config;
+        Ok(())
+    }
+
+    fn compile_shader(config: &Config) -> Result<Shader, ShaderError> {
+        compile_shaders!(
+            "assets/shaders/debug/debugConeTracing.glsl",
+            defines = config.shader_defines(),
+            debug = true,
+        )
     }
 
     pub unsafe fn run(
         &mut self,
         textures: &OctreeTextures,
-        projection: &Matrix4<f32>,
-        view: &Matrix4<f32>,
         selected_debug_nodes: &mut Vec<DebugNode>,
         geometry_buffers: &Textures<GEOMETRY_BUFFERS>,
         geometry_buffer_coordinates: &Vector2<f32>,
-        light: &Light,
+        light: Option<&Light>,
     ) {
         helpers::clear_texture_buffer(self.sampled_colors_texture.1, 100, 42f32, gl::DYNAMIC_READ);
         self.shader.use_program();
@@ -186,37 +206,41 @@ impl DebugCone {
             geometry_buffer_coordinates.y,
         );
 
-        let config = Config::instance();
+        let config = &self.config;
 
         self.shader
             .set_uint(c_str!("voxelDimension"), config.voxel_dimension());
         self.shader
             .set_uint(c_str!("maxOctreeLevel"), config.octree_levels() - 1);
-        self.shader.set_mat4(c_str!("projection"), projection);
-        self.shader.set_mat4(c_str!("view"), view);
         self.shader.set_vec3(
             c_str!("position"),
             self.transform.position.x,
             self.transform.position.y,
             self.transform.position.z,
         );
-        self.shader
-            .set_bool(c_str!("pointToLight"), self.point_to_light);
-        self.shader.set_vec3(
-            c_str!("lightPosition"),
-            light.transform().position.x,
-            light.transform().position.y,
-            light.transform().position.z,
+        // Without lights there is nothing to point to
+        self.shader.set_bool(
+            c_str!("pointToLight"),
+            self.point_to_light && light.is_some(),
         );
+        if let Some(light) = light {
+            self.shader.set_vec3(
+                c_str!("lightPosition"),
+                light.transform().position.x,
+                light.transform().position.y,
+                light.transform().position.z,
+            );
+        }
         self.shader.set_vec3(
             c_str!("axis"),
             self.direction.x,
             self.direction.y,
             self.direction.z,
         );
-        self.parameters.set_uniforms("parameters", &self.shader);
+        self.parameters_buffer.update(&self.parameters);
 
         let number_of_cones = 1; // For now
+        self.shader.validate();
         gl::DrawArrays(gl::POINTS, 0, number_of_cones);
 
         let values = helpers::get_values_from_texture_buffer(self.nodes_queried.1, 1000, 42u32);
@@ -1,13 +1,11 @@
-use std::ffi::CString;
-
-use engine::prelude::*;
+use engine::{prelude::*, shader::half_angle_in_radians};
 use serde::{Deserialize, Serialize};
 
 mod debug_cone;
 pub use debug_cone::DebugCone;
 
 mod voxel_cone_trace;
-pub use voxel_cone_trace::{ConeTracer, Toggles};
+pub use voxel_cone_trace::{ConeTracer, Toggles, MAX_LIGHTS};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ConeParameters {
@@ -24,16 +22,40 @@ impl Default for ConeParameters {
     }
 }
 
-impl ConeParameters {
-    pub unsafe fn set_uniforms(&self, parameters_key: &str, shader: &Shader) {
-        let half_cone_angle_uniform_key =
-            CString::new(format!("{parameters_key}.halfConeAngle").as_bytes()).unwrap();
-        let max_distance_uniform_key =
-            CString::new(format!("{parameters_key}.maxDistance").as_bytes()).unwrap();
-        shader.set_float(
-            &half_cone_angle_uniform_key,
-            self.cone_angle_in_degrees.to_radians() / 2.0,
-        );
-        shader.set_float(&max_distance_uniform_key, self.max_distance);
+/// The `ConeParameters` struct in `_uniformBlocks.glsl`, also the `DebugConeParameters` block
+/// of the debug cone. `ConeSettings` writes one for each kind of lighting
+impl UniformBlock for ConeParameters {
+    const BINDING: u32 = 3;
+
+    fn write_std140(&self, buffer: &mut Std140) {
+        buffer.structure(|parameters| {
+            parameters
+                .float(half_angle_in_radians(&self.cone_angle_in_degrees))
+                .float(self.max_distance);
+        });
+    }
+}
+
+/// `ConeSettings` uniform block, the cones traced for each kind of lighting
+#[derive(Debug, Default, Clone)]
+pub struct ConeSettings {
+    pub shadow: ConeParameters,
+    pub ambient_occlusion: ConeParameters,
+    pub diffuse: ConeParameters,
+    pub specular: ConeParameters,
+}
+
+impl UniformBlock for ConeSettings {
+    const BINDING: u32 = 2;
+
+    fn write_std140(&self, buffer: &mut Std140) {
+        for parameters in [
+            &self.shadow,
+            &self.ambient_occlusion,
+            &self.diffuse,
+            &self.specular,
+        ] {
+            parameters.write_std140(buffer);
+        }
     }
 }
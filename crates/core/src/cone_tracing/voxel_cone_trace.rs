@@ -1,105 +1,65 @@
-use std::collections::HashMap;
-
 use c_str_macro::c_str;
+use cgmath::{Point3, Vector3};
 use engine::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use super::ConeParameters;
+use super::ConeSettings;
 
 use crate::{config::Config, octree::OctreeTextures};
 
+/// Lights that contribute direct lighting, has to match `MAX_LIGHTS` in `_uniformBlocks.glsl`.
+/// Every light still contributes indirect lighting
+pub const MAX_LIGHTS: usize = 8;
+
 pub struct ConeTracer {
     shader: Shader,
     pub toggles: Toggles,
     framebuffer: Framebuffer<1>,
     post_processing_shader: Shader,
     processed_framebuffer: Framebuffer<1>,
+    lights_buffer: UniformBuffer<LightsBlock>,
+    cone_settings_buffer: UniformBuffer<ConeSettings>,
+    config: Config,
 }
 
 impl ConeTracer {
-    pub fn init() -> Self {
-        Self {
-            shader: compile_shaders!("assets/shaders/octree/coneTracing.glsl"),
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: Self::compile_shader(config)?,
             toggles: Toggles::default(),
             framebuffer: unsafe { Framebuffer::<1>::new_floating_point() },
-            post_processing_shader: compile_shaders!("assets/shaders/octree/postProcessing.glsl"),
+            post_processing_shader: compile_shaders!("assets/shaders/octree/postProcessing.glsl")?,
             processed_framebuffer: unsafe { Framebuffer::<1>::new() },
-        }
+            lights_buffer: unsafe { UniformBuffer::new() },
+            cone_settings_buffer: unsafe { UniformBuffer::new() },
+            config: *config,
+        })
+    }
+
+    /// Updates the `Lights` and `ConeSettings` uniform blocks, once per frame
+    pub unsafe fn update_blocks(&self, lights: &[Light], cone_settings: &ConeSettings) {
+        self.lights_buffer.update(&LightsBlock::new(lights));
+        self.cone_settings_buffer.update(cone_settings);
     }
 
     pub unsafe fn run(
         &self,
-        light: &Light,
         textures: &OctreeTextures,
         geometry_buffers: &Textures<GEOMETRY_BUFFERS>,
-        light_maps: (u32, u32, u32),
+        light_maps: &[(u32, u32, u32)],
         quad: &Quad,
-        camera: &Camera,
-        parameters: &HashMap<&str, ConeParameters>,
         screenshot_folder: Option<String>,
         exposure: f32,
     ) {
         self.shader.use_program();
 
-        let config = Config::instance();
-
-        self.shader
-            .set_uint(c_str!("voxelDimension"), config.voxel_dimension());
-        self.shader
-            .set_uint(c_str!("maxOctreeLevel"), config.last_octree_level());
-        self.shader
-            .set_bool(c_str!("shouldShowColor"), self.toggles.should_show_color());
-        self.shader.set_bool(
-            c_str!("shouldShowDirect"),
-            self.toggles.should_show_direct(),
-        );
-        self.shader.set_bool(
-            c_str!("shouldShowIndirect"),
-            self.toggles.should_show_indirect(),
-        );
-        self.shader.set_bool(
-            c_str!("shouldShowIndirectSpecular"),
-            self.toggles.should_show_indirect_specular(),
-        );
-        self.shader.set_bool(
-            c_str!("shouldShowAmbientOcclusion"),
-            self.toggles.should_show_ambient_occlusion(),
-        );
-        self.shader.set_vec3(
-            c_str!("eyePosition"),
-            camera.transform.position.x,
-            camera.transform.position.y,
-            camera.transform.position.z,
-        );
-        if light.is_directional() {
-            self.shader.set_vec3(
-                c_str!("directionalLight.direction"),
-                light.transform().get_forward().x,
-                light.transform().get_forward().y,
-                light.transform().get_forward().z,
-            );
-            self.shader
-                .set_float(c_str!("directionalLight.intensity"), light.intensity());
-        } else {
-            self.shader.set_vec3(
-                c_str!("pointLight.position"),
-                light.transform().position.x,
-                light.transform().position.y,
-                light.transform().position.z,
-            );
-            self.shader
-                .set_float(c_str!("pointLight.intensity"), light.intensity());
-        }
-        self.shader.set_vec3(
-            c_str!("pointLight.color"),
-            1.0, // White
-            1.0,
-            1.0,
-        );
-        self.shader.set_float(c_str!("shininess"), 30.0); // TODO: This should be decided per material
-        for (key, value) in parameters.iter() {
-            value.set_uniforms(&key, &self.shader);
-        }
+        let uniforms = FrameUniforms {
+            voxel_dimension: self.config.voxel_dimension(),
+            max_octree_level: self.config.last_octree_level(),
+            toggles: &self.toggles,
+            shininess: 30.0, // TODO: This should be decided per material
+        };
+        uniforms.set_uniforms("", &self.shader);
         helpers::bind_image_texture(0, textures.node_pool.0, gl::READ_ONLY, gl::R32UI);
 
         let brick_pool_textures = vec![
@@ -173,9 +133,6 @@ impl ConeTracer {
             texture_counter += 1;
         }
 
-        self.shader
-            .set_bool(c_str!("isDirectional"), light.is_directional());
-
         if self.toggles.should_show_final_image_quad() {
             self.create_image(quad); // Loads it in the framebuffer
             self.run_post_processing(quad, exposure); // Runs post processing effects on the framebuffer, stores in final framebuffer
@@ -189,12 +146,36 @@ impl ConeTracer {
         }
     }
 
+    /// Uses `config` from now on, for when the octree is rebuilt with it.
+    /// Keeps the previous one if the shader doesn't compile with it
+    pub fn set_config(&mut self, config: &Config) -> Result<(), ShaderError> {
+        let shader = Self::compile_shader(config)?;
+        unsafe { std::mem::replace(&mut self.shader, shader).delete() };
+        self.config = *config;
+        Ok(())
+    }
+
+    fn compile_shader(config: &Config) -> Result<Shader, ShaderError> {
+        compile_shaders!(
+            "assets/shaders/octree/coneTracing.glsl",
+            defines = config.shader_defines(),
+        )
+    }
+
+    /// Framebuffer holding the final image, after post processing
+    pub fn processed_framebuffer(&self) -> &Framebuffer<1> {
+        &self.processed_framebuffer
+    }
+
     unsafe fn create_image(&self, quad: &Quad) {
+        let _timer = profiler::scope("Cone tracing");
+
         gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer.fbo());
         gl::Enable(gl::DEPTH_TEST);
         gl::ClearColor(0.0, 0.0, 0.0, 0.0);
         gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
         gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        self.shader.validate();
         gl::BindVertexArray(quad.get_vao());
         gl::DrawElements(
             gl::TRIANGLES,
@@ -207,6 +188,8 @@ impl ConeTracer {
     }
 
     unsafe fn run_post_processing(&self, quad: &Quad, exposure: f32) {
+        let _timer = profiler::scope("Post processing");
+
         // Set uniforms
         self.post_processing_shader.use_program();
         gl::ActiveTexture(gl::TEXTURE0);
@@ -225,6 +208,7 @@ impl ConeTracer {
         gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
         // Draw using quad
+        self.post_processing_shader.validate();
         gl::BindVertexArray(quad.get_vao());
         gl::DrawElements(
             gl::TRIANGLES,
@@ -241,7 +225,84 @@ impl ConeTracer {
     }
 }
 
-#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+/// Uniforms of `coneTracing.glsl` that change every frame
+#[derive(ShaderUniforms)]
+struct FrameUniforms<'a> {
+    voxel_dimension: u32,
+    max_octree_level: u32,
+    #[uniform(flatten)]
+    toggles: &'a Toggles,
+    shininess: f32,
+}
+
+/// `Lights` uniform block, only the first `MAX_LIGHTS` lights contribute direct lighting
+struct LightsBlock {
+    lights: Vec<LightData>,
+}
+
+impl LightsBlock {
+    fn new(lights: &[Light]) -> Self {
+        Self {
+            lights: lights.iter().take(MAX_LIGHTS).map(LightData::new).collect(),
+        }
+    }
+}
+
+impl UniformBlock for LightsBlock {
+    const BINDING: u32 = 1;
+
+    fn write_std140(&self, buffer: &mut Std140) {
+        for index in 0..MAX_LIGHTS {
+            // The whole array is written, unused lights are zeroed
+            let light = self.lights.get(index).copied().unwrap_or_default();
+            buffer.structure(|data| {
+                data.point3(light.position)
+                    .vec3(light.direction)
+                    .point3(light.color)
+                    .float(light.intensity)
+                    .bool(light.is_directional);
+            });
+        }
+        buffer.uint(self.lights.len() as u32);
+    }
+}
+
+/// `Light` in `_uniformBlocks.glsl`
+#[derive(Clone, Copy)]
+struct LightData {
+    position: Point3<f32>,
+    direction: Vector3<f32>,
+    color: Point3<f32>,
+    intensity: f32,
+    is_directional: bool,
+}
+
+impl LightData {
+    fn new(light: &Light) -> Self {
+        Self {
+            position: light.transform().position,
+            direction: light.transform().get_forward(),
+            color: light.color(),
+            intensity: light.intensity(),
+            is_directional: light.is_directional(),
+        }
+    }
+}
+
+impl Default for LightData {
+    fn default() -> Self {
+        Self {
+            position: Point3::new(0.0, 0.0, 0.0),
+            direction: Vector3::new(0.0, 0.0, 0.0),
+            color: Point3::new(0.0, 0.0, 0.0),
+            intensity: 0.0,
+            is_directional: false,
+        }
+    }
+}
+
+/// Set as the `shouldShow*` uniforms
+#[derive(Debug, Default, Serialize, Deserialize, Clone, ShaderUniforms)]
 #[serde(default)]
 pub struct Toggles {
     should_show_color: bool,
@@ -1,22 +1,37 @@
-use once_cell::sync::OnceCell;
-use serde::Deserialize;
+use std::{fs::File, io, path::Path};
 
-#[derive(Debug, Deserialize, Clone)]
+use engine::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::BRICK_SIZE;
+
+/// Configuration of everything the octree and the renderer depend on.
+/// Each octree, pass and renderer keeps its own copy, so several of them with
+/// different configurations can live in the same process
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(from = "ConfigFile")]
 pub struct Config {
-    #[serde(default = "default_brick_pool_resolution")]
     pub brick_pool_resolution: u32,
-    // TODO: This could be different than the one in the shaders right now
-    #[serde(default = "default_working_group_size")]
+    /// Size in x of the work groups of the octree compute shaders, see `shader_defines`
     pub working_group_size: u32,
-    #[serde(default = "default_viewport_dimensions")]
     viewport_dimensions: (i32, i32),
     voxel_dimension: u32,
-    #[serde(skip_deserializing)]
     octree_levels: u32,
-    #[serde(skip_deserializing)]
     last_octree_level: u32,
 }
 
+/// Config as it's written in the file, with the voxel dimension as an exponent
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default = "default_brick_pool_resolution")]
+    brick_pool_resolution: u32,
+    #[serde(default = "default_working_group_size")]
+    working_group_size: u32,
+    #[serde(default = "default_viewport_dimensions")]
+    viewport_dimensions: (i32, i32),
+    voxel_dimension: u32,
+}
+
 const fn default_brick_pool_resolution() -> u32 {
     384
 }
@@ -29,37 +44,61 @@ const fn default_viewport_dimensions() -> (i32, i32) {
     (840, 840)
 }
 
-static mut INSTANCE: OnceCell<Config> = OnceCell::new();
-
-impl Config {
-    /// Get the single instance of this struct
-    pub fn instance() -> &'static Self {
-        unsafe {
-            if let Some(config) = INSTANCE.get() {
-                &config
-            } else {
-                panic!("Must initialize core config");
-            }
-        }
-    }
+/// Part of the config that can be changed while the application is running,
+/// it's saved in presets
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct OctreeSettings {
+    pub voxel_dimension_exponent: u32,
+    pub brick_pool_resolution: u32,
+    pub working_group_size: u32,
+}
 
-    /// Initializes the config
-    /// Must be called before any call to `instance`
-    /// Overrides any previous config
-    pub unsafe fn initialize(mut config: Self) {
-        config.set_voxel_dimension(config.voxel_dimension);
-        let _ = INSTANCE.set(config);
+impl From<ConfigFile> for Config {
+    fn from(file: ConfigFile) -> Self {
+        let mut config = Self::new(file.voxel_dimension);
+        config.brick_pool_resolution = file.brick_pool_resolution;
+        config.working_group_size = file.working_group_size;
+        config.viewport_dimensions = file.viewport_dimensions;
+        config
     }
+}
 
+impl Config {
+    /// Config with a voxel dimension of 2^`voxel_dimension_exponent` and defaults for the rest
     pub fn new(voxel_dimension_exponent: u32) -> Self {
-        Self {
-            brick_pool_resolution: 384,
-            working_group_size: 64,
-            viewport_dimensions: (840, 840),
-            // Will be set correctly in `initialize` later
-            voxel_dimension: voxel_dimension_exponent,
+        let mut config = Self {
+            brick_pool_resolution: default_brick_pool_resolution(),
+            working_group_size: default_working_group_size(),
+            viewport_dimensions: default_viewport_dimensions(),
+            voxel_dimension: 0,
             octree_levels: 0,
             last_octree_level: 0,
+        };
+        config.set_voxel_dimension(voxel_dimension_exponent);
+        config
+    }
+
+    /// Loads the config from a RON file, like `config.ron`
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        ron::de::from_reader(file)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Same config with other octree settings, the viewport stays the same
+    pub fn with_octree_settings(&self, settings: OctreeSettings) -> Self {
+        let mut config = *self;
+        config.brick_pool_resolution = settings.brick_pool_resolution;
+        config.working_group_size = settings.working_group_size;
+        config.set_voxel_dimension(settings.voxel_dimension_exponent);
+        config
+    }
+
+    pub fn octree_settings(&self) -> OctreeSettings {
+        OctreeSettings {
+            voxel_dimension_exponent: self.voxel_dimension.ilog2(),
+            brick_pool_resolution: self.brick_pool_resolution,
+            working_group_size: self.working_group_size,
         }
     }
 
@@ -79,6 +118,17 @@ impl Config {
         self.viewport_dimensions
     }
 
+    /// Defines for the octree shaders, so their constants agree with this config.
+    /// Compute shaders use `WORKING_GROUP_SIZE` as `local_size_x`
+    pub fn shader_defines(&self) -> ShaderDefines {
+        ShaderDefines::new()
+            .define("WORKING_GROUP_SIZE", self.working_group_size)
+            .define("BRICK_SIZE", BRICK_SIZE)
+            .define("BRICK_POOL_RESOLUTION", self.brick_pool_resolution)
+            .define("VOXEL_DIMENSION", self.voxel_dimension)
+            .define("OCTREE_LEVELS", self.octree_levels)
+    }
+
     /// Sets the voxel dimension, the number of octree levels is based on that
     fn set_voxel_dimension(&mut self, voxel_dimension_exponent: u32) {
         self.voxel_dimension = 2_u32.pow(voxel_dimension_exponent);
@@ -91,32 +141,49 @@ impl Config {
 mod tests {
     use super::*;
 
-    // TODO: Fix, the `should_panic` is not working
     #[test]
-    #[should_panic]
-    // Given this is stateful code, this test should run first (they are ran alfabetically)
-    fn aauninitialized_config_should_panic() {
-        let _ = Config::instance();
-    }
-
-    #[test]
-    fn config_initialization_works() {
-        let voxel_dimension_exponent = 4;
-        unsafe {
-            Config::initialize(Config::new(voxel_dimension_exponent));
-        };
-        let config = Config::instance();
+    fn octree_levels_depend_on_voxel_dimension() {
+        let config = Config::new(4);
         assert_eq!(config.voxel_dimension(), 16);
         assert_eq!(config.octree_levels(), 4);
         assert_eq!(config.last_octree_level(), 3);
     }
 
     #[test]
-    #[should_panic]
-    fn initializing_twice_should_panic() {
-        let voxel_dimension_exponent = 4;
-        unsafe {
-            Config::initialize(Config::new(voxel_dimension_exponent));
+    fn configs_are_independent() {
+        let small = Config::new(4);
+        let big = Config::new(9);
+        assert_eq!(small.voxel_dimension(), 16);
+        assert_eq!(big.voxel_dimension(), 512);
+        assert_eq!(big.octree_levels(), 9);
+    }
+
+    #[test]
+    fn deserialized_config_has_voxel_dimension_exponent() {
+        let config: Config =
+            ron::de::from_str("(voxel_dimension: 5, brick_pool_resolution: 48)").unwrap();
+        assert_eq!(config.voxel_dimension(), 32);
+        assert_eq!(config.octree_levels(), 5);
+        assert_eq!(config.brick_pool_resolution, 48);
+        assert_eq!(config.working_group_size, 64);
+        assert_eq!(config.viewport_dimensions(), (840, 840));
+    }
+
+    #[test]
+    fn octree_settings_replace_the_ones_of_the_config() {
+        let config = Config::new(8);
+        let settings = OctreeSettings {
+            voxel_dimension_exponent: 5,
+            brick_pool_resolution: 96,
+            working_group_size: 32,
         };
+        let changed = config.with_octree_settings(settings);
+        assert_eq!(changed.voxel_dimension(), 32);
+        assert_eq!(changed.octree_levels(), 5);
+        assert_eq!(changed.brick_pool_resolution, 96);
+        assert_eq!(changed.working_group_size, 32);
+        assert_eq!(changed.viewport_dimensions(), config.viewport_dimensions());
+        assert_eq!(changed.octree_settings(), settings);
+        assert_eq!(config.octree_settings().voxel_dimension_exponent, 8);
     }
 }
@@ -1,4 +1,6 @@
 pub const CHILDREN_PER_NODE: u32 = 8;
+/// Voxels per side of a brick
+pub const BRICK_SIZE: u32 = 3;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Axis {
@@ -29,6 +29,8 @@ pub struct SubMenus {
     camera: CameraMenu,
     cone_tracing: ConeTracingMenu,
     picker: PickerMenu,
+    settings: SettingsMenu,
+    shaders: ShadersMenu,
 }
 
 impl Menu {
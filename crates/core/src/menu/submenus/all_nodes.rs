@@ -3,7 +3,6 @@ use serde::{Serialize, Deserialize};
 
 use super::super::get_button_text;
 use super::SubMenu;
-use crate::config::Config;
 use crate::octree::OctreeDataType;
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -13,6 +12,16 @@ pub struct AllNodesMenu {
     pub output: AllNodesMenuOutput,
 }
 
+pub struct AllNodesMenuInput {
+    last_octree_level: u32,
+}
+
+impl AllNodesMenuInput {
+    pub fn new(last_octree_level: u32) -> Self {
+        Self { last_octree_level }
+    }
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct AllNodesMenuOutput {
@@ -22,7 +31,7 @@ pub struct AllNodesMenuOutput {
 }
 
 impl<'a> SubMenu for AllNodesMenu {
-    type InputData<'b> = ();
+    type InputData<'b> = AllNodesMenuInput;
     type OutputData = AllNodesMenuOutput;
 
     fn is_showing(&self) -> bool {
@@ -37,13 +46,11 @@ impl<'a> SubMenu for AllNodesMenu {
         &self.output
     }
 
-    fn render<'b>(&mut self, context: &egui::Context, _: &Self::InputData<'b>) {
+    fn render<'b>(&mut self, context: &egui::Context, input: &Self::InputData<'b>) {
         if !self.is_showing() {
             return;
         }
 
-        let config = Config::instance();
-
         egui::Window::new("All Nodes").show(context, |ui| {
             if ui
                 .button(get_button_text(
@@ -69,7 +76,7 @@ impl<'a> SubMenu for AllNodesMenu {
             ui.add(
                 egui::Slider::new(
                     &mut self.output.current_octree_level,
-                    0..=config.last_octree_level(),
+                    0..=input.last_octree_level,
                 )
             );
         });
@@ -1,23 +1,25 @@
-use engine::ui::prelude::*;
+use engine::{profiler, ui::prelude::*};
 use serde::{Serialize, Deserialize};
 
 use super::SubMenu;
+use crate::octree::LightTimings;
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct DiagnosticsMenu;
 
-pub struct DiagnosticsMenuInput {
+pub struct DiagnosticsMenuInput<'a> {
     fps: f64,
+    light_timings: &'a LightTimings,
 }
 
-impl DiagnosticsMenuInput {
-    pub fn new(fps: f64) -> Self {
-        Self { fps }
+impl<'a> DiagnosticsMenuInput<'a> {
+    pub fn new(fps: f64, light_timings: &'a LightTimings) -> Self {
+        Self { fps, light_timings }
     }
 }
 
 impl<'a> SubMenu for DiagnosticsMenu {
-    type InputData<'b> = DiagnosticsMenuInput;
+    type InputData<'b> = DiagnosticsMenuInput<'b>;
     type OutputData = ();
 
     fn is_showing(&self) -> bool {
@@ -38,6 +40,37 @@ impl<'a> SubMenu for DiagnosticsMenu {
         egui::Window::new("Diagnostics").show(context, |ui| {
             let fps_text = format!("FPS: {:.2}", input.fps);
             ui.label(fps_text);
+
+            ui.separator();
+            ui.label(format!(
+                "Light injection: {:.2} ms",
+                input.light_timings.total().as_secs_f64() * 1000.0
+            ));
+            for (stage, duration) in input.light_timings.stages() {
+                ui.label(format!("  {}: {:.2} ms", stage, duration.as_secs_f64() * 1000.0));
+            }
+            if input.light_timings.stages().is_empty() {
+                ui.label("  Enable GPU profiling and inject the light again to time it");
+            }
+
+            ui.separator();
+            let mut is_profiling = profiler::is_enabled();
+            if ui.checkbox(&mut is_profiling, "GPU profiling").changed() {
+                profiler::set_enabled(is_profiling);
+            }
+            for section in profiler::sections().iter() {
+                ui.collapsing(section.name, |ui| {
+                    ui.label(format!(
+                        "Last run: {:.2} ms, average of {} runs: {:.2} ms",
+                        section.last_run_milliseconds(),
+                        section.runs,
+                        section.average_milliseconds()
+                    ));
+                    for pass in section.last_run.iter() {
+                        ui.label(format!("  {}: {:.2} ms", pass.name, pass.milliseconds));
+                    }
+                });
+            }
         });
     }
 }
@@ -1,7 +1,7 @@
 use engine::ui::prelude::*;
 
 mod all_nodes;
-pub use all_nodes::AllNodesMenu;
+pub use all_nodes::{AllNodesMenu, AllNodesMenuInput};
 
 mod bricks;
 pub use bricks::BricksMenu;
@@ -33,6 +33,12 @@ pub use cone_tracing::{ConeTracingMenu, ConeTracingMenuOutput};
 mod picker;
 pub use picker::{PickerMenu, PickerMenuOutput};
 
+mod settings;
+pub use settings::{SettingsMenu, SettingsMenuInput, SettingsMenuOutput};
+
+mod shaders;
+pub use shaders::ShadersMenu;
+
 use serde::{Serialize, Deserialize};
 
 pub trait SubMenu: std::fmt::Debug + Default + for<'a> Deserialize<'a> + Serialize + Clone {
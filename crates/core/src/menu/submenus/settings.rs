@@ -0,0 +1,107 @@
+use engine::ui::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::SubMenu;
+use crate::config::{Config, OctreeSettings};
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SettingsMenu {
+    is_showing: bool,
+    output: SettingsMenuOutput,
+    /// Settings being edited, they start as the ones currently used
+    #[serde(skip)]
+    edited_settings: Option<OctreeSettings>,
+}
+
+pub struct SettingsMenuInput<'a> {
+    config: Config,
+    error: Option<&'a str>,
+}
+
+impl<'a> SettingsMenuInput<'a> {
+    /// `config` is the one the current octree was built with,
+    /// `error` why the last settings couldn't be applied, if they couldn't
+    pub fn new(config: Config, error: Option<&'a str>) -> Self {
+        Self { config, error }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SettingsMenuOutput {
+    /// Last applied settings, they take precedence over the config file.
+    /// `None` if they were never changed
+    pub settings: Option<OctreeSettings>,
+    /// Only true on the frame "Apply" is clicked, the octree has to be rebuilt with `settings`
+    #[serde(skip)]
+    pub should_apply: bool,
+}
+
+impl<'a> SubMenu for SettingsMenu {
+    type InputData<'b> = SettingsMenuInput<'b>;
+    type OutputData = SettingsMenuOutput;
+
+    fn is_showing(&self) -> bool {
+        self.is_showing
+    }
+
+    fn toggle_showing(&mut self) {
+        self.is_showing = !self.is_showing;
+    }
+
+    fn get_data(&self) -> &Self::OutputData {
+        &self.output
+    }
+
+    fn render<'b>(&mut self, context: &egui::Context, input: &Self::InputData<'b>) {
+        self.output.should_apply = false;
+        if !self.is_showing() {
+            return;
+        }
+
+        let current_settings = input.config.octree_settings();
+        let settings = self.edited_settings.get_or_insert(current_settings);
+
+        egui::Window::new("Settings").show(context, |ui| {
+            ui.label(format!(
+                "Voxel dimension: {}",
+                2_u32.pow(settings.voxel_dimension_exponent)
+            ));
+            // Below 4x4x4 voxels the octree is only the root, and the build needs a level under it
+            ui.add(
+                egui::Slider::new(&mut settings.voxel_dimension_exponent, 2..=10).text("Exponent"),
+            );
+            ui.label("Brick pool resolution");
+            // Bricks are 3x3x3 voxels
+            ui.add(egui::Slider::new(&mut settings.brick_pool_resolution, 3..=768).step_by(3.0));
+            ui.label("Working group size");
+            ui.add(egui::Slider::new(
+                &mut settings.working_group_size,
+                1..=1024,
+            ));
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Apply").clicked() {
+                    self.output.settings = Some(*settings);
+                    self.output.should_apply = true;
+                }
+                if ui.button("Reset").clicked() {
+                    *settings = current_settings;
+                }
+            });
+            if *settings != current_settings {
+                ui.label("Not applied yet, applying rebuilds the octree");
+            }
+            if let Some(error) = input.error {
+                ui.label(
+                    egui::RichText::new(format!(
+                        "Couldn't apply the settings, the old ones are kept: {error}"
+                    ))
+                    .color(egui::Color32::RED),
+                );
+            }
+        });
+    }
+}
@@ -0,0 +1,112 @@
+use engine::{
+    shader::{
+        hot_reload,
+        reflection::{self, ProgramReflection, UniformKind},
+    },
+    ui::prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use super::SubMenu;
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct ShadersMenu {
+    is_showing: bool,
+}
+
+impl<'a> SubMenu for ShadersMenu {
+    type InputData<'b> = ();
+    type OutputData = ();
+
+    fn is_showing(&self) -> bool {
+        self.is_showing
+    }
+
+    fn toggle_showing(&mut self) {
+        self.is_showing = !self.is_showing;
+    }
+
+    fn get_data(&self) -> &Self::OutputData {
+        &()
+    }
+
+    fn render<'b>(&mut self, context: &egui::Context, _: &Self::InputData<'b>) {
+        if !self.is_showing() {
+            return;
+        }
+
+        egui::Window::new("Shaders").show(context, |ui| {
+            let mut is_hot_reloading = hot_reload::is_enabled();
+            if ui
+                .checkbox(
+                    &mut is_hot_reloading,
+                    "Reload shaders when their files change",
+                )
+                .changed()
+            {
+                hot_reload::set_enabled(is_hot_reloading);
+            }
+            ui.label(format!(
+                "Shaders tracked: {}",
+                hot_reload::tracked_shaders()
+            ));
+
+            ui.separator();
+            let errors = hot_reload::errors();
+            if errors.is_empty() {
+                ui.label("No errors, every shader runs its last version");
+            } else {
+                ui.label(format!(
+                    "{} shaders failed to compile and run their previous version",
+                    errors.len()
+                ));
+            }
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for error in errors.iter() {
+                    ui.collapsing(error.shader.as_str(), |ui| {
+                        ui.label(egui::RichText::new(&error.message).monospace());
+                    });
+                }
+
+                ui.separator();
+                ui.collapsing("Uniforms", |ui| {
+                    for (name, program) in unsafe { reflection::programs() } {
+                        Self::show_uniforms(ui, &name, &program);
+                    }
+                });
+            });
+        });
+    }
+}
+
+impl ShadersMenu {
+    /// Uniforms of a program, the ones never set and the names it doesn't declare stand out
+    fn show_uniforms(ui: &mut egui::Ui, name: &str, program: &ProgramReflection) {
+        let unset = program.unset_uniforms().count();
+        let problems = unset + program.unknown_uniforms.len();
+        let header = if problems > 0 {
+            egui::RichText::new(format!("{name} ({problems} problems)"))
+                .color(egui::Color32::YELLOW)
+        } else {
+            egui::RichText::new(name)
+        };
+        ui.collapsing(header, |ui| {
+            for uniform in program.uniforms.iter() {
+                let text = egui::RichText::new(uniform.to_string()).monospace();
+                if uniform.kind == UniformKind::Value && !uniform.is_set {
+                    ui.label(text.color(egui::Color32::YELLOW))
+                        .on_hover_text("Never set, it's still 0");
+                } else {
+                    ui.label(text);
+                }
+            }
+            for unknown in program.unknown_uniforms.iter() {
+                ui.label(
+                    egui::RichText::new(format!("{unknown} isn't declared"))
+                        .monospace()
+                        .color(egui::Color32::RED),
+                );
+            }
+        });
+    }
+}
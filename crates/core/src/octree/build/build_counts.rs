@@ -0,0 +1,140 @@
+use std::{ffi::c_void, mem::size_of};
+
+use c_str_macro::c_str;
+use engine::{prelude::*, shader};
+
+use crate::config::Config;
+
+const NEXT_FREE_NODE: usize = 16;
+const BORDER_VOXEL_FRAGMENTS: usize = 17;
+const LENGTH: usize = 18;
+
+/// Counts the GPU keeps while building the octree, like the next node to allocate.
+/// Passes are dispatched from them with `glDispatchComputeIndirect`, so nothing has to be
+/// read back until the octree is built. The layout has to match `_buildCounts.glsl`
+#[derive(Clone)]
+pub struct BuildCounts {
+    counts: BufferTextureV2<u32>,
+    working_group_size: u32,
+    max_groups_in_x: u32,
+}
+
+/// Indirect dispatches in `BuildCounts`, the value is where they start
+#[derive(Debug, Clone, Copy)]
+pub enum IndirectDispatch {
+    /// One invocation per node of the level being built
+    NodesInLevel = 0,
+    /// One invocation per child pointer of the nodes of the previous level
+    ChildrenOfPreviousLevel = 4,
+    /// One invocation per voxel fragment of the geometry
+    VoxelFragments = 8,
+    /// One invocation per border voxel fragment of the level being built
+    BorderVoxelFragments = 12,
+}
+
+impl BuildCounts {
+    /// Counts of a build that starts with only the root node
+    pub unsafe fn new(config: &Config, number_of_voxel_fragments: u32) -> Self {
+        let mut counts = vec![0; LENGTH];
+        counts[NEXT_FREE_NODE] = 1;
+
+        let build_counts = Self {
+            counts: BufferTextureV2::from_data(counts),
+            working_group_size: config.working_group_size,
+            max_groups_in_x: shader::max_work_group_count().x,
+        };
+        // Known from the voxelization, the other dispatches come from earlier passes
+        build_counts.set_dispatch(IndirectDispatch::VoxelFragments, number_of_voxel_fragments);
+        build_counts
+    }
+
+    pub fn texture(&self) -> Texture {
+        self.counts.texture()
+    }
+
+    /// Most work groups in x of the dispatches, the rest go in y
+    pub fn max_groups_in_x(&self) -> u32 {
+        self.max_groups_in_x
+    }
+
+    /// Dispatches `shader` with the work groups of `dispatch`, which has to be in use.
+    /// Its `indirectDispatch` uniform is set for `dispatchedInvocations` if it includes `_buildCounts.glsl`
+    pub unsafe fn dispatch(&self, shader: &Shader, dispatch: IndirectDispatch) {
+        if shader.declares_uniform(c_str!("indirectDispatch")) {
+            shader.set_uint(c_str!("indirectDispatch"), dispatch as u32);
+        }
+        shader.dispatch_indirect(self.counts.buffer(), dispatch as usize * size_of::<u32>());
+    }
+
+    /// Sets the work groups of `dispatch` from the CPU, for when `invocations` is already known
+    pub unsafe fn set_dispatch(&self, dispatch: IndirectDispatch, invocations: u32) {
+        let groups = dispatch_groups(invocations, self.working_group_size, self.max_groups_in_x);
+        self.write(
+            dispatch as usize,
+            &[groups[0], groups[1], groups[2], invocations],
+        );
+    }
+
+    /// The next octree level has no border voxel fragments yet
+    pub unsafe fn reset_border_voxel_fragments(&self) {
+        self.write(BORDER_VOXEL_FRAGMENTS, &[0]);
+    }
+
+    /// Nodes are allocated from `node` on, like after the static nodes when building the
+    /// ones of dynamic objects
+    pub unsafe fn set_next_free_node(&self, node: u32) {
+        self.write(NEXT_FREE_NODE, &[node]);
+    }
+
+    /// Reads back the border voxel fragments of the last level built, waits for the GPU
+    pub unsafe fn border_voxel_fragments(&self) -> u32 {
+        self.counts.data()[BORDER_VOXEL_FRAGMENTS]
+    }
+
+    pub unsafe fn delete(self) {
+        gl::DeleteTextures(1, &self.counts.texture());
+        gl::DeleteBuffers(1, &self.counts.buffer());
+    }
+
+    unsafe fn write(&self, start: usize, values: &[u32]) {
+        gl::BindBuffer(gl::TEXTURE_BUFFER, self.counts.buffer());
+        gl::BufferSubData(
+            gl::TEXTURE_BUFFER,
+            (start * size_of::<u32>()) as isize,
+            (values.len() * size_of::<u32>()) as isize,
+            values.as_ptr() as *const c_void,
+        );
+        gl::BindBuffer(gl::TEXTURE_BUFFER, 0);
+    }
+}
+
+/// Work groups in x, y and z for `invocations` invocations, the same as `setDispatch`
+/// in `updateBuildCounts.comp.glsl`. Groups past `max_groups_in_x` go in y
+fn dispatch_groups(invocations: u32, working_group_size: u32, max_groups_in_x: u32) -> [u32; 3] {
+    let groups = invocations.div_ceil(working_group_size);
+    let groups_in_x = groups.min(max_groups_in_x);
+    let groups_in_y = if groups_in_x == 0 {
+        0
+    } else {
+        groups.div_ceil(groups_in_x)
+    };
+    [groups_in_x, groups_in_y, 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispatch_groups_cover_every_invocation() {
+        assert_eq!(dispatch_groups(65, 32, 65535), [3, 1, 1]);
+        assert_eq!(dispatch_groups(64, 32, 65535), [2, 1, 1]);
+        assert_eq!(dispatch_groups(0, 32, 65535), [0, 0, 1]);
+    }
+
+    #[test]
+    fn dispatch_groups_past_the_maximum_go_in_y() {
+        // 10 groups, 4 in each row
+        assert_eq!(dispatch_groups(10 * 64, 64, 4), [4, 3, 1]);
+    }
+}
@@ -1,14 +1,13 @@
 use log;
 
-use super::{Octree, OctreeDataType};
-use crate::{
-    config::Config,
-    constants::{Direction, Sign, Axis},
-};
+use super::Octree;
+use crate::constants::{Direction, Sign, Axis};
 use engine::prelude::*;
 
+mod build_counts;
 mod stages;
 
+pub use build_counts::{BuildCounts, IndirectDispatch};
 pub use stages::*;
 
 #[derive(Debug, Clone, Copy)]
@@ -20,43 +19,56 @@ pub enum BrickPoolValues {
 
 impl Octree {
     pub unsafe fn build(&mut self) {
-        let allocated_nodes_counter = helpers::generate_atomic_counter_buffer();
+        self.mark_all_nodes_dirty();
 
-        // Root node is in the geometry pool
-        self.geometry_data.node_data.nodes_per_level.push(1);
-
-        let mut first_free_node = 1; // Index of first free node (unallocated) in the octree
-
-        self.voxels_to_nodes(
-            OctreeDataType::Geometry,
-            &mut first_free_node,
-            allocated_nodes_counter,
-        );
-        let number_of_nodes = self.number_of_nodes() as u32;
+        self.voxels_to_nodes();
 
         self.builder
             .write_leaf_nodes_pass
-            .run(&self.geometry_data.voxel_data, &self.textures);
+            .run(&self.geometry_data.voxel_data, &self.textures, false);
 
-        self.builder
-            .process_raw_brick_pool_colors
-            .run(&self.geometry_data.node_data, &self.textures);
+        self.write_bricks();
+    }
 
-        self.builder
-            .create_alpha_map
-            .run(&self.textures, &self.geometry_data.node_data);
+    /// Builds the nodes and bricks again from the voxel fragment list, reusing the textures
+    /// and compiled shaders of this octree. The dynamic objects, if any, are added again
+    /// with their last voxel fragments.
+    /// Light has to be injected again afterwards
+    pub unsafe fn rebuild(&mut self) {
+        let dynamic_data = self.dynamic_data.take();
+        self.clear_nodes();
+        self.build();
+        if let Some(dynamic_data) = dynamic_data {
+            self.update_dynamic_voxels(dynamic_data.into_voxel_data());
+        }
+    }
 
-        self.builder.spread_leaf_bricks_pass.run(
-            &self.textures,
-            &self.geometry_data.node_data,
-            BrickPoolValues::Colors,
-        );
+    /// Writes the bricks of the dirty nodes from the raw colors of their leaves
+    pub(super) unsafe fn write_bricks(&self) {
+        for node_data in self.geometry_node_data() {
+            self.builder
+                .process_raw_brick_pool_colors
+                .run(node_data, &self.textures);
 
-        self.builder.leaf_border_transfer_pass.run(
-            &self.textures,
-            &self.geometry_data.node_data,
-            BrickPoolValues::Colors,
-        );
+            self.builder
+                .create_alpha_map
+                .run(&self.textures, node_data);
+
+            self.builder.spread_leaf_bricks_pass.run(
+                &self.textures,
+                node_data,
+                BrickPoolValues::Colors,
+            );
+        }
+
+        // Borders are shared with the leaves of the other node data
+        for node_data in self.geometry_node_data() {
+            self.builder.leaf_border_transfer_pass.run(
+                &self.textures,
+                node_data,
+                BrickPoolValues::Colors,
+            );
+        }
 
         self.run_mipmap(BrickPoolValues::Colors);
     }
@@ -71,185 +83,195 @@ impl Octree {
             Direction::new(Axis::Z, Sign::Neg),
         ];
 
-        let config = Config::instance();
+        let config = self.config;
 
+        // Each level reads the one below, which can have both static and dynamic nodes
         for level in (0..config.octree_levels() - 1).rev() {
-            for direction in all_directions.iter() {
-                self.builder.mipmap_anisotropic_pass.run(
-                    &self.textures,
-                    &self.geometry_data.node_data,
-                    level,
-                    *direction,
-                    brick_pool_values,
-                );
-
-                self.builder.mipmap_isotropic_pass.run(
-                    &self.textures,
-                    &self.geometry_data.node_data,
-                    level,
-                );
+            // Normals only depend on the geometry, so they don't change with the light
+            if let BrickPoolValues::Colors = brick_pool_values {
+                for node_data in self.geometry_node_data() {
+                    self.builder
+                        .mipmap_isotropic_pass
+                        .run(&self.textures, node_data, level);
+                }
+            }
 
-                if level > 0 {
-                    self.builder.anisotropic_border_transfer_pass.run(
+            for direction in all_directions.iter() {
+                for node_data in self.geometry_node_data() {
+                    self.builder.mipmap_anisotropic_pass.run(
                         &self.textures,
-                        &self.geometry_data.node_data,
+                        node_data,
                         level,
-                        brick_pool_values,
                         *direction,
+                        brick_pool_values,
                     );
                 }
+
+                if level > 0 {
+                    for node_data in self.geometry_node_data() {
+                        self.builder.anisotropic_border_transfer_pass.run(
+                            &self.textures,
+                            node_data,
+                            level,
+                            brick_pool_values,
+                            *direction,
+                        );
+                    }
+                }
             }
         }
     }
 
-    unsafe fn voxels_to_nodes(
-        &mut self,
-        octree_data_type: OctreeDataType,
-        first_free_node: &mut i32,
-        allocated_nodes_counter: u32,
-    ) {
-        log::trace!("Voxels to nodes called for: {:?}", octree_data_type);
+    /// Builds the nodes level by level. How many nodes each level has is only known by the GPU,
+    /// so passes are dispatched from `BuildCounts` and it's read back once at the end
+    unsafe fn voxels_to_nodes(&mut self) {
+        let config = self.config;
 
-        let config = Config::instance();
-
-        let mut octree_level_start_indices = Vec::with_capacity(config.octree_levels() as usize);
-        let mut first_node_in_level = 0; // Index of first node in a given octree level
+        let voxel_data = &self.geometry_data.voxel_data;
+        let node_data = &self.geometry_data.node_data;
+        let build_counts = BuildCounts::new(&config, voxel_data.number_of_voxel_fragments);
+        let update_build_counts = |octree_level| {
+            self.builder.update_build_counts_pass.run(
+                &build_counts,
+                node_data.level_start_indices,
+                octree_level,
+            )
+        };
+        let node_pool = BufferTextureV2::from_texture_and_buffer(self.textures.node_pool);
+        let level_start_indices =
+            BufferTextureV2::from_texture_and_buffer(node_data.level_start_indices);
 
-        // First level of nodes only has root.
-        // If the octree_data doesn't have root, this should have no meaning.
-        let first_level_start = 0;
-        octree_level_start_indices.push(first_level_start);
+        // Only the root is in level 0, the next levels start where the previous one ends
+        helpers::fill_texture_buffer_with_data(
+            node_data.level_start_indices.1,
+            &vec![0u32; config.octree_levels() as usize + 1],
+            gl::STATIC_DRAW,
+        );
+        update_build_counts(0);
 
-        let voxel_data = &self.geometry_data.voxel_data;
-        // Not necessary because we default to 0 on texutures, and first node will always be 
-        // at 0, 0, 0. But just in case...
-        self.builder
-            .store_node_positions_pass
-            .run(&self.textures, 0, &voxel_data);
+        // The root is always at 0, 0, 0, which is what node positions are cleared to, but it's
+        // stored like every other level so the build doesn't rely on that
+        self.builder.store_node_positions_pass.run(
+            &self.textures,
+            0,
+            voxel_data,
+            &build_counts,
+            IndirectDispatch::VoxelFragments,
+        );
 
         for octree_level in 1..=config.last_octree_level() {
-            let previous_level_node_amount = self.geometry_data.node_data.nodes_per_level[octree_level as usize - 1];
             // Flag and allocate previous level of octree with nodes for current level
             // of octree
-            let flag_nodes_input = FlagNodesInput {
+            let allocate_nodes_input = AllocateNodesInput {
                 octree_level: octree_level - 1,
-                voxel_data: voxel_data.clone(),
-                node_pool: BufferTextureV2::from_texture_and_buffer(self.textures.node_pool),
+                node_pool: node_pool.clone(),
+                level_start_indices: level_start_indices.clone(),
+                build_counts: build_counts.clone(),
             };
-            let allocate_nodes_input = AllocateNodesInput {
+            update_build_counts(octree_level);
+            self.builder.flag_nodes_pass.run(FlagNodesInput {
+                octree_level: octree_level - 1,
                 voxel_data: voxel_data.clone(),
-                allocated_nodes_counter,
-                first_node_in_level,
-                first_free_node: *first_free_node,
-                node_pool: BufferTextureV2::from_texture_and_buffer(self.textures.node_pool),
-                previous_level_node_amount,
-            };
-            self.builder
-                .flag_nodes_pass
-                .run(flag_nodes_input);
-            self.builder.allocate_nodes_pass.run(allocate_nodes_input);
-
-            let non_border_nodes_allocated = helpers::get_value_from_atomic_counter_without_reset(allocated_nodes_counter);
-            log::debug!(
-                "{octree_data_type:?} non border nodes allocated for {}: {}",
+                node_pool: node_pool.clone(),
+                build_counts: build_counts.clone(),
+                voxel_fragments: IndirectDispatch::VoxelFragments,
+            });
+            self.builder.allocate_nodes_pass.run(allocate_nodes_input.clone());
+            update_build_counts(octree_level);
+
+            self.builder.store_node_positions_pass.run(
+                &self.textures,
                 octree_level,
-                non_border_nodes_allocated
+                voxel_data,
+                &build_counts,
+                IndirectDispatch::VoxelFragments,
             );
 
-            self.builder
-                .store_node_positions_pass
-                .run(&self.textures, octree_level, &voxel_data);
-
             self.builder.neighbor_pointers_pass.run(
-                &self.geometry_data.voxel_data,
-                &self.geometry_data.node_data,
+                voxel_data,
+                node_data,
                 &self.textures,
                 octree_level,
-                *first_free_node as u32,
-                non_border_nodes_allocated,
+                &build_counts,
+                0,
             );
 
             self.builder.append_border_voxel_fragments_pass.run(
-                &self.geometry_data,
-                &mut self.border_data,
+                &self.border_data,
                 octree_level,
-                *first_free_node as u32,
-                non_border_nodes_allocated,
                 &self.textures,
+                node_data,
+                &build_counts,
             );
+            update_build_counts(octree_level);
 
-            let flag_nodes_input = FlagNodesInput {
+            self.builder.flag_nodes_pass.run(FlagNodesInput {
                 octree_level: octree_level - 1,
                 voxel_data: self.border_data.voxel_data.clone(),
-                node_pool: BufferTextureV2::from_texture_and_buffer(self.textures.node_pool),
-            };
-            self.builder
-                .flag_nodes_pass
-                .run(flag_nodes_input);
-            let allocate_nodes_input = AllocateNodesInput {
-                voxel_data: self.border_data.voxel_data.clone(),
-                allocated_nodes_counter,
-                first_node_in_level,
-                first_free_node: *first_free_node,
-                node_pool: BufferTextureV2::from_texture_and_buffer(self.textures.node_pool),
-                previous_level_node_amount,
-            };
+                node_pool: node_pool.clone(),
+                build_counts: build_counts.clone(),
+                voxel_fragments: IndirectDispatch::BorderVoxelFragments,
+            });
             self.builder.allocate_nodes_pass.run(allocate_nodes_input);
-            self.builder
-                .store_node_positions_pass
-                .run(&self.textures, octree_level, &self.border_data.voxel_data);
+            update_build_counts(octree_level);
 
-            let nodes_allocated = helpers::get_value_from_atomic_counter(allocated_nodes_counter);
-            self.builder.neighbor_pointers_pass.run(
-                &self.geometry_data.voxel_data,
-                &self.geometry_data.node_data,
+            self.builder.store_node_positions_pass.run(
                 &self.textures,
                 octree_level,
-                *first_free_node as u32,
-                nodes_allocated,
+                &self.border_data.voxel_data,
+                &build_counts,
+                IndirectDispatch::BorderVoxelFragments,
             );
 
-            // TODO: Separate node_data and voxel_data top-level.
-            // The only mutable thing we need is node_data.
-            // If we had node_data separate from voxel_data we could mutably borrow it and
-            // immutably borrow voxel_data on a per voxel type basis.
-            self.geometry_data
-                .node_data
-                .nodes_per_level
-                .push(nodes_allocated);
-
-            first_node_in_level = *first_free_node;
-            *first_free_node += nodes_allocated as i32;
-
-            octree_level_start_indices.push(first_node_in_level);
+            self.builder.neighbor_pointers_pass.run(
+                voxel_data,
+                node_data,
+                &self.textures,
+                octree_level,
+                &build_counts,
+                0,
+            );
         }
 
         self.builder.store_node_positions_pass.run(
             &self.textures,
             config.last_octree_level(),
-            &voxel_data,
+            voxel_data,
+            &build_counts,
+            IndirectDispatch::VoxelFragments,
         );
 
-        // Start of level config.octree_levels(), shouldn't have anything
-        // since we go from level 0 to config.octree_levels() - 1.
-        // Works as an upper bound to the nodeIDs in config.octree_levels() - 1, the last level.
-        octree_level_start_indices.push(*first_free_node);
-
-        let octree_data = &self.geometry_data;
-
-        helpers::fill_texture_buffer_with_data(
-            octree_data.node_data.level_start_indices.1,
-            &octree_level_start_indices,
-            gl::STATIC_DRAW,
+        // The only time the CPU waits for the GPU while building the nodes.
+        // The start of level config.octree_levels() works as an upper bound to the nodeIDs
+        // in the last level
+        gl::MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT);
+        let octree_level_start_indices = helpers::get_values_from_texture_buffer(
+            node_data.level_start_indices.1,
+            config.octree_levels() as usize + 1,
+            0u32,
         );
+        let nodes_per_level: Vec<u32> = octree_level_start_indices
+            .windows(2)
+            .map(|window| window[1] - window[0])
+            .collect();
+        let number_of_border_voxel_fragments = build_counts.border_voxel_fragments();
+        build_counts.delete();
+
+        self.border_data.voxel_data.number_of_voxel_fragments = number_of_border_voxel_fragments;
+        self.geometry_data.node_data.nodes_per_level = nodes_per_level;
 
         log::debug!(
-            "{octree_data_type:?} nodes_per_level: {:?}",
-            &octree_data.node_data.nodes_per_level
+            "nodes_per_level: {:?}",
+            &self.geometry_data.node_data.nodes_per_level
         );
         log::debug!(
-            "{octree_data_type:?} level_start_indices: {:?}",
+            "level_start_indices: {:?}",
             &octree_level_start_indices
         );
+        log::debug!(
+            "border voxel fragments for {}: {}",
+            config.last_octree_level(),
+            number_of_border_voxel_fragments
+        );
     }
 }
@@ -3,7 +3,7 @@ use engine::prelude::*;
 
 use crate::{
     config::Config,
-    octree::{OctreeTextures, VoxelData},
+    octree::build::{BuildCounts, IndirectDispatch},
 };
 
 pub struct AllocateNodesPass {
@@ -12,19 +12,27 @@ pub struct AllocateNodesPass {
 
 #[derive(Clone)]
 pub struct AllocateNodesInput {
-    pub voxel_data: VoxelData,
-    pub allocated_nodes_counter: u32,
-    pub first_node_in_level: i32,
-    pub first_free_node: i32,
+    /// Level of the nodes whose flagged children are allocated
+    pub octree_level: u32,
     pub node_pool: BufferTextureV2<u32>,
-    pub previous_level_node_amount: u32,
+    pub level_start_indices: BufferTextureV2<u32>,
+    /// Nodes are allocated from its next free node, with one invocation per child
+    /// of the nodes in `octree_level`
+    pub build_counts: BuildCounts,
 }
 
 impl AllocateNodesPass {
-    pub fn init() -> Self {
-        Self {
-            shader: compile_compute!("assets/shaders/octree/allocateNodes.comp.glsl"),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(
+                "assets/shaders/octree/allocateNodes.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.shader.delete();
     }
 }
 
@@ -32,41 +40,24 @@ impl ShaderPass for AllocateNodesPass {
     type Input<'a> = AllocateNodesInput;
 
     unsafe fn run<'a>(&self, input: Self::Input<'a>) {
+        let _timer = profiler::scope("Allocate nodes");
+
         self.shader.use_program();
 
-        self.shader
-            .set_int(c_str!("firstNodeInLevel"), input.first_node_in_level);
-        self.shader
-            .set_int(c_str!("firstFreeNode"), input.first_free_node);
-        gl::BindImageTexture(
-            0,
-            input.node_pool.texture(),
-            0,
-            gl::FALSE,
-            0,
-            gl::READ_WRITE,
+        self.shader.set_uint(c_str!("octreeLevel"), input.octree_level);
+        helpers::bind_image_texture(0, input.node_pool.texture(), gl::READ_WRITE, gl::R32UI);
+        helpers::bind_image_texture(1, input.build_counts.texture(), gl::READ_WRITE, gl::R32UI);
+        helpers::bind_image_texture(
+            2,
+            input.level_start_indices.texture(),
+            gl::READ_ONLY,
             gl::R32UI,
         );
-        gl::BindBufferBase(gl::ATOMIC_COUNTER_BUFFER, 0, input.allocated_nodes_counter);
-
-        let config = Config::instance();
-
-        // TODO: The number of nodes should be `nodes_per_level` but for that
-        // `voxel_data` and `node_data` need to be the top level split instead of
-        // `geometry_data` and `border_data`
-        // TODO: Should move to its own function since we use it all over the place
-        let groups_count = ((input.previous_level_node_amount * 8) as f32
-            / config.working_group_size as f32)
-            .ceil() as u32;
 
-        let (debug_texture, debug_texture_buffer) = helpers::generate_texture_buffer(20, gl::R32F, 42f32);
-        helpers::bind_image_texture(1, debug_texture, gl::WRITE_ONLY, gl::R32F);
-
-        self.shader.dispatch(groups_count);
-        self.shader.wait();
-
-        // let values = helpers::get_values_from_texture_buffer(debug_texture_buffer, 20, 0f32);
-        // dbg!(&values);
+        input
+            .build_counts
+            .dispatch(&self.shader, IndirectDispatch::ChildrenOfPreviousLevel);
+        self.shader.wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
     }
 }
 
@@ -78,13 +69,14 @@ mod tests {
 
     struct TestCase {
         pub input: AllocateNodesInput,
+        pub first_free_node: u32,
         pub expected_output: Vec<u32>,
         pub description: String,
     }
 
     #[test]
     fn allocate_nodes_works() {
-        let (_glfw, _window) = test_utils::init_opengl_context();
+        let _context = test_utils::init_opengl_context();
 
         // To go from the crate root to the workspace root
         // This is stateful, so not a good testing practice, doing it on first test ran for now
@@ -96,14 +88,14 @@ mod tests {
         let voxel_dimension_exponent = 4;
 
         unsafe {
-            let test_data = test_cases();
+            let config = Config::new(voxel_dimension_exponent);
+            let test_data = test_cases(&config);
 
             // Initialize everything
-            Config::initialize_test_sensitive(Config::new(voxel_dimension_exponent), true);
-            let allocate_nodes_pass = AllocateNodesPass::init();
+            let allocate_nodes_pass = AllocateNodesPass::init(&config).unwrap();
             let mut allocations: Vec<u32> = vec![];
 
-            for TestCase { input, expected_output, description } in test_data.iter() {
+            for TestCase { input, first_free_node, expected_output, description } in test_data.iter() {
                 // Run the shader
                 allocate_nodes_pass.run(input.clone());
 
@@ -119,7 +111,7 @@ mod tests {
                         allocations.push(output[index].clone());
                     }
                 }
-                let expected_allocations: Vec<u32> = (input.first_free_node as u32..input.first_free_node as u32 + allocations.len() as u32 ).collect();
+                let expected_allocations: Vec<u32> = (*first_free_node..*first_free_node + allocations.len() as u32).collect();
                 allocations.sort();
                 assert_eq!(*allocations, *expected_allocations);
                 allocations.clear();
@@ -128,15 +120,22 @@ mod tests {
         }
     }
 
-    unsafe fn test_cases() -> std::vec::Vec<TestCase> {
+    /// Counts of a build that has allocated up to `first_free_node`,
+    /// with `children` children in the level being allocated
+    unsafe fn build_counts(config: &Config, first_free_node: u32, children: u32) -> BuildCounts {
+        let build_counts = BuildCounts::new(config, 0);
+        build_counts.set_next_free_node(first_free_node);
+        build_counts.set_dispatch(IndirectDispatch::ChildrenOfPreviousLevel, children);
+        build_counts
+    }
+
+    unsafe fn test_cases(config: &Config) -> std::vec::Vec<TestCase> {
         let F = 1 << 31;
         vec![
             TestCase {
                 description: String::from("Base case, empty node pool"),
                 input: AllocateNodesInput {
-                    voxel_data: BufferTextureV2::from_data(vec![
-                       helpers::rgb10_a2ui_to_r32ui(0, 0, 0),
-                    ]).into(),
+                    octree_level: 0,
                     node_pool: BufferTextureV2::from_data(vec![
                       // Level 0
                       F, 0, 0, 0, 0, 0, 0, 0,
@@ -144,10 +143,10 @@ mod tests {
                       0, 0, 0, 0, 0, 0, 0, 0,
                       0, 0, 0, 0, 0, 0, 0, 0,
                     ]),
-                    allocated_nodes_counter: helpers::generate_atomic_counter_buffer(),
-                    first_node_in_level: 0,
-                    first_free_node: 1,
+                    level_start_indices: BufferTextureV2::from_data(vec![0, 1]),
+                    build_counts: build_counts(config, 1, 8),
                 },
+                first_free_node: 1,
                 expected_output: vec![
                     // Level 0
                     1, 0, 0, 0, 0, 0, 0, 0,
@@ -159,9 +158,7 @@ mod tests {
             TestCase {
                 description: String::from("Base case, empty node pool"),
                 input: AllocateNodesInput {
-                    voxel_data: BufferTextureV2::from_data(vec![
-                       helpers::rgb10_a2ui_to_r32ui(4, 4, 4),
-                    ]).into(),
+                    octree_level: 1,
                     node_pool: BufferTextureV2::from_data(vec![
                       // Level 0
                       1, 0, 0, 0, 0, 0, 0, 0,
@@ -169,10 +166,10 @@ mod tests {
                       0, 0, 0, 0, 0, 0, 0, F,
                       0, 0, 0, 0, 0, 0, 0, 0,
                     ]),
-                    allocated_nodes_counter: helpers::generate_atomic_counter_buffer(),
-                    first_node_in_level: 1,
-                    first_free_node: 2,
+                    level_start_indices: BufferTextureV2::from_data(vec![0, 1, 2]),
+                    build_counts: build_counts(config, 2, 8),
                 },
+                first_free_node: 2,
                 expected_output: vec![
                     // Level 0
                     1, 0, 0, 0, 0, 0, 0, 0,
@@ -184,12 +181,7 @@ mod tests {
             TestCase {
                 description: String::from("First two levels already complete, allocate many subnodes"),
                 input: AllocateNodesInput {
-                    voxel_data: BufferTextureV2::from_data(vec![
-                       helpers::rgb10_a2ui_to_r32ui(0, 0, 0),
-                       helpers::rgb10_a2ui_to_r32ui(8, 10, 6),
-                       helpers::rgb10_a2ui_to_r32ui(8, 7, 10),
-                       helpers::rgb10_a2ui_to_r32ui(8, 7, 9),
-                    ]).into(),
+                    octree_level: 2,
                     node_pool: BufferTextureV2::from_data(vec![
                       // Level 0
                       1, 0, 0, 2, 0, 3, 0, 0,
@@ -201,12 +193,11 @@ mod tests {
                       F, 0, 0, 0, 0, 0, 0, 0,
                       0, 0, 0, 0, 0, 0, F, 0,
                       0, 0, F, 0, 0, 0, F, 0,
-                    ],
-                    ),
-                    allocated_nodes_counter: helpers::generate_atomic_counter_buffer(),
-                    first_node_in_level: 4,
-                    first_free_node: 7,
+                    ]),
+                    level_start_indices: BufferTextureV2::from_data(vec![0, 1, 4, 7]),
+                    build_counts: build_counts(config, 7, 24),
                 },
+                first_free_node: 7,
                 expected_output: vec![
                   // Level 0
                   1, 0, 0, 2, 0, 3, 0, 0,
@@ -9,13 +9,22 @@ use crate::{
 
 pub struct AnisotropicBorderTransferPass {
     shader: Shader,
+    config: Config,
 }
 
 impl AnisotropicBorderTransferPass {
-    pub fn init() -> Self {
-        Self {
-            shader: compile_compute!("assets/shaders/octree/anisotropicBorderTransfer.comp.glsl",),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(
+                "assets/shaders/octree/anisotropicBorderTransfer.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.shader.delete();
     }
 
     /// Runs the border transfer pass.
@@ -29,9 +38,11 @@ impl AnisotropicBorderTransferPass {
         brick_pool_values: BrickPoolValues,
         direction: Direction,
     ) {
+        let _timer = profiler::scope("Anisotropic border transfer");
+
         self.shader.use_program();
 
-        let config = Config::instance();
+        let config = &self.config;
 
         self.shader.set_uint(c_str!("octreeLevel"), octree_level);
         self.shader
@@ -53,6 +64,8 @@ impl AnisotropicBorderTransferPass {
             Sign::Neg => neighbors_texture_number + 1,
         };
 
+        helpers::bind_image_texture(7, textures.dirty_nodes.0, gl::READ_ONLY, gl::R32UI);
+
         match brick_pool_values {
             BrickPoolValues::Colors => helpers::bind_3d_image_texture(
                 1,
@@ -89,7 +102,6 @@ impl AnisotropicBorderTransferPass {
             gl::R32UI,
         );
         let nodes_in_level = geometry_node_data.nodes_per_level[octree_level as usize];
-        let groups_count = (nodes_in_level as f32 / config.working_group_size as f32).ceil() as u32;
         for axis in other_axis.iter() {
             self.shader.set_uint(c_str!("axis"), (*axis).into());
             let mut neighbors_texture_number = match axis {
@@ -103,8 +115,9 @@ impl AnisotropicBorderTransferPass {
                 gl::READ_ONLY,
                 gl::R32UI,
             );
-            self.shader.dispatch(groups_count);
-            self.shader.wait();
+            self.shader.dispatch_invocations(nodes_in_level);
+            self.shader
+                .wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
         }
         let mut neighbors_texture_number = match direction.axis {
             Axis::X => 0,
@@ -122,8 +135,9 @@ impl AnisotropicBorderTransferPass {
             gl::R32UI,
         );
         self.shader.set_uint(c_str!("axis"), direction.axis.into());
-        self.shader.dispatch(groups_count);
-        self.shader.wait();
+        self.shader.dispatch_invocations(nodes_in_level);
+        self.shader
+            .wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
 
         // Second run for border nodes
         // helpers::bind_image_texture(
@@ -133,7 +147,6 @@ impl AnisotropicBorderTransferPass {
         //     gl::R32UI,
         // );
         // let nodes_in_level = border_node_data.nodes_per_level[octree_level as usize];
-        // let groups_count = (nodes_in_level as f32 / config.working_group_size as f32).ceil() as u32;
         // for axis in other_axis.iter() {
         //     self.shader.set_uint(c_str!("axis"), (*axis).into());
         //     let mut neighbors_texture_number = match axis {
@@ -151,7 +164,7 @@ impl AnisotropicBorderTransferPass {
         //         gl::READ_ONLY,
         //         gl::R32UI,
         //     );
-        //     self.shader.dispatch(groups_count);
+        //     self.shader.dispatch_invocations(nodes_in_level);
         //     self.shader.wait();
         // }
         // let mut neighbors_texture_number = match direction.axis {
@@ -170,7 +183,7 @@ impl AnisotropicBorderTransferPass {
         //     gl::R32UI,
         // );
         // self.shader.set_uint(c_str!("axis"), direction.axis.into());
-        // self.shader.dispatch(groups_count);
+        // self.shader.dispatch_invocations(nodes_in_level);
         // self.shader.wait();
     }
 }
@@ -3,49 +3,53 @@ use engine::prelude::*;
 
 use crate::{
     config::Config,
-    octree::{OctreeData, OctreeTextures},
+    octree::{
+        build::{BuildCounts, IndirectDispatch},
+        NodeData, OctreeData, OctreeTextures,
+    },
 };
 
 pub struct AppendBorderVoxelFragmentsPass {
     shader: Shader,
+    config: Config,
 }
 
 impl AppendBorderVoxelFragmentsPass {
-    pub fn init() -> Self {
-        Self {
-            shader: compile_compute!("assets/shaders/octree/appendBorderVoxelFragments.comp.glsl",),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(
+                "assets/shaders/octree/appendBorderVoxelFragments.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
     }
 
+    pub unsafe fn delete(self) {
+        self.shader.delete();
+    }
+
+    /// Appends to `border_data` the voxel fragments next to the nodes of `octree_level`
+    /// that have no neighbor, counting them in `build_counts`
     pub unsafe fn run(
         &self,
-        geometry_data: &OctreeData,
-        border_data: &mut OctreeData,
+        border_data: &OctreeData,
         octree_level: u32,
-        level_start: u32,
-        nodes_in_current_level: u32,
         textures: &OctreeTextures,
+        node_data: &NodeData,
+        build_counts: &BuildCounts,
     ) {
-        log::debug!(
-            "{}, {}, {}",
-            octree_level,
-            level_start,
-            nodes_in_current_level
-        );
-        let config = Config::instance();
+        let _timer = profiler::scope("Append border voxel fragments");
+
+        let config = &self.config;
         self.shader.use_program();
         self.shader
             .set_uint(c_str!("maxOctreeLevel"), config.last_octree_level());
         self.shader
             .set_uint(c_str!("voxelDimension"), config.voxel_dimension());
-        self.shader
-            .set_uint(c_str!("levelStart"), level_start);
-        self.shader
-            .set_uint(c_str!("nextLevelStart"), level_start + nodes_in_current_level);
         self.shader
             .set_uint(c_str!("octreeLevel"), octree_level);
 
-
         helpers::bind_image_texture(
             0,
             border_data.voxel_data.voxel_positions.texture(),
@@ -53,44 +57,30 @@ impl AppendBorderVoxelFragmentsPass {
             gl::RGB10_A2UI,
         );
         helpers::bind_image_texture(1, textures.node_positions.0, gl::READ_ONLY, gl::RGB10_A2UI);
+        helpers::bind_image_texture(5, node_data.level_start_indices.0, gl::READ_ONLY, gl::R32UI);
+        helpers::bind_image_texture(6, build_counts.texture(), gl::READ_WRITE, gl::R32UI);
 
-        let next_voxel_fragment_counter = helpers::generate_atomic_counter_buffer();
-        gl::BindBufferBase(gl::ATOMIC_COUNTER_BUFFER, 0, next_voxel_fragment_counter);
-
-        let (debug_texture, debug_texture_buffer) = helpers::generate_texture_buffer(20, gl::R32F, 42f32);
-        helpers::bind_image_texture(5, debug_texture, gl::WRITE_ONLY, gl::R32F);
-
-        self.run_pass(nodes_in_current_level, textures);
+        // Only the ones of this level are kept
+        build_counts.reset_border_voxel_fragments();
 
-        let values = helpers::get_values_from_texture_buffer(debug_texture_buffer, 20, 0f32);
-        // dbg!(&values);
-
-        // Get the number of voxel fragments
-        let number_of_voxel_fragments =
-            helpers::get_value_from_atomic_counter(next_voxel_fragment_counter);
-        border_data.voxel_data.number_of_voxel_fragments = number_of_voxel_fragments;
-        log::debug!(
-            "border voxel fragments for {}: {}",
-            octree_level,
-            number_of_voxel_fragments
-        );
+        self.run_pass(textures, build_counts);
     }
 
-    unsafe fn run_pass(&self, nodes_in_current_level: u32, textures: &OctreeTextures) {
-        self.run_one(0, 2, nodes_in_current_level, textures);
-        self.run_one(1, 2, nodes_in_current_level, textures);
-        self.run_one(2, 4, nodes_in_current_level, textures);
-        self.run_one(3, 4, nodes_in_current_level, textures);
-        self.run_one(4, 0, nodes_in_current_level, textures);
-        self.run_one(5, 0, nodes_in_current_level, textures);
+    unsafe fn run_pass(&self, textures: &OctreeTextures, build_counts: &BuildCounts) {
+        self.run_one(0, 2, textures, build_counts);
+        self.run_one(1, 2, textures, build_counts);
+        self.run_one(2, 4, textures, build_counts);
+        self.run_one(3, 4, textures, build_counts);
+        self.run_one(4, 0, textures, build_counts);
+        self.run_one(5, 0, textures, build_counts);
     }
 
     unsafe fn run_one(
         &self,
         base_texture: usize,
         side_texture: usize,
-        nodes_in_current_level: u32,
         textures: &OctreeTextures,
+        build_counts: &BuildCounts,
     ) {
         self.shader
             .set_uint(c_str!("offsetTexture"), base_texture as u32);
@@ -115,11 +105,7 @@ impl AppendBorderVoxelFragmentsPass {
             gl::R32UI,
         );
 
-        let config = Config::instance();
-
-        let groups_count =
-            (nodes_in_current_level as f32 / config.working_group_size as f32).ceil() as u32;
-        self.shader.dispatch(groups_count);
-        self.shader.wait();
+        build_counts.dispatch(&self.shader, IndirectDispatch::NodesInLevel);
+        self.shader.wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
     }
 }
@@ -0,0 +1,42 @@
+use engine::prelude::*;
+
+use crate::{config::Config, octree::OctreeTextures};
+
+/// Clears the raw colors and normals of the dirty nodes, the ones the build accumulates into,
+/// so the voxel fragments that are still there can be written again
+pub struct ClearDirtyBricksPass {
+    shader: Shader,
+}
+
+impl ClearDirtyBricksPass {
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(
+                "assets/shaders/octree/clearDirtyBricks.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.shader.delete();
+    }
+
+    pub unsafe fn run(&self, textures: &OctreeTextures, number_of_nodes: usize) {
+        let _timer = profiler::scope("Clear dirty bricks");
+
+        self.shader.use_program();
+
+        helpers::bind_3d_image_texture(
+            0,
+            textures.brick_pool_colors_raw,
+            gl::WRITE_ONLY,
+            gl::R32UI,
+        );
+        helpers::bind_3d_image_texture(1, textures.brick_pool_normals, gl::WRITE_ONLY, gl::RGBA32F);
+        helpers::bind_image_texture(2, textures.dirty_nodes.0, gl::READ_ONLY, gl::R32UI);
+
+        self.shader.dispatch_invocations(number_of_nodes as u32);
+        self.shader.wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    }
+}
@@ -8,19 +8,30 @@ use crate::{
 
 pub struct CreateAlphaMap {
     shader: Shader,
+    config: Config,
 }
 
 impl CreateAlphaMap {
-    pub fn init() -> Self {
-        Self {
-            shader: compile_compute!("assets/shaders/octree/createAlphaMap.comp.glsl"),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(
+                "assets/shaders/octree/createAlphaMap.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.shader.delete();
     }
 
     pub unsafe fn run(&self, textures: &OctreeTextures, node_data: &NodeData) {
+        let _timer = profiler::scope("Create alpha map");
+
         self.shader.use_program();
 
-        let config = Config::instance();
+        let config = &self.config;
 
         self.shader
             .set_uint(c_str!("octreeLevel"), config.last_octree_level());
@@ -28,11 +39,12 @@ impl CreateAlphaMap {
         helpers::bind_image_texture(0, node_data.level_start_indices.0, gl::READ_ONLY, gl::R32UI);
         helpers::bind_3d_image_texture(1, textures.brick_pool_colors[0], gl::READ_ONLY, gl::RGBA8);
         helpers::bind_3d_image_texture(2, textures.brick_pool_alpha, gl::WRITE_ONLY, gl::RGBA8);
+        helpers::bind_image_texture(3, textures.dirty_nodes.0, gl::READ_ONLY, gl::R32UI);
 
         let nodes_in_level = node_data.nodes_per_level[config.last_octree_level() as usize];
-        let groups_count = (nodes_in_level as f32 / config.working_group_size as f32).ceil() as u32;
 
-        self.shader.dispatch(groups_count);
-        self.shader.wait();
+        self.shader.dispatch_invocations(nodes_in_level);
+        self.shader
+            .wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
     }
 }
@@ -4,20 +4,29 @@ use log;
 
 use crate::{
     config::Config,
-    octree::{OctreeTextures, VoxelData},
+    octree::{
+        build::{BuildCounts, IndirectDispatch},
+        OctreeTextures, VoxelData,
+    },
 };
 
 pub struct FlagNodesPass {
     shader: Shader,
+    config: Config,
 }
 
 const SHADER_PATH: &'static str = "assets/shaders/octree/flagNodes.comp.glsl";
 
 impl FlagNodesPass {
-    pub fn init() -> Self {
-        Self {
-            shader: compile_compute!(SHADER_PATH),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(SHADER_PATH, defines = config.shader_defines())?,
+            config: *config,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.shader.delete();
     }
 }
 
@@ -26,33 +35,31 @@ pub struct FlagNodesInput {
     pub octree_level: u32,
     pub voxel_data: VoxelData,
     pub node_pool: BufferTextureV2<u32>,
+    pub build_counts: BuildCounts,
+    /// One invocation per voxel fragment of `voxel_data`
+    pub voxel_fragments: IndirectDispatch,
 }
 
 impl ShaderPass for FlagNodesPass {
     type Input<'a> = FlagNodesInput;
 
     unsafe fn run<'a>(&self, input: Self::Input<'a>) {
+        let _timer = profiler::scope("Flag nodes");
+
         self.shader.use_program();
 
-        let config = Config::instance();
+        let config = &self.config;
 
-        self.shader.set_uint(
-            c_str!("numberOfVoxelFragments"),
-            input.voxel_data.number_of_voxel_fragments,
-        );
         self.shader.set_uint(c_str!("octreeLevel"), input.octree_level);
         self.shader
             .set_uint(c_str!("voxelDimension"), config.voxel_dimension());
 
-        helpers::bind_image_texture(0, input.voxel_data.voxel_positions.texture(), gl::READ_ONLY, gl::RGB10_A2);
+        helpers::bind_image_texture(0, input.voxel_data.voxel_positions.texture(), gl::READ_ONLY, gl::RGB10_A2UI);
         helpers::bind_image_texture(1, input.node_pool.texture(), gl::READ_WRITE, gl::R32UI);
+        helpers::bind_image_texture(2, input.build_counts.texture(), gl::READ_ONLY, gl::R32UI);
 
-        let groups_count = (input.voxel_data.number_of_voxel_fragments as f32
-            / config.working_group_size as f32)
-            .ceil() as u32;
-
-        self.shader.dispatch(groups_count);
-        self.shader.wait();
+        input.build_counts.dispatch(&self.shader, input.voxel_fragments);
+        self.shader.wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
     }
 }
 
@@ -70,16 +77,16 @@ mod tests {
 
     #[test]
     fn flag_nodes_works() {
-        let (_glfw, _window) = test_utils::init_opengl_context();
+        let _context = test_utils::init_opengl_context();
 
         let voxel_dimension_exponent = 4;
 
         unsafe {
-            let test_data = test_cases();
+            let config = Config::new(voxel_dimension_exponent);
+            let test_data = test_cases(&config);
 
             // Initialize everything
-            Config::initialize_test_sensitive(Config::new(voxel_dimension_exponent), true);
-            let flag_nodes_pass = FlagNodesPass::init();
+            let flag_nodes_pass = FlagNodesPass::init(&config).unwrap();
 
             for TestCase { input, expected_output, description } in test_data.iter() {
                 // Run the shader
@@ -94,7 +101,7 @@ mod tests {
         }
     }
 
-    unsafe fn test_cases() -> std::vec::Vec<TestCase> {
+    unsafe fn test_cases(config: &Config) -> std::vec::Vec<TestCase> {
         // Flag left in node's child pointers to notify the child they point to
         // should be allocated, in `allocate_nodes`
         #[allow(non_snake_case)]
@@ -114,6 +121,8 @@ mod tests {
                       0, 0, 0, 0, 0, 0, 0, 0,
                       0, 0, 0, 0, 0, 0, 0, 0,
                     ]),
+                    build_counts: BuildCounts::new(config, 1),
+                    voxel_fragments: IndirectDispatch::VoxelFragments,
                 },
                 expected_output: vec![
                     // Level 0
@@ -137,6 +146,8 @@ mod tests {
                       0, 0, 0, 0, 0, 0, 0, 0,
                       0, 0, 0, 0, 0, 0, 0, 0,
                     ]),
+                    build_counts: BuildCounts::new(config, 1),
+                    voxel_fragments: IndirectDispatch::VoxelFragments,
                 },
                 expected_output: vec![
                     // Level 0
@@ -160,6 +171,8 @@ mod tests {
                       0, 0, 0, 0, 0, 0, 0, 0,
                       0, 0, 0, 0, 0, 0, 0, 0,
                     ]),
+                    build_counts: BuildCounts::new(config, 1),
+                    voxel_fragments: IndirectDispatch::VoxelFragments,
                 },
                 expected_output: vec![
                     // Level 0
@@ -184,6 +197,8 @@ mod tests {
                       // Level 2
                       0, 0, 0, 0, 0, 0, 0, 0,
                     ]),
+                    build_counts: BuildCounts::new(config, 1),
+                    voxel_fragments: IndirectDispatch::VoxelFragments,
                 },
                 expected_output: vec![
                     // Level 0
@@ -211,6 +226,8 @@ mod tests {
                       0, 0, 0, 0, 0, 0, 0, 0,
                       0, 0, 0, 0, 0, 0, 0, 0,
                     ]),
+                    build_counts: BuildCounts::new(config, 4),
+                    voxel_fragments: IndirectDispatch::VoxelFragments,
                 },
                 expected_output: vec![
                     // Level 0
@@ -238,6 +255,8 @@ mod tests {
                       0, 0, 0, 0, 0, 0, 0, 0,
                       0, 0, 0, 0, 0, 0, 0, 0,
                     ]),
+                    build_counts: BuildCounts::new(config, 4),
+                    voxel_fragments: IndirectDispatch::VoxelFragments,
                 },
                 expected_output: vec![
                     // Level 0
@@ -270,6 +289,8 @@ mod tests {
                         0, 0, 0, 0, 0, 0, 0, 0,
                         0, 0, 0, 0, 0, 0, 0, 0,
                     ]),
+                    build_counts: BuildCounts::new(config, 4),
+                    voxel_fragments: IndirectDispatch::VoxelFragments,
                 },
                 expected_output: vec![
                     // Level 0
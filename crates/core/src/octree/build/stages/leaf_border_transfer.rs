@@ -9,13 +9,22 @@ use crate::{
 
 pub struct LeafBorderTransferPass {
     shader: Shader,
+    config: Config,
 }
 
 impl LeafBorderTransferPass {
-    pub fn init() -> Self {
-        Self {
-            shader: compile_compute!("assets/shaders/octree/leafBorderTransfer.comp.glsl"),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(
+                "assets/shaders/octree/leafBorderTransfer.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.shader.delete();
     }
 
     pub unsafe fn run(
@@ -24,13 +33,17 @@ impl LeafBorderTransferPass {
         geometry_node_data: &NodeData,
         brick_pool_values: BrickPoolValues,
     ) {
+        let _timer = profiler::scope("Leaf border transfer");
+
         self.shader.use_program();
 
-        let config = Config::instance();
+        let config = &self.config;
 
         self.shader
             .set_uint(c_str!("octreeLevel"), config.last_octree_level());
 
+        helpers::bind_image_texture(7, textures.dirty_nodes.0, gl::READ_ONLY, gl::R32UI);
+
         match brick_pool_values {
             BrickPoolValues::Colors => helpers::bind_3d_image_texture(
                 1,
@@ -54,8 +67,6 @@ impl LeafBorderTransferPass {
 
         let geometry_nodes_in_level =
             geometry_node_data.nodes_per_level[config.last_octree_level() as usize];
-        let geometry_groups_count =
-            (geometry_nodes_in_level as f32 / config.working_group_size as f32).ceil() as u32;
 
         for axis in Axis::all_axis().iter() {
             self.shader.set_uint(c_str!("axis"), (*axis).into());
@@ -74,8 +85,9 @@ impl LeafBorderTransferPass {
                 gl::READ_ONLY,
                 gl::R32UI,
             );
-            self.shader.dispatch(geometry_groups_count);
-            self.shader.wait();
+            self.shader.dispatch_invocations(geometry_nodes_in_level);
+            self.shader
+                .wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
         }
     }
 }
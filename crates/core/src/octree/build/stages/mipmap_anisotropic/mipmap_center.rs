@@ -9,13 +9,22 @@ use crate::{
 
 pub struct MipmapCenterPass {
     shader: Shader,
+    config: Config,
 }
 
 impl MipmapCenterPass {
-    pub fn init() -> Self {
-        Self {
-            shader: compile_compute!("assets/shaders/octree/mipmapCenter.comp.glsl"),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(
+                "assets/shaders/octree/mipmapCenter.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.shader.delete();
     }
 
     pub unsafe fn run(
@@ -26,6 +35,8 @@ impl MipmapCenterPass {
         direction: Direction,
         brick_pool_values: BrickPoolValues,
     ) {
+        let _timer = profiler::scope("Mipmap anisotropic centers");
+
         let mut neighbors_texture_number = match direction.axis {
             Axis::X => 0,
             Axis::Y => 2,
@@ -39,7 +50,7 @@ impl MipmapCenterPass {
 
         self.shader.use_program();
 
-        let config = Config::instance();
+        let config = &self.config;
 
         self.shader.set_uint(c_str!("octreeLevel"), level);
         self.shader
@@ -49,6 +60,7 @@ impl MipmapCenterPass {
         self.shader
             .set_int(c_str!("direction.sign"), direction.sign.into());
 
+        helpers::bind_image_texture(7, textures.dirty_nodes.0, gl::READ_ONLY, gl::R32UI);
         helpers::bind_image_texture(0, textures.node_pool.0, gl::READ_ONLY, gl::R32UI);
         let anisotropic_texture = match brick_pool_values {
             BrickPoolValues::Colors => textures.brick_pool_colors,
@@ -85,9 +97,9 @@ impl MipmapCenterPass {
         );
 
         let nodes_in_level = node_data.nodes_per_level[level as usize];
-        let groups_count = (nodes_in_level as f32 / config.working_group_size as f32).ceil() as u32;
 
-        self.shader.dispatch(groups_count);
-        self.shader.wait();
+        self.shader.dispatch_invocations(nodes_in_level);
+        self.shader
+            .wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
     }
 }
@@ -8,7 +8,10 @@ use mipmap_corners::MipmapCornersPass;
 use mipmap_edges::MipmapEdgesPass;
 use mipmap_faces::MipmapFacesPass;
 
+use engine::prelude::ShaderError;
+
 use crate::{
+    config::Config,
     constants::Direction,
     octree::{build::BrickPoolValues, NodeData, OctreeTextures},
 };
@@ -21,13 +24,20 @@ pub struct MipmapAnisotropicPass {
 }
 
 impl MipmapAnisotropicPass {
-    pub fn init() -> Self {
-        Self {
-            center: MipmapCenterPass::init(),
-            corners: MipmapCornersPass::init(),
-            edges: MipmapEdgesPass::init(),
-            faces: MipmapFacesPass::init(),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            center: MipmapCenterPass::init(config)?,
+            corners: MipmapCornersPass::init(config)?,
+            edges: MipmapEdgesPass::init(config)?,
+            faces: MipmapFacesPass::init(config)?,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.center.delete();
+        self.corners.delete();
+        self.edges.delete();
+        self.faces.delete();
     }
 
     pub unsafe fn run(
@@ -8,21 +8,30 @@ use crate::{
 
 pub struct MipmapCornersPass {
     shader: Shader,
+    config: Config,
 }
 
 impl MipmapCornersPass {
-    pub fn init() -> Self {
-        Self {
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
             shader: compile_compute!(
                 "assets/shaders/octree/isotropicMipMaps/mipmapCorners.comp.glsl",
-            ),
-        }
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.shader.delete();
     }
 
     pub unsafe fn run(&self, textures: &OctreeTextures, node_data: &NodeData, level: u32) {
+        let _timer = profiler::scope("Mipmap isotropic corners");
+
         self.shader.use_program();
 
-        let config = Config::instance();
+        let config = &self.config;
 
         self.shader.set_uint(c_str!("octreeLevel"), level);
         self.shader
@@ -33,9 +42,9 @@ impl MipmapCornersPass {
         helpers::bind_image_texture(2, node_data.level_start_indices.0, gl::READ_ONLY, gl::R32UI);
 
         let nodes_in_level = node_data.nodes_per_level[level as usize];
-        let groups_count = (nodes_in_level as f32 / config.working_group_size as f32).ceil() as u32;
 
-        self.shader.dispatch(groups_count);
-        self.shader.wait();
+        self.shader.dispatch_invocations(nodes_in_level);
+        self.shader
+            .wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
     }
 }
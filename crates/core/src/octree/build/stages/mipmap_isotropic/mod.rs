@@ -8,7 +8,12 @@ use mipmap_corners::MipmapCornersPass;
 use mipmap_edges::MipmapEdgesPass;
 use mipmap_faces::MipmapFacesPass;
 
-use crate::octree::{NodeData, OctreeTextures};
+use engine::prelude::ShaderError;
+
+use crate::{
+    config::Config,
+    octree::{NodeData, OctreeTextures},
+};
 
 pub struct MipmapIsotropicPass {
     center: MipmapCenterPass,
@@ -18,13 +23,20 @@ pub struct MipmapIsotropicPass {
 }
 
 impl MipmapIsotropicPass {
-    pub fn init() -> Self {
-        Self {
-            center: MipmapCenterPass::init(),
-            corners: MipmapCornersPass::init(),
-            edges: MipmapEdgesPass::init(),
-            faces: MipmapFacesPass::init(),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            center: MipmapCenterPass::init(config)?,
+            corners: MipmapCornersPass::init(config)?,
+            edges: MipmapEdgesPass::init(config)?,
+            faces: MipmapFacesPass::init(config)?,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.center.delete();
+        self.corners.delete();
+        self.edges.delete();
+        self.faces.delete();
     }
 
     pub unsafe fn run(&self, textures: &OctreeTextures, node_data: &NodeData, level: u32) {
@@ -36,3 +36,9 @@ pub use process_raw_brick_pool_colors::ProcessRawBrickPoolColors;
 
 mod create_alpha_map;
 pub use create_alpha_map::CreateAlphaMap;
+
+mod update_build_counts;
+pub use update_build_counts::UpdateBuildCountsPass;
+
+mod clear_dirty_bricks;
+pub use clear_dirty_bricks::ClearDirtyBricksPass;
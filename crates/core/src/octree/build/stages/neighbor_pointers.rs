@@ -3,43 +3,57 @@ use engine::prelude::*;
 
 use crate::{
     config::Config,
-    octree::{OctreeTextures, VoxelData, NodeData},
+    octree::{
+        build::{BuildCounts, IndirectDispatch},
+        OctreeTextures, VoxelData, NodeData,
+    },
 };
 
 pub struct NeighborPointersPass {
     shader: Shader,
+    config: Config,
 }
 
 impl NeighborPointersPass {
-    pub fn init() -> Self {
-        Self {
-            shader: compile_compute!("assets/shaders/octree/neighborPointers.comp.glsl"),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(
+                "assets/shaders/octree/neighborPointers.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
     }
 
+    pub unsafe fn delete(self) {
+        self.shader.delete();
+    }
+
+    /// Static nodes before `first_dynamic_node` get a pointer back to the nodes of this level
+    /// next to them, 0 when building the static nodes
     pub unsafe fn run(
         &self,
         voxel_data: &VoxelData,
         node_data: &NodeData,
         textures: &OctreeTextures,
         current_octree_level: u32,
-        level_start: u32,
-        nodes_in_current_level: u32
+        build_counts: &BuildCounts,
+        first_dynamic_node: u32,
     ) {
+        let _timer = profiler::scope("Neighbor pointers");
+
         self.shader.use_program();
 
-        let config = Config::instance();
+        let config = &self.config;
 
         // Set uniforms
         self.shader
             .set_uint(c_str!("voxelDimension"), config.voxel_dimension() as u32);
         self.shader
             .set_uint(c_str!("octreeLevel"), current_octree_level);
-        self.shader
-            .set_uint(c_str!("levelStart"), level_start);
-        self.shader
-            .set_uint(c_str!("nextLevelStart"), level_start + nodes_in_current_level);
         self.shader.set_int(c_str!("axis"), 0);
+        self.shader
+            .set_uint(c_str!("firstDynamicNode"), first_dynamic_node);
 
         // Bind images
         helpers::bind_image_texture(0, textures.node_pool.0, gl::WRITE_ONLY, gl::R32UI);
@@ -48,26 +62,23 @@ impl NeighborPointersPass {
         helpers::bind_image_texture(2, textures.neighbors[0].0, gl::WRITE_ONLY, gl::R32UI);
         helpers::bind_image_texture(3, textures.neighbors[1].0, gl::WRITE_ONLY, gl::R32UI);
         helpers::bind_image_texture(4, textures.node_positions.0, gl::READ_ONLY, gl::RGB10_A2UI);
+        helpers::bind_image_texture(5, node_data.level_start_indices.0, gl::READ_ONLY, gl::R32UI);
 
-        let groups_count = (nodes_in_current_level as f32
-            / config.working_group_size as f32)
-            .ceil() as u32;
-
-        self.shader.dispatch(groups_count);
-        self.shader.wait();
+        build_counts.dispatch(&self.shader, IndirectDispatch::NodesInLevel);
+        self.shader.wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
 
         self.shader.set_int(c_str!("axis"), 1);
         helpers::bind_image_texture(2, textures.neighbors[2].0, gl::WRITE_ONLY, gl::R32UI);
         helpers::bind_image_texture(3, textures.neighbors[3].0, gl::WRITE_ONLY, gl::R32UI);
 
-        self.shader.dispatch(groups_count);
-        self.shader.wait();
+        build_counts.dispatch(&self.shader, IndirectDispatch::NodesInLevel);
+        self.shader.wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
 
         self.shader.set_int(c_str!("axis"), 2);
         helpers::bind_image_texture(2, textures.neighbors[4].0, gl::WRITE_ONLY, gl::R32UI);
         helpers::bind_image_texture(3, textures.neighbors[5].0, gl::WRITE_ONLY, gl::R32UI);
 
-        self.shader.dispatch(groups_count);
-        self.shader.wait();
+        build_counts.dispatch(&self.shader, IndirectDispatch::NodesInLevel);
+        self.shader.wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
     }
 }
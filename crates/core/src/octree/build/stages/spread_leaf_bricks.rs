@@ -8,13 +8,22 @@ use crate::{
 
 pub struct SpreadLeafBricksPass {
     shader: Shader,
+    config: Config,
 }
 
 impl SpreadLeafBricksPass {
-    pub fn init() -> Self {
-        Self {
-            shader: compile_compute!("assets/shaders/octree/spreadLeafBricks.comp.glsl"),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(
+                "assets/shaders/octree/spreadLeafBricks.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.shader.delete();
     }
 
     pub unsafe fn run(
@@ -23,14 +32,18 @@ impl SpreadLeafBricksPass {
         node_data: &NodeData,
         brick_pool_values: BrickPoolValues,
     ) {
+        let _timer = profiler::scope("Spread leaf bricks");
+
         self.shader.use_program();
 
-        let config = Config::instance();
+        let config = &self.config;
         let octree_level = config.octree_levels() - 1;
         self.shader.set_uint(c_str!("octreeLevel"), octree_level);
         self.shader
             .set_uint(c_str!("voxelDimension"), config.voxel_dimension());
 
+        helpers::bind_image_texture(7, textures.dirty_nodes.0, gl::READ_ONLY, gl::R32UI);
+
         match brick_pool_values {
             BrickPoolValues::Colors => helpers::bind_3d_image_texture(
                 0,
@@ -54,9 +67,9 @@ impl SpreadLeafBricksPass {
         helpers::bind_image_texture(1, node_data.level_start_indices.0, gl::READ_ONLY, gl::R32UI);
 
         let nodes_in_level = node_data.nodes_per_level[octree_level as usize];
-        let groups_count = (nodes_in_level as f32 / config.working_group_size as f32).ceil() as u32;
 
-        self.shader.dispatch(groups_count);
-        self.shader.wait();
+        self.shader.dispatch_invocations(nodes_in_level);
+        self.shader
+            .wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
     }
 }
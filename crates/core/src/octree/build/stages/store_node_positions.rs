@@ -3,24 +3,46 @@ use engine::prelude::*;
 
 use crate::{
     config::Config,
-    octree::{OctreeTextures, VoxelData},
+    octree::{
+        build::{BuildCounts, IndirectDispatch},
+        OctreeTextures, VoxelData,
+    },
 };
 
 pub struct StoreNodePositions {
     shader: Shader,
+    config: Config,
 }
 
 impl StoreNodePositions {
-    pub fn init() -> Self {
-        Self {
-            shader: compile_compute!("assets/shaders/octree/storeNodePositions.comp.glsl"),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(
+                "assets/shaders/octree/storeNodePositions.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
     }
 
-    pub unsafe fn run(&self, textures: &OctreeTextures, octree_level: u32, voxel_data: &VoxelData) {
+    pub unsafe fn delete(self) {
+        self.shader.delete();
+    }
+
+    /// `voxel_fragments` has one invocation per voxel fragment of `voxel_data`
+    pub unsafe fn run(
+        &self,
+        textures: &OctreeTextures,
+        octree_level: u32,
+        voxel_data: &VoxelData,
+        build_counts: &BuildCounts,
+        voxel_fragments: IndirectDispatch,
+    ) {
+        let _timer = profiler::scope("Store node positions");
+
         self.shader.use_program();
 
-        let config = Config::instance();
+        let config = &self.config;
 
         self.shader.set_uint(c_str!("octreeLevel"), octree_level);
         self.shader
@@ -34,11 +56,9 @@ impl StoreNodePositions {
         );
         helpers::bind_image_texture(1, textures.node_positions.0, gl::WRITE_ONLY, gl::RGB10_A2UI);
         helpers::bind_image_texture(2, textures.node_pool.0, gl::READ_ONLY, gl::R32UI);
+        helpers::bind_image_texture(3, build_counts.texture(), gl::READ_ONLY, gl::R32UI);
 
-        let groups_count = (voxel_data.number_of_voxel_fragments as f32
-            / config.working_group_size as f32)
-            .ceil() as u32;
-        self.shader.dispatch(groups_count);
-        self.shader.wait();
+        build_counts.dispatch(&self.shader, voxel_fragments);
+        self.shader.wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
     }
 }
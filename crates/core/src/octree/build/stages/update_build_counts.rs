@@ -0,0 +1,52 @@
+use c_str_macro::c_str;
+use engine::prelude::*;
+
+use crate::{config::Config, octree::build::BuildCounts};
+
+/// Updates the start of the next octree level and the indirect dispatches of `BuildCounts`
+/// from the nodes allocated so far, on the GPU so they don't have to be read back
+pub struct UpdateBuildCountsPass {
+    shader: Shader,
+}
+
+impl UpdateBuildCountsPass {
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(
+                "assets/shaders/octree/updateBuildCounts.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.shader.delete();
+    }
+
+    /// `octree_level` is the one being built, its nodes are the ones allocated after its start
+    pub unsafe fn run(
+        &self,
+        build_counts: &BuildCounts,
+        level_start_indices: BufferTexture,
+        octree_level: u32,
+    ) {
+        let _timer = profiler::scope("Update build counts");
+
+        self.shader.use_program();
+
+        self.shader.set_uint(c_str!("octreeLevel"), octree_level);
+        self.shader
+            .set_uint(c_str!("maxWorkGroupCount"), build_counts.max_groups_in_x());
+
+        helpers::bind_image_texture(0, build_counts.texture(), gl::READ_WRITE, gl::R32UI);
+        helpers::bind_image_texture(1, level_start_indices.0, gl::READ_WRITE, gl::R32UI);
+
+        self.shader.dispatch(1);
+        // The counts are read as dispatch arguments and written again from the CPU
+        self.shader.wait_for(
+            gl::SHADER_IMAGE_ACCESS_BARRIER_BIT
+                | gl::COMMAND_BARRIER_BIT
+                | gl::BUFFER_UPDATE_BARRIER_BIT,
+        );
+    }
+}
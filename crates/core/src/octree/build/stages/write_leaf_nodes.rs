@@ -8,18 +8,36 @@ use crate::{
 
 pub struct WriteLeafNodesPass {
     shader: Shader,
+    config: Config,
 }
 
 impl WriteLeafNodesPass {
-    pub fn init() -> Self {
-        Self {
-            shader: compile_compute!("assets/shaders/octree/writeLeafNodes.comp.glsl"),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(
+                "assets/shaders/octree/writeLeafNodes.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
     }
 
-    pub unsafe fn run(&self, voxel_data: &VoxelData, textures: &OctreeTextures) {
+    pub unsafe fn delete(self) {
+        self.shader.delete();
+    }
+
+    /// Leaves written are flagged as changed. With `only_dirty_leaves` the voxel fragments
+    /// are only written to the leaves that are already dirty, so the rest aren't written twice
+    pub unsafe fn run(
+        &self,
+        voxel_data: &VoxelData,
+        textures: &OctreeTextures,
+        only_dirty_leaves: bool,
+    ) {
+        let _timer = profiler::scope("Write leaf nodes");
+
         self.shader.use_program();
-        let config = Config::instance();
+        let config = &self.config;
         let octree_level = config.last_octree_level();
 
         self.shader
@@ -29,6 +47,8 @@ impl WriteLeafNodesPass {
             c_str!("numberOfVoxelFragments"),
             voxel_data.number_of_voxel_fragments,
         );
+        self.shader
+            .set_bool(c_str!("onlyDirtyLeaves"), only_dirty_leaves);
 
         helpers::bind_image_texture(
             0,
@@ -46,11 +66,11 @@ impl WriteLeafNodesPass {
         helpers::bind_image_texture(3, textures.node_pool.0, gl::READ_WRITE, gl::R32UI);
         helpers::bind_image_texture(4, voxel_data.voxel_normals.0, gl::READ_ONLY, gl::RGBA32F);
         helpers::bind_3d_image_texture(5, textures.brick_pool_normals, gl::WRITE_ONLY, gl::RGBA32F);
+        helpers::bind_image_texture(6, textures.dirty_nodes.0, gl::READ_WRITE, gl::R32UI);
 
-        self.shader.dispatch(
-            (voxel_data.number_of_voxel_fragments as f32 / config.working_group_size as f32).ceil()
-                as u32,
-        );
-        self.shader.wait();
+        self.shader
+            .dispatch_invocations(voxel_data.number_of_voxel_fragments);
+        self.shader
+            .wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
     }
 }
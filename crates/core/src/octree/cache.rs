@@ -0,0 +1,491 @@
+//! Saving a built octree to disk, so unchanged scenes skip voxelization and building.
+//!
+//! The file is little endian and starts with a header:
+//! magic, format version, cache key, voxel dimension, brick pool resolution and number of nodes.
+//! Then come the buffers, each one prefixed with its length in bytes.
+//! Only the used part of each buffer and brick pool is stored.
+//! Lengths are checked against the ones the header and config give before reading, so a
+//! truncated or corrupted file fails with `io::ErrorKind::InvalidData`.
+
+use std::{
+    ffi::c_void,
+    fs::{self, File},
+    hash::Hasher,
+    io::{self, BufReader, BufWriter, Read, Write},
+    mem::size_of,
+    path::Path,
+};
+
+use engine::prelude::*;
+use fnv::FnvHasher;
+use gl::types::{GLenum, GLuint};
+
+use super::{Builder, LightTimings, NodeData, Octree, OctreeData, Renderer, VoxelData};
+use crate::{config::Config, constants};
+
+const MAGIC: &[u8; 4] = b"VCTO";
+/// Bump whenever the layout of the file or of the octree changes
+const VERSION: u32 = 1;
+
+/// Hashes the contents of all files, in order.
+/// The hash is stable across runs and platforms, so it can be stored.
+pub fn cache_key<P: AsRef<Path>>(files: &[P]) -> io::Result<u64> {
+    let mut hasher = FnvHasher::default();
+    for file in files {
+        let contents = fs::read(file)?;
+        hasher.write_u64(contents.len() as u64);
+        hasher.write(&contents);
+    }
+    Ok(hasher.finish())
+}
+
+/// A 3D texture of the brick pool and how to read and write its texels
+struct BrickPool {
+    texture: Texture3D,
+    format: GLenum,
+    type_: GLenum,
+    bytes_per_texel: usize,
+}
+
+impl BrickPool {
+    fn rgba(texture: Texture3D) -> Self {
+        Self {
+            texture,
+            format: gl::RGBA,
+            type_: gl::UNSIGNED_BYTE,
+            bytes_per_texel: 4,
+        }
+    }
+
+    fn rgba32f(texture: Texture3D) -> Self {
+        Self {
+            texture,
+            format: gl::RGBA,
+            type_: gl::FLOAT,
+            bytes_per_texel: 16,
+        }
+    }
+
+    fn r32ui(texture: Texture3D) -> Self {
+        Self {
+            texture,
+            format: gl::RED_INTEGER,
+            type_: gl::UNSIGNED_INT,
+            bytes_per_texel: 4,
+        }
+    }
+}
+
+impl Octree {
+    /// Writes the octree to `path`, tagged with `key` so `load` can tell if it's stale
+    pub unsafe fn save(&self, path: impl AsRef<Path>, key: u64) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        // Make sure all image stores of the build are visible to the reads below
+        gl::MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT | gl::TEXTURE_UPDATE_BARRIER_BIT);
+
+        let config = &self.config;
+        let number_of_nodes = self.number_of_nodes();
+
+        writer.write_all(MAGIC)?;
+        write_u32(&mut writer, VERSION)?;
+        writer.write_all(&key.to_le_bytes())?;
+        write_u32(&mut writer, config.voxel_dimension())?;
+        write_u32(&mut writer, config.brick_pool_resolution)?;
+        write_u32(&mut writer, number_of_nodes as u32)?;
+
+        let children_bytes =
+            number_of_nodes * constants::CHILDREN_PER_NODE as usize * size_of::<u32>();
+        let node_bytes = number_of_nodes * size_of::<u32>();
+        write_buffer(&mut writer, self.textures.node_pool.1, children_bytes)?;
+        write_buffer(&mut writer, self.textures.brick_pointers.1, node_bytes)?;
+        write_buffer(&mut writer, self.textures.node_positions.1, node_bytes)?;
+        for neighbors in self.textures.neighbors.iter() {
+            write_buffer(&mut writer, neighbors.1, node_bytes)?;
+        }
+
+        for octree_data in [&self.geometry_data, &self.border_data] {
+            write_octree_data(&mut writer, octree_data, config)?;
+        }
+
+        let depth = used_brick_pool_depth(config, number_of_nodes);
+        for brick_pool in self.brick_pools() {
+            write_brick_pool(&mut writer, &brick_pool, depth, config)?;
+        }
+
+        writer.flush()
+    }
+
+    /// Reads an octree written by `save`.
+    /// Fails with `io::ErrorKind::InvalidData` if the file was made with another `key`,
+    /// another version of the format or another `config`, and with `io::ErrorKind::Other`
+    /// if its shaders don't compile.
+    pub unsafe fn load(config: &Config, path: impl AsRef<Path>, key: u64) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not an octree cache file"));
+        }
+        if read_u32(&mut reader)? != VERSION {
+            return Err(invalid_data("octree cache file from another version"));
+        }
+        let mut stored_key = [0; 8];
+        reader.read_exact(&mut stored_key)?;
+        if u64::from_le_bytes(stored_key) != key {
+            return Err(invalid_data("octree cache file is stale"));
+        }
+
+        if read_u32(&mut reader)? != config.voxel_dimension()
+            || read_u32(&mut reader)? != config.brick_pool_resolution
+        {
+            return Err(invalid_data("octree cache file made with another config"));
+        }
+        let number_of_nodes = read_u32(&mut reader)? as usize;
+
+        let max_node_pool_size = Self::get_max_node_pool_size(config);
+        if number_of_nodes * constants::CHILDREN_PER_NODE as usize > max_node_pool_size {
+            return Err(invalid_data("octree cache file with more nodes than fit"));
+        }
+        let max_node_pool_size_in_bytes = size_of::<GLuint>() * max_node_pool_size;
+        let textures = Self::initialize_textures(config, max_node_pool_size_in_bytes);
+
+        let children_bytes =
+            number_of_nodes * constants::CHILDREN_PER_NODE as usize * size_of::<u32>();
+        let node_bytes = number_of_nodes * size_of::<u32>();
+        read_buffer(&mut reader, textures.node_pool.1, children_bytes)?;
+        read_buffer(&mut reader, textures.brick_pointers.1, node_bytes)?;
+        read_buffer(&mut reader, textures.node_positions.1, node_bytes)?;
+        for neighbors in textures.neighbors.iter() {
+            read_buffer(&mut reader, neighbors.1, node_bytes)?;
+        }
+
+        let geometry_data = read_octree_data(&mut reader, config)?;
+        let border_data = read_octree_data(&mut reader, config)?;
+
+        let octree = Self {
+            config: *config,
+            dynamic_data: None,
+            light_timings: LightTimings::default(),
+            geometry_data,
+            border_data,
+            textures,
+            renderer: Renderer::init(config).map_err(io::Error::other)?,
+            builder: Builder::init(config).map_err(io::Error::other)?,
+        };
+
+        let depth = used_brick_pool_depth(config, number_of_nodes);
+        for brick_pool in octree.brick_pools() {
+            read_brick_pool(&mut reader, &brick_pool, depth, config)?;
+        }
+
+        Ok(octree)
+    }
+
+    fn brick_pools(&self) -> Vec<BrickPool> {
+        let textures = &self.textures;
+        let mut brick_pools = vec![BrickPool::r32ui(textures.brick_pool_colors_raw)];
+        brick_pools.extend(
+            textures
+                .brick_pool_colors
+                .iter()
+                .copied()
+                .map(BrickPool::rgba),
+        );
+        brick_pools.push(BrickPool::rgba(textures.brick_pool_alpha));
+        brick_pools.extend(
+            textures
+                .brick_pool_irradiance
+                .iter()
+                .copied()
+                .map(BrickPool::rgba),
+        );
+        brick_pools.push(BrickPool::rgba32f(textures.brick_pool_normals));
+        brick_pools.push(BrickPool::r32ui(textures.brick_pool_photons));
+        brick_pools
+    }
+}
+
+/// Bricks are laid out by node index, filling whole XY slices before moving on in Z,
+/// so everything after the slice of the last node is unused
+pub(super) fn used_brick_pool_depth(config: &Config, number_of_nodes: usize) -> u32 {
+    let bricks_per_side = (config.brick_pool_resolution / constants::BRICK_SIZE) as usize;
+    let bricks_per_slice = bricks_per_side * bricks_per_side;
+    let used_slices = (number_of_nodes + bricks_per_slice - 1) / bricks_per_slice;
+    (used_slices as u32 * constants::BRICK_SIZE).min(config.brick_pool_resolution)
+}
+
+unsafe fn write_octree_data(
+    writer: &mut impl Write,
+    octree_data: &OctreeData,
+    config: &Config,
+) -> io::Result<()> {
+    let node_data = &octree_data.node_data;
+    write_u32(writer, node_data.nodes_per_level.len() as u32)?;
+    for &nodes in node_data.nodes_per_level.iter() {
+        write_u32(writer, nodes)?;
+    }
+    let level_start_indices_bytes = (config.octree_levels() + 1) as usize * size_of::<u32>();
+    write_buffer(
+        writer,
+        node_data.level_start_indices.1,
+        level_start_indices_bytes,
+    )?;
+
+    let voxel_data = &octree_data.voxel_data;
+    let number_of_voxel_fragments = voxel_data.number_of_voxel_fragments as usize;
+    write_u32(writer, voxel_data.number_of_voxel_fragments)?;
+    write_buffer(
+        writer,
+        voxel_data.voxel_positions.buffer(),
+        number_of_voxel_fragments * size_of::<u32>(),
+    )?;
+    // Colors are RGBA8 and normals RGBA32F
+    write_buffer(
+        writer,
+        voxel_data.voxel_colors.1,
+        number_of_voxel_fragments * 4,
+    )?;
+    write_buffer(
+        writer,
+        voxel_data.voxel_normals.1,
+        number_of_voxel_fragments * 16,
+    )
+}
+
+unsafe fn read_octree_data(reader: &mut impl Read, config: &Config) -> io::Result<OctreeData> {
+    let levels = read_u32(reader)?;
+    if levels > config.octree_levels() + 1 {
+        return Err(invalid_data(
+            "octree cache file with more levels than the config",
+        ));
+    }
+    let nodes_per_level = (0..levels)
+        .map(|_| read_u32(reader))
+        .collect::<io::Result<Vec<u32>>>()?;
+    let level_start_indices =
+        helpers::generate_texture_buffer((config.octree_levels() + 1) as usize, gl::R32UI, 0u32);
+    let level_start_indices_bytes = (config.octree_levels() + 1) as usize * size_of::<u32>();
+    read_buffer(reader, level_start_indices.1, level_start_indices_bytes)?;
+
+    let number_of_voxel_fragments = read_u32(reader)?;
+    let fragments = number_of_voxel_fragments as usize;
+    // Colors are RGBA8 and normals RGBA32F
+    let voxel_positions = read_new_buffer(reader, gl::R32UI, fragments * size_of::<u32>())?;
+    let voxel_colors = read_new_buffer(reader, gl::RGBA8, fragments * 4)?;
+    let voxel_normals = read_new_buffer(reader, gl::RGBA32F, fragments * 16)?;
+
+    Ok(OctreeData {
+        node_data: NodeData {
+            nodes_per_level,
+            level_start_indices,
+        },
+        voxel_data: VoxelData {
+            voxel_positions: BufferTextureV2::from_texture_and_buffer(voxel_positions),
+            number_of_voxel_fragments,
+            voxel_colors,
+            voxel_normals,
+        },
+    })
+}
+
+/// Buffers that were never created (0) are written as empty
+unsafe fn write_buffer(
+    writer: &mut impl Write,
+    buffer: TextureBuffer,
+    size_in_bytes: usize,
+) -> io::Result<()> {
+    if buffer == 0 {
+        return writer.write_all(&0u64.to_le_bytes());
+    }
+    let data = helpers::get_values_from_texture_buffer(buffer, size_in_bytes, 0u8);
+    writer.write_all(&(size_in_bytes as u64).to_le_bytes())?;
+    writer.write_all(&data)
+}
+
+/// Reads `size_in_bytes` into the start of an already allocated buffer
+unsafe fn read_buffer(
+    reader: &mut impl Read,
+    buffer: TextureBuffer,
+    size_in_bytes: usize,
+) -> io::Result<()> {
+    let data = read_bytes(reader, &[size_in_bytes])?;
+    gl::BindBuffer(gl::TEXTURE_BUFFER, buffer);
+    gl::BufferSubData(
+        gl::TEXTURE_BUFFER,
+        0,
+        data.len() as isize,
+        data.as_ptr() as *const c_void,
+    );
+    gl::BindBuffer(gl::TEXTURE_BUFFER, 0);
+    Ok(())
+}
+
+/// Creates a buffer texture with exactly what was stored, or none if it was empty.
+/// Buffers that were never created are stored empty, so that size is allowed too
+unsafe fn read_new_buffer(
+    reader: &mut impl Read,
+    format: GLenum,
+    size_in_bytes: usize,
+) -> io::Result<BufferTexture> {
+    let data = read_bytes(reader, &[0, size_in_bytes])?;
+    if data.is_empty() {
+        return Ok((0, 0));
+    }
+    Ok(helpers::generate_texture_buffer_with_initial_data(
+        data.len(),
+        format,
+        data,
+    ))
+}
+
+unsafe fn write_brick_pool(
+    writer: &mut impl Write,
+    brick_pool: &BrickPool,
+    depth: u32,
+    config: &Config,
+) -> io::Result<()> {
+    let resolution = config.brick_pool_resolution as usize;
+    let size_in_bytes = resolution * resolution * depth as usize * brick_pool.bytes_per_texel;
+    let data = vec![0u8; size_in_bytes];
+    gl::GetTextureSubImage(
+        brick_pool.texture,
+        0,
+        0,
+        0,
+        0,
+        resolution as i32,
+        resolution as i32,
+        depth as i32,
+        brick_pool.format,
+        brick_pool.type_,
+        size_in_bytes as i32,
+        data.as_ptr() as *mut c_void,
+    );
+    writer.write_all(&(size_in_bytes as u64).to_le_bytes())?;
+    writer.write_all(&data)
+}
+
+unsafe fn read_brick_pool(
+    reader: &mut impl Read,
+    brick_pool: &BrickPool,
+    depth: u32,
+    config: &Config,
+) -> io::Result<()> {
+    let resolution = config.brick_pool_resolution as usize;
+    let size_in_bytes = resolution * resolution * depth as usize * brick_pool.bytes_per_texel;
+    let data = read_bytes(reader, &[size_in_bytes])?;
+    gl::TextureSubImage3D(
+        brick_pool.texture,
+        0,
+        0,
+        0,
+        0,
+        resolution as i32,
+        resolution as i32,
+        depth as i32,
+        brick_pool.format,
+        brick_pool.type_,
+        data.as_ptr() as *const c_void,
+    );
+    Ok(())
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Reads a length prefixed blob, whose length has to be one of `expected_lengths`.
+/// The stored length isn't trusted to allocate, a truncated file fails at its end
+fn read_bytes(reader: &mut impl Read, expected_lengths: &[usize]) -> io::Result<Vec<u8>> {
+    let mut length = [0; 8];
+    reader.read_exact(&mut length)?;
+    let length = u64::from_le_bytes(length);
+    if !expected_lengths
+        .iter()
+        .any(|&expected| expected as u64 == length)
+    {
+        return Err(invalid_data(
+            "octree cache file with a buffer of unexpected size",
+        ));
+    }
+    let mut data = Vec::new();
+    reader.take(length).read_to_end(&mut data)?;
+    if data.len() as u64 != length {
+        return Err(invalid_data("octree cache file is truncated"));
+    }
+    Ok(data)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    #[test]
+    fn cache_key_depends_on_contents() {
+        let folder = env::temp_dir().join("vct_cache_key_test");
+        fs::create_dir_all(&folder).unwrap();
+        let first = folder.join("first.ron");
+        let second = folder.join("second.ron");
+        fs::write(&first, "voxel_dimension: 9").unwrap();
+        fs::write(&second, "voxel_dimension: 9").unwrap();
+
+        let key = cache_key(&[&first]).unwrap();
+        assert_eq!(key, cache_key(&[&second]).unwrap());
+        assert_ne!(key, cache_key(&[&first, &second]).unwrap());
+
+        fs::write(&second, "voxel_dimension: 10").unwrap();
+        assert_ne!(key, cache_key(&[&second]).unwrap());
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn cache_key_fails_on_missing_file() {
+        assert!(cache_key(&["this/file/does/not/exist.obj"]).is_err());
+    }
+
+    #[test]
+    fn length_prefixed_blobs_roundtrip() {
+        let mut bytes = Vec::new();
+        write_u32(&mut bytes, 42).unwrap();
+        bytes.extend_from_slice(&3u64.to_le_bytes());
+        bytes.extend_from_slice(&[1, 2, 3]);
+
+        let mut reader = &bytes[..];
+        assert_eq!(read_u32(&mut reader).unwrap(), 42);
+        assert_eq!(read_bytes(&mut reader, &[3]).unwrap(), vec![1, 2, 3]);
+        assert!(read_u32(&mut reader).is_err());
+    }
+
+    #[test]
+    fn unexpected_or_truncated_blobs_are_invalid_data() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        let error = read_bytes(&mut &bytes[..], &[3]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&3u64.to_le_bytes());
+        bytes.extend_from_slice(&[1, 2]);
+        let error = read_bytes(&mut &bytes[..], &[3]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,372 @@
+use std::mem::size_of;
+
+use engine::prelude::*;
+use gl::types::GLuint;
+
+use super::{
+    build::{AllocateNodesInput, BuildCounts, FlagNodesInput, IndirectDispatch},
+    lighting::{NODE_CHANGED, NODE_CLEAN},
+    NodeData, Octree, OctreeData, OctreeTextures, VoxelData,
+};
+use crate::constants::CHILDREN_PER_NODE;
+
+/// Nodes and voxel fragments of the dynamic objects.
+/// Their nodes go after the static ones in the node pool, so they can be cleared and
+/// built again without touching the static ones
+pub(super) struct DynamicData {
+    /// Its levels start after the static nodes
+    pub(super) octree_data: OctreeData,
+    static_pointers: StaticPointers,
+}
+
+impl DynamicData {
+    /// Frees everything but the voxel fragments
+    pub(super) unsafe fn into_voxel_data(self) -> VoxelData {
+        let level_start_indices = self.octree_data.node_data.level_start_indices;
+        gl::DeleteTextures(1, &level_start_indices.0);
+        gl::DeleteBuffers(1, &level_start_indices.1);
+        self.static_pointers.delete();
+        self.octree_data.voxel_data
+    }
+}
+
+/// Child and neighbor pointers of the static nodes before any dynamic node was linked to them
+struct StaticPointers {
+    node_pool: GLuint,
+    neighbors: [GLuint; 6],
+}
+
+impl StaticPointers {
+    unsafe fn save(textures: &OctreeTextures, static_nodes: usize) -> Self {
+        Self {
+            node_pool: helpers::copy_to_new_buffer(
+                textures.node_pool.1,
+                static_nodes * CHILDREN_PER_NODE as usize * size_of::<GLuint>(),
+            ),
+            neighbors: textures.neighbors.map(|neighbors| {
+                helpers::copy_to_new_buffer(neighbors.1, static_nodes * size_of::<GLuint>())
+            }),
+        }
+    }
+
+    unsafe fn restore(&self, textures: &OctreeTextures, static_nodes: usize) {
+        helpers::copy_buffer(
+            self.node_pool,
+            textures.node_pool.1,
+            static_nodes * CHILDREN_PER_NODE as usize * size_of::<GLuint>(),
+        );
+        for (saved, neighbors) in self.neighbors.iter().zip(textures.neighbors) {
+            helpers::copy_buffer(*saved, neighbors.1, static_nodes * size_of::<GLuint>());
+        }
+    }
+
+    unsafe fn delete(self) {
+        gl::DeleteBuffers(1, &self.node_pool);
+        gl::DeleteBuffers(self.neighbors.len() as i32, self.neighbors.as_ptr());
+    }
+}
+
+impl Octree {
+    /// Replaces the voxel fragments of dynamic objects with `dynamic_voxel_data` and builds
+    /// their nodes and bricks again, after the static nodes in the node pool.
+    /// Only the leaves the dynamic objects were and are in, the dynamic nodes and everything
+    /// that reads from or shares borders with them get their bricks written again.
+    /// They are left dirty, so `inject_lights_into_dirty_nodes` updates the light afterwards
+    pub unsafe fn update_dynamic_voxels(&mut self, dynamic_voxel_data: VoxelData) {
+        let static_nodes = self.geometry_data.number_of_nodes();
+
+        helpers::fill_texture_buffer(self.textures.dirty_nodes.1, NODE_CLEAN);
+        let static_pointers = match self.remove_dynamic_nodes() {
+            Some(static_pointers) => {
+                // The static leaves the dynamic objects were in were cleared
+                self.builder.write_leaf_nodes_pass.run(
+                    &self.geometry_data.voxel_data,
+                    &self.textures,
+                    true,
+                );
+                static_pointers
+            }
+            None => StaticPointers::save(&self.textures, static_nodes),
+        };
+
+        self.reserve_border_voxel_fragments(dynamic_voxel_data.number_of_voxel_fragments);
+        let node_data = self.dynamic_voxels_to_nodes(&dynamic_voxel_data);
+        self.builder
+            .write_leaf_nodes_pass
+            .run(&dynamic_voxel_data, &self.textures, false);
+        self.dynamic_data = Some(DynamicData {
+            octree_data: OctreeData {
+                node_data,
+                voxel_data: dynamic_voxel_data,
+            },
+            static_pointers,
+        });
+
+        helpers::fill_texture_buffer_range(
+            self.textures.dirty_nodes.1,
+            static_nodes..self.number_of_nodes(),
+            NODE_CHANGED,
+        );
+        self.flag_dirty_neighbors_and_parents();
+        self.write_bricks();
+    }
+
+    /// Flags the nodes of the dynamic objects and the leaves they were in, with their neighbors
+    /// and parents, and clears their raw colors and normals. Then gives the static nodes back
+    /// the pointers they had before the dynamic nodes were linked to them.
+    /// Returns those pointers, `None` if there were no dynamic nodes
+    unsafe fn remove_dynamic_nodes(&mut self) -> Option<StaticPointers> {
+        let dynamic_voxel_data = &self.dynamic_data.as_ref()?.octree_data.voxel_data;
+        let static_nodes = self.geometry_data.number_of_nodes();
+        let number_of_nodes = self.number_of_nodes();
+
+        self.builder
+            .flag_dirty_nodes
+            .voxel_fragments(&self.textures, dynamic_voxel_data);
+        helpers::fill_texture_buffer_range(
+            self.textures.dirty_nodes.1,
+            static_nodes..number_of_nodes,
+            NODE_CHANGED,
+        );
+        self.flag_dirty_neighbors_and_parents();
+        self.builder
+            .clear_dirty_bricks_pass
+            .run(&self.textures, number_of_nodes);
+
+        let dynamic_data = self.dynamic_data.take()?;
+        let textures = &self.textures;
+        dynamic_data.static_pointers.restore(textures, static_nodes);
+        // The next dynamic nodes are allocated from empty pointers again
+        helpers::fill_texture_buffer_range(
+            textures.node_pool.1,
+            static_nodes * CHILDREN_PER_NODE as usize..number_of_nodes * CHILDREN_PER_NODE as usize,
+            0,
+        );
+        for buffer_texture in [
+            textures.brick_pointers,
+            textures.node_positions,
+            textures.lit_nodes,
+        ]
+        .into_iter()
+        .chain(textures.neighbors)
+        {
+            helpers::fill_texture_buffer_range(buffer_texture.1, static_nodes..number_of_nodes, 0);
+        }
+
+        let DynamicData {
+            octree_data,
+            static_pointers,
+        } = dynamic_data;
+        let level_start_indices = octree_data.node_data.level_start_indices;
+        gl::DeleteTextures(1, &level_start_indices.0);
+        gl::DeleteBuffers(1, &level_start_indices.1);
+        octree_data.voxel_data.delete();
+
+        Some(static_pointers)
+    }
+
+    /// Same as `voxels_to_nodes`, but the nodes are allocated after the static ones,
+    /// for the flagged children of both dynamic and static nodes.
+    /// Static nodes get pointers back to the dynamic nodes next to them
+    unsafe fn dynamic_voxels_to_nodes(&self, voxel_data: &VoxelData) -> NodeData {
+        let config = self.config;
+
+        let static_node_data = &self.geometry_data.node_data;
+        let first_dynamic_node = static_node_data.number_of_nodes() as u32;
+        let level_start_indices = helpers::generate_texture_buffer(
+            (config.octree_levels() + 1) as usize,
+            gl::R32UI,
+            0u32,
+        );
+        // Every level is empty until its nodes are allocated
+        helpers::fill_texture_buffer_with_data(
+            level_start_indices.1,
+            &vec![first_dynamic_node; config.octree_levels() as usize + 1],
+            gl::STATIC_DRAW,
+        );
+        let node_data = NodeData {
+            nodes_per_level: Vec::new(),
+            level_start_indices,
+        };
+
+        let build_counts = BuildCounts::new(&config, voxel_data.number_of_voxel_fragments);
+        build_counts.set_next_free_node(first_dynamic_node);
+        let update_build_counts = |octree_level| {
+            self.builder.update_build_counts_pass.run(
+                &build_counts,
+                node_data.level_start_indices,
+                octree_level,
+            )
+        };
+        let node_pool = BufferTextureV2::from_texture_and_buffer(self.textures.node_pool);
+        let allocate_nodes = |octree_level: u32| {
+            self.builder.allocate_nodes_pass.run(AllocateNodesInput {
+                octree_level: octree_level - 1,
+                node_pool: node_pool.clone(),
+                level_start_indices: BufferTextureV2::from_texture_and_buffer(
+                    node_data.level_start_indices,
+                ),
+                build_counts: build_counts.clone(),
+            });
+            // Voxel fragments also go through static nodes, whose children may not exist yet
+            build_counts.set_dispatch(
+                IndirectDispatch::ChildrenOfPreviousLevel,
+                static_node_data.nodes_per_level[octree_level as usize - 1] * CHILDREN_PER_NODE,
+            );
+            self.builder.allocate_nodes_pass.run(AllocateNodesInput {
+                octree_level: octree_level - 1,
+                node_pool: node_pool.clone(),
+                level_start_indices: BufferTextureV2::from_texture_and_buffer(
+                    static_node_data.level_start_indices,
+                ),
+                build_counts: build_counts.clone(),
+            });
+        };
+
+        update_build_counts(0);
+        for octree_level in 1..=config.last_octree_level() {
+            update_build_counts(octree_level);
+            self.builder.flag_nodes_pass.run(FlagNodesInput {
+                octree_level: octree_level - 1,
+                voxel_data: voxel_data.clone(),
+                node_pool: node_pool.clone(),
+                build_counts: build_counts.clone(),
+                voxel_fragments: IndirectDispatch::VoxelFragments,
+            });
+            allocate_nodes(octree_level);
+            update_build_counts(octree_level);
+
+            self.builder.store_node_positions_pass.run(
+                &self.textures,
+                octree_level,
+                voxel_data,
+                &build_counts,
+                IndirectDispatch::VoxelFragments,
+            );
+
+            self.builder.neighbor_pointers_pass.run(
+                voxel_data,
+                &node_data,
+                &self.textures,
+                octree_level,
+                &build_counts,
+                first_dynamic_node,
+            );
+
+            self.builder.append_border_voxel_fragments_pass.run(
+                &self.border_data,
+                octree_level,
+                &self.textures,
+                &node_data,
+                &build_counts,
+            );
+            update_build_counts(octree_level);
+
+            self.builder.flag_nodes_pass.run(FlagNodesInput {
+                octree_level: octree_level - 1,
+                voxel_data: self.border_data.voxel_data.clone(),
+                node_pool: node_pool.clone(),
+                build_counts: build_counts.clone(),
+                voxel_fragments: IndirectDispatch::BorderVoxelFragments,
+            });
+            allocate_nodes(octree_level);
+            update_build_counts(octree_level);
+
+            self.builder.store_node_positions_pass.run(
+                &self.textures,
+                octree_level,
+                &self.border_data.voxel_data,
+                &build_counts,
+                IndirectDispatch::BorderVoxelFragments,
+            );
+
+            self.builder.neighbor_pointers_pass.run(
+                voxel_data,
+                &node_data,
+                &self.textures,
+                octree_level,
+                &build_counts,
+                first_dynamic_node,
+            );
+        }
+
+        self.builder.store_node_positions_pass.run(
+            &self.textures,
+            config.last_octree_level(),
+            voxel_data,
+            &build_counts,
+            IndirectDispatch::VoxelFragments,
+        );
+
+        gl::MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT);
+        let level_start_indices = helpers::get_values_from_texture_buffer(
+            node_data.level_start_indices.1,
+            config.octree_levels() as usize + 1,
+            0u32,
+        );
+        build_counts.delete();
+
+        let node_data = NodeData {
+            nodes_per_level: level_start_indices
+                .windows(2)
+                .map(|window| window[1] - window[0])
+                .collect(),
+            ..node_data
+        };
+        log::debug!("Dynamic nodes_per_level: {:?}", &node_data.nodes_per_level);
+
+        node_data
+    }
+
+    /// Makes sure the border voxel fragment list fits the ones of `number_of_voxel_fragments`
+    /// voxel fragments
+    unsafe fn reserve_border_voxel_fragments(&mut self, number_of_voxel_fragments: u32) {
+        // There can't be more border voxel fragments than voxel fragments
+        let border_voxel_positions = &mut self.border_data.voxel_data.voxel_positions;
+        if border_voxel_positions.len() < number_of_voxel_fragments as usize {
+            let old_voxel_positions = std::mem::replace(
+                border_voxel_positions,
+                BufferTextureV2::from_data(vec![0u32; number_of_voxel_fragments as usize]),
+            );
+            gl::DeleteTextures(1, &old_voxel_positions.texture());
+            gl::DeleteBuffers(1, &old_voxel_positions.buffer());
+        }
+    }
+
+    /// Empties the node pool and the brick pools that the build accumulates into,
+    /// the rest of the brick pools are completely written by the build
+    pub(super) unsafe fn clear_nodes(&mut self) {
+        let textures = &self.textures;
+        let node_buffers = [
+            textures.node_pool.1,
+            textures.brick_pointers.1,
+            textures.node_positions.1,
+        ];
+        for buffer in node_buffers
+            .into_iter()
+            .chain(textures.neighbors.iter().map(|neighbors| neighbors.1))
+        {
+            helpers::fill_texture_buffer(buffer, 0);
+        }
+
+        // The passes before only wait for image accesses and texture fetches
+        gl::MemoryBarrier(gl::TEXTURE_UPDATE_BARRIER_BIT);
+        gl::ClearTexImage(
+            textures.brick_pool_colors_raw,
+            0,
+            gl::RED_INTEGER,
+            gl::UNSIGNED_INT,
+            std::ptr::null(),
+        );
+        gl::ClearTexImage(
+            textures.brick_pool_normals,
+            0,
+            gl::RGBA,
+            gl::FLOAT,
+            std::ptr::null(),
+        );
+
+        self.geometry_data.node_data.nodes_per_level.clear();
+        self.border_data.node_data.nodes_per_level.clear();
+    }
+}
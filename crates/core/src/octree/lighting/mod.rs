@@ -1,89 +1,327 @@
 use engine::prelude::*;
 use gl::types::GLuint;
 
-use crate::{config::Config, constants::Axis};
+use crate::constants::Axis;
 
-use super::{build::BrickPoolValues, Octree};
+use super::{build::BrickPoolValues, cache::used_brick_pool_depth, Octree};
 
 mod stages;
+mod timings;
 pub use stages::*;
+pub use timings::LightTimings;
+
+/// Lights are tracked in the bits of `OctreeTextures::lit_nodes`,
+/// moving any light after these injects every light again
+pub const MAX_TRACKED_LIGHTS: usize = u32::BITS as usize;
+
+// Same as in `_dirtyNodes.glsl`
+pub(super) const NODE_CLEAN: u32 = 0;
+pub(super) const NODE_CHANGED: u32 = 1;
 
 impl Octree {
     pub unsafe fn clear_light(&self) {
         let input = ClearLightInput {
             brick_pool_photons: self.textures.brick_pool_photons,
-            brick_pool_irradiance: self.textures.brick_pool_irradiance,
+            brick_pool_irradiance: Some(self.textures.brick_pool_irradiance),
             number_of_nodes: self.number_of_nodes(),
         };
         self.builder.clear_light.run(input);
     }
 
-    pub unsafe fn inject_light(
-        &self,
+    unsafe fn clear_photons(&self) {
+        let input = ClearLightInput {
+            brick_pool_photons: self.textures.brick_pool_photons,
+            brick_pool_irradiance: None,
+            number_of_nodes: self.number_of_nodes(),
+        };
+        self.builder.clear_light.run(input);
+    }
+
+    /// Injects the photons of every light and accumulates their irradiance.
+    /// Returns the light view map, its view and the shadow map of each light, in order
+    pub unsafe fn inject_lights(
+        &mut self,
         objects: &mut [Object],
-        light: &Light,
+        lights: &[Light],
         scene_aabb: &Aabb,
-    ) -> (GLuint, GLuint, GLuint) {
+    ) -> Vec<(GLuint, GLuint, GLuint)> {
+        profiler::begin_section("Light injection");
+        self.light_timings.start();
+
+        // Every node gets its irradiance again, lights mark the nodes they reach
+        self.mark_all_nodes_dirty();
+        helpers::fill_texture_buffer(self.textures.lit_nodes.1, 0);
         self.clear_light();
+        self.copy_alpha_to_irradiance();
+        self.light_timings.mark("Clear light");
 
-        let (light_view_map, light_view_map_view, shadow_map) =
-            self.create_light_view_map(objects, light, scene_aabb);
+        let mut light_maps = Vec::with_capacity(lights.len());
+        for (index, light) in lights.iter().enumerate() {
+            let light_map = self.create_light_view_map(objects, light, scene_aabb);
+            self.light_timings.mark("Light view maps");
+            self.inject_light(light, light_mask(index), light_map.0);
+            light_maps.push(light_map);
+        }
 
-        let store_photons_input = StorePhotonsInput {
-            light_view_map,
-            node_pool: self.textures.node_pool,
-            brick_pool_photons: self.textures.brick_pool_photons,
-            is_directional: light.is_directional(),
-        };
-        self.builder.store_photons.run(store_photons_input);
+        self.mipmap_irradiance();
+        profiler::end_section();
 
-        let config = Config::instance();
+        light_maps
+    }
 
-        for axis in Axis::all_axis().iter() {
-            self.builder.light_transfer.run(
+    /// Faster `inject_lights` for when only the light at `moved_light` changed since the last
+    /// injection. Only the leaves its old and new light view maps reach, their neighbors and
+    /// their parents are cleared, get the irradiance of every light again and are mipmapped.
+    /// The other lights reuse their light view maps of the last injection.
+    /// Returns the same as `inject_lights`
+    pub unsafe fn update_light(
+        &mut self,
+        objects: &mut [Object],
+        lights: &[Light],
+        moved_light: usize,
+        scene_aabb: &Aabb,
+    ) -> Vec<(GLuint, GLuint, GLuint)> {
+        if moved_light >= MAX_TRACKED_LIGHTS {
+            return self.inject_lights(objects, lights, scene_aabb);
+        }
+
+        let moved_light_mask = light_mask(moved_light);
+        profiler::begin_section("Light injection");
+        self.light_timings.start();
+
+        // Where the light was
+        helpers::fill_texture_buffer(self.textures.dirty_nodes.1, NODE_CLEAN);
+        for node_data in self.geometry_node_data() {
+            self.builder
+                .flag_dirty_nodes
+                .leaves(&self.textures, node_data, moved_light_mask, true);
+        }
+        self.light_timings.mark("Flag dirty nodes");
+
+        let moved_light_map = self.create_light_view_map(objects, &lights[moved_light], scene_aabb);
+        self.light_timings.mark("Light view maps");
+        self.store_photons(&lights[moved_light], moved_light_mask, moved_light_map.0);
+
+        // Where the light is now, and everything that reads from or shares borders with it
+        for node_data in self.geometry_node_data() {
+            self.builder.flag_dirty_nodes.leaves(
                 &self.textures,
+                node_data,
+                moved_light_mask,
+                false,
+            );
+        }
+        self.flag_dirty_neighbors_and_parents();
+        self.light_timings.mark("Flag dirty nodes");
+
+        self.builder
+            .clear_light
+            .reset_dirty_irradiance(&self.textures, self.number_of_nodes());
+        self.light_timings.mark("Clear light");
+
+        // The photons of the moved light are still stored
+        self.photons_to_irradiance(&lights[moved_light]);
+
+        let mut light_maps = Vec::with_capacity(lights.len());
+        for (index, light) in lights.iter().enumerate() {
+            if index == moved_light {
+                light_maps.push(moved_light_map);
+                continue;
+            }
+            let [light_view_map, light_view_map_view, shadow_map] = light.light_map_textures();
+            self.inject_light(light, light_mask(index), light_view_map);
+            light_maps.push((light_view_map, light_view_map_view, shadow_map));
+        }
+
+        self.mipmap_irradiance();
+        profiler::end_section();
+
+        light_maps
+    }
+
+    /// Faster `inject_lights` for after `update_dynamic_voxels`. Every light takes its light view
+    /// map again, since the dynamic objects cast shadows, but only the nodes it left dirty and the
+    /// leaves whose lights changed, with their neighbors and parents, are cleared, get the
+    /// irradiance of every light again and are mipmapped.
+    /// Returns the same as `inject_lights`
+    pub unsafe fn inject_lights_into_dirty_nodes(
+        &mut self,
+        objects: &mut [Object],
+        lights: &[Light],
+        scene_aabb: &Aabb,
+    ) -> Vec<(GLuint, GLuint, GLuint)> {
+        if lights.len() > MAX_TRACKED_LIGHTS {
+            return self.inject_lights(objects, lights, scene_aabb);
+        }
+
+        profiler::begin_section("Light injection");
+        self.light_timings.start();
+
+        // Which lights reach each leaf is only known after storing their photons
+        helpers::copy_buffer(
+            self.textures.lit_nodes.1,
+            self.textures.previous_lit_nodes.1,
+            helpers::buffer_size(self.textures.lit_nodes.1),
+        );
+        helpers::fill_texture_buffer(self.textures.lit_nodes.1, 0);
+        let mut light_maps = Vec::with_capacity(lights.len());
+        for (index, light) in lights.iter().enumerate() {
+            let light_map = self.create_light_view_map(objects, light, scene_aabb);
+            self.light_timings.mark("Light view maps");
+            self.store_photons(light, light_mask(index), light_map.0);
+            light_maps.push(light_map);
+        }
+
+        for node_data in self.geometry_node_data() {
+            self.builder
+                .flag_dirty_nodes
+                .lit_changes(&self.textures, node_data);
+        }
+        self.flag_dirty_neighbors_and_parents();
+        self.light_timings.mark("Flag dirty nodes");
+
+        self.builder
+            .clear_light
+            .reset_dirty_irradiance(&self.textures, self.number_of_nodes());
+        self.light_timings.mark("Clear light");
+
+        for (index, light) in lights.iter().enumerate() {
+            if index == lights.len() - 1 {
+                // Its photons are still stored
+                self.photons_to_irradiance(light);
+            } else {
+                self.inject_light(light, light_mask(index), light_maps[index].0);
+            }
+        }
+
+        self.mipmap_irradiance();
+        profiler::end_section();
+
+        light_maps
+    }
+
+    /// Flags the nodes next to a changed leaf, diagonals included, and the parents of dirty
+    /// nodes with their own neighbors, level by level
+    pub(super) unsafe fn flag_dirty_neighbors_and_parents(&self) {
+        let config = self.config;
+        for node_data in self.geometry_node_data() {
+            self.builder.flag_dirty_nodes.dilate(
+                &self.textures,
+                node_data,
                 config.last_octree_level(),
-                &self.geometry_data.node_data,
-                *axis,
-                light_view_map,
             );
         }
+        for level in (0..config.last_octree_level()).rev() {
+            for node_data in self.geometry_node_data() {
+                self.builder
+                    .flag_dirty_nodes
+                    .parents(&self.textures, node_data, level);
+            }
+            for node_data in self.geometry_node_data() {
+                self.builder
+                    .flag_dirty_nodes
+                    .dilate(&self.textures, node_data, level);
+            }
+        }
+    }
 
-        self.copy_alpha_to_irradiance();
+    /// Makes every pass that only writes the bricks of dirty nodes write all of them
+    pub(super) unsafe fn mark_all_nodes_dirty(&self) {
+        helpers::fill_texture_buffer(self.textures.dirty_nodes.1, NODE_CHANGED);
+    }
+
+    /// How long each stage of the last `inject_lights` or `update_light` took
+    pub fn light_timings(&self) -> &LightTimings {
+        &self.light_timings
+    }
 
-        let photons_to_irradiance_input = PhotonsToIrradianceInput {
+    /// Adds the irradiance of a single light to the dirty leaves
+    unsafe fn inject_light(&mut self, light: &Light, light_mask: u32, light_view_map: GLuint) {
+        self.store_photons(light, light_mask, light_view_map);
+        self.photons_to_irradiance(light);
+    }
+
+    /// Replaces the photons with the ones of `light` and shares them across brick borders
+    unsafe fn store_photons(&mut self, light: &Light, light_mask: u32, light_view_map: GLuint) {
+        self.clear_photons();
+
+        let store_photons_input = StorePhotonsInput {
+            light_view_map,
             node_pool: self.textures.node_pool,
-            brick_pool_colors_last_level: self.textures.brick_pool_colors[0],
             brick_pool_photons: self.textures.brick_pool_photons,
-            brick_pool_irradiance_last_level: self.textures.brick_pool_irradiance[0],
-            light_view_map,
+            lit_nodes: self.textures.lit_nodes,
+            light_mask,
             light,
         };
-        self.builder
-            .photons_to_irradiance_pass
-            .run(photons_to_irradiance_input);
+        self.builder.store_photons.run(store_photons_input);
+        self.light_timings.mark("Store photons");
 
-        self.builder.spread_leaf_bricks_pass.run(
-            &self.textures,
-            &self.geometry_data.node_data,
-            BrickPoolValues::Irradiance,
-        );
+        let config = self.config;
+        for axis in Axis::all_axis().iter() {
+            for node_data in self.geometry_node_data() {
+                self.builder.light_transfer.run(
+                    &self.textures,
+                    config.last_octree_level(),
+                    node_data,
+                    *axis,
+                    light_view_map,
+                );
+            }
+        }
+        self.light_timings.mark("Light transfer");
+    }
 
-        self.builder.leaf_border_transfer_pass.run(
-            &self.textures,
-            &self.geometry_data.node_data,
-            BrickPoolValues::Irradiance,
-        );
+    /// Adds the irradiance of the stored photons of `light` to the dirty leaves
+    unsafe fn photons_to_irradiance(&mut self, light: &Light) {
+        let config = self.config;
 
-        self.run_mipmap(BrickPoolValues::Irradiance);
+        for node_data in self.geometry_node_data() {
+            let photons_to_irradiance_input = PhotonsToIrradianceInput {
+                node_positions: self.textures.node_positions,
+                level_start_indices: node_data.level_start_indices,
+                nodes_in_last_level: node_data.nodes_per_level[config.last_octree_level() as usize],
+                brick_pool_colors_last_level: self.textures.brick_pool_colors[0],
+                brick_pool_photons: self.textures.brick_pool_photons,
+                brick_pool_irradiance_last_level: self.textures.brick_pool_irradiance[0],
+                dirty_nodes: self.textures.dirty_nodes,
+                light,
+            };
+            self.builder
+                .photons_to_irradiance_pass
+                .run(photons_to_irradiance_input);
+        }
+        self.light_timings.mark("Photons to irradiance");
+    }
 
-        (light_view_map, light_view_map_view, shadow_map)
+    /// Spreads the irradiance of the dirty leaves to the rest of the octree
+    unsafe fn mipmap_irradiance(&mut self) {
+        for node_data in self.geometry_node_data() {
+            self.builder.spread_leaf_bricks_pass.run(
+                &self.textures,
+                node_data,
+                BrickPoolValues::Irradiance,
+            );
+        }
+
+        for node_data in self.geometry_node_data() {
+            self.builder.leaf_border_transfer_pass.run(
+                &self.textures,
+                node_data,
+                BrickPoolValues::Irradiance,
+            );
+        }
+        self.light_timings.mark("Spread leaf bricks");
+
+        self.run_mipmap(BrickPoolValues::Irradiance);
+        self.light_timings.mark("Mipmap");
     }
 
     #[inline]
     unsafe fn copy_alpha_to_irradiance(&self) {
-        let config = Config::instance();
+        let config = self.config;
 
+        // The passes before only wait for image accesses and texture fetches
+        gl::MemoryBarrier(gl::TEXTURE_UPDATE_BARRIER_BIT);
         gl::CopyImageSubData(
             self.textures.brick_pool_alpha,
             gl::TEXTURE_3D,
@@ -99,7 +337,8 @@ impl Octree {
             0,
             config.brick_pool_resolution as i32,
             config.brick_pool_resolution as i32,
-            config.brick_pool_resolution as i32,
+            // The rest of the brick pool isn't used by any node
+            used_brick_pool_depth(&config, self.number_of_nodes()) as i32,
         );
     }
 
@@ -109,7 +348,8 @@ impl Octree {
         light: &Light,
         scene_aabb: &Aabb,
     ) -> (GLuint, GLuint, GLuint) {
-        let config = Config::instance();
+        let config = self.config;
+        let _timer = profiler::scope("Light view map");
 
         gl::CullFace(gl::FRONT);
         let light_map_buffers = light.take_photo(objects, scene_aabb, config.voxel_dimension());
@@ -122,3 +362,12 @@ impl Octree {
         )
     }
 }
+
+/// Bit of the light at `index` in `OctreeTextures::lit_nodes`
+fn light_mask(index: usize) -> u32 {
+    if index < MAX_TRACKED_LIGHTS {
+        1 << index
+    } else {
+        0
+    }
+}
@@ -14,22 +14,64 @@ use crate::{
 pub struct ClearLight {
     uint_shader: Shader,
     float_shader: Shader,
+    reset_dirty_irradiance_shader: Shader,
+    config: Config,
 }
 
 impl ClearLight {
-    pub fn init() -> Self {
-        Self {
-            uint_shader: compile_compute!("assets/shaders/octree/clearBricks.comp.glsl"),
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            uint_shader: compile_compute!(
+                "assets/shaders/octree/clearBricks.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
             float_shader: compile_compute!(
                 "assets/shaders/octree/clearBricksFloat.comp.glsl",
-            ),
+                defines = config.shader_defines(),
+            )?,
+            reset_dirty_irradiance_shader: compile_compute!(
+                "assets/shaders/octree/resetDirtyIrradiance.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.uint_shader.delete();
+        self.float_shader.delete();
+        self.reset_dirty_irradiance_shader.delete();
+    }
+
+    /// Leaves the irradiance of dirty nodes as if the light had just been cleared,
+    /// that is, with the alpha map copied to it, without touching the rest
+    pub unsafe fn reset_dirty_irradiance(&self, textures: &OctreeTextures, number_of_nodes: usize) {
+        let _timer = profiler::scope("Reset dirty irradiance");
+
+        self.reset_dirty_irradiance_shader.use_program();
+
+        helpers::bind_3d_image_texture(0, textures.brick_pool_alpha, gl::READ_ONLY, gl::RGBA8);
+        for (index, brick_pool_irradiance) in textures.brick_pool_irradiance.iter().enumerate() {
+            helpers::bind_3d_image_texture(
+                1 + index as u32,
+                *brick_pool_irradiance,
+                gl::WRITE_ONLY,
+                gl::RGBA8,
+            );
         }
+        helpers::bind_image_texture(7, textures.dirty_nodes.0, gl::READ_ONLY, gl::R32UI);
+
+        self.reset_dirty_irradiance_shader.dispatch_invocations(number_of_nodes as u32);
+        self.reset_dirty_irradiance_shader.wait_for(
+            gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT,
+        );
     }
 }
 
 pub struct ClearLightInput {
     pub brick_pool_photons: Texture3D,
-    pub brick_pool_irradiance: [Texture3D; 6],
+    /// Irradiance is left untouched if `None`, to accumulate several lights
+    pub brick_pool_irradiance: Option<[Texture3D; 6]>,
     pub number_of_nodes: usize,
 }
 
@@ -37,7 +79,9 @@ impl ShaderPass for ClearLight {
     type Input<'a> = ClearLightInput;
 
     unsafe fn run(&self, input: Self::Input<'_>) {
-        let config = Config::instance();
+        let _timer = profiler::scope("Clear light");
+
+        let config = &self.config;
 
         self.uint_shader.use_program();
         self.uint_shader
@@ -50,11 +94,14 @@ impl ShaderPass for ClearLight {
             gl::R32UI,
         );
 
-        let number_of_groups =
-            (input.number_of_nodes as f64 / config.working_group_size as f64).ceil() as u32;
+        self.uint_shader.dispatch_invocations(input.number_of_nodes as u32);
+        self.uint_shader.wait_for(
+            gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT,
+        );
 
-        self.uint_shader.dispatch(number_of_groups);
-        self.uint_shader.wait();
+        let Some(brick_pool_irradiance) = input.brick_pool_irradiance else {
+            return;
+        };
 
         self.float_shader.use_program();
         self.float_shader
@@ -63,14 +110,16 @@ impl ShaderPass for ClearLight {
         for texture_number in 0..6 {
             helpers::bind_3d_image_texture(
                 0,
-                input.brick_pool_irradiance[texture_number as usize],
+                brick_pool_irradiance[texture_number as usize],
                 gl::WRITE_ONLY,
                 gl::RGBA8,
             );
 
             self.float_shader
-                .dispatch(number_of_groups);
-            self.float_shader.wait();
+                .dispatch_invocations(input.number_of_nodes as u32);
+            self.float_shader.wait_for(
+                gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT,
+            );
         }
     }
 }
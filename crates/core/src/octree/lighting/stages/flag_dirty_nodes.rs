@@ -0,0 +1,208 @@
+use c_str_macro::c_str;
+use engine::prelude::*;
+
+use crate::{
+    config::Config,
+    constants::Axis,
+    octree::{NodeData, OctreeTextures, VoxelData},
+};
+
+/// Shader passes for finding the nodes whose irradiance changes when a single light or
+/// the dynamic objects move, so the rest of the octree can keep the bricks it already has
+pub struct FlagDirtyNodes {
+    leaves_shader: Shader,
+    voxel_fragments_shader: Shader,
+    lit_changes_shader: Shader,
+    parents_shader: Shader,
+    dilate_shader: Shader,
+    config: Config,
+}
+
+impl FlagDirtyNodes {
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            leaves_shader: compile_compute!(
+                "assets/shaders/octree/flagDirtyLeaves.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            voxel_fragments_shader: compile_compute!(
+                "assets/shaders/octree/flagDirtyVoxelFragments.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            lit_changes_shader: compile_compute!(
+                "assets/shaders/octree/flagDirtyLitChanges.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            parents_shader: compile_compute!(
+                "assets/shaders/octree/flagDirtyParents.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            dilate_shader: compile_compute!(
+                "assets/shaders/octree/dilateDirtyNodes.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.leaves_shader.delete();
+        self.voxel_fragments_shader.delete();
+        self.lit_changes_shader.delete();
+        self.parents_shader.delete();
+        self.dilate_shader.delete();
+    }
+
+    /// Flags the leaves the light with `light_mask` reaches.
+    /// With `should_forget_light` they are no longer marked as reached by it,
+    /// to flag where the light was before storing its photons again
+    pub unsafe fn leaves(
+        &self,
+        textures: &OctreeTextures,
+        node_data: &NodeData,
+        light_mask: u32,
+        should_forget_light: bool,
+    ) {
+        let _timer = profiler::scope("Flag dirty leaves");
+
+        let config = &self.config;
+        let octree_level = config.last_octree_level();
+
+        self.leaves_shader.use_program();
+        self.leaves_shader
+            .set_uint(c_str!("octreeLevel"), octree_level);
+        self.leaves_shader.set_uint(c_str!("lightMask"), light_mask);
+        self.leaves_shader
+            .set_bool(c_str!("shouldForgetLight"), should_forget_light);
+
+        helpers::bind_image_texture(0, textures.lit_nodes.0, gl::READ_WRITE, gl::R32UI);
+        helpers::bind_image_texture(1, textures.dirty_nodes.0, gl::READ_WRITE, gl::R32UI);
+        helpers::bind_image_texture(2, node_data.level_start_indices.0, gl::READ_ONLY, gl::R32UI);
+
+        self.leaves_shader
+            .dispatch_invocations(nodes_in_level(node_data, octree_level));
+        self.leaves_shader
+            .wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    }
+
+    /// Flags the leaves with voxel fragments of `voxel_data`,
+    /// to flag where the dynamic objects were before they moved
+    pub unsafe fn voxel_fragments(&self, textures: &OctreeTextures, voxel_data: &VoxelData) {
+        let _timer = profiler::scope("Flag dirty voxel fragments");
+
+        let config = &self.config;
+
+        self.voxel_fragments_shader.use_program();
+        self.voxel_fragments_shader
+            .set_uint(c_str!("voxelDimension"), config.voxel_dimension());
+        self.voxel_fragments_shader
+            .set_uint(c_str!("octreeLevel"), config.last_octree_level());
+        self.voxel_fragments_shader.set_uint(
+            c_str!("numberOfVoxelFragments"),
+            voxel_data.number_of_voxel_fragments,
+        );
+
+        helpers::bind_image_texture(
+            0,
+            voxel_data.voxel_positions.texture(),
+            gl::READ_ONLY,
+            gl::RGB10_A2UI,
+        );
+        helpers::bind_image_texture(1, textures.node_pool.0, gl::READ_ONLY, gl::R32UI);
+        helpers::bind_image_texture(2, textures.dirty_nodes.0, gl::READ_WRITE, gl::R32UI);
+
+        self.voxel_fragments_shader
+            .dispatch_invocations(voxel_data.number_of_voxel_fragments);
+        self.voxel_fragments_shader
+            .wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    }
+
+    /// Flags the leaves whose lights in `lit_nodes` aren't the ones in `previous_lit_nodes`
+    pub unsafe fn lit_changes(&self, textures: &OctreeTextures, node_data: &NodeData) {
+        let _timer = profiler::scope("Flag dirty lit changes");
+
+        let octree_level = self.config.last_octree_level();
+
+        self.lit_changes_shader.use_program();
+        self.lit_changes_shader
+            .set_uint(c_str!("octreeLevel"), octree_level);
+
+        helpers::bind_image_texture(0, textures.lit_nodes.0, gl::READ_ONLY, gl::R32UI);
+        helpers::bind_image_texture(1, textures.previous_lit_nodes.0, gl::READ_ONLY, gl::R32UI);
+        helpers::bind_image_texture(2, textures.dirty_nodes.0, gl::READ_WRITE, gl::R32UI);
+        helpers::bind_image_texture(3, node_data.level_start_indices.0, gl::READ_ONLY, gl::R32UI);
+
+        self.lit_changes_shader
+            .dispatch_invocations(nodes_in_level(node_data, octree_level));
+        self.lit_changes_shader
+            .wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    }
+
+    /// Flags the nodes in `octree_level` with a dirty child
+    pub unsafe fn parents(
+        &self,
+        textures: &OctreeTextures,
+        node_data: &NodeData,
+        octree_level: u32,
+    ) {
+        let _timer = profiler::scope("Flag dirty parents");
+
+        self.parents_shader.use_program();
+        self.parents_shader
+            .set_uint(c_str!("octreeLevel"), octree_level);
+
+        helpers::bind_image_texture(0, textures.node_pool.0, gl::READ_ONLY, gl::R32UI);
+        helpers::bind_image_texture(1, textures.dirty_nodes.0, gl::READ_WRITE, gl::R32UI);
+        helpers::bind_image_texture(2, node_data.level_start_indices.0, gl::READ_ONLY, gl::R32UI);
+
+        self.parents_shader
+            .dispatch_invocations(nodes_in_level(node_data, octree_level));
+        self.parents_shader
+            .wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    }
+
+    /// Flags the nodes in `octree_level` next to a changed one, diagonals included,
+    /// since they share border voxels
+    pub unsafe fn dilate(
+        &self,
+        textures: &OctreeTextures,
+        node_data: &NodeData,
+        octree_level: u32,
+    ) {
+        let _timer = profiler::scope("Dilate dirty nodes");
+
+        self.dilate_shader.use_program();
+        self.dilate_shader
+            .set_uint(c_str!("octreeLevel"), octree_level);
+
+        helpers::bind_image_texture(0, textures.dirty_nodes.0, gl::READ_WRITE, gl::R32UI);
+        helpers::bind_image_texture(1, node_data.level_start_indices.0, gl::READ_ONLY, gl::R32UI);
+
+        let nodes_in_level = nodes_in_level(node_data, octree_level);
+        for axis in Axis::all_axis().iter() {
+            self.dilate_shader.set_uint(c_str!("axis"), (*axis).into());
+            let neighbors_index: usize = (*axis).into();
+            let neighbors_index = neighbors_index * 2;
+            helpers::bind_image_texture(
+                2,
+                textures.neighbors[neighbors_index].0,
+                gl::READ_ONLY,
+                gl::R32UI,
+            );
+            helpers::bind_image_texture(
+                3,
+                textures.neighbors[neighbors_index + 1].0,
+                gl::READ_ONLY,
+                gl::R32UI,
+            );
+
+            self.dilate_shader.dispatch_invocations(nodes_in_level);
+            self.dilate_shader
+                .wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+    }
+}
+
+fn nodes_in_level(node_data: &NodeData, octree_level: u32) -> u32 {
+    node_data.nodes_per_level[octree_level as usize]
+}
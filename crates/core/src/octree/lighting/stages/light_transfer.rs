@@ -10,13 +10,22 @@ use crate::{
 
 pub struct LightTransfer {
     shader: Shader,
+    config: Config,
 }
 
 impl LightTransfer {
-    pub fn init() -> Self {
-        Self {
-            shader: compile_compute!("assets/shaders/octree/lightTransfer.comp.glsl"),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(
+                "assets/shaders/octree/lightTransfer.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.shader.delete();
     }
 
     pub unsafe fn run(
@@ -27,9 +36,11 @@ impl LightTransfer {
         axis: Axis,
         _light_view_map: GLuint,
     ) {
+        let _timer = profiler::scope("Light transfer");
+
         self.shader.use_program();
 
-        let config = Config::instance();
+        let config = &self.config;
 
         self.shader.set_uint(c_str!("octreeLevel"), octree_level);
         self.shader
@@ -54,8 +65,8 @@ impl LightTransfer {
         self.shader.set_uint(c_str!("axis"), axis.into());
 
         let nodes_in_level = node_data.nodes_per_level[octree_level as usize];
-        let groups_count = (nodes_in_level as f32 / config.working_group_size as f32).ceil() as u32;
-        self.shader.dispatch(groups_count);
-        self.shader.wait();
+        self.shader.dispatch_invocations(nodes_in_level);
+        self.shader
+            .wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
     }
 }
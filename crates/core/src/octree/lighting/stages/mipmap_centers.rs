@@ -11,20 +11,25 @@ use crate::{
 pub struct MipmapCentersPass {
     shader: Shader,
     light_view_map: GLuint,
+    config: Config,
 }
 
 impl MipmapCentersPass {
-    pub fn init(light_view_map: GLuint) -> Self {
-        Self {
-            shader: compile_compute!("assets/shaders/octree/mipmapCenterPhotons.comp.glsl"),
+    pub fn init(config: &Config, light_view_map: GLuint) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader: compile_compute!(
+                "assets/shaders/octree/mipmapCenterPhotons.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
             light_view_map,
-        }
+            config: *config,
+        })
     }
 
     pub unsafe fn run(&self, textures: &OctreeTextures, level: u32) {
         self.shader.use_program();
 
-        let config = Config::instance();
+        let config = &self.config;
 
         self.shader.set_uint(c_str!("octreeLevel"), level);
         self.shader
@@ -39,11 +44,8 @@ impl MipmapCentersPass {
 
         let (viewport_width, viewport_height) = config.viewport_dimensions();
 
-        self.shader.dispatch_xyz(vec3(
-            (viewport_width as f32 / 32 as f32).ceil() as u32,
-            (viewport_height as f32 / 32 as f32).ceil() as u32,
-            1,
-        ));
-        self.shader.wait();
+        // One invocation per pixel of the light view map
+        self.shader.dispatch_invocations_xyz(vec3(viewport_width as u32, viewport_height as u32, 1));
+        self.shader.wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
     }
 }
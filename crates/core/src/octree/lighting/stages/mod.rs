@@ -21,3 +21,6 @@ pub use store_photons::{StorePhotons, StorePhotonsInput};
 
 mod clear_light;
 pub use clear_light::{ClearLight, ClearLightInput};
+
+mod flag_dirty_nodes;
+pub use flag_dirty_nodes::FlagDirtyNodes;
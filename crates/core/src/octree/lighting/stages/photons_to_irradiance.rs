@@ -1,5 +1,4 @@
 use c_str_macro::c_str;
-use cgmath::vec3;
 use engine::prelude::*;
 
 use crate::config::Config;
@@ -7,27 +6,41 @@ use crate::config::Config;
 pub struct PhotonsToIrradiance {
     directional_shader: Shader,
     point_shader: Shader,
+    config: Config,
 }
 
 impl PhotonsToIrradiance {
-    pub fn init() -> Self {
-        Self {
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
             directional_shader: compile_compute!(
-                "assets/shaders/octree/photonsToIrradianceDirectional.comp.glsl"
-            ),
+                "assets/shaders/octree/photonsToIrradianceDirectional.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
             point_shader: compile_compute!(
-                "assets/shaders/octree/photonsToIrradiancePoint.comp.glsl"
-            ),
-        }
+                "assets/shaders/octree/photonsToIrradiancePoint.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.directional_shader.delete();
+        self.point_shader.delete();
     }
 }
 
+/// Adds the irradiance of one light to the last level of the irradiance brick pool,
+/// so it can be run once per light after clearing the photons
 pub struct PhotonsToIrradianceInput<'a> {
-    pub node_pool: BufferTexture,
+    pub node_positions: BufferTexture,
+    pub level_start_indices: BufferTexture,
+    pub nodes_in_last_level: u32,
     pub brick_pool_colors_last_level: Texture3D,
     pub brick_pool_photons: Texture3D,
     pub brick_pool_irradiance_last_level: Texture3D,
-    pub light_view_map: Texture2D,
+    /// Only dirty nodes get the irradiance
+    pub dirty_nodes: BufferTexture,
     pub light: &'a Light,
 }
 
@@ -35,17 +48,27 @@ impl ShaderPass for PhotonsToIrradiance {
     type Input<'a> = PhotonsToIrradianceInput<'a>;
 
     unsafe fn run(&self, input: Self::Input<'_>) {
-        let shader = if input.light.is_directional() {
-            self.directional_shader
-        } else {
-            self.point_shader
+        let _timer = profiler::scope("Photons to irradiance");
+
+        // Spot lights are attenuated with the distance to them like point lights,
+        // directional lights light the whole scene uniformly
+        let shader = match input.light {
+            Light::Point(_) | Light::Spot(_) => self.point_shader,
+            Light::Directional(_) => self.directional_shader,
         };
-        let config = Config::instance();
+        let config = &self.config;
 
         shader.use_program();
         shader.set_uint(c_str!("voxelDimension"), config.voxel_dimension());
         shader.set_uint(c_str!("octreeLevel"), config.last_octree_level());
         shader.set_float(c_str!("lightIntensity"), input.light.intensity());
+        let light_color = input.light.color();
+        shader.set_vec3(
+            c_str!("lightColor"),
+            light_color.x,
+            light_color.y,
+            light_color.z,
+        );
         shader.set_vec3(
             c_str!("lightPosition"),
             input.light.transform().position.x,
@@ -58,29 +81,18 @@ impl ShaderPass for PhotonsToIrradiance {
         gl::ActiveTexture(gl::TEXTURE1);
         gl::BindTexture(gl::TEXTURE_3D, input.brick_pool_photons);
         shader.set_int(c_str!("brickPoolPhotons"), 1);
-        gl::ActiveTexture(gl::TEXTURE2);
-        if input.light.is_directional() {
-            gl::BindTexture(gl::TEXTURE_2D, input.light_view_map);
-        } else {
-            gl::BindTexture(gl::TEXTURE_2D_ARRAY, input.light_view_map);
-        }
-        shader.set_int(c_str!("lightViewMap"), 2);
         helpers::bind_3d_image_texture(
             0,
             input.brick_pool_irradiance_last_level,
-            gl::WRITE_ONLY,
+            gl::READ_WRITE,
             gl::RGBA8,
         );
-        helpers::bind_image_texture(1, input.node_pool.0, gl::READ_ONLY, gl::R32UI);
+        helpers::bind_image_texture(1, input.node_positions.0, gl::READ_ONLY, gl::RGB10_A2UI);
+        helpers::bind_image_texture(2, input.level_start_indices.0, gl::READ_ONLY, gl::R32UI);
+        helpers::bind_image_texture(7, input.dirty_nodes.0, gl::READ_ONLY, gl::R32UI);
 
-        let (viewport_width, viewport_height) = config.viewport_dimensions();
-        let local_group_size = if input.light.is_directional() { 32 } else { 12 };
-        shader.dispatch_xyz(vec3(
-            (viewport_width as f32 / local_group_size as f32).ceil() as u32,
-            (viewport_height as f32 / local_group_size as f32).ceil() as u32,
-            1,
-        ));
-        shader.wait();
+        shader.dispatch_invocations(input.nodes_in_last_level);
+        shader.wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
     }
 }
 
@@ -92,7 +104,7 @@ mod tests {
 
     #[test]
     fn it_works() {
-        let (_glfw, _window) = test_utils::init_opengl_context();
+        let _context = test_utils::init_opengl_context();
 
         // To go from the crate root to the workspace root
         let mut path = PathBuf::from(env::current_dir().unwrap());
@@ -10,45 +10,72 @@ use crate::{
 
 pub struct StorePhotons {
     point_shader: Shader,
+    spot_shader: Shader,
     directional_shader: Shader,
+    config: Config,
 }
 
 impl StorePhotons {
-    pub fn init() -> Self {
-        Self {
-            point_shader: compile_compute!("assets/shaders/octree/storePhotonsPoint.comp.glsl"),
-            directional_shader: compile_compute!("assets/shaders/octree/storePhotonsDirectional.comp.glsl"),
-        }
+    pub fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            point_shader: compile_compute!(
+                "assets/shaders/octree/storePhotonsPoint.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            spot_shader: compile_compute!(
+                "assets/shaders/octree/storePhotonsSpot.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            directional_shader: compile_compute!(
+                "assets/shaders/octree/storePhotonsDirectional.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            config: *config,
+        })
+    }
+
+    pub unsafe fn delete(self) {
+        self.point_shader.delete();
+        self.spot_shader.delete();
+        self.directional_shader.delete();
     }
 }
 
-pub struct StorePhotonsInput {
+pub struct StorePhotonsInput<'a> {
     pub light_view_map: Texture2D,
     pub node_pool: BufferTexture,
     pub brick_pool_photons: Texture3D,
-    pub is_directional: bool,
+    /// The nodes the light reaches get `light_mask` set
+    pub lit_nodes: BufferTexture,
+    pub light_mask: u32,
+    pub light: &'a Light,
 }
 
 impl ShaderPass for StorePhotons {
-    type Input<'a> = StorePhotonsInput;
+    type Input<'a> = StorePhotonsInput<'a>;
 
     unsafe fn run(&self, input: Self::Input<'_>) {
-        let shader = if input.is_directional { self.directional_shader } else { self.point_shader };
+        let _timer = profiler::scope("Store photons");
+
+        // Point lights see the six faces of a cube, spot and directional lights
+        // have an orthographic projection to a single 2D light view map
+        let (shader, texture_target, faces) = match input.light {
+            Light::Point(_) => (self.point_shader, gl::TEXTURE_2D_ARRAY, 6),
+            Light::Spot(_) => (self.spot_shader, gl::TEXTURE_2D, 1),
+            Light::Directional(_) => (self.directional_shader, gl::TEXTURE_2D, 1),
+        };
 
         shader.use_program();
-        let config = Config::instance();
+        let config = &self.config;
         shader
             .set_uint(c_str!("octreeLevel"), config.last_octree_level());
         shader
             .set_uint(c_str!("voxelDimension"), config.voxel_dimension());
         gl::ActiveTexture(gl::TEXTURE0);
-        if input.is_directional {
-            gl::BindTexture(gl::TEXTURE_2D, input.light_view_map);
-        } else {
-            gl::BindTexture(gl::TEXTURE_2D_ARRAY, input.light_view_map);
-        }
+        gl::BindTexture(texture_target, input.light_view_map);
         shader
             .set_int(c_str!("lightViewMap"), 0);
+        shader.set_uint(c_str!("lightMask"), input.light_mask);
         helpers::bind_image_texture(0, input.node_pool.0, gl::READ_ONLY, gl::R32UI);
         helpers::bind_3d_image_texture(
             1,
@@ -56,20 +83,11 @@ impl ShaderPass for StorePhotons {
             gl::READ_WRITE,
             gl::R32UI,
         );
-        let total_photons = helpers::generate_texture_buffer(1, gl::R32UI, 0u32);
-        helpers::bind_image_texture(2, total_photons.0, gl::READ_WRITE, gl::R32UI);
+        helpers::bind_image_texture(3, input.lit_nodes.0, gl::READ_WRITE, gl::R32UI);
 
         let (viewport_width, viewport_height) = config.viewport_dimensions();
-        let local_group_size = if input.is_directional { 32 } else { 12 };
-        shader.dispatch_xyz(vec3(
-            (viewport_width as f32 / local_group_size as f32).ceil() as u32,
-            (viewport_height as f32 / local_group_size as f32).ceil() as u32,
-            1,
-        ));
-        shader.wait();
-
-        // let total_photons_values =
-        //     helpers::get_values_from_texture_buffer(total_photons.1, 1, 69u32);
-        // log::debug!("Total photons in scene: {}", total_photons_values[0]);
+        // One invocation per pixel of the light view map, and per face for cube maps
+        shader.dispatch_invocations_xyz(vec3(viewport_width as u32, viewport_height as u32, faces));
+        shader.wait_for(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
     }
 }
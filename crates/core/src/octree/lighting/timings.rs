@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+use engine::profiler;
+
+/// How long each stage of the last light injection took, in the order they first ran.
+/// Stages that run once per light are added up.
+/// Only timed while profiling is enabled, since it waits for the GPU after every stage
+#[derive(Debug, Clone, Default)]
+pub struct LightTimings {
+    stages: Vec<(&'static str, Duration)>,
+    last_mark: Option<Instant>,
+}
+
+impl LightTimings {
+    /// Forgets the previous injection and starts timing from now
+    pub unsafe fn start(&mut self) {
+        self.stages.clear();
+        self.last_mark = None;
+        if !profiler::is_enabled() {
+            return;
+        }
+        gl::Finish();
+        self.last_mark = Some(Instant::now());
+    }
+
+    /// Adds the time since the last mark to `stage`.
+    /// Waits for the GPU to finish, so the time is spent in the stage and not the next one
+    pub unsafe fn mark(&mut self, stage: &'static str) {
+        // Profiling was disabled when the injection started
+        let Some(last_mark) = self.last_mark else {
+            return;
+        };
+        gl::Finish();
+        let now = Instant::now();
+        let elapsed = now - last_mark;
+        self.last_mark = Some(now);
+
+        match self.stages.iter_mut().find(|(name, _)| *name == stage) {
+            Some((_, duration)) => *duration += elapsed,
+            None => self.stages.push((stage, elapsed)),
+        }
+    }
+
+    pub fn stages(&self) -> &[(&'static str, Duration)] {
+        &self.stages
+    }
+
+    pub fn total(&self) -> Duration {
+        self.stages.iter().map(|(_, duration)| *duration).sum()
+    }
+}
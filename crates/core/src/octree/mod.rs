@@ -8,20 +8,31 @@ use serde::{Deserialize, Serialize};
 use crate::{config::Config, constants};
 
 mod build;
+mod cache;
+mod dynamic;
 mod lighting;
+mod reference;
 mod visualize;
 mod voxel_data;
 
 use build::*;
+pub use cache::cache_key;
+use dynamic::DynamicData;
+pub use reference::OctreeStructure;
 pub use visualize::{BrickAttribute, BricksToShow};
 
-use lighting::{ClearLight, LightTransfer, PhotonsToIrradiance, StorePhotons};
+use lighting::{ClearLight, FlagDirtyNodes, LightTransfer, PhotonsToIrradiance, StorePhotons};
+pub use lighting::{LightTimings, MAX_TRACKED_LIGHTS};
 pub use voxel_data::VoxelData;
 
 pub struct Octree {
+    config: Config,
     pub geometry_data: OctreeData,
     pub border_data: OctreeData,
     pub textures: OctreeTextures,
+    /// Nodes and voxel fragments of the dynamic objects, `None` until they're added
+    dynamic_data: Option<DynamicData>,
+    light_timings: LightTimings,
     renderer: Renderer,
     builder: Builder,
 }
@@ -39,9 +50,49 @@ pub struct OctreeTextures {
     pub brick_pool_photons: Texture3D,
     pub photons_buffer: BufferTexture,
     pub children_buffer: BufferTexture,
+    /// Bitmask per node of the lights that reach it, see `lighting::light_mask`
+    lit_nodes: BufferTexture,
+    /// `lit_nodes` before the last injection into dirty nodes, to find the shadows that moved
+    previous_lit_nodes: BufferTexture,
+    /// Per node flag of the nodes whose bricks have to be written again
+    dirty_nodes: BufferTexture,
     pub color_quad_textures: [Texture2D; 2],
 }
 
+impl OctreeTextures {
+    unsafe fn delete(&self) {
+        let buffer_textures = [
+            self.node_pool,
+            self.brick_pointers,
+            self.node_positions,
+            self.photons_buffer,
+            self.children_buffer,
+            self.lit_nodes,
+            self.previous_lit_nodes,
+            self.dirty_nodes,
+        ]
+        .into_iter()
+        .chain(self.neighbors);
+        for (texture, buffer) in buffer_textures {
+            gl::DeleteTextures(1, &texture);
+            gl::DeleteBuffers(1, &buffer);
+        }
+
+        let textures: Vec<GLuint> = [
+            self.brick_pool_colors_raw,
+            self.brick_pool_alpha,
+            self.brick_pool_normals,
+            self.brick_pool_photons,
+        ]
+        .into_iter()
+        .chain(self.brick_pool_colors)
+        .chain(self.brick_pool_irradiance)
+        .chain(self.color_quad_textures)
+        .collect();
+        gl::DeleteTextures(textures.len() as i32, textures.as_ptr());
+    }
+}
+
 pub struct OctreeData {
     pub node_data: NodeData,
     pub voxel_data: VoxelData,
@@ -114,26 +165,181 @@ struct Builder {
     mipmap_anisotropic_pass: MipmapAnisotropicPass,
     mipmap_isotropic_pass: MipmapIsotropicPass,
     append_border_voxel_fragments_pass: AppendBorderVoxelFragmentsPass,
+    update_build_counts_pass: UpdateBuildCountsPass,
     photons_to_irradiance_pass: PhotonsToIrradiance,
     process_raw_brick_pool_colors: ProcessRawBrickPoolColors,
     create_alpha_map: CreateAlphaMap,
     store_photons: StorePhotons,
     clear_light: ClearLight,
     light_transfer: LightTransfer,
+    flag_dirty_nodes: FlagDirtyNodes,
+    clear_dirty_bricks_pass: ClearDirtyBricksPass,
+}
+
+impl Renderer {
+    fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            vao: 0,
+            node_count: 0,
+            shader: compile_shaders!(
+                "assets/shaders/octree/visualize.vert.glsl",
+                "assets/shaders/octree/visualize.frag.glsl",
+                "assets/shaders/octree/visualize.geom.glsl",
+                defines = config.shader_defines(),
+            )?,
+            normals_shader: compile_shaders!(
+                "assets/shaders/octree/visualizeBrickNormals.vert.glsl",
+                "assets/shaders/octree/visualizeBrickNormals.frag.glsl",
+                "assets/shaders/octree/visualizeBrickNormals.geom.glsl",
+                defines = config.shader_defines(),
+            )?,
+            bricks_shader: compile_shaders!(
+                "assets/shaders/octree/visualizeBricks.vert.glsl",
+                "assets/shaders/octree/visualizeBricks.frag.glsl",
+                "assets/shaders/octree/visualizeBricks.geom.glsl",
+                defines = config.shader_defines(),
+            )?,
+            bricks_to_show: BricksToShow::default(),
+            node_positions_shader: compile_shaders!(
+                "assets/shaders/debug/nodePositions.vert.glsl",
+                "assets/shaders/debug/nodePositions.frag.glsl",
+                "assets/shaders/debug/nodePositions.geom.glsl",
+                defines = config.shader_defines(),
+            )?,
+            node_neighbors_shader: compile_shaders!(
+                "assets/shaders/debug/nodeNeighbors.vert.glsl",
+                "assets/shaders/debug/nodeNeighbors.frag.glsl",
+                "assets/shaders/debug/nodeNeighbors.geom.glsl",
+                defines = config.shader_defines(),
+            )?,
+            node_bricks_shader: compile_shaders!(
+                "assets/shaders/debug/nodeBricks.vert.glsl",
+                "assets/shaders/debug/nodeBricks.frag.glsl",
+                "assets/shaders/debug/nodeBricks.geom.glsl",
+                defines = config.shader_defines(),
+            )?,
+            get_photons_shader: compile_compute!(
+                "assets/shaders/debug/getPhotons.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            get_children_shader: compile_compute!(
+                "assets/shaders/debug/getChildren.comp.glsl",
+                defines = config.shader_defines(),
+            )?,
+            eye_ray_shader: compile_shaders!(
+                "assets/shaders/debug/eyeRay.glsl",
+                defines = config.shader_defines(),
+            )?,
+            get_colors_quad_shader: compile_shaders!(
+                "assets/shaders/debug/debugInterpolation.glsl",
+                defines = config.shader_defines(),
+            )?,
+        })
+    }
+
+    unsafe fn delete(self) {
+        gl::DeleteVertexArrays(1, &self.vao);
+        for shader in [
+            self.shader,
+            self.normals_shader,
+            self.bricks_shader,
+            self.node_positions_shader,
+            self.node_neighbors_shader,
+            self.node_bricks_shader,
+            self.get_photons_shader,
+            self.get_children_shader,
+            self.eye_ray_shader,
+            self.get_colors_quad_shader,
+        ] {
+            shader.delete();
+        }
+    }
+}
+
+impl Builder {
+    fn init(config: &Config) -> Result<Self, ShaderError> {
+        Ok(Self {
+            neighbor_pointers_pass: NeighborPointersPass::init(config)?,
+            flag_nodes_pass: FlagNodesPass::init(config)?,
+            allocate_nodes_pass: AllocateNodesPass::init(config)?,
+            store_node_positions_pass: StoreNodePositions::init(config)?,
+            write_leaf_nodes_pass: WriteLeafNodesPass::init(config)?,
+            spread_leaf_bricks_pass: SpreadLeafBricksPass::init(config)?,
+            leaf_border_transfer_pass: LeafBorderTransferPass::init(config)?,
+            anisotropic_border_transfer_pass: AnisotropicBorderTransferPass::init(config)?,
+            mipmap_anisotropic_pass: MipmapAnisotropicPass::init(config)?,
+            mipmap_isotropic_pass: MipmapIsotropicPass::init(config)?,
+            append_border_voxel_fragments_pass: AppendBorderVoxelFragmentsPass::init(config)?,
+            update_build_counts_pass: UpdateBuildCountsPass::init(config)?,
+            photons_to_irradiance_pass: PhotonsToIrradiance::init(config)?,
+            process_raw_brick_pool_colors: ProcessRawBrickPoolColors::init(config)?,
+            create_alpha_map: CreateAlphaMap::init(config)?,
+            store_photons: StorePhotons::init(config)?,
+            clear_light: ClearLight::init(config)?,
+            light_transfer: LightTransfer::init(config)?,
+            flag_dirty_nodes: FlagDirtyNodes::init(config)?,
+            clear_dirty_bricks_pass: ClearDirtyBricksPass::init(config)?,
+        })
+    }
+
+    unsafe fn delete(self) {
+        self.neighbor_pointers_pass.delete();
+        self.flag_nodes_pass.delete();
+        self.allocate_nodes_pass.delete();
+        self.store_node_positions_pass.delete();
+        self.write_leaf_nodes_pass.delete();
+        self.spread_leaf_bricks_pass.delete();
+        self.leaf_border_transfer_pass.delete();
+        self.anisotropic_border_transfer_pass.delete();
+        self.mipmap_anisotropic_pass.delete();
+        self.mipmap_isotropic_pass.delete();
+        self.append_border_voxel_fragments_pass.delete();
+        self.update_build_counts_pass.delete();
+        self.photons_to_irradiance_pass.delete();
+        self.process_raw_brick_pool_colors.delete();
+        self.create_alpha_map.delete();
+        self.store_photons.delete();
+        self.clear_light.delete();
+        self.light_transfer.delete();
+        self.flag_dirty_nodes.delete();
+        self.clear_dirty_bricks_pass.delete();
+    }
 }
 
 impl Octree {
     /// Creates a Sparse Voxel Octree (SVO)
     pub unsafe fn new(
+        config: &Config,
         voxel_positions: BufferTextureV2<u32>,
         number_of_voxel_fragments: u32,
         voxel_colors: BufferTexture,
         voxel_normals: BufferTexture,
-    ) -> Self {
-        let config = Config::instance();
-        let max_node_pool_size = Self::get_max_node_pool_size();
+    ) -> Result<Self, ShaderError> {
+        // Shaders first, so nothing else is allocated if one of them doesn't compile
+        let voxel_data = VoxelData {
+            voxel_positions,
+            number_of_voxel_fragments,
+            voxel_colors,
+            voxel_normals,
+        };
+        let renderer = match Renderer::init(config) {
+            Ok(renderer) => renderer,
+            Err(error) => {
+                voxel_data.delete();
+                return Err(error);
+            }
+        };
+        let builder = match Builder::init(config) {
+            Ok(builder) => builder,
+            Err(error) => {
+                renderer.delete();
+                voxel_data.delete();
+                return Err(error);
+            }
+        };
+        let max_node_pool_size = Self::get_max_node_pool_size(config);
         let max_node_pool_size_in_bytes = size_of::<GLuint>() * max_node_pool_size as usize;
-        let textures = Self::initialize_textures(max_node_pool_size_in_bytes);
+        let textures = Self::initialize_textures(config, max_node_pool_size_in_bytes);
         let geometry_data = OctreeData {
             node_data: NodeData {
                 nodes_per_level: Vec::new(),
@@ -143,12 +349,7 @@ impl Octree {
                     0u32,
                 ),
             },
-            voxel_data: VoxelData {
-                voxel_positions,
-                number_of_voxel_fragments,
-                voxel_colors,
-                voxel_normals,
-            },
+            voxel_data,
         };
         let border_data = OctreeData {
             node_data: NodeData {
@@ -168,82 +369,23 @@ impl Octree {
                 voxel_normals: (0, 0),        // Will be initialized empty later
             },
         };
-        let octree_renderer = Renderer {
-            vao: 0,
-            node_count: 0,
-            shader: compile_shaders!(
-                "assets/shaders/octree/visualize.vert.glsl",
-                "assets/shaders/octree/visualize.frag.glsl",
-                "assets/shaders/octree/visualize.geom.glsl",
-            ),
-            normals_shader: compile_shaders!(
-                "assets/shaders/octree/visualizeBrickNormals.vert.glsl",
-                "assets/shaders/octree/visualizeBrickNormals.frag.glsl",
-                "assets/shaders/octree/visualizeBrickNormals.geom.glsl",
-            ),
-            bricks_shader: compile_shaders!(
-                "assets/shaders/octree/visualizeBricks.vert.glsl",
-                "assets/shaders/octree/visualizeBricks.frag.glsl",
-                "assets/shaders/octree/visualizeBricks.geom.glsl",
-            ),
-            bricks_to_show: BricksToShow::default(),
-            node_positions_shader: compile_shaders!(
-                "assets/shaders/debug/nodePositions.vert.glsl",
-                "assets/shaders/debug/nodePositions.frag.glsl",
-                "assets/shaders/debug/nodePositions.geom.glsl",
-            ),
-            node_neighbors_shader: compile_shaders!(
-                "assets/shaders/debug/nodeNeighbors.vert.glsl",
-                "assets/shaders/debug/nodeNeighbors.frag.glsl",
-                "assets/shaders/debug/nodeNeighbors.geom.glsl",
-            ),
-            node_bricks_shader: compile_shaders!(
-                "assets/shaders/debug/nodeBricks.vert.glsl",
-                "assets/shaders/debug/nodeBricks.frag.glsl",
-                "assets/shaders/debug/nodeBricks.geom.glsl",
-            ),
-            get_photons_shader: compile_compute!("assets/shaders/debug/getPhotons.comp.glsl"),
-            get_children_shader: compile_compute!("assets/shaders/debug/getChildren.comp.glsl"),
-            eye_ray_shader: compile_shaders!("assets/shaders/debug/eyeRay.glsl"),
-            get_colors_quad_shader: compile_shaders!(
-                "assets/shaders/debug/debugInterpolation.glsl",
-            ),
-        };
-        let builder = Builder {
-            neighbor_pointers_pass: NeighborPointersPass::init(),
-            flag_nodes_pass: FlagNodesPass::init(),
-            allocate_nodes_pass: AllocateNodesPass::init(),
-            store_node_positions_pass: StoreNodePositions::init(),
-            write_leaf_nodes_pass: WriteLeafNodesPass::init(),
-            spread_leaf_bricks_pass: SpreadLeafBricksPass::init(),
-            leaf_border_transfer_pass: LeafBorderTransferPass::init(),
-            anisotropic_border_transfer_pass: AnisotropicBorderTransferPass::init(),
-            mipmap_anisotropic_pass: MipmapAnisotropicPass::init(),
-            mipmap_isotropic_pass: MipmapIsotropicPass::init(),
-            append_border_voxel_fragments_pass: AppendBorderVoxelFragmentsPass::init(),
-            photons_to_irradiance_pass: PhotonsToIrradiance::init(),
-            process_raw_brick_pool_colors: ProcessRawBrickPoolColors::init(),
-            create_alpha_map: CreateAlphaMap::init(),
-            store_photons: StorePhotons::init(),
-            clear_light: ClearLight::init(),
-            light_transfer: LightTransfer::init(),
-        };
-
         let mut octree = Self {
+            config: *config,
             geometry_data,
             border_data,
             textures,
-            renderer: octree_renderer,
+            dynamic_data: None,
+            light_timings: LightTimings::default(),
+            renderer,
             builder,
         };
 
         octree.build();
 
-        octree
+        Ok(octree)
     }
 
-    unsafe fn get_max_node_pool_size() -> usize {
-        let config = Config::instance();
+    unsafe fn get_max_node_pool_size(config: &Config) -> usize {
         let number_of_nodes = (0..config.octree_levels())
             .map(|exponent| 8_usize.pow(exponent))
             .sum::<usize>();
@@ -271,8 +413,7 @@ impl Octree {
         max_node_pool_size
     }
 
-    unsafe fn initialize_textures(max_node_pool_size: usize) -> OctreeTextures {
-        let config = Config::instance();
+    unsafe fn initialize_textures(config: &Config, max_node_pool_size: usize) -> OctreeTextures {
         OctreeTextures {
             node_pool: helpers::generate_texture_buffer(max_node_pool_size, gl::R32UI, 0u32),
             brick_pointers: helpers::generate_texture_buffer(max_node_pool_size, gl::R32UI, 0u32),
@@ -307,6 +448,13 @@ impl Octree {
             brick_pool_photons: helpers::generate_3d_r32ui_texture(config.brick_pool_resolution),
             photons_buffer: helpers::generate_texture_buffer(27, gl::R32UI, 0u32), // 27 voxels in a brick
             children_buffer: helpers::generate_texture_buffer(8, gl::R32UI, 0_u32), // 8 children in a node
+            lit_nodes: helpers::generate_texture_buffer(max_node_pool_size, gl::R32UI, 0u32),
+            previous_lit_nodes: helpers::generate_texture_buffer(
+                max_node_pool_size,
+                gl::R32UI,
+                0u32,
+            ),
+            dirty_nodes: helpers::generate_texture_buffer(max_node_pool_size, gl::R32UI, 1u32), // Everything starts dirty
             color_quad_textures: {
                 let mut textures = [0; 2];
 
@@ -350,14 +498,51 @@ impl Octree {
         }
     }
 
+    /// Config the octree was built with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
     pub fn number_of_nodes(&self) -> usize {
-        self.geometry_data.number_of_nodes() + self.border_data.number_of_nodes()
+        let dynamic_nodes = self
+            .dynamic_data
+            .as_ref()
+            .map_or(0, |dynamic_data| dynamic_data.octree_data.number_of_nodes());
+        self.geometry_data.number_of_nodes() + self.border_data.number_of_nodes() + dynamic_nodes
+    }
+
+    /// Node data of the static objects and, if there are any, of the dynamic objects.
+    /// Passes that go level by level run once for each
+    fn geometry_node_data(&self) -> impl Iterator<Item = &NodeData> {
+        std::iter::once(&self.geometry_data.node_data).chain(
+            self.dynamic_data
+                .as_ref()
+                .map(|dynamic_data| &dynamic_data.octree_data.node_data),
+        )
+    }
+
+    /// Frees the textures, buffers and shader programs of the octree in the GPU,
+    /// its voxel fragment lists included
+    pub unsafe fn delete(self) {
+        self.textures.delete();
+        self.renderer.delete();
+        self.builder.delete();
+        for octree_data in [self.geometry_data, self.border_data] {
+            let level_start_indices = octree_data.node_data.level_start_indices;
+            gl::DeleteTextures(1, &level_start_indices.0);
+            gl::DeleteBuffers(1, &level_start_indices.1);
+            octree_data.voxel_data.delete();
+        }
+        if let Some(dynamic_data) = self.dynamic_data {
+            dynamic_data.into_voxel_data().delete();
+        }
     }
 
     pub unsafe fn show_nodes(&self, offset: usize, number_of_nodes: usize) {
-        let max_node_pool_size = Self::get_max_node_pool_size();
+        let max_node_pool_size = Self::get_max_node_pool_size(&self.config);
 
         let values = vec![1u32; max_node_pool_size];
+        gl::MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT);
         gl::BindBuffer(gl::TEXTURE_BUFFER, self.textures.node_pool.1);
         gl::GetBufferSubData(
             gl::TEXTURE_BUFFER,
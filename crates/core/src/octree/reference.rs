@@ -0,0 +1,571 @@
+//! CPU reference of the octree build.
+//! Mirrors what `voxels_to_nodes` does with the compute passes, level by level,
+//! including the nodes allocated for border voxel fragments, so the GPU build can be
+//! checked against it without an OpenGL context.
+//!
+//! Nodes are allocated in a deterministic order here, while the GPU allocates them with an
+//! atomic counter, so two structures are compared by node position instead of by node index.
+
+use std::collections::BTreeMap;
+
+use engine::prelude::*;
+
+use super::Octree;
+use crate::constants::CHILDREN_PER_NODE;
+
+/// Value a child pointer gets when it should be allocated, same as `NODE_FLAG_VALUE` in shaders
+const NODE_FLAG_VALUE: u32 = 0x8000_0000;
+
+/// The pairs of (neighbor texture, first of the two side neighbor textures) that
+/// `AppendBorderVoxelFragmentsPass` runs with
+const BORDER_RUNS: [(usize, usize); 6] = [(0, 2), (1, 2), (2, 4), (3, 4), (4, 0), (5, 0)];
+
+/// Everything that defines the structure of the octree, without the bricks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OctreeStructure {
+    /// Child pointers, `CHILDREN_PER_NODE` per node
+    pub node_pool: Vec<u32>,
+    /// Lower corner of each node in voxel coordinates, packed as `rgb10_a2ui`
+    pub node_positions: Vec<u32>,
+    /// Neighbor pointers per node, in order X+, X-, Y+, Y-, Z+, Z-
+    pub neighbors: [Vec<u32>; 6],
+    pub nodes_per_level: Vec<u32>,
+    /// First node of each level plus the end of the last level
+    pub level_start_indices: Vec<u32>,
+}
+
+/// What a node looks like regardless of the index it was allocated in
+#[derive(Debug, PartialEq, Eq)]
+struct CanonicalNode {
+    children: [Option<(u32, u32, u32)>; CHILDREN_PER_NODE as usize],
+    neighbors: [Option<(u32, u32, u32)>; 6],
+}
+
+impl OctreeStructure {
+    /// Builds the octree on the CPU from the voxel fragment positions, packed as `rgb10_a2ui`.
+    /// Colors and normals only end up in the bricks, so they don't affect the structure.
+    ///
+    /// The border voxel fragments buffer is as big as the geometry one, so with few voxels
+    /// some border nodes are never allocated. Which ones depends on the order the GPU saves
+    /// them in, so only inputs where they all fit are expected to match the GPU exactly.
+    pub fn build(voxel_positions: &[u32], voxel_dimension: u32) -> Self {
+        let mut builder = ReferenceBuilder::new(voxel_dimension);
+        builder.build(voxel_positions);
+        builder.structure
+    }
+
+    pub fn number_of_nodes(&self) -> usize {
+        self.nodes_per_level.iter().sum::<u32>() as usize
+    }
+
+    /// Compares both octrees ignoring the order in which nodes were allocated inside each level.
+    /// Returns a description of the first difference found
+    pub fn first_divergence(&self, other: &Self) -> Option<String> {
+        if self.nodes_per_level != other.nodes_per_level {
+            return Some(format!(
+                "nodes_per_level differ: {:?} != {:?}",
+                self.nodes_per_level, other.nodes_per_level
+            ));
+        }
+        if self.level_start_indices != other.level_start_indices {
+            return Some(format!(
+                "level_start_indices differ: {:?} != {:?}",
+                self.level_start_indices, other.level_start_indices
+            ));
+        }
+
+        let (ours, theirs) = match (self.canonical_nodes(), other.canonical_nodes()) {
+            (Ok(ours), Ok(theirs)) => (ours, theirs),
+            (Err(error), _) | (_, Err(error)) => return Some(error),
+        };
+        for (key, node) in ours.iter() {
+            match theirs.get(key) {
+                None => {
+                    return Some(format!(
+                        "node at level {} position {:?} is missing",
+                        key.0, key.1
+                    ))
+                }
+                Some(other_node) if other_node != node => {
+                    return Some(format!(
+                        "node at level {} position {:?} differs: {:?} != {:?}",
+                        key.0, key.1, node, other_node
+                    ))
+                }
+                _ => {}
+            }
+        }
+        theirs
+            .keys()
+            .find(|key| !ours.contains_key(key))
+            .map(|key| format!("unexpected node at level {} position {:?}", key.0, key.1))
+    }
+
+    /// Nodes keyed by (level, position), with pointers replaced by the position they point to.
+    /// A position identifies a node inside a level, so this doesn't depend on allocation order
+    fn canonical_nodes(&self) -> Result<BTreeMap<(u32, (u32, u32, u32)), CanonicalNode>, String> {
+        let number_of_nodes = self.number_of_nodes();
+        let position_of = |node: u32| -> Result<Option<(u32, u32, u32)>, String> {
+            match node {
+                0 => Ok(None),
+                node if node as usize >= number_of_nodes => Err(format!(
+                    "pointer to node {node} out of {number_of_nodes} nodes"
+                )),
+                node => Ok(Some(helpers::r32ui_to_rgb10_a2ui(
+                    self.node_positions[node as usize],
+                ))),
+            }
+        };
+
+        let mut nodes = BTreeMap::new();
+        for (level, window) in self.level_start_indices.windows(2).enumerate() {
+            for node in window[0]..window[1] {
+                let first_child = (node * CHILDREN_PER_NODE) as usize;
+                let mut children = [None; CHILDREN_PER_NODE as usize];
+                for (index, child) in children.iter_mut().enumerate() {
+                    *child = position_of(self.node_pool[first_child + index])?;
+                }
+                let mut neighbors = [None; 6];
+                for (index, neighbor) in neighbors.iter_mut().enumerate() {
+                    *neighbor = position_of(self.neighbors[index][node as usize])?;
+                }
+                let position = helpers::r32ui_to_rgb10_a2ui(self.node_positions[node as usize]);
+                nodes.insert(
+                    (level as u32, position),
+                    CanonicalNode {
+                        children,
+                        neighbors,
+                    },
+                );
+            }
+        }
+        Ok(nodes)
+    }
+}
+
+impl Octree {
+    /// Reads back the structure built on the GPU, to compare it with `OctreeStructure::build`
+    pub unsafe fn structure(&self) -> OctreeStructure {
+        let config = self.config;
+        let number_of_nodes = self.number_of_nodes();
+        let read = |buffer: TextureBuffer, size: usize| {
+            helpers::get_values_from_texture_buffer(buffer, size, 0_u32)
+        };
+        OctreeStructure {
+            node_pool: read(
+                self.textures.node_pool.1,
+                number_of_nodes * CHILDREN_PER_NODE as usize,
+            ),
+            node_positions: read(self.textures.node_positions.1, number_of_nodes),
+            neighbors: self
+                .textures
+                .neighbors
+                .map(|neighbors| read(neighbors.1, number_of_nodes)),
+            nodes_per_level: self.geometry_data.node_data.nodes_per_level.clone(),
+            level_start_indices: read(
+                self.geometry_data.node_data.level_start_indices.1,
+                (config.octree_levels() + 1) as usize,
+            ),
+        }
+    }
+}
+
+struct ReferenceBuilder {
+    voxel_dimension: u32,
+    octree_levels: u32,
+    structure: OctreeStructure,
+}
+
+impl ReferenceBuilder {
+    fn new(voxel_dimension: u32) -> Self {
+        Self {
+            voxel_dimension,
+            // Same as `Config`, one level per halving of the voxel dimension
+            octree_levels: voxel_dimension.ilog2(),
+            structure: OctreeStructure {
+                node_pool: vec![0; CHILDREN_PER_NODE as usize],
+                node_positions: vec![0],
+                neighbors: Default::default(),
+                nodes_per_level: vec![1],
+                level_start_indices: vec![0],
+            },
+        }
+        .with_neighbors_for_root()
+    }
+
+    fn with_neighbors_for_root(mut self) -> Self {
+        for neighbors in self.structure.neighbors.iter_mut() {
+            neighbors.push(0);
+        }
+        self
+    }
+
+    /// Same steps as `Octree::voxels_to_nodes`
+    fn build(&mut self, voxel_positions: &[u32]) {
+        let voxel_positions: Vec<(u32, u32, u32)> = voxel_positions
+            .iter()
+            .map(|&position| helpers::r32ui_to_rgb10_a2ui(position))
+            .collect();
+        // The border voxel fragments buffer is as big as the geometry one
+        let border_capacity = voxel_positions.len();
+
+        let mut first_node_in_level = 0;
+        let mut first_free_node = 1;
+
+        for octree_level in 1..self.octree_levels {
+            let previous_level_node_amount =
+                self.structure.nodes_per_level[octree_level as usize - 1];
+
+            self.flag_nodes(&voxel_positions, octree_level - 1);
+            let non_border_nodes_allocated = self.allocate_nodes(
+                first_node_in_level,
+                previous_level_node_amount,
+                first_free_node,
+                0,
+                octree_level,
+            );
+            self.neighbor_pointers(octree_level, first_free_node, non_border_nodes_allocated);
+
+            let border_voxel_positions = self.border_voxel_fragments(
+                octree_level,
+                first_free_node,
+                non_border_nodes_allocated,
+                border_capacity,
+            );
+            self.flag_nodes(&border_voxel_positions, octree_level - 1);
+            let nodes_allocated = self.allocate_nodes(
+                first_node_in_level,
+                previous_level_node_amount,
+                first_free_node,
+                non_border_nodes_allocated,
+                octree_level,
+            );
+            self.neighbor_pointers(octree_level, first_free_node, nodes_allocated);
+
+            self.structure.nodes_per_level.push(nodes_allocated);
+            first_node_in_level = first_free_node;
+            first_free_node += nodes_allocated;
+            self.structure.level_start_indices.push(first_node_in_level);
+        }
+
+        self.structure.level_start_indices.push(first_free_node);
+    }
+
+    /// Size of a node in voxels
+    fn node_size(&self, octree_level: u32) -> u32 {
+        self.voxel_dimension >> octree_level
+    }
+
+    /// Index (0 to 7) of the child of a node in `octree_level` that contains `position`
+    fn child_local_id(&self, position: (u32, u32, u32), octree_level: u32) -> u32 {
+        let shift = self.octree_levels - 1 - octree_level;
+        ((position.0 >> shift) & 1)
+            + ((position.1 >> shift) & 1) * 2
+            + ((position.2 >> shift) & 1) * 4
+    }
+
+    /// Node in `octree_level` that contains `position`, like `traverseOctree`
+    fn traverse(&self, position: (u32, u32, u32), octree_level: u32) -> Option<u32> {
+        let mut node = 0;
+        for level in 0..octree_level {
+            let child = node * CHILDREN_PER_NODE + self.child_local_id(position, level);
+            node = self.structure.node_pool[child as usize];
+            if node == 0 {
+                return None;
+            }
+        }
+        Some(node)
+    }
+
+    fn flag_nodes(&mut self, voxel_positions: &[(u32, u32, u32)], octree_level: u32) {
+        for &position in voxel_positions.iter() {
+            if let Some(node) = self.traverse(position, octree_level) {
+                let child = node * CHILDREN_PER_NODE + self.child_local_id(position, octree_level);
+                let child_pointer = &mut self.structure.node_pool[child as usize];
+                if *child_pointer == 0 {
+                    *child_pointer = NODE_FLAG_VALUE;
+                }
+            }
+        }
+    }
+
+    /// Allocates flagged children of the previous level in order, starting after the
+    /// `already_allocated` nodes of `octree_level`.
+    /// Returns the total number of nodes allocated in the level, like the atomic counter
+    fn allocate_nodes(
+        &mut self,
+        first_node_in_level: u32,
+        previous_level_node_amount: u32,
+        first_free_node: u32,
+        already_allocated: u32,
+        octree_level: u32,
+    ) -> u32 {
+        let mut allocated = already_allocated;
+        let node_size = self.node_size(octree_level);
+        for parent in first_node_in_level..first_node_in_level + previous_level_node_amount {
+            let parent_position =
+                helpers::r32ui_to_rgb10_a2ui(self.structure.node_positions[parent as usize]);
+            for child_local_id in 0..CHILDREN_PER_NODE {
+                let child = (parent * CHILDREN_PER_NODE + child_local_id) as usize;
+                if self.structure.node_pool[child] & NODE_FLAG_VALUE == 0 {
+                    continue;
+                }
+                let node = first_free_node + allocated;
+                allocated += 1;
+                self.structure.node_pool[child] = node;
+
+                // What `StoreNodePositions` would find traversing to this node
+                let position = (
+                    parent_position.0 + (child_local_id & 1) * node_size,
+                    parent_position.1 + ((child_local_id >> 1) & 1) * node_size,
+                    parent_position.2 + ((child_local_id >> 2) & 1) * node_size,
+                );
+                self.push_node(helpers::rgb10_a2ui_to_r32ui(
+                    position.0, position.1, position.2,
+                ));
+            }
+        }
+        allocated
+    }
+
+    fn push_node(&mut self, position: u32) {
+        let structure = &mut self.structure;
+        structure
+            .node_pool
+            .extend_from_slice(&[0; CHILDREN_PER_NODE as usize]);
+        structure.node_positions.push(position);
+        for neighbors in structure.neighbors.iter_mut() {
+            neighbors.push(0);
+        }
+    }
+
+    /// Like `neighborPointers.comp.glsl` for all three axis
+    fn neighbor_pointers(&mut self, octree_level: u32, level_start: u32, nodes_in_level: u32) {
+        let node_size = self.node_size(octree_level);
+        for node in level_start..level_start + nodes_in_level {
+            let position =
+                helpers::r32ui_to_rgb10_a2ui(self.structure.node_positions[node as usize]);
+            for axis in 0..3 {
+                let coordinate = get_axis(position, axis);
+
+                let positive = if coordinate + node_size < self.voxel_dimension {
+                    self.traverse(
+                        set_axis(position, axis, coordinate + node_size),
+                        octree_level,
+                    )
+                } else {
+                    None
+                };
+                let negative = if coordinate >= node_size {
+                    self.traverse(
+                        set_axis(position, axis, coordinate - node_size),
+                        octree_level,
+                    )
+                } else {
+                    None
+                };
+
+                self.structure.neighbors[axis * 2][node as usize] = positive.unwrap_or(0);
+                self.structure.neighbors[axis * 2 + 1][node as usize] = negative.unwrap_or(0);
+            }
+        }
+    }
+
+    /// Like `AppendBorderVoxelFragmentsPass`.
+    /// Fragments that don't fit in the buffer are still counted, and read back as the origin
+    fn border_voxel_fragments(
+        &self,
+        octree_level: u32,
+        level_start: u32,
+        nodes_in_level: u32,
+        capacity: usize,
+    ) -> Vec<(u32, u32, u32)> {
+        let node_size = self.node_size(octree_level) as i32;
+        let offset = |neighbor: usize| {
+            let mut offset = [0; 3];
+            offset[neighbor / 2] = if neighbor % 2 == 0 {
+                node_size
+            } else {
+                -node_size
+            };
+            offset
+        };
+        let add = |position: [i32; 3], offset: [i32; 3]| {
+            [
+                position[0] + offset[0],
+                position[1] + offset[1],
+                position[2] + offset[2],
+            ]
+        };
+
+        let mut fragments = Vec::new();
+        let mut save = |position: [i32; 3]| {
+            let inside = position
+                .iter()
+                .all(|&coordinate| coordinate >= 0 && coordinate < self.voxel_dimension as i32);
+            if inside {
+                fragments.push((position[0] as u32, position[1] as u32, position[2] as u32));
+            }
+        };
+
+        for (base_neighbor, side_neighbor) in BORDER_RUNS {
+            for node in level_start..level_start + nodes_in_level {
+                if self.structure.neighbors[base_neighbor][node as usize] != 0 {
+                    continue;
+                }
+                let position =
+                    helpers::r32ui_to_rgb10_a2ui(self.structure.node_positions[node as usize]);
+                let position = [position.0 as i32, position.1 as i32, position.2 as i32];
+                let base = add(position, offset(base_neighbor));
+                save(base);
+                for direction_sign in 0..2 {
+                    let side = side_neighbor + direction_sign;
+                    if self.structure.neighbors[side][node as usize] == 0 {
+                        save(add(base, offset(side)));
+                    }
+                }
+            }
+        }
+
+        for fragment in fragments.iter_mut().skip(capacity) {
+            *fragment = (0, 0, 0);
+        }
+        fragments
+    }
+}
+
+fn get_axis(position: (u32, u32, u32), axis: usize) -> u32 {
+    match axis {
+        0 => position.0,
+        1 => position.1,
+        _ => position.2,
+    }
+}
+
+fn set_axis(position: (u32, u32, u32), axis: usize, value: u32) -> (u32, u32, u32) {
+    match axis {
+        0 => (value, position.1, position.2),
+        1 => (position.0, value, position.2),
+        _ => (position.0, position.1, value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_at(structure: &OctreeStructure, position: (u32, u32, u32), level: usize) -> u32 {
+        let start = structure.level_start_indices[level];
+        let end = structure.level_start_indices[level + 1];
+        (start..end)
+            .find(|&node| {
+                helpers::r32ui_to_rgb10_a2ui(structure.node_positions[node as usize]) == position
+            })
+            .expect("Node should exist")
+    }
+
+    #[test]
+    fn border_nodes_are_allocated_around_geometry() {
+        // Fill the first node of level 1, so there is room for all of its border voxel fragments
+        let voxels: Vec<u32> = (0..8)
+            .map(|index| helpers::rgb10_a2ui_to_r32ui(index & 1, (index >> 1) & 1, index >> 2))
+            .collect();
+        let structure = OctreeStructure::build(&voxels, 4);
+
+        // Every neighbor of the geometry node, including diagonals, except (2, 2, 2)
+        assert_eq!(structure.nodes_per_level, vec![1, 7]);
+        assert_eq!(structure.level_start_indices, vec![0, 1, 8]);
+        assert_eq!(structure.node_pool[..8], [1, 2, 3, 4, 5, 6, 7, 0]);
+        assert!(structure.node_pool[8..].iter().all(|&child| child == 0));
+
+        let geometry_node = node_at(&structure, (0, 0, 0), 1) as usize;
+        assert_eq!(
+            structure.neighbors[0][geometry_node],
+            node_at(&structure, (2, 0, 0), 1)
+        );
+        assert_eq!(structure.neighbors[1][geometry_node], 0);
+        assert_eq!(
+            structure.neighbors[2][geometry_node],
+            node_at(&structure, (0, 2, 0), 1)
+        );
+        assert_eq!(
+            structure.neighbors[4][geometry_node],
+            node_at(&structure, (0, 0, 2), 1)
+        );
+    }
+
+    #[test]
+    fn border_voxel_fragments_over_capacity_are_lost() {
+        let structure = OctreeStructure::build(&[helpers::rgb10_a2ui_to_r32ui(0, 0, 0)], 4);
+
+        // Only X+ fits in the border buffer, the rest end up flagging the origin again
+        assert_eq!(structure.nodes_per_level, vec![1, 2]);
+        assert_eq!(structure.node_pool[..8], [1, 2, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(structure.neighbors[0][1], 2);
+        assert_eq!(structure.neighbors[1][2], 1);
+    }
+
+    #[test]
+    fn voxels_in_same_node_allocate_once() {
+        let structure = OctreeStructure::build(
+            &[
+                helpers::rgb10_a2ui_to_r32ui(4, 4, 4),
+                helpers::rgb10_a2ui_to_r32ui(5, 5, 5),
+            ],
+            8,
+        );
+
+        assert_eq!(structure.level_start_indices.len(), 4);
+        let leaf = node_at(&structure, (4, 4, 4), 2);
+        let parent = node_at(&structure, (4, 4, 4), 1);
+        assert_eq!(structure.node_pool[(parent * 8) as usize], leaf);
+    }
+
+    #[test]
+    fn allocation_order_doesnt_diverge() {
+        let voxels = [
+            helpers::rgb10_a2ui_to_r32ui(1, 2, 3),
+            helpers::rgb10_a2ui_to_r32ui(6, 6, 1),
+            helpers::rgb10_a2ui_to_r32ui(7, 0, 4),
+        ];
+        let structure = OctreeStructure::build(&voxels, 8);
+
+        // Swap the first two nodes of the last level, like a different atomic counter order would
+        let mut swapped = structure.clone();
+        let (first, second) = (
+            structure.level_start_indices[2],
+            structure.level_start_indices[2] + 1,
+        );
+        let swap = |node: &mut u32| {
+            if *node == first {
+                *node = second;
+            } else if *node == second {
+                *node = first;
+            }
+        };
+        swapped.node_pool.iter_mut().for_each(swap);
+        for neighbors in swapped.neighbors.iter_mut() {
+            neighbors.iter_mut().for_each(swap);
+            neighbors.swap(first as usize, second as usize);
+        }
+        swapped.node_positions.swap(first as usize, second as usize);
+
+        assert_eq!(structure.first_divergence(&swapped), None);
+    }
+
+    #[test]
+    fn different_structure_diverges() {
+        let voxels = [helpers::rgb10_a2ui_to_r32ui(1, 2, 3)];
+        let structure = OctreeStructure::build(&voxels, 8);
+
+        let mut missing_neighbor = structure.clone();
+        let last_node = missing_neighbor.number_of_nodes() - 1;
+        for neighbors in missing_neighbor.neighbors.iter_mut() {
+            neighbors[last_node] = 0;
+        }
+        assert!(structure.first_divergence(&missing_neighbor).is_some());
+
+        let other = OctreeStructure::build(&[helpers::rgb10_a2ui_to_r32ui(6, 6, 6)], 8);
+        assert!(structure.first_divergence(&other).is_some());
+    }
+}
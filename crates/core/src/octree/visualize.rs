@@ -6,15 +6,12 @@ use gl::types::GLuint;
 use serde::{Serialize, Deserialize};
 use engine::prelude::*;
 
-use crate::config::Config;
 use super::{NodeData, Octree};
 
 impl Octree {
     pub unsafe fn render(
         &self,
         model: &Matrix4<f32>,
-        view: &Matrix4<f32>,
-        projection: &Matrix4<f32>,
         octree_level: u32,
         color_direction: Vector3<f32>,
         should_show_normals: bool,
@@ -23,14 +20,12 @@ impl Octree {
         node_data: &NodeData,
     ) {
         if should_show_normals {
-            self.show_normals(octree_level, projection, view, model);
+            self.show_normals(octree_level, model);
         }
 
         if self.renderer.bricks_to_show.at_least_one() {
             self.show_bricks(
                 octree_level,
-                projection,
-                view,
                 model,
                 color_direction,
                 brick_attribute,
@@ -52,7 +47,7 @@ impl Octree {
                 gl::R32UI,
             );
 
-            let config = Config::instance();
+            let config = self.config;
 
             self.renderer
                 .shader
@@ -61,16 +56,13 @@ impl Octree {
                 .shader
                 .set_uint(c_str!("voxelDimension"), config.voxel_dimension());
 
-            self.renderer
-                .shader
-                .set_mat4(c_str!("projection"), projection);
-            self.renderer.shader.set_mat4(c_str!("view"), view);
             self.renderer.shader.set_mat4(c_str!("model"), model);
 
             let mut vao = 0;
             gl::GenVertexArrays(1, &mut vao);
             gl::BindVertexArray(vao);
 
+            self.renderer.shader.validate();
             gl::DrawArrays(
                 gl::POINTS,
                 0,
@@ -83,21 +75,15 @@ impl Octree {
     unsafe fn show_normals(
         &self,
         octree_level: u32,
-        projection: &Matrix4<f32>,
-        view: &Matrix4<f32>,
         model: &Matrix4<f32>,
     ) {
         self.renderer.normals_shader.use_program();
 
-        self.renderer
-            .normals_shader
-            .set_mat4(c_str!("projection"), projection);
-        self.renderer.normals_shader.set_mat4(c_str!("view"), view);
         self.renderer
             .normals_shader
             .set_mat4(c_str!("model"), model);
 
-        let config = Config::instance();
+        let config = self.config;
 
         self.renderer
             .normals_shader
@@ -136,6 +122,7 @@ impl Octree {
                 .normals_shader
                 .set_uint(c_str!("bricksToShow"), all_bricks_to_show & 1);
 
+            self.renderer.normals_shader.validate();
             gl::DrawArrays(
                 gl::POINTS,
                 0,
@@ -148,6 +135,7 @@ impl Octree {
                 .normals_shader
                 .set_uint(c_str!("bricksToShow"), all_bricks_to_show & 2);
 
+            self.renderer.normals_shader.validate();
             gl::DrawArrays(
                 gl::POINTS,
                 0,
@@ -160,6 +148,7 @@ impl Octree {
                 .normals_shader
                 .set_uint(c_str!("bricksToShow"), all_bricks_to_show & 4);
 
+            self.renderer.normals_shader.validate();
             gl::DrawArrays(
                 gl::POINTS,
                 0,
@@ -171,8 +160,6 @@ impl Octree {
     unsafe fn show_bricks(
         &self,
         octree_level: u32,
-        projection: &Matrix4<f32>,
-        view: &Matrix4<f32>,
         model: &Matrix4<f32>,
         color_direction: Vector3<f32>,
         brick_attribute: BrickAttribute,
@@ -180,13 +167,9 @@ impl Octree {
     ) {
         self.renderer.bricks_shader.use_program();
 
-        self.renderer
-            .bricks_shader
-            .set_mat4(c_str!("projection"), projection);
-        self.renderer.bricks_shader.set_mat4(c_str!("view"), view);
         self.renderer.bricks_shader.set_mat4(c_str!("model"), model);
 
-        let config = Config::instance();
+        let config = self.config;
 
         self.renderer
             .bricks_shader
@@ -312,6 +295,7 @@ impl Octree {
                         .bricks_shader
                         .set_uint(c_str!("bricksToShow"), z_layer * 3 + x_layer);
 
+                    self.renderer.bricks_shader.validate();
                     gl::DrawArrays(
                         gl::POINTS,
                         0,
@@ -364,8 +348,6 @@ impl Octree {
 
     pub unsafe fn run_node_positions_shader(
         &self,
-        projection: &Matrix4<f32>,
-        view: &Matrix4<f32>,
         model: &Matrix4<f32>,
     ) {
         if self.renderer.node_count == 0 {
@@ -374,7 +356,7 @@ impl Octree {
 
         self.renderer.node_positions_shader.use_program();
 
-        let config = Config::instance();
+        let config = self.config;
 
         self.renderer
             .node_positions_shader
@@ -383,12 +365,6 @@ impl Octree {
             .node_positions_shader
             .set_uint(c_str!("maxOctreeLevel"), config.octree_levels() + 1);
 
-        self.renderer
-            .node_positions_shader
-            .set_mat4(c_str!("projection"), &projection);
-        self.renderer
-            .node_positions_shader
-            .set_mat4(c_str!("view"), &view);
         self.renderer
             .node_positions_shader
             .set_mat4(c_str!("model"), &model);
@@ -412,21 +388,20 @@ impl Octree {
             gl::R32UI,
         );
 
+        self.renderer.node_positions_shader.validate();
         gl::BindVertexArray(self.renderer.vao);
         gl::DrawArrays(gl::POINTS, 0, self.renderer.node_count as i32);
     }
 
     pub unsafe fn run_node_neighbors_shader(
         &self,
-        projection: &Matrix4<f32>,
-        view: &Matrix4<f32>,
         model: &Matrix4<f32>,
     ) {
         if self.renderer.node_count == 0 {
             return;
         }
 
-        let config = Config::instance();
+        let config = self.config;
 
         self.renderer.node_neighbors_shader.use_program();
 
@@ -437,12 +412,6 @@ impl Octree {
             .node_neighbors_shader
             .set_uint(c_str!("maxOctreeLevel"), config.octree_levels() + 1);
 
-        self.renderer
-            .node_neighbors_shader
-            .set_mat4(c_str!("projection"), &projection);
-        self.renderer
-            .node_neighbors_shader
-            .set_mat4(c_str!("view"), &view);
         self.renderer
             .node_neighbors_shader
             .set_mat4(c_str!("model"), &model);
@@ -476,6 +445,7 @@ impl Octree {
             );
         }
 
+        self.renderer.node_neighbors_shader.validate();
         gl::BindVertexArray(self.renderer.vao);
         gl::DrawArrays(gl::POINTS, 0, self.renderer.node_count as i32);
 
@@ -488,6 +458,7 @@ impl Octree {
             );
         }
 
+        self.renderer.node_neighbors_shader.validate();
         gl::BindVertexArray(self.renderer.vao);
         gl::DrawArrays(gl::POINTS, 0, self.renderer.node_count as i32);
     }
@@ -495,7 +466,7 @@ impl Octree {
     pub unsafe fn run_get_photons_shader(&self, node_index: u32) {
         self.renderer.get_photons_shader.use_program();
 
-        let config = Config::instance();
+        let config = self.config;
 
         self.renderer
             .get_photons_shader
@@ -513,7 +484,7 @@ impl Octree {
         );
 
         self.renderer.get_photons_shader.dispatch(1);
-        self.renderer.get_photons_shader.wait();
+        self.renderer.get_photons_shader.wait_for(gl::BUFFER_UPDATE_BARRIER_BIT);
     }
 
     pub unsafe fn run_get_children_shader(&self, node_index: u32) {
@@ -532,13 +503,11 @@ impl Octree {
         helpers::bind_image_texture(1, self.textures.node_pool.0, gl::READ_ONLY, gl::R32UI);
 
         self.renderer.get_children_shader.dispatch(1);
-        self.renderer.get_children_shader.wait();
+        self.renderer.get_children_shader.wait_for(gl::BUFFER_UPDATE_BARRIER_BIT);
     }
 
     pub unsafe fn run_node_bricks_shader(
         &self,
-        projection: &Matrix4<f32>,
-        view: &Matrix4<f32>,
         model: &Matrix4<f32>,
         color_direction: Vector3<f32>,
         brick_attribute: BrickAttribute,
@@ -561,7 +530,7 @@ impl Octree {
             color_direction.z,
         );
 
-        let config = Config::instance();
+        let config = self.config;
 
         self.renderer
             .node_bricks_shader
@@ -570,12 +539,6 @@ impl Octree {
             .node_bricks_shader
             .set_uint(c_str!("maxOctreeLevel"), config.octree_levels() + 1);
 
-        self.renderer
-            .node_bricks_shader
-            .set_mat4(c_str!("projection"), &projection);
-        self.renderer
-            .node_bricks_shader
-            .set_mat4(c_str!("view"), &view);
         self.renderer
             .node_bricks_shader
             .set_mat4(c_str!("model"), &model);
@@ -700,6 +663,7 @@ impl Octree {
                         .node_bricks_shader
                         .set_uint(c_str!("bricksToShow"), z_layer * 3 + x_layer);
 
+                    self.renderer.node_bricks_shader.validate();
                     gl::BindVertexArray(self.renderer.vao);
                     gl::DrawArrays(gl::POINTS, 0, self.renderer.node_count as i32);
                 }
@@ -711,7 +675,7 @@ impl Octree {
     pub unsafe fn run_colors_quad_shader(&self, node_index: u32) {
         self.renderer.get_colors_quad_shader.use_program();
 
-        let config = Config::instance();
+        let config = self.config;
 
         gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
         self.renderer
@@ -767,7 +731,7 @@ impl Octree {
             println!("ERROR::FRAMEBUFFER: Framebuffer is not complete!");
         }
 
-        let quad = Quad::new();
+        let quad = Quad::new().unwrap();
 
         let (debug, buffer) = helpers::generate_texture_buffer(3, gl::R32F, 69f32);
         helpers::bind_image_texture(0, debug, gl::WRITE_ONLY, gl::R32F);
@@ -790,6 +754,7 @@ impl Octree {
         self.renderer
             .get_colors_quad_shader
             .set_bool(c_str!("isNeighbor"), false);
+        self.renderer.get_colors_quad_shader.validate();
         gl::DrawElements(
             gl::TRIANGLES,
             quad.get_num_indices() as i32,
@@ -800,6 +765,7 @@ impl Octree {
         self.renderer
             .get_colors_quad_shader
             .set_bool(c_str!("isNeighbor"), true);
+        self.renderer.get_colors_quad_shader.validate();
         gl::DrawElements(
             gl::TRIANGLES,
             quad.get_num_indices() as i32,
@@ -814,18 +780,12 @@ impl Octree {
 
     pub unsafe fn run_eye_ray_shader(
         &self,
-        projection: &Matrix4<f32>,
-        view: &Matrix4<f32>,
         eye: &Transform,
         eye_view_map: GLuint,
         eye_view_map_normals: GLuint,
     ) {
-        let config = Config::instance();
+        let config = self.config;
         self.renderer.eye_ray_shader.use_program();
-        self.renderer
-            .eye_ray_shader
-            .set_mat4(c_str!("projection"), &projection);
-        self.renderer.eye_ray_shader.set_mat4(c_str!("view"), &view);
         self.renderer
             .eye_ray_shader
             .set_mat4(c_str!("model"), &eye.get_model_matrix());
@@ -843,6 +803,7 @@ impl Octree {
             .eye_ray_shader
             .set_int(c_str!("eyeViewMapNormals"), 1);
 
+        self.renderer.eye_ray_shader.validate();
         gl::BindVertexArray(eye.vao);
         gl::DrawArrays(gl::POINTS, 0, 1);
     }
@@ -22,3 +22,22 @@ impl From<BufferTextureV2<u32>> for VoxelData {
         }
     }
 }
+
+impl VoxelData {
+    /// Frees the buffers in the GPU.
+    /// For voxel fragment lists that are made again and again, like the one of dynamic objects
+    pub unsafe fn delete(self) {
+        let textures = [
+            self.voxel_positions.texture(),
+            self.voxel_colors.0,
+            self.voxel_normals.0,
+        ];
+        let buffers = [
+            self.voxel_positions.buffer(),
+            self.voxel_colors.1,
+            self.voxel_normals.1,
+        ];
+        gl::DeleteTextures(textures.len() as i32, textures.as_ptr());
+        gl::DeleteBuffers(buffers.len() as i32, buffers.as_ptr());
+    }
+}
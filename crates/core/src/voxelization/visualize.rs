@@ -11,20 +11,22 @@ pub struct RenderVoxelFragmentsShader {
     voxel_colors_texture: GLuint,
     number_of_voxel_fragments: u32,
     vao: GLuint,
+    config: Config,
 }
 
 impl RenderVoxelFragmentsShader {
     pub fn init(
+        config: &Config,
         voxel_positions_texture: GLuint,
         voxel_colors_texture: GLuint,
         number_of_voxel_fragments: u32,
-    ) -> Self {
-        Self {
+    ) -> Result<Self, ShaderError> {
+        Ok(Self {
             shader: compile_shaders!(
                 "assets/shaders/voxel_fragment/renderVoxel.vert.glsl",
                 "assets/shaders/voxel_fragment/renderVoxel.frag.glsl",
                 "assets/shaders/voxel_fragment/renderVoxel.geom.glsl",
-            ),
+            )?,
             voxel_positions_texture,
             voxel_colors_texture,
             number_of_voxel_fragments,
@@ -34,10 +36,25 @@ impl RenderVoxelFragmentsShader {
 
                 vao
             },
-        }
+            config: *config,
+        })
     }
 
-    pub unsafe fn run(&self, projection: &Matrix4<f32>, view: &Matrix4<f32>, model: &Matrix4<f32>) {
+    /// Renders other voxel fragments from now on, like the ones of an octree rebuilt with `config`
+    pub fn set_voxel_fragments(
+        &mut self,
+        config: &Config,
+        voxel_positions_texture: GLuint,
+        voxel_colors_texture: GLuint,
+        number_of_voxel_fragments: u32,
+    ) {
+        self.config = *config;
+        self.voxel_positions_texture = voxel_positions_texture;
+        self.voxel_colors_texture = voxel_colors_texture;
+        self.number_of_voxel_fragments = number_of_voxel_fragments;
+    }
+
+    pub unsafe fn run(&self, model: &Matrix4<f32>) {
         gl::Enable(gl::DEPTH_TEST);
 
         self.shader.use_program();
@@ -62,16 +79,15 @@ impl RenderVoxelFragmentsShader {
             gl::RGBA8,
         );
 
-        self.shader.set_mat4(c_str!("projection"), projection);
-        self.shader.set_mat4(c_str!("view"), view);
         self.shader.set_mat4(c_str!("model"), model);
 
-        let config = Config::instance();
+        let config = &self.config;
         self.shader
             .set_uint(c_str!("voxelDimension"), config.voxel_dimension());
         self.shader
             .set_float(c_str!("halfDimension"), 1.0 / config.voxel_dimension() as f32);
 
+        self.shader.validate();
         gl::BindVertexArray(self.vao);
         gl::DrawArrays(gl::POINTS, 0, self.number_of_voxel_fragments as i32);
     }
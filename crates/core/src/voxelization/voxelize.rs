@@ -8,6 +8,7 @@ use engine::prelude::*;
 use crate::config::Config;
 
 unsafe fn calculate_voxel_fragment_list_length(
+    config: &Config,
     voxelization_shader: &Shader,
     objects: &mut [Object],
     scene_aabb: &Aabb,
@@ -16,10 +17,11 @@ unsafe fn calculate_voxel_fragment_list_length(
     voxelization_shader.use_program();
     voxelization_shader.set_bool(c_str!("shouldStore"), false);
     voxelization_shader.set_bool(c_str!("hasBump"), false);
-    voxelize_scene(voxelization_shader, objects, scene_aabb, atomic_counter);
+    voxelize_scene(config, voxelization_shader, objects, scene_aabb, atomic_counter);
 }
 
 unsafe fn populate_voxel_fragment_list(
+    config: &Config,
     voxelization_shader: &Shader,
     objects: &mut [Object],
     scene_aabb: &Aabb,
@@ -36,18 +38,19 @@ unsafe fn populate_voxel_fragment_list(
     helpers::bind_image_texture(1, voxel_colors.0, gl::WRITE_ONLY, gl::RGBA8);
     helpers::bind_image_texture(2, voxel_normals.0, gl::WRITE_ONLY, gl::RGBA32F);
 
-    voxelize_scene(voxelization_shader, objects, scene_aabb, atomic_counter);
+    voxelize_scene(config, voxelization_shader, objects, scene_aabb, atomic_counter);
 }
 
 unsafe fn voxelize_scene(
+    config: &Config,
     voxelization_shader: &Shader,
     objects: &mut [Object],
     scene_aabb: &Aabb,
     atomic_counter: &mut u32,
 ) {
-    gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+    let _timer = profiler::scope("Voxelization");
 
-    let config = Config::instance();
+    gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
     gl::Viewport(
         0,
@@ -110,18 +113,24 @@ unsafe fn voxelize_scene(
 }
 
 pub unsafe fn build_voxel_fragment_list(
+    config: &Config,
     objects: &mut [Object],
     scene_aabb: &Aabb,
-) -> (BufferTextureV2<u32>, u32, BufferTexture, BufferTexture) {
-    let mut atomic_counter: u32 = helpers::generate_atomic_counter_buffer();
-
+) -> Result<(BufferTextureV2<u32>, u32, BufferTexture, BufferTexture), ShaderError> {
     let voxelization_shader = compile_shaders!(
         "assets/shaders/voxel_fragment/voxelize.vert.glsl",
         "assets/shaders/voxel_fragment/voxelize.frag.glsl",
         "assets/shaders/voxel_fragment/voxelize.geom.glsl",
-    );
+    )?;
+    let mut atomic_counter: u32 = helpers::generate_atomic_counter_buffer();
 
-    calculate_voxel_fragment_list_length(&voxelization_shader, objects, scene_aabb, &mut atomic_counter);
+    calculate_voxel_fragment_list_length(
+        config,
+        &voxelization_shader,
+        objects,
+        scene_aabb,
+        &mut atomic_counter,
+    );
     gl::MemoryBarrier(gl::ATOMIC_COUNTER_BUFFER);
 
     gl::BindBuffer(gl::ATOMIC_COUNTER_BUFFER, atomic_counter);
@@ -156,6 +165,7 @@ pub unsafe fn build_voxel_fragment_list(
     gl::BindBuffer(gl::ATOMIC_COUNTER_BUFFER, 0);
 
     populate_voxel_fragment_list(
+        config,
         &voxelization_shader,
         objects,
         scene_aabb,
@@ -166,11 +176,13 @@ pub unsafe fn build_voxel_fragment_list(
     );
 
     gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    // Dynamic objects are voxelized again whenever they move
+    voxelization_shader.delete();
 
-    (
+    Ok((
         BufferTextureV2::from_texture_and_buffer(voxel_positions),
         number_of_voxel_fragments,
         voxel_colors,
         voxel_normals,
-    )
+    ))
 }
@@ -1,6 +1,242 @@
+use std::{env, path::PathBuf};
+
+use core::{
+    config::Config,
+    octree::{Octree, OctreeStructure, VoxelData},
+};
 use engine::prelude::*;
 
+fn pack_positions(positions: &[(u32, u32, u32)]) -> Vec<u32> {
+    positions
+        .iter()
+        .map(|&(x, y, z)| helpers::rgb10_a2ui_to_r32ui(x, y, z))
+        .collect()
+}
+
+/// A solid block, dense enough for every border voxel fragment to fit in its buffer
+fn solid_block() -> Vec<u32> {
+    let mut positions = Vec::new();
+    for x in 3..9 {
+        for y in 0..5 {
+            for z in 6..13 {
+                positions.push((x, y, z));
+            }
+        }
+    }
+    pack_positions(&positions)
+}
+
+/// Config with a voxel dimension of 2^`voxel_dimension_exponent` and a
+/// small brick pool so it doesn't take forever on software rendering
+fn test_config(voxel_dimension_exponent: u32) -> Config {
+    let mut config = Config::new(voxel_dimension_exponent);
+    config.brick_pool_resolution = 48;
+    config
+}
+
+/// Builds an octree from the given voxel fragment positions
+unsafe fn build_test_octree(config: &Config, voxel_positions: Vec<u32>) -> Octree {
+    // To go from the crate root to the workspace root
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.pop();
+    path.pop();
+    env::set_current_dir(path).unwrap();
+
+    let number_of_voxel_fragments = voxel_positions.len() as u32;
+    let voxel_positions = BufferTextureV2::from_data(voxel_positions);
+    let voxel_colors =
+        helpers::generate_texture_buffer(number_of_voxel_fragments as usize, gl::RGBA8, u32::MAX);
+    let voxel_normals = helpers::generate_texture_buffer(
+        number_of_voxel_fragments as usize * 4,
+        gl::RGBA32F,
+        1.0f32,
+    );
+    Octree::new(
+        config,
+        voxel_positions,
+        number_of_voxel_fragments,
+        voxel_colors,
+        voxel_normals,
+    )
+    .unwrap()
+}
+
+/// Voxel fragments of a dynamic object at the given positions
+unsafe fn dynamic_voxel_data(positions: &[(u32, u32, u32)]) -> VoxelData {
+    let voxel_positions = pack_positions(positions);
+    let number_of_voxel_fragments = voxel_positions.len() as u32;
+    VoxelData {
+        voxel_positions: BufferTextureV2::from_data(voxel_positions),
+        number_of_voxel_fragments,
+        voxel_colors: helpers::generate_texture_buffer(
+            number_of_voxel_fragments as usize,
+            gl::RGBA8,
+            u32::MAX,
+        ),
+        voxel_normals: helpers::generate_texture_buffer(
+            number_of_voxel_fragments as usize * 4,
+            gl::RGBA32F,
+            1.0f32,
+        ),
+    }
+}
+
 #[test]
 fn voxelization_until_light_works() {
-    let (_glfw, _window) = test_utils::init_opengl_context();
+    let _context = test_utils::init_opengl_context();
+}
+
+#[test]
+fn octree_cache_roundtrip_works() {
+    let _context = test_utils::init_opengl_context();
+
+    unsafe {
+        let number_of_voxel_fragments = 3;
+        let config = test_config(4);
+        let octree = build_test_octree(
+            &config,
+            pack_positions(&[(0, 0, 0), (8, 10, 6), (8, 7, 10)]),
+        );
+
+        let cache_path = env::temp_dir().join("vct_octree_cache_test.octree");
+        let key = 42;
+        octree.save(&cache_path, key).unwrap();
+        assert!(Octree::load(&config, &cache_path, key + 1).is_err());
+        assert!(Octree::load(&test_config(5), &cache_path, key).is_err());
+        let loaded = Octree::load(&config, &cache_path, key).unwrap();
+
+        let number_of_nodes = octree.number_of_nodes();
+        assert_eq!(loaded.number_of_nodes(), number_of_nodes);
+        assert_eq!(
+            loaded.geometry_data.voxel_data.number_of_voxel_fragments,
+            number_of_voxel_fragments,
+        );
+        let node_pool = |octree: &Octree| {
+            helpers::get_values_from_texture_buffer(
+                octree.textures.node_pool.1,
+                number_of_nodes * 8,
+                0_u32,
+            )
+        };
+        assert_eq!(node_pool(&loaded), node_pool(&octree));
+        let node_positions = |octree: &Octree| {
+            helpers::get_values_from_texture_buffer(
+                octree.textures.node_positions.1,
+                number_of_nodes,
+                0_u32,
+            )
+        };
+        assert_eq!(node_positions(&loaded), node_positions(&octree));
+
+        let file = std::fs::read(&cache_path).unwrap();
+        std::fs::write(&cache_path, &file[..file.len() - 1]).unwrap();
+        let error = Octree::load(&config, &cache_path, key).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        std::fs::remove_file(cache_path).unwrap();
+    }
+}
+
+#[test]
+fn octree_build_matches_cpu_reference() {
+    let _context = test_utils::init_opengl_context();
+
+    unsafe {
+        let voxel_positions = solid_block();
+        let config = test_config(4);
+        let octree = build_test_octree(&config, voxel_positions.clone());
+
+        let reference = OctreeStructure::build(&voxel_positions, config.voxel_dimension());
+        let built = octree.structure();
+
+        if let Some(divergence) = reference.first_divergence(&built) {
+            panic!("GPU octree diverges from the CPU reference: {divergence}");
+        }
+    }
+}
+
+#[test]
+fn octrees_with_different_voxel_dimensions_coexist() {
+    let _context = test_utils::init_opengl_context();
+
+    unsafe {
+        let voxel_positions = solid_block();
+        let small = build_test_octree(&test_config(4), voxel_positions.clone());
+        let big = build_test_octree(&test_config(5), voxel_positions.clone());
+
+        for octree in [&small, &big] {
+            let reference =
+                OctreeStructure::build(&voxel_positions, octree.config().voxel_dimension());
+            if let Some(divergence) = reference.first_divergence(&octree.structure()) {
+                panic!(
+                    "GPU octree with voxel dimension {} diverges from the CPU reference: {divergence}",
+                    octree.config().voxel_dimension()
+                );
+            }
+        }
+        assert!(big.number_of_nodes() > small.number_of_nodes());
+
+        small.delete();
+        big.delete();
+    }
+}
+
+#[test]
+fn octree_rebuild_matches_first_build() {
+    let _context = test_utils::init_opengl_context();
+
+    unsafe {
+        let mut octree = build_test_octree(
+            &test_config(4),
+            pack_positions(&[(0, 0, 0), (8, 10, 6), (8, 7, 10), (9, 7, 10)]),
+        );
+        let number_of_nodes = octree.number_of_nodes();
+        let node_pool = |octree: &Octree| {
+            helpers::get_values_from_texture_buffer(
+                octree.textures.node_pool.1,
+                number_of_nodes * 8,
+                0_u32,
+            )
+        };
+        let first_node_pool = node_pool(&octree);
+        let first_structure = octree.structure();
+
+        octree.rebuild();
+
+        assert_eq!(octree.number_of_nodes(), number_of_nodes);
+        assert_eq!(node_pool(&octree), first_node_pool);
+        if let Some(divergence) = first_structure.first_divergence(&octree.structure()) {
+            panic!("Rebuilt octree diverges from the first build: {divergence}");
+        }
+        octree.delete();
+    }
+}
+
+#[test]
+fn dynamic_nodes_are_removed_when_they_move() {
+    let _context = test_utils::init_opengl_context();
+
+    unsafe {
+        let mut octree = build_test_octree(&test_config(4), solid_block());
+        let number_of_nodes = octree.number_of_nodes();
+        let node_pool = |octree: &Octree| {
+            helpers::get_values_from_texture_buffer(
+                octree.textures.node_pool.1,
+                number_of_nodes * 8,
+                0_u32,
+            )
+        };
+        let static_node_pool = node_pool(&octree);
+
+        // Away from the block, so it needs nodes of its own
+        octree.update_dynamic_voxels(dynamic_voxel_data(&[(14, 14, 1)]));
+        assert!(octree.number_of_nodes() > number_of_nodes);
+
+        // Inside the block, where every node already exists
+        octree.update_dynamic_voxels(dynamic_voxel_data(&[(4, 1, 7)]));
+        assert_eq!(octree.number_of_nodes(), number_of_nodes);
+        assert_eq!(node_pool(&octree), static_node_pool);
+
+        octree.delete();
+    }
 }
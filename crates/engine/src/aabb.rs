@@ -69,6 +69,31 @@ impl Aabb {
         self.min_vertex.z = self.min_vertex.z.min(other.min_vertex.z);
     }
 
+    /// Whether `other` is completely inside this one
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min_vertex.x <= other.min_vertex.x
+            && self.min_vertex.y <= other.min_vertex.y
+            && self.min_vertex.z <= other.min_vertex.z
+            && self.max_vertex.x >= other.max_vertex.x
+            && self.max_vertex.y >= other.max_vertex.y
+            && self.max_vertex.z >= other.max_vertex.z
+    }
+
+    /// The eight vertices of the box
+    pub fn corners(&self) -> [Vector3<f32>; 8] {
+        let (min, max) = (self.min_vertex, self.max_vertex);
+        [
+            vec3(min.x, min.y, min.z),
+            vec3(max.x, min.y, min.z),
+            vec3(min.x, max.y, min.z),
+            vec3(max.x, max.y, min.z),
+            vec3(min.x, min.y, max.z),
+            vec3(max.x, min.y, max.z),
+            vec3(min.x, max.y, max.z),
+            vec3(max.x, max.y, max.z),
+        ]
+    }
+
     pub fn normalization_matrix(&self) -> Matrix4<f32> {
         let center_matrix = Matrix4::from_translation(-self.middle_point());
         let normalize_size_matrix = Matrix4::from_scale(2_f32 / self.longer_axis_length());
@@ -130,4 +155,32 @@ mod tests {
         aabb_1.join(&aabb_2);
         assert_eq!(aabb_1, expected);
     }
+
+    #[test]
+    fn corners_span_the_box() {
+        let aabb = Aabb {
+            min_vertex: vec3(-1.0, 0.0, 2.0),
+            max_vertex: vec3(1.0, 3.0, 4.0),
+        };
+        let mut from_corners = Aabb::default();
+        for corner in aabb.corners() {
+            from_corners.refresh_aabb(corner.x, corner.y, corner.z);
+        }
+        assert_eq!(from_corners, aabb);
+    }
+
+    #[test]
+    fn contains_works() {
+        let aabb = Aabb {
+            min_vertex: vec3(-1.0, -1.0, -1.0),
+            max_vertex: vec3(1.0, 1.0, 1.0),
+        };
+        let inside = Aabb {
+            min_vertex: vec3(-0.5, -1.0, 0.0),
+            max_vertex: vec3(0.5, 1.0, 0.5),
+        };
+        let sticking_out = inside.offsetted(vec3(0.0, 0.0, 0.75));
+        assert!(aabb.contains(&inside));
+        assert!(!aabb.contains(&sticking_out));
+    }
 }
@@ -1,8 +1,10 @@
 use serde::{Serialize, Deserialize};
-use cgmath::{Matrix4, Deg};
+use cgmath::{Matrix4, Deg, Point3};
+use gl::types::GLuint;
 
 use super::{
     transform::Transform, common,
+    uniform_buffer::{Std140, UniformBlock},
 };
 
 // Default camera values
@@ -120,4 +122,32 @@ impl Camera {
             10000.0,
         )
     }
+
+    /// Matrices and position of the camera for the `Camera` uniform block, shared by every shader
+    pub fn uniform_block(&self) -> CameraBlock {
+        CameraBlock {
+            projection: self.get_projection_matrix(),
+            view: self.transform.get_view_matrix(),
+            eye_position: self.transform.position,
+        }
+    }
+}
+
+/// `Camera` uniform block in `_uniformBlocks.glsl`
+#[derive(Debug, Clone, Copy)]
+pub struct CameraBlock {
+    pub projection: Matrix4<f32>,
+    pub view: Matrix4<f32>,
+    pub eye_position: Point3<f32>,
+}
+
+impl UniformBlock for CameraBlock {
+    const BINDING: GLuint = 0;
+
+    fn write_std140(&self, buffer: &mut Std140) {
+        buffer
+            .mat4(&self.projection)
+            .mat4(&self.view)
+            .point3(self.eye_position);
+    }
 }
@@ -1,10 +1,11 @@
-use std::{cell::RefCell, ffi::CStr, ptr, sync::mpsc::Receiver};
+use std::{cell::RefCell, ffi::CStr, fmt, ptr, str::FromStr, sync::mpsc::Receiver, time::Instant};
 
 use egui_glfw_gl::glfw::{self, Action, Context, Glfw, Key, Window, WindowEvent};
 use log;
 
 use super::{
     camera::Camera,
+    headless::{self, HeadlessContext},
     transform::{Direction, Transform},
 };
 use crate::{helpers, toggle_boolean};
@@ -14,9 +15,117 @@ use crate::ui::Ui;
 
 pub static mut WINDOW: RefCell<Option<glfw::Window>> = RefCell::new(None);
 
-/// Panics if glfw hasn't been initialized yet
+/// Panics if no context has been set up yet
 pub unsafe fn get_framebuffer_size() -> (i32, i32) {
-    WINDOW.borrow().as_ref().unwrap().get_framebuffer_size()
+    match WINDOW.borrow().as_ref() {
+        Some(window) => window.get_framebuffer_size(),
+        None => headless::framebuffer_size().expect("No OpenGL context has been set up"),
+    }
+}
+
+/// How the OpenGL context gets created
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContextBackend {
+    /// A GLFW window, hidden if running headless. Needs a display server
+    #[default]
+    Glfw,
+    /// A surfaceless EGL context. Always headless, works without display nor GPU
+    Egl,
+}
+
+impl FromStr for ContextBackend {
+    type Err = String;
+
+    fn from_str(backend: &str) -> Result<Self, Self::Err> {
+        match backend.to_lowercase().as_str() {
+            "glfw" => Ok(Self::Glfw),
+            "egl" => Ok(Self::Egl),
+            _ => Err(format!(
+                "Unknown context backend `{backend}`, expected `glfw` or `egl`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ContextBackend {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Glfw => write!(formatter, "glfw"),
+            Self::Egl => write!(formatter, "egl"),
+        }
+    }
+}
+
+/// Keeps the OpenGL context alive and provides what the render loop needs from it,
+/// regardless of the backend
+pub enum ContextHandle {
+    Glfw {
+        glfw: Glfw,
+        events: Receiver<(f64, WindowEvent)>,
+    },
+    Egl {
+        // Only here to be dropped along with the handle
+        _context: HeadlessContext,
+        start: Instant,
+    },
+}
+
+impl ContextHandle {
+    /// Seconds since the context was created
+    pub fn get_time(&self) -> f64 {
+        match self {
+            Self::Glfw { glfw, .. } => glfw.get_time(),
+            Self::Egl { start, .. } => start.elapsed().as_secs_f64(),
+        }
+    }
+
+    /// Whether swapping buffers waits for the display. Headless contexts never wait
+    pub fn set_vsync(&mut self, vsync: bool) {
+        if let Self::Glfw { glfw, .. } = self {
+            glfw.set_swap_interval(if vsync {
+                glfw::SwapInterval::Sync(1)
+            } else {
+                glfw::SwapInterval::None
+            });
+        }
+    }
+
+    pub fn poll_events(&mut self) {
+        if let Self::Glfw { glfw, .. } = self {
+            glfw.poll_events();
+        }
+    }
+
+    /// Window events received since the last call. Always empty without a window
+    pub fn flush_events(&self) -> Vec<WindowEvent> {
+        match self {
+            Self::Glfw { events, .. } => glfw::flush_messages(events)
+                .map(|(_, event)| event)
+                .collect(),
+            Self::Egl { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Creates the OpenGL context with the chosen backend.
+/// EGL contexts are always headless
+pub unsafe fn setup_context(
+    backend: ContextBackend,
+    viewport_width: i32,
+    viewport_height: i32,
+    debug: bool,
+    headless: bool,
+) -> ContextHandle {
+    match backend {
+        ContextBackend::Glfw => {
+            let (glfw, events) = setup_glfw(viewport_width, viewport_height, debug, headless);
+            ContextHandle::Glfw { glfw, events }
+        }
+        ContextBackend::Egl => ContextHandle::Egl {
+            _context: setup_egl(viewport_width, viewport_height, debug),
+            start: Instant::now(),
+        },
+    }
 }
 
 unsafe fn set_window(window: Window) {
@@ -60,7 +169,31 @@ pub unsafe fn setup_glfw(
     // GL: Load all OpenGL function pointers
     gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
 
-    // Enable OpenGL Debug Context if allowed
+    enable_debug_output();
+
+    #[cfg(feature = "ui")]
+    Ui::setup(&mut window);
+
+    set_window(window);
+
+    (glfw, events)
+}
+
+/// Creates a surfaceless EGL context, no window involved
+pub unsafe fn setup_egl(viewport_width: i32, viewport_height: i32, debug: bool) -> HeadlessContext {
+    let context = HeadlessContext::new(viewport_width, viewport_height, debug);
+    gl::Viewport(0, 0, viewport_width, viewport_height);
+
+    enable_debug_output();
+
+    #[cfg(feature = "ui")]
+    Ui::setup_headless(viewport_width, viewport_height);
+
+    context
+}
+
+/// Enable OpenGL Debug Context if allowed
+unsafe fn enable_debug_output() {
     let mut flags = 0;
     gl::GetIntegerv(gl::CONTEXT_FLAGS, &mut flags);
     if flags as u32 & gl::CONTEXT_FLAG_DEBUG_BIT != 0 {
@@ -78,18 +211,14 @@ pub unsafe fn setup_glfw(
     } else {
         println!("Debug Context not active");
     }
-
-    #[cfg(feature = "ui")]
-    Ui::setup(&mut window);
-
-    set_window(window);
-
-    (glfw, events)
 }
 
+/// Does nothing without a window
 pub fn swap_buffers() {
     unsafe {
-        WINDOW.borrow_mut().as_mut().unwrap().swap_buffers();
+        if let Some(window) = WINDOW.borrow_mut().as_mut() {
+            window.swap_buffers();
+        }
     }
 }
 
@@ -137,7 +266,9 @@ pub fn process_events(
 
 pub unsafe fn process_movement_input(delta_time: f32, transform: &mut Transform) {
     let binding = WINDOW.borrow();
-    let window = binding.as_ref().unwrap();
+    let Some(window) = binding.as_ref() else {
+        return;
+    };
     if window.get_key(Key::W) == Action::Press {
         transform.process_keyboard(Direction::Forward, delta_time);
     }
@@ -158,15 +289,16 @@ pub unsafe fn process_movement_input(delta_time: f32, transform: &mut Transform)
     }
 }
 
+/// Without a window only the caller can decide when to stop
 pub fn should_close_window() -> bool {
     unsafe {
         let binding = WINDOW.borrow();
-        let window = binding.as_ref().unwrap();
-        window.should_close()
+        binding.as_ref().is_some_and(|window| window.should_close())
     }
 }
 
 toggle_boolean!(C, handle_light_movement);
+toggle_boolean!(O, handle_object_movement);
 toggle_boolean!(Num1, handle_show_model);
 toggle_boolean!(Num2, handle_show_voxel_fragment_list);
 
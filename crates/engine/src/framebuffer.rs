@@ -6,7 +6,8 @@
 use std::{fs, mem::MaybeUninit, path::PathBuf};
 
 use gl::types::*;
-use image::{GenericImageView, ImageBuffer, Pixel, Rgba, RgbaImage};
+use image::{ImageBuffer, Rgba, RgbaImage};
+pub use image_comparison::{compare_images, ImageComparison, ImageComparisonError};
 
 use super::{common, types::*};
 
@@ -16,7 +17,7 @@ pub struct Framebuffer<const N: usize> {
     attachments: [ColorAttachment; N],
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ColorAttachment {
     name: String,
     texture_id: GLuint,
@@ -33,6 +34,16 @@ pub const LIGHT_MAP_BUFFERS: usize = 3;
 pub type GeometryFramebuffer = Framebuffer<GEOMETRY_BUFFERS>;
 pub type LightFramebuffer = Framebuffer<LIGHT_MAP_BUFFERS>;
 
+/// A framebuffer without any OpenGL objects yet, like the one of a light before it's initialized
+impl<const N: usize> Default for Framebuffer<N> {
+    fn default() -> Self {
+        Self {
+            fbo: 0,
+            attachments: std::array::from_fn(|_| ColorAttachment::default()),
+        }
+    }
+}
+
 /// Implementation of framebuffer with only 1 output buffer.
 /// Meant to be used for easily visualizing and saving any rendering artifact.
 impl Framebuffer<1> {
@@ -543,7 +554,8 @@ impl Framebuffer<LIGHT_MAP_BUFFERS> {
         }
     }
 
-    pub unsafe fn new_directional() -> Self {
+    /// Light view map of a single 2D texture, for the lights with an orthographic projection
+    pub unsafe fn new_orthographic() -> Self {
         let mut fbo = 0;
         gl::GenFramebuffers(1, &mut fbo);
         gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
@@ -747,38 +759,17 @@ impl<const N: usize> Framebuffer<N> {
 
     /// Compares the texture in the `attachment_index` attachment of this framebuffer
     /// to an image on disk with path `file_to_compare`.
-    /// Returns whether the images were deemed the same.
+    /// `tolerance` is the maximum difference allowed per channel, in the range [0.0, 1.0].
     pub fn compare_attachment_to_file(
         &self,
         attachment_index: usize,
         file_to_compare: &str,
-    ) -> Result<bool, ()> {
+        tolerance: f32,
+    ) -> Result<ImageComparison, ImageComparisonError> {
         let image = self.get_image_from_attachment(attachment_index);
-        let image_to_compare = image::open(file_to_compare).map_err(|_| ())?; // We know this error has something to do with creating the file. We'll handle it outside.
-
-        // Ensure the two images have the same dimensions
-        let (width_1, height_1) = image.dimensions();
-        let (width_2, height_2) = image_to_compare.dimensions();
-        if width_1 != width_2 || height_1 != height_2 {
-            return Ok(false);
-        }
-
-        let tolerance = 0.05f32; // 5 percent
-        let channel_tolerance = (tolerance * 255.0f32).round() as u8;
-
-        for y in 0..height_1 {
-            for x in 0..width_1 {
-                let pixel_1 = image.get_pixel(x, y).to_rgb();
-                let pixel_2 = image_to_compare.get_pixel(x, y).to_rgb();
-                if ((pixel_1[0] as i16 - pixel_2[0] as i16).abs() > channel_tolerance as i16
-                    || (pixel_1[1] as i16 - pixel_2[1] as i16).abs() > channel_tolerance as i16
-                    || (pixel_1[2] as i16 - pixel_2[2] as i16).abs() > channel_tolerance as i16)
-                {
-                    return Ok(false);
-                }
-            }
-        }
-
-        Ok(true)
+        let image_to_compare = image::open(file_to_compare)
+            .map_err(ImageComparisonError::MissingReference)?
+            .to_rgba8();
+        compare_images(&image, &image_to_compare, tolerance)
     }
 }
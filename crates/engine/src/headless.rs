@@ -0,0 +1,155 @@
+//! Offscreen OpenGL context through surfaceless EGL.
+//! Doesn't need a display server nor a GPU, Mesa's llvmpipe is enough.
+//! A pbuffer stands in for the window, so drawing to the default framebuffer
+//! (e.g. voxelization) still rasterizes.
+
+use std::{env, ptr};
+
+use khronos_egl as egl;
+
+/// Not exposed by `khronos_egl`, defined in `EGL_MESA_platform_surfaceless`
+const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+/// There is no window to ask for its size, so we remember the one we were created with
+static mut FRAMEBUFFER_SIZE: Option<(i32, i32)> = None;
+
+/// llvmpipe implements everything we use but only advertises 4.5.
+/// These only affect Mesa drivers and are read when the context is created.
+pub const MESA_OVERRIDES: [(&str, &str); 2] = [
+    ("MESA_GL_VERSION_OVERRIDE", "4.6"),
+    ("MESA_GLSL_VERSION_OVERRIDE", "460"),
+];
+
+/// Sets the [`MESA_OVERRIDES`] the user didn't set already.
+/// Opt-in, it's up to binaries to call it.
+///
+/// # Safety
+/// Mutates the process environment, so it must be called before any other thread is spawned
+/// (e.g. first thing in `main`).
+pub unsafe fn set_mesa_overrides() {
+    for (variable, value) in MESA_OVERRIDES {
+        if env::var_os(variable).is_none() {
+            env::set_var(variable, value);
+        }
+    }
+}
+
+pub struct HeadlessContext {
+    egl: egl::DynamicInstance<egl::EGL1_5>,
+    display: egl::Display,
+    surface: egl::Surface,
+    context: egl::Context,
+}
+
+impl HeadlessContext {
+    /// Creates an OpenGL 4.6 core context with a pbuffer of the viewport size and makes it
+    /// current on the calling thread.
+    /// Panics if EGL can't be loaded or the context can't be created.
+    /// On llvmpipe, creating a 4.6 context needs [`MESA_OVERRIDES`] in the environment.
+    pub unsafe fn new(viewport_width: i32, viewport_height: i32, debug: bool) -> Self {
+        let egl =
+            egl::DynamicInstance::<egl::EGL1_5>::load_required().expect("Failed to load libEGL");
+
+        let display = egl
+            .get_platform_display(
+                PLATFORM_SURFACELESS_MESA,
+                egl::DEFAULT_DISPLAY,
+                &[egl::ATTRIB_NONE],
+            )
+            .or_else(|_| {
+                egl.get_display(egl::DEFAULT_DISPLAY)
+                    .ok_or(egl::Error::BadDisplay)
+            })
+            .expect("Failed to get an EGL display");
+        egl.initialize(display).expect("Failed to initialize EGL");
+        egl.bind_api(egl::OPENGL_API)
+            .expect("Failed to bind the OpenGL API");
+
+        let config = egl
+            .choose_first_config(
+                display,
+                &[
+                    egl::SURFACE_TYPE,
+                    egl::PBUFFER_BIT,
+                    egl::RENDERABLE_TYPE,
+                    egl::OPENGL_BIT,
+                    egl::RED_SIZE,
+                    8,
+                    egl::GREEN_SIZE,
+                    8,
+                    egl::BLUE_SIZE,
+                    8,
+                    egl::ALPHA_SIZE,
+                    8,
+                    egl::DEPTH_SIZE,
+                    24,
+                    egl::NONE,
+                ],
+            )
+            .expect("Failed to choose an EGL config")
+            .expect("No EGL config supports OpenGL");
+
+        let context = egl
+            .create_context(
+                display,
+                config,
+                None,
+                &[
+                    egl::CONTEXT_MAJOR_VERSION,
+                    4,
+                    egl::CONTEXT_MINOR_VERSION,
+                    6,
+                    egl::CONTEXT_OPENGL_PROFILE_MASK,
+                    egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                    egl::CONTEXT_OPENGL_DEBUG,
+                    debug as egl::Int,
+                    egl::NONE,
+                ],
+            )
+            .expect("Failed to create OpenGL 4.6 context through EGL");
+        let surface = egl
+            .create_pbuffer_surface(
+                display,
+                config,
+                &[
+                    egl::WIDTH,
+                    viewport_width,
+                    egl::HEIGHT,
+                    viewport_height,
+                    egl::NONE,
+                ],
+            )
+            .expect("Failed to create an EGL pbuffer");
+        egl.make_current(display, Some(surface), Some(surface), Some(context))
+            .expect("Failed to make the EGL context current");
+
+        // GL: Load all OpenGL function pointers
+        gl::load_with(|symbol| {
+            egl.get_proc_address(symbol)
+                .map_or(ptr::null(), |function| function as *const _)
+        });
+
+        FRAMEBUFFER_SIZE = Some((viewport_width, viewport_height));
+
+        Self {
+            egl,
+            display,
+            surface,
+            context,
+        }
+    }
+}
+
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        // The display isn't terminated since other contexts (i.e. other tests) may share it
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_surface(self.display, self.surface);
+        let _ = self.egl.destroy_context(self.display, self.context);
+    }
+}
+
+/// Size given to the last headless context created, if any
+pub fn framebuffer_size() -> Option<(i32, i32)> {
+    unsafe { FRAMEBUFFER_SIZE }
+}
@@ -4,6 +4,7 @@ use std::{
     env,
     ffi::{c_void, CStr},
     mem::size_of,
+    ops::Range,
     path::Path,
 };
 
@@ -233,6 +234,8 @@ where
     T: Clone,
 {
     let values = vec![default_value; size];
+    // Shaders that wrote to it through images don't wait for buffer reads
+    gl::MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT);
     gl::BindBuffer(gl::TEXTURE_BUFFER, texture_buffer);
     gl::GetBufferSubData(
         gl::TEXTURE_BUFFER,
@@ -263,6 +266,84 @@ pub unsafe fn clear_texture_buffer<T>(
     gl::BindBuffer(gl::TEXTURE_BUFFER, 0);
 }
 
+/// Sets every `u32` of the texture buffer to `value`, keeping its storage unlike `clear_texture_buffer`
+pub unsafe fn fill_texture_buffer(texture_buffer: GLuint, value: u32) {
+    // Shaders that wrote to it through images don't wait for buffer updates
+    gl::MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT);
+    gl::BindBuffer(gl::TEXTURE_BUFFER, texture_buffer);
+    gl::ClearBufferData(
+        gl::TEXTURE_BUFFER,
+        gl::R32UI,
+        gl::RED_INTEGER,
+        gl::UNSIGNED_INT,
+        &value as *const u32 as *const c_void,
+    );
+    gl::BindBuffer(gl::TEXTURE_BUFFER, 0);
+}
+
+/// Sets the `u32`s of the texture buffer in `range` to `value`
+pub unsafe fn fill_texture_buffer_range(texture_buffer: GLuint, range: Range<usize>, value: u32) {
+    if range.is_empty() {
+        return;
+    }
+    gl::MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT);
+    gl::BindBuffer(gl::TEXTURE_BUFFER, texture_buffer);
+    gl::ClearBufferSubData(
+        gl::TEXTURE_BUFFER,
+        gl::R32UI,
+        (range.start * size_of::<u32>()) as isize,
+        (range.len() * size_of::<u32>()) as isize,
+        gl::RED_INTEGER,
+        gl::UNSIGNED_INT,
+        &value as *const u32 as *const c_void,
+    );
+    gl::BindBuffer(gl::TEXTURE_BUFFER, 0);
+}
+
+/// Size of the buffer in bytes
+pub unsafe fn buffer_size(buffer: GLuint) -> usize {
+    let mut size = 0;
+    gl::BindBuffer(gl::COPY_READ_BUFFER, buffer);
+    gl::GetBufferParameteriv(gl::COPY_READ_BUFFER, gl::BUFFER_SIZE, &mut size);
+    gl::BindBuffer(gl::COPY_READ_BUFFER, 0);
+    size as usize
+}
+
+/// Copies the first `size` bytes of `source` to the start of `destination`
+pub unsafe fn copy_buffer(source: GLuint, destination: GLuint, size: usize) {
+    if size == 0 {
+        return;
+    }
+    gl::MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT);
+    gl::BindBuffer(gl::COPY_READ_BUFFER, source);
+    gl::BindBuffer(gl::COPY_WRITE_BUFFER, destination);
+    gl::CopyBufferSubData(
+        gl::COPY_READ_BUFFER,
+        gl::COPY_WRITE_BUFFER,
+        0,
+        0,
+        size as isize,
+    );
+    gl::BindBuffer(gl::COPY_READ_BUFFER, 0);
+    gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+}
+
+/// A new buffer with a copy of the first `size` bytes of `source`
+pub unsafe fn copy_to_new_buffer(source: GLuint, size: usize) -> GLuint {
+    let mut buffer = 0;
+    gl::GenBuffers(1, &mut buffer);
+    gl::BindBuffer(gl::COPY_WRITE_BUFFER, buffer);
+    gl::BufferData(
+        gl::COPY_WRITE_BUFFER,
+        size as isize,
+        std::ptr::null(),
+        gl::STATIC_COPY,
+    );
+    gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+    copy_buffer(source, buffer, size);
+    buffer
+}
+
 pub unsafe fn bind_image_texture(
     image_index: u32,
     texture: GLuint,
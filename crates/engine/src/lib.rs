@@ -5,9 +5,11 @@ pub mod camera;
 pub mod common;
 pub mod framebuffer;
 pub mod gizmo;
+pub mod headless;
 pub mod light;
 pub mod macros;
 pub mod mesh;
+pub mod preprocessor;
 pub mod model;
 pub mod quad;
 pub mod shader;
@@ -18,9 +20,11 @@ pub mod types;
 pub mod scene;
 pub mod material;
 pub mod object;
+pub mod profiler;
 pub mod asset_registry;
 pub mod test_utils;
 pub mod texture_buffer;
+pub mod uniform_buffer;
 mod traits;
 
 #[cfg(feature = "ui")]
@@ -29,10 +33,11 @@ pub mod ui;
 pub mod prelude {
     pub use super::{
         transform::Transform,
-        shader::{Shader, ShaderPass, compile_shaders, compile_compute},
+        shader::{Shader, ShaderDefines, ShaderError, ShaderPass, ShaderUniforms, compile_shaders, compile_compute},
         helpers,
+        profiler,
         types::*,
-        camera::Camera,
+        camera::{Camera, CameraBlock},
         framebuffer::{
             Framebuffer,
             GeometryFramebuffer,
@@ -52,5 +57,6 @@ pub mod prelude {
         common,
         test_utils,
         texture_buffer::BufferTextureV2,
+        uniform_buffer::{Std140, UniformBlock, UniformBuffer},
     };
 }
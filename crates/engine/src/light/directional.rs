@@ -0,0 +1,179 @@
+use c_str_macro::c_str;
+use cgmath::{point3, vec3, Matrix4, Point3};
+use serde::Deserialize;
+
+use super::{gizmo::LightGizmo, render_light_view_map};
+use crate::{
+    aabb::Aabb,
+    framebuffer::{LightFramebuffer, LIGHT_MAP_BUFFERS},
+    gizmo::RenderGizmo,
+    object::Object,
+    shader::{compile_shaders, Shader, ShaderError},
+    transform::Transform,
+    types::Textures,
+};
+
+/// Light infinitely far away, like the sun.
+/// All its rays are parallel to the forward vector of its transform, its position
+/// is only used for the gizmo.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct DirectionalLight {
+    pub transform: Transform,
+    pub intensity: f32,
+    pub color: Point3<f32>,
+    #[serde(skip_deserializing)]
+    gizmo: LightGizmo,
+    #[serde(skip_deserializing)]
+    light_map_shader: Shader,
+    #[serde(skip_deserializing)]
+    framebuffer: LightFramebuffer,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self {
+            transform: Transform::default(),
+            intensity: 1.0,
+            color: point3(1.0, 1.0, 1.0),
+            gizmo: LightGizmo::default(),
+            light_map_shader: Shader::default(),
+            framebuffer: LightFramebuffer::default(),
+        }
+    }
+}
+
+impl DirectionalLight {
+    pub unsafe fn new(color: Point3<f32>, intensity: f32) -> Result<Self, ShaderError> {
+        let mut light = Self {
+            color,
+            intensity,
+            ..Default::default()
+        };
+        light.init()?;
+        Ok(light)
+    }
+
+    /// Creates the box gizmo, the light view map shader and its orthographic framebuffer, see `Light::init`
+    pub unsafe fn init(&mut self) -> Result<(), ShaderError> {
+        self.transform.update_vectors();
+        self.gizmo = LightGizmo::new_box()?;
+        self.light_map_shader = light_map_shader()?;
+        self.framebuffer = LightFramebuffer::new_orthographic();
+        Ok(())
+    }
+
+    /// View matrix looking along the light direction from the center of the
+    /// normalized scene
+    pub fn get_view_matrix(&self) -> Matrix4<f32> {
+        let origin = point3(0.0, 0.0, 0.0);
+        Matrix4::look_at_rh(
+            origin,
+            origin + self.transform.get_forward(),
+            self.transform.get_up(),
+        )
+    }
+
+    /// Orthographic projection that fits the whole scene, so every voxel can get photons
+    pub fn get_projection_matrix(&self, scene_aabb: &Aabb) -> Matrix4<f32> {
+        fit_orthographic_projection(&self.get_view_matrix(), scene_aabb)
+    }
+
+    /// Light view map of the last `take_photo`, still valid while the light and objects stay put
+    pub fn light_map_textures(&self) -> Textures<LIGHT_MAP_BUFFERS> {
+        self.framebuffer.textures()
+    }
+
+    pub unsafe fn take_photo(
+        &self,
+        objects: &mut [Object],
+        scene_aabb: &Aabb,
+        voxel_dimension: u32, // TODO: Find another way. This breaks separation of concerns
+    ) -> Textures<LIGHT_MAP_BUFFERS> {
+        let projection = self.get_projection_matrix(scene_aabb);
+        self.light_map_shader.use_program();
+        self.light_map_shader
+            .set_mat4(c_str!("projection"), &projection);
+        self.light_map_shader
+            .set_mat4(c_str!("view"), &self.get_view_matrix());
+        self.light_map_shader
+            .set_uint(c_str!("voxelDimension"), voxel_dimension);
+
+        render_light_view_map(
+            &self.framebuffer,
+            &self.light_map_shader,
+            objects,
+            scene_aabb,
+        )
+    }
+}
+
+/// Smallest orthographic projection that contains the normalized `scene_aabb`
+/// as seen from `view`
+fn fit_orthographic_projection(view: &Matrix4<f32>, scene_aabb: &Aabb) -> Matrix4<f32> {
+    let normalization_matrix = scene_aabb.normalization_matrix();
+    let mut light_space_aabb = Aabb::default();
+    for corner in scene_aabb.corners() {
+        let corner = view * normalization_matrix * corner.extend(1.0);
+        light_space_aabb.refresh_aabb(corner.x, corner.y, corner.z);
+    }
+    let (min, max) = (light_space_aabb.min_vertex, light_space_aabb.max_vertex);
+
+    // The view looks towards -Z, so the nearest point has the biggest Z
+    cgmath::ortho(min.x, max.x, min.y, max.y, -max.z, -min.z)
+}
+
+fn light_map_shader() -> Result<Shader, ShaderError> {
+    compile_shaders!("assets/shaders/octree/lightViewMapDirectional.glsl",)
+}
+
+impl RenderGizmo for DirectionalLight {
+    unsafe fn draw_gizmo(&self) {
+        self.gizmo
+            .draw(&self.transform, self.color, Some(vec3(0.05, 0.05, 0.01)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{vec3, Vector4};
+
+    use super::*;
+
+    #[test]
+    fn orthographic_projection_fits_the_scene() {
+        let scene_aabb = Aabb {
+            min_vertex: vec3(-10.0, 0.0, -4.0),
+            max_vertex: vec3(10.0, 5.0, 4.0),
+        };
+        let view = Matrix4::look_at_rh(
+            point3(0.0, 0.0, 0.0),
+            point3(0.3, -1.0, 0.2),
+            vec3(0.0, 0.0, 1.0),
+        );
+        let light_space = fit_orthographic_projection(&view, &scene_aabb)
+            * view
+            * scene_aabb.normalization_matrix();
+
+        let clip_coordinates: Vec<Vector4<f32>> = scene_aabb
+            .corners()
+            .iter()
+            .map(|corner| light_space * corner.extend(1.0))
+            .collect();
+        for coordinates in clip_coordinates.iter() {
+            for value in [coordinates.x, coordinates.y, coordinates.z] {
+                assert!(
+                    value.abs() <= 1.0 + 1e-5,
+                    "{coordinates:?} outside of the light view"
+                );
+            }
+        }
+        // It's tight, some corner touches every side
+        for axis in 0..3 {
+            let values = clip_coordinates.iter().map(|coordinates| coordinates[axis]);
+            let max = values.clone().fold(f32::NEG_INFINITY, f32::max);
+            let min = values.fold(f32::INFINITY, f32::min);
+            assert!((max - 1.0).abs() < 1e-5 && (min + 1.0).abs() < 1e-5);
+        }
+    }
+}
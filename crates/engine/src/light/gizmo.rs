@@ -0,0 +1,94 @@
+use std::{ffi::c_void, mem::size_of};
+
+use c_str_macro::c_str;
+use cgmath::{point3, Point3, Vector3};
+use gl::types::GLuint;
+
+use crate::{
+    shader::{compile_shaders, Shader, ShaderError},
+    transform::Transform,
+};
+
+/// Gizmo of a light, a single point that the geometry shader expands into its shape
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LightGizmo {
+    shader: Shader,
+    vao: GLuint,
+}
+
+impl LightGizmo {
+    /// Cube around the position of the light
+    pub unsafe fn new_cubic() -> Result<Self, ShaderError> {
+        Ok(Self::new(compile_shaders!(
+            "assets/shaders/debug/cubicGizmo.vert.glsl",
+            "assets/shaders/debug/cubicGizmo.frag.glsl",
+            "assets/shaders/debug/cubicGizmo.geom.glsl",
+        )?))
+    }
+
+    /// Box with the `dimensions` given to `draw`, and the axes of the light
+    pub unsafe fn new_box() -> Result<Self, ShaderError> {
+        Ok(Self::new(compile_shaders!(
+            "assets/shaders/debug/gizmo.vert.glsl",
+            "assets/shaders/debug/gizmo.frag.glsl",
+            "assets/shaders/debug/gizmo.geom.glsl",
+        )?))
+    }
+
+    unsafe fn new(shader: Shader) -> Self {
+        let mut vao = 0;
+        gl::GenVertexArrays(1, &mut vao);
+        let mut vbo = 0;
+        gl::GenBuffers(1, &mut vbo);
+        gl::BindVertexArray(vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+        let size = size_of::<Point3<f32>>() as isize;
+        let local_position = point3(0_f32, 0_f32, 0_f32);
+        let data = &[local_position][0] as *const Point3<f32> as *const c_void;
+        gl::BufferData(gl::ARRAY_BUFFER, size, data, gl::STATIC_DRAW);
+        gl::EnableVertexAttribArray(0);
+        gl::VertexAttribPointer(
+            0,
+            3,
+            gl::FLOAT,
+            gl::FALSE,
+            size_of::<Point3<f32>>() as i32,
+            0 as *const c_void,
+        );
+        gl::BindVertexArray(0);
+        // TODO: We wanna draw a cube and we already have a GLSL helper for drawing cubes.
+        // However, we should start doing that on the CPU once instead of on the GPU every frame
+        // in the geometry shader. Will speed things up a lot.
+        // Not that relevant since it's debugging code.
+        Self { shader, vao }
+    }
+
+    /// `dimensions` are the half sizes of the box, only box gizmos have them
+    pub unsafe fn draw(
+        &self,
+        transform: &Transform,
+        color: Point3<f32>,
+        dimensions: Option<Vector3<f32>>,
+    ) {
+        self.shader.use_program();
+
+        self.shader
+            .set_mat4(c_str!("model"), &transform.get_model_matrix());
+
+        self.shader
+            .set_vec3(c_str!("color"), color.x, color.y, color.z);
+        if let Some(dimensions) = dimensions {
+            self.shader.set_vec3(
+                c_str!("dimensions"),
+                dimensions.x,
+                dimensions.y,
+                dimensions.z,
+            );
+        }
+
+        self.shader.validate();
+        gl::BindVertexArray(self.vao);
+        gl::DrawArrays(gl::POINTS, 0, 1);
+        gl::BindVertexArray(0);
+    }
+}
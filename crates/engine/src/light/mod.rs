@@ -1,7 +1,12 @@
-use cgmath::{point3, Matrix4, Point3};
+use cgmath::{Matrix4, Point3};
 use serde::Deserialize;
 
-use crate::prelude::{Aabb, Object, RenderGizmo, Textures, Transform, LIGHT_MAP_BUFFERS};
+use crate::prelude::{
+    Aabb, LightFramebuffer, Object, RenderGizmo, Shader, ShaderError, Textures, Transform,
+    LIGHT_MAP_BUFFERS,
+};
+
+mod gizmo;
 
 mod point;
 use point::PointLight;
@@ -9,25 +14,54 @@ use point::PointLight;
 mod spot;
 use spot::SpotLight;
 
+mod directional;
+use directional::DirectionalLight;
+
+/// Every light emits `color * intensity`. An intensity of 1 is the light that fully
+/// lights a white surface right in front of it, both directly and through photons
 #[derive(Debug, Deserialize, Clone)]
 pub enum Light {
     Point(PointLight),
     Spot(SpotLight),
+    Directional(DirectionalLight),
 }
 
 impl Light {
-    pub unsafe fn new_point(color: Point3<f32>, intensity: f32) -> Self {
-        Self::Point(PointLight::new(color, intensity))
+    pub unsafe fn new_point(color: Point3<f32>, intensity: f32) -> Result<Self, ShaderError> {
+        Ok(Self::Point(PointLight::new(color, intensity)?))
+    }
+
+    pub unsafe fn new_spot(
+        width: f32,
+        height: f32,
+        color: Point3<f32>,
+        intensity: f32,
+    ) -> Result<Self, ShaderError> {
+        Ok(Self::Spot(SpotLight::new(width, height, color, intensity)?))
     }
 
-    pub unsafe fn new_spot(width: f32, height: f32, color: Point3<f32>, intensity: f32) -> Self {
-        Self::Spot(SpotLight::new(width, height, color, intensity))
+    pub unsafe fn new_directional(color: Point3<f32>, intensity: f32) -> Result<Self, ShaderError> {
+        Ok(Self::Directional(DirectionalLight::new(color, intensity)?))
     }
 
-    pub fn get_projection_matrix(&self) -> Matrix4<f32> {
+    /// Compiles the shaders of the light and creates its gizmo and the framebuffer of its light view map.
+    /// Deserialized lights only have their parameters, so this has to be called once before they're used,
+    /// which `process_scene` does for the lights of a scene
+    pub unsafe fn init(&mut self) -> Result<(), ShaderError> {
+        match self {
+            Self::Point(point_light) => point_light.init(),
+            Self::Spot(spot_light) => spot_light.init(),
+            Self::Directional(directional_light) => directional_light.init(),
+        }
+    }
+
+    pub fn get_projection_matrix(&self, scene_aabb: &Aabb) -> Matrix4<f32> {
         match self {
             Self::Point(point_light) => point_light.get_projection_matrix(),
             Self::Spot(spot_light) => spot_light.get_projection_matrix(),
+            Self::Directional(directional_light) => {
+                directional_light.get_projection_matrix(scene_aabb)
+            }
         }
     }
 
@@ -35,6 +69,15 @@ impl Light {
         match self {
             Self::Point(point_light) => point_light.intensity,
             Self::Spot(spot_light) => spot_light.intensity,
+            Self::Directional(directional_light) => directional_light.intensity,
+        }
+    }
+
+    pub fn color(&self) -> Point3<f32> {
+        match self {
+            Self::Point(point_light) => point_light.color,
+            Self::Spot(spot_light) => spot_light.color,
+            Self::Directional(directional_light) => directional_light.color,
         }
     }
 
@@ -42,6 +85,7 @@ impl Light {
         match self {
             Self::Point(point_light) => &point_light.transform,
             Self::Spot(spot_light) => &spot_light.transform,
+            Self::Directional(directional_light) => &directional_light.transform,
         }
     }
 
@@ -49,13 +93,24 @@ impl Light {
         match self {
             Self::Point(point_light) => &mut point_light.transform,
             Self::Spot(spot_light) => &mut spot_light.transform,
+            Self::Directional(directional_light) => &mut directional_light.transform,
         }
     }
 
+    /// Only directional lights are infinitely far away, with parallel rays
     pub fn is_directional(&self) -> bool {
         match self {
-            Self::Point(_) => false,
-            Self::Spot(_) => true,
+            Self::Point(_) | Self::Spot(_) => false,
+            Self::Directional(_) => true,
+        }
+    }
+
+    /// Light view map of the last `take_photo`, still valid while the light and objects stay put
+    pub fn light_map_textures(&self) -> Textures<LIGHT_MAP_BUFFERS> {
+        match self {
+            Self::Point(point_light) => point_light.light_map_textures(),
+            Self::Spot(spot_light) => spot_light.light_map_textures(),
+            Self::Directional(directional_light) => directional_light.light_map_textures(),
         }
     }
 
@@ -70,21 +125,47 @@ impl Light {
                 point_light.take_photo(objects, scene_aabb, voxel_dimension)
             }
             Self::Spot(spot_light) => spot_light.take_photo(objects, scene_aabb, voxel_dimension),
+            Self::Directional(directional_light) => {
+                directional_light.take_photo(objects, scene_aabb, voxel_dimension)
+            }
         }
     }
 }
 
+/// Renders the positions of the `objects` the light sees with `shader`, which has its
+/// projection and view already set
+unsafe fn render_light_view_map(
+    framebuffer: &LightFramebuffer,
+    shader: &Shader,
+    objects: &mut [Object],
+    scene_aabb: &Aabb,
+) -> Textures<LIGHT_MAP_BUFFERS> {
+    gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.fbo());
+    gl::Enable(gl::DEPTH_TEST);
+    gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+    gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+    gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+    for object in objects.iter_mut() {
+        object.draw(shader, &scene_aabb.normalization_matrix());
+    }
+    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+    framebuffer.textures()
+}
+
+/// A white point light, without the OpenGL objects of `init`
 impl Default for Light {
     fn default() -> Self {
-        unsafe { Self::new_point(point3(1.0, 1.0, 1.0), 1_000_000.0) }
+        Self::Point(PointLight::default())
     }
 }
 
 impl RenderGizmo for Light {
-    unsafe fn draw_gizmo(&self, projection: &Matrix4<f32>, view: &Matrix4<f32>) {
+    unsafe fn draw_gizmo(&self) {
         match self {
-            Self::Point(point_light) => point_light.draw_gizmo(projection, view),
-            Self::Spot(spot_light) => spot_light.draw_gizmo(projection, view),
+            Self::Point(point_light) => point_light.draw_gizmo(),
+            Self::Spot(spot_light) => spot_light.draw_gizmo(),
+            Self::Directional(directional_light) => directional_light.draw_gizmo(),
         }
     }
 }
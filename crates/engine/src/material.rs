@@ -2,7 +2,7 @@ use c_str_macro::c_str;
 use cgmath::Vector3;
 use serde::Deserialize;
 
-use super::prelude::Shader;
+use super::prelude::{Shader, ShaderUniforms};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Material {
@@ -11,26 +11,18 @@ pub struct Material {
 }
 
 impl Material {
+    /// Sets the `material` uniform, if the shader has it
     pub unsafe fn set_uniforms(&self, shader: &Shader) {
-        self.properties.set_uniforms(shader);
+        if !shader.declares_uniform(c_str!("material")) {
+            return;
+        }
+        shader.set_bool(c_str!("hasMaterial"), true);
+        self.properties.set_uniforms("material", shader);
     }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, ShaderUniforms)]
 pub struct MaterialProperties {
     pub color: Vector3<f32>,
     pub specular: f32,
 }
-
-impl MaterialProperties {
-    pub unsafe fn set_uniforms(&self, shader: &Shader) {
-        shader.set_bool(c_str!("hasMaterial"), true);
-        shader.set_vec3(
-            c_str!("material.color"),
-            self.color.x,
-            self.color.y,
-            self.color.z,
-        );
-        shader.set_float(c_str!("material.specular"), self.specular);
-    }
-}
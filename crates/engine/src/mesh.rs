@@ -85,13 +85,13 @@ impl Mesh {
         let mut num_specular = 0;
         let mut num_normal = 0;
         let mut num_height = 0;
-        if self.textures.is_empty() {
-            shader.set_bool(c_str!("hasTexture"), false);
-        } else {
-            shader.set_bool(c_str!("hasTexture"), true);
+        if shader.declares_uniform(c_str!("hasTexture")) {
+            shader.set_bool(c_str!("hasTexture"), !self.textures.is_empty());
         }
-        if let Some(diffuse) = self.diffuse {
-            shader.set_bool(c_str!("hasDiffuse"), true);
+        // Set even when the mesh doesn't have them, or it would be drawn with the previous mesh's
+        if shader.declares_uniform(c_str!("hasDiffuse")) {
+            let diffuse = self.diffuse.unwrap_or_default();
+            shader.set_bool(c_str!("hasDiffuse"), self.diffuse.is_some());
             shader.set_vec3(
                 c_str!("materialDiffuse"),
                 diffuse[0],
@@ -99,8 +99,9 @@ impl Mesh {
                 diffuse[2],
             );
         }
-        if let Some(specular) = self.specular {
-            shader.set_bool(c_str!("hasMaterialSpecular"), true);
+        if shader.declares_uniform(c_str!("hasMaterialSpecular")) {
+            let specular = self.specular.unwrap_or_default();
+            shader.set_bool(c_str!("hasMaterialSpecular"), self.specular.is_some());
             shader.set_vec3(
                 c_str!("materialSpecular"),
                 specular[0],
@@ -108,6 +109,10 @@ impl Mesh {
                 specular[2],
             );
         }
+        let sets_texture_specular = shader.declares_uniform(c_str!("hasTextureSpecular"));
+        if sets_texture_specular {
+            shader.set_bool(c_str!("hasTextureSpecular"), false);
+        }
         for (i, texture) in self.textures.iter().enumerate() {
             gl::ActiveTexture(gl::TEXTURE0 + i as u32); // active proper texture unit before binding
                                                         // retrieve texture number (the N in diffuse_textureN)
@@ -118,7 +123,9 @@ impl Mesh {
                     num_diffuse
                 }
                 "texture_specular" => {
-                    shader.set_bool(c_str!("hasTextureSpecular"), true);
+                    if sets_texture_specular {
+                        shader.set_bool(c_str!("hasTextureSpecular"), true);
+                    }
                     num_specular += 1;
                     num_specular
                 }
@@ -144,6 +151,7 @@ impl Mesh {
         }
 
         // draw mesh
+        shader.validate();
         gl::BindVertexArray(self.vao);
         gl::DrawElements(
             gl::TRIANGLES,
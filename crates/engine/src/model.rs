@@ -1,5 +1,7 @@
+use std::fs;
+use std::io;
 use std::os::raw::c_void;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use cgmath::{vec2, vec3};
 use image::DynamicImage::*;
@@ -126,6 +128,39 @@ impl Model {
         self.aabb = aabb;
     }
 
+    /// Files loading the model at `path` reads: the .obj file, its .mtl files and the
+    /// textures of their materials
+    pub fn files(path: &str) -> io::Result<Vec<PathBuf>> {
+        let path = Path::new(path);
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut files = vec![path.to_path_buf()];
+        for line in fs::read_to_string(path)?.lines() {
+            let Some(material_library) = line.trim().strip_prefix("mtllib ") else {
+                continue;
+            };
+            // Like `tobj`, material libraries are relative to the .obj file
+            let material_library = directory.join(material_library.trim());
+            let (materials, _) = tobj::load_mtl(&material_library)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+            files.push(material_library);
+            // And textures are relative to the current directory, like in `load_material_texture`
+            for material in materials {
+                let textures = [
+                    material.diffuse_texture,
+                    material.specular_texture,
+                    material.normal_texture,
+                ];
+                for texture in textures.into_iter().filter(|texture| !texture.is_empty()) {
+                    let texture = PathBuf::from(texture);
+                    if !files.contains(&texture) {
+                        files.push(texture);
+                    }
+                }
+            }
+        }
+        Ok(files)
+    }
+
     fn load_material_texture(&mut self, path: &str, type_name: &str) -> Texture {
         {
             let texture = self.textures_loaded.iter().find(|t| t.path == path);
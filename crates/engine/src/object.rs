@@ -11,6 +11,10 @@ pub struct Object {
     model: AssetHandle,
     material: AssetHandle,
     pub transform: Transform,
+    /// Dynamic objects can move at runtime, they are voxelized apart from the static ones
+    /// so only they have to be voxelized again when they do
+    #[serde(default)]
+    pub dynamic: bool,
     #[serde(skip_deserializing)]
     actual_model: Option<&'static Model>,
     #[serde(skip_deserializing)]
@@ -23,6 +27,7 @@ impl Object {
             model: model_handle,
             material: material_handle,
             transform,
+            dynamic: false,
             actual_model: None,
             actual_material: None,
         }
@@ -34,7 +39,9 @@ impl Object {
             // Transform's model matrix
             shader.set_mat4(c_str!("model"), &self.transform.get_model_matrix());
             shader.set_mat4(c_str!("modelNormalizationMatrix"), model_normalization_matrix);
-            shader.set_mat3(c_str!("normalMatrix"), &self.transform.get_normal_matrix());
+            if shader.declares_uniform(c_str!("normalMatrix")) {
+                shader.set_mat3(c_str!("normalMatrix"), &self.transform.get_normal_matrix());
+            }
             // Material properties
             if let Some(material) = self.material() {
                 material.set_uniforms(shader);
@@ -0,0 +1,696 @@
+//! Preprocessing done on shader files before the driver compiles them.
+//!
+//! - `#include "path"` is replaced by the processed file, recursively. Paths starting with
+//!   `./` or `../` are relative to the file including them, the rest to the current directory.
+//! - `#pragma once` makes the next includes of a file do nothing, until the next stage of a
+//!   file with several starts at a `#shader` line.
+//! - `#if NAME`, `#else` and `#endif` keep or drop code when the condition `NAME` is set,
+//!   they can be nested. Other conditionals like `#ifdef` are left for the driver.
+//!
+//! Every line of the result remembers where it came from, so driver logs can point to the
+//! original file and line instead of the one in the flattened source.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::ffi::CString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Processed shader code with the file and line each of its lines came from
+#[derive(Debug, Clone, Default)]
+pub struct Source {
+    lines: Vec<String>,
+    line_map: LineMap,
+}
+
+/// Original location of each line of a `Source`
+#[derive(Debug, Clone, Default)]
+pub struct LineMap {
+    files: Vec<PathBuf>,
+    /// Index in `files` and line in that file, starting at 1
+    locations: Vec<(usize, usize)>,
+}
+
+#[derive(Debug)]
+pub enum PreprocessError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// The files including each other, the first and last one are the same
+    IncludeCycle(Vec<PathBuf>),
+    MalformedInclude {
+        path: PathBuf,
+        line: usize,
+    },
+    /// `#else` or `#endif` without `#if`
+    UnmatchedDirective {
+        path: PathBuf,
+        line: usize,
+        directive: String,
+    },
+    /// `#if` without `#endif` at the end of the file
+    UnterminatedIf {
+        path: PathBuf,
+        line: usize,
+    },
+    /// Only `#if NAME` and `#else` are supported in conditionals handled by the preprocessor
+    UnsupportedDirective {
+        path: PathBuf,
+        line: usize,
+        directive: String,
+    },
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "Failed to read {}: {error}", path.display()),
+            Self::IncludeCycle(paths) => {
+                let paths: Vec<_> = paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                write!(f, "Include cycle: {}", paths.join(" includes "))
+            }
+            Self::MalformedInclude { path, line } => {
+                write!(f, "{}:{line}: expected #include \"path\"", path.display())
+            }
+            Self::UnmatchedDirective {
+                path,
+                line,
+                directive,
+            } => {
+                write!(f, "{}:{line}: #{directive} without #if", path.display())
+            }
+            Self::UnterminatedIf { path, line } => {
+                write!(f, "{}:{line}: #if without #endif", path.display())
+            }
+            Self::UnsupportedDirective {
+                path,
+                line,
+                directive,
+            } => write!(
+                f,
+                "{}:{line}: #{directive} is not supported inside #if NAME",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl Error for PreprocessError {}
+
+/// Processes shader files, `is_set` tells whether the condition of `#if NAME` is set
+pub struct Preprocessor<F: Fn(&str) -> bool> {
+    is_set: F,
+}
+
+/// Conditional opened in the file being processed
+enum Conditional {
+    /// `#if NAME`, removed from the source
+    Handled {
+        is_set: bool,
+        in_else: bool,
+        line: usize,
+    },
+    /// Any other conditional, left for the driver
+    Driver,
+}
+
+impl Conditional {
+    fn keeps_code(&self) -> bool {
+        match self {
+            Self::Handled {
+                is_set, in_else, ..
+            } => is_set != in_else,
+            Self::Driver => true,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    source: Source,
+    /// Files being included, to detect cycles
+    include_stack: Vec<PathBuf>,
+    /// Files with `#pragma once` that were already included
+    included_once: HashSet<PathBuf>,
+}
+
+impl<F: Fn(&str) -> bool> Preprocessor<F> {
+    pub fn new(is_set: F) -> Self {
+        Self { is_set }
+    }
+
+    pub fn process(&self, path: &Path) -> Result<Source, PreprocessError> {
+        let mut state = State::default();
+        self.include(path, &mut state)?;
+        Ok(state.source)
+    }
+
+    fn include(&self, path: &Path, state: &mut State) -> Result<(), PreprocessError> {
+        let io_error = |error| PreprocessError::Io {
+            path: path.to_path_buf(),
+            error,
+        };
+        let canonical_path = path.canonicalize().map_err(io_error)?;
+        if state.included_once.contains(&canonical_path) {
+            return Ok(());
+        }
+        if state.include_stack.contains(&canonical_path) {
+            let mut cycle = state.include_stack.clone();
+            cycle.push(canonical_path);
+            return Err(PreprocessError::IncludeCycle(cycle));
+        }
+        let code = fs::read_to_string(path).map_err(io_error)?;
+
+        let file = state.source.line_map.add_file(path);
+        state.include_stack.push(canonical_path.clone());
+
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        for (index, line) in code.lines().enumerate() {
+            let line_number = index + 1;
+            let keeps_code = conditionals.iter().all(Conditional::keeps_code);
+            let unmatched = |directive: &str| PreprocessError::UnmatchedDirective {
+                path: path.to_path_buf(),
+                line: line_number,
+                directive: directive.to_string(),
+            };
+
+            let Some((directive, argument)) = parse_directive(line) else {
+                if keeps_code {
+                    state.source.push(line, file, line_number);
+                }
+                continue;
+            };
+            match directive {
+                "if" if is_identifier(argument) => {
+                    conditionals.push(Conditional::Handled {
+                        is_set: (self.is_set)(argument),
+                        in_else: false,
+                        line: line_number,
+                    });
+                    continue;
+                }
+                "if" | "ifdef" | "ifndef" => conditionals.push(Conditional::Driver),
+                "else" | "elif" => match conditionals.last_mut() {
+                    Some(Conditional::Handled { in_else, .. })
+                        if directive == "else" && !*in_else =>
+                    {
+                        *in_else = true;
+                        continue;
+                    }
+                    Some(Conditional::Handled { .. }) => {
+                        return Err(PreprocessError::UnsupportedDirective {
+                            path: path.to_path_buf(),
+                            line: line_number,
+                            directive: directive.to_string(),
+                        })
+                    }
+                    Some(Conditional::Driver) => {}
+                    None => return Err(unmatched(directive)),
+                },
+                "endif" => match conditionals.pop() {
+                    Some(Conditional::Handled { .. }) => continue,
+                    Some(Conditional::Driver) => {}
+                    None => return Err(unmatched(directive)),
+                },
+                _ if !keeps_code => continue,
+                "include" => {
+                    let included_path = parse_include(argument)
+                        .map(|included_path| resolve_include(path, included_path))
+                        .ok_or_else(|| PreprocessError::MalformedInclude {
+                            path: path.to_path_buf(),
+                            line: line_number,
+                        })?;
+                    self.include(&included_path, state)?;
+                    continue;
+                }
+                "pragma" if argument == "once" => {
+                    state.included_once.insert(canonical_path.clone());
+                    continue;
+                }
+                // Each stage is compiled on its own, so it needs its own includes
+                "shader" => state.included_once.clear(),
+                _ => {}
+            }
+
+            if keeps_code {
+                state.source.push(line, file, line_number);
+            }
+        }
+
+        let unterminated_if = conditionals
+            .iter()
+            .find_map(|conditional| match conditional {
+                Conditional::Handled { line, .. } => Some(*line),
+                Conditional::Driver => None,
+            });
+        if let Some(line) = unterminated_if {
+            return Err(PreprocessError::UnterminatedIf {
+                path: path.to_path_buf(),
+                line,
+            });
+        }
+
+        state.include_stack.pop();
+        Ok(())
+    }
+}
+
+/// Name and argument of a directive line like `#include "file"`
+fn parse_directive(line: &str) -> Option<(&str, &str)> {
+    let directive = line.trim_start().strip_prefix('#')?.trim_start();
+    let name_end = directive
+        .find(|character: char| character.is_whitespace())
+        .unwrap_or(directive.len());
+    let (name, argument) = directive.split_at(name_end);
+    Some((name, argument.trim()))
+}
+
+fn parse_include(argument: &str) -> Option<&str> {
+    argument.strip_prefix('"')?.strip_suffix('"')
+}
+
+fn is_identifier(argument: &str) -> bool {
+    let mut characters = argument.chars();
+    matches!(characters.next(), Some(first) if first.is_ascii_alphabetic() || first == '_')
+        && characters.all(|character| character.is_ascii_alphanumeric() || character == '_')
+}
+
+fn resolve_include(including_path: &Path, included_path: &str) -> PathBuf {
+    if included_path.starts_with("./") || included_path.starts_with("../") {
+        let directory = including_path.parent().unwrap_or(Path::new(""));
+        directory.join(included_path.strip_prefix("./").unwrap_or(included_path))
+    } else {
+        PathBuf::from(included_path)
+    }
+}
+
+impl Source {
+    /// Code that isn't read from a file, `name` is shown in logs instead of a path
+    pub fn from_string(name: &str, code: &str) -> Self {
+        let mut source = Self::default();
+        let file = source.line_map.add_file(Path::new(name));
+        for (index, line) in code.lines().enumerate() {
+            source.push(line, file, index + 1);
+        }
+        source
+    }
+
+    pub fn code(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    pub fn to_c_string(&self) -> CString {
+        CString::new(self.code()).unwrap()
+    }
+
+    pub fn line_map(&self) -> &LineMap {
+        &self.line_map
+    }
+
+    /// Inserts `lines` after every `#version` line, they are mapped to the `#version` line
+    pub fn insert_after_version(&mut self, lines: &[String]) {
+        if lines.is_empty() {
+            return;
+        }
+
+        let mut source = self.without_lines();
+        for (line, &(file, line_number)) in self.lines.iter().zip(&self.line_map.locations) {
+            source.push(line, file, line_number);
+            if line.trim_start().starts_with("#version") {
+                for inserted_line in lines {
+                    source.push(inserted_line, file, line_number);
+                }
+            }
+        }
+        *self = source;
+    }
+
+    /// Splits a file with several stages at its `directive` lines, like `#shader vertex`,
+    /// into the source of each stage by the name after the directive.
+    /// Lines before the first directive go to `first_stage`
+    pub fn split_stages(&self, directive: &str, first_stage: &str) -> Vec<(String, Source)> {
+        let mut stages: Vec<(String, Source)> = Vec::new();
+        let mut current_stage = first_stage.to_string();
+        for (line, &(file, line_number)) in self.lines.iter().zip(&self.line_map.locations) {
+            if let Some(stage) = line.trim_start().strip_prefix(directive) {
+                current_stage = stage.trim().to_string();
+                continue;
+            }
+            let index = match stages.iter().position(|(stage, _)| *stage == current_stage) {
+                Some(index) => index,
+                None => {
+                    stages.push((current_stage.clone(), self.without_lines()));
+                    stages.len() - 1
+                }
+            };
+            stages[index].1.push(line, file, line_number);
+        }
+        stages
+    }
+
+    fn without_lines(&self) -> Self {
+        Self {
+            lines: Vec::new(),
+            line_map: LineMap {
+                files: self.line_map.files.clone(),
+                locations: Vec::new(),
+            },
+        }
+    }
+
+    fn push(&mut self, line: &str, file: usize, line_number: usize) {
+        self.lines.push(line.to_string());
+        self.line_map.locations.push((file, line_number));
+    }
+}
+
+impl LineMap {
+    /// Every file the source was processed from, in the order they were first included
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// File and line a line of the processed source came from, lines start at 1
+    pub fn location(&self, line: usize) -> Option<(&Path, usize)> {
+        let &(file, line_number) = self.locations.get(line.checked_sub(1)?)?;
+        Some((&self.files[file], line_number))
+    }
+
+    /// Original file and line of the first error in a driver's compile log, see `rewrite_log`.
+    /// The first location of any kind if no line says it's an error
+    pub fn error_location(&self, log: &str) -> Option<(&Path, usize)> {
+        let location = |line: &str| {
+            let (_, _, line_number) = find_log_location(line)?;
+            self.location(line_number)
+        };
+        log.lines()
+            .filter(|line| line.to_lowercase().contains("error"))
+            .find_map(location)
+            .or_else(|| log.lines().find_map(location))
+    }
+
+    /// Replaces the locations in a driver's compile log with the original file and line.
+    /// Knows the formats of Mesa (`0:12(5): error`), NVIDIA (`0(12) : error`)
+    /// and AMD or Intel (`ERROR: 0:12: ...`), leaves other lines as they are
+    pub fn rewrite_log(&self, log: &str) -> String {
+        log.lines()
+            .map(|line| {
+                let Some((start, end, line_number)) = find_log_location(line) else {
+                    return line.to_string();
+                };
+                match self.location(line_number) {
+                    Some((path, original_line)) => format!(
+                        "{}{}:{original_line}{}",
+                        &line[..start],
+                        path.display(),
+                        &line[end..]
+                    ),
+                    None => line.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn add_file(&mut self, path: &Path) -> usize {
+        match self.files.iter().position(|file| file == path) {
+            Some(index) => index,
+            None => {
+                self.files.push(path.to_path_buf());
+                self.files.len() - 1
+            }
+        }
+    }
+}
+
+/// Start and end of the `source:line` part of a log line and its line
+fn find_log_location(log_line: &str) -> Option<(usize, usize, usize)> {
+    let start = ["ERROR: ", "WARNING: "]
+        .iter()
+        .find_map(|prefix| log_line.strip_prefix(prefix).map(|_| prefix.len()))
+        .unwrap_or(0);
+    let rest = &log_line[start..];
+
+    let source_digits = rest.find(|character: char| !character.is_ascii_digit())?;
+    if source_digits == 0 {
+        return None;
+    }
+    let after_source = &rest[source_digits..];
+    let (line_start, closing) = if after_source.starts_with(':') {
+        (source_digits + 1, "")
+    } else if after_source.starts_with('(') {
+        (source_digits + 1, ")")
+    } else {
+        return None;
+    };
+
+    let line_digits = rest[line_start..]
+        .find(|character: char| !character.is_ascii_digit())
+        .unwrap_or(rest.len() - line_start);
+    let line_number = rest[line_start..line_start + line_digits].parse().ok()?;
+    let end = line_start + line_digits;
+    if !rest[end..].starts_with(closing) {
+        return None;
+    }
+    Some((start, start + end + closing.len(), line_number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// Writes `files` in a new folder for the test, returns the folder
+    fn write_files(test_name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let folder = env::temp_dir()
+            .join("vct_preprocessor_test")
+            .join(test_name);
+        let _ = fs::remove_dir_all(&folder);
+        for (path, code) in files {
+            let path = folder.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, code).unwrap();
+        }
+        folder
+    }
+
+    fn process(folder: &Path, file: &str, conditions: &[&str]) -> Result<Source, PreprocessError> {
+        Preprocessor::new(|name: &str| conditions.contains(&name)).process(&folder.join(file))
+    }
+
+    #[test]
+    fn nested_includes_are_relative_to_the_file_including_them() {
+        let folder = write_files(
+            "nested_includes",
+            &[
+                (
+                    "main.glsl",
+                    "#version 460 core\n#include \"./lib/a.glsl\"\nvoid main() {}",
+                ),
+                ("lib/a.glsl", "#include \"./b.glsl\"\nfloat a;"),
+                ("lib/b.glsl", "float b;"),
+            ],
+        );
+        let source = process(&folder, "main.glsl", &[]).unwrap();
+        assert_eq!(
+            source.code(),
+            "#version 460 core\nfloat b;\nfloat a;\nvoid main() {}"
+        );
+    }
+
+    #[test]
+    fn pragma_once_includes_a_file_once() {
+        let folder = write_files(
+            "pragma_once",
+            &[
+                (
+                    "main.glsl",
+                    "#include \"./a.glsl\"\n#include \"./b.glsl\"\n#include \"./a.glsl\"",
+                ),
+                ("a.glsl", "#pragma once\nfloat a;"),
+                ("b.glsl", "#include \"./a.glsl\"\nfloat b;"),
+            ],
+        );
+        let source = process(&folder, "main.glsl", &[]).unwrap();
+        assert_eq!(source.code(), "float a;\nfloat b;");
+    }
+
+    #[test]
+    fn pragma_once_includes_a_file_once_per_stage() {
+        let folder = write_files(
+            "pragma_once_per_stage",
+            &[
+                (
+                    "main.glsl",
+                    "#shader vertex\n#include \"./a.glsl\"\n#shader fragment\n#include \"./a.glsl\"",
+                ),
+                ("a.glsl", "#pragma once\nfloat a;"),
+            ],
+        );
+        let source = process(&folder, "main.glsl", &[]).unwrap();
+        assert_eq!(
+            source.code(),
+            "#shader vertex\nfloat a;\n#shader fragment\nfloat a;"
+        );
+    }
+
+    #[test]
+    fn include_cycles_are_errors() {
+        let folder = write_files(
+            "include_cycle",
+            &[
+                ("a.glsl", "#include \"./b.glsl\""),
+                ("b.glsl", "#include \"./a.glsl\""),
+            ],
+        );
+        match process(&folder, "a.glsl", &[]) {
+            Err(PreprocessError::IncludeCycle(cycle)) => {
+                assert_eq!(cycle.len(), 3);
+                assert_eq!(cycle.first(), cycle.last());
+            }
+            result => panic!("Expected an include cycle, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn nested_conditionals_keep_the_code_of_set_conditions() {
+        let code = "\
+            #if debug\n\
+            float debug;\n\
+            #if verbose\n\
+            float verbose;\n\
+            #else\n\
+            float quiet;\n\
+            #endif\n\
+            #else\n\
+            float release;\n\
+            #endif";
+        let folder = write_files("nested_conditionals", &[("main.glsl", code)]);
+
+        let source = process(&folder, "main.glsl", &["debug"]).unwrap();
+        assert_eq!(source.code(), "float debug;\nfloat quiet;");
+        let source = process(&folder, "main.glsl", &["debug", "verbose"]).unwrap();
+        assert_eq!(source.code(), "float debug;\nfloat verbose;");
+        let source = process(&folder, "main.glsl", &["verbose"]).unwrap();
+        assert_eq!(source.code(), "float release;");
+    }
+
+    #[test]
+    fn driver_conditionals_are_left_as_they_are() {
+        let code = "\
+            #ifdef DEBUG\n\
+            #if debug\n\
+            float debug;\n\
+            #endif\n\
+            #else\n\
+            float release;\n\
+            #endif";
+        let folder = write_files("driver_conditionals", &[("main.glsl", code)]);
+        let source = process(&folder, "main.glsl", &[]).unwrap();
+        assert_eq!(source.code(), "#ifdef DEBUG\n#else\nfloat release;\n#endif");
+    }
+
+    #[test]
+    fn unmatched_conditionals_are_errors() {
+        let folder = write_files(
+            "unmatched_conditionals",
+            &[
+                ("endif.glsl", "float a;\n#endif"),
+                ("if.glsl", "#if debug\nfloat a;"),
+            ],
+        );
+        assert!(matches!(
+            process(&folder, "endif.glsl", &[]),
+            Err(PreprocessError::UnmatchedDirective { line: 2, .. })
+        ));
+        assert!(matches!(
+            process(&folder, "if.glsl", &[]),
+            Err(PreprocessError::UnterminatedIf { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn lines_map_to_their_original_file() {
+        let folder = write_files(
+            "line_map",
+            &[
+                ("main.glsl", "#version 460 core\n#include \"./a.glsl\"\n#if debug\nfloat b;\n#endif\nfloat c;"),
+                ("a.glsl", "// a\nfloat a;"),
+            ],
+        );
+        let mut source = process(&folder, "main.glsl", &[]).unwrap();
+        source.insert_after_version(&[String::from("#define A")]);
+        let main = folder.join("main.glsl");
+        let a = folder.join("a.glsl");
+
+        let line_map = source.line_map();
+        assert_eq!(line_map.location(1), Some((main.as_path(), 1)));
+        assert_eq!(line_map.location(2), Some((main.as_path(), 1)));
+        assert_eq!(line_map.location(4), Some((a.as_path(), 2)));
+        assert_eq!(line_map.location(5), Some((main.as_path(), 6)));
+        assert_eq!(line_map.location(6), None);
+    }
+
+    #[test]
+    fn logs_point_to_the_original_lines() {
+        let mut source = Source::from_string("main.glsl", "#version 460 core\nvoid main() {}");
+        source.insert_after_version(&[String::from("#define A")]);
+        let line_map = source.line_map();
+
+        assert_eq!(
+            line_map.rewrite_log("0:3(5): error: syntax error"),
+            "main.glsl:2(5): error: syntax error"
+        );
+        assert_eq!(
+            line_map.rewrite_log("0(3) : error C0000: syntax error"),
+            "main.glsl:2 : error C0000: syntax error"
+        );
+        assert_eq!(
+            line_map.rewrite_log("ERROR: 0:3: syntax error"),
+            "ERROR: main.glsl:2: syntax error"
+        );
+        assert_eq!(
+            line_map.rewrite_log("error: linking failed"),
+            "error: linking failed"
+        );
+    }
+
+    #[test]
+    fn error_location_skips_warnings() {
+        let source = Source::from_string("main.glsl", "#version 460 core\nuint a;\nvoid main() {}");
+        let line_map = source.line_map();
+
+        let log = "0:2(1): warning: implicit conversion\n0:3(1): error: syntax error";
+        assert_eq!(
+            line_map.error_location(log),
+            Some((Path::new("main.glsl"), 3))
+        );
+        assert_eq!(
+            line_map.error_location("0:2(1): warning: implicit conversion"),
+            Some((Path::new("main.glsl"), 2))
+        );
+        assert_eq!(line_map.error_location("error: linking failed"), None);
+    }
+
+    #[test]
+    fn stages_keep_their_line_map() {
+        let source = Source::from_string(
+            "shader.glsl",
+            "#shader vertex\nfloat v;\n#shader fragment\nfloat f;",
+        );
+        let stages = source.split_stages("#shader ", "vertex");
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[1].0, "fragment");
+        assert_eq!(stages[1].1.code(), "float f;");
+        assert_eq!(
+            stages[1].1.line_map().location(1),
+            Some((Path::new("shader.glsl"), 4))
+        );
+    }
+}
@@ -0,0 +1,340 @@
+//! GPU profiler based on `GL_TIME_ELAPSED` queries.
+//!
+//! Passes are timed with [`scope`] inside the innermost open section, like a frame or an
+//! octree build, see [`begin_section`]. Each section keeps the timings of its passes in the
+//! last run and added up over every run, to average them.
+//! Results are read when the GPU has them, without waiting for it, so a run shows up
+//! a frame or more after it ends. [`finish`] waits for the ones still missing.
+//! Nothing is timed until it's enabled with [`set_enabled`].
+
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use gl::types::{GLuint, GLuint64};
+use serde::Serialize;
+
+thread_local! {
+    static PROFILER: RefCell<Profiler> = RefCell::new(Profiler::default());
+}
+
+/// GPU time of a pass, added up over every time it ran
+#[derive(Debug, Clone, Serialize)]
+pub struct PassTimings {
+    pub name: &'static str,
+    pub calls: u32,
+    pub milliseconds: f64,
+}
+
+/// Timings of the passes run inside a section
+#[derive(Debug, Clone, Serialize)]
+pub struct SectionTimings {
+    pub name: &'static str,
+    pub runs: u32,
+    /// Passes of the last run, in the order they first ran
+    pub last_run: Vec<PassTimings>,
+    /// Passes of every run, added up
+    pub all_runs: Vec<PassTimings>,
+}
+
+impl SectionTimings {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            runs: 0,
+            last_run: Vec::new(),
+            all_runs: Vec::new(),
+        }
+    }
+
+    /// GPU time of the last run
+    pub fn last_run_milliseconds(&self) -> f64 {
+        self.last_run.iter().map(|pass| pass.milliseconds).sum()
+    }
+
+    /// Average GPU time of a run
+    pub fn average_milliseconds(&self) -> f64 {
+        if self.runs == 0 {
+            return 0.0;
+        }
+        self.all_runs
+            .iter()
+            .map(|pass| pass.milliseconds)
+            .sum::<f64>()
+            / self.runs as f64
+    }
+}
+
+/// Times the GPU work of a pass until it's dropped
+#[must_use = "the pass is only timed until the timer is dropped"]
+pub struct GpuTimer {
+    /// `None` if this timer isn't timing anything
+    query: Option<PendingQuery>,
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        let Some(query) = self.query.take() else {
+            return;
+        };
+        unsafe { gl::EndQuery(gl::TIME_ELAPSED) };
+        PROFILER.with(|profiler| {
+            let mut profiler = profiler.borrow_mut();
+            profiler.is_timing = false;
+            profiler.pending_queries.push(query);
+        });
+    }
+}
+
+#[derive(Default)]
+struct Profiler {
+    is_enabled: bool,
+    sections: Vec<SectionTimings>,
+    /// Indices in `sections` of the open sections, innermost last.
+    /// `None` for sections opened while disabled
+    open_sections: Vec<Option<usize>>,
+    /// Only one `GL_TIME_ELAPSED` query can run at a time
+    is_timing: bool,
+    pending_queries: Vec<PendingQuery>,
+    /// Runs that ended but whose results the GPU may not have yet, oldest first
+    ended_runs: Vec<EndedRun>,
+    free_queries: Vec<GLuint>,
+}
+
+struct EndedRun {
+    section: usize,
+    queries: Vec<PendingQuery>,
+}
+
+struct PendingQuery {
+    query: GLuint,
+    section: usize,
+    pass: &'static str,
+}
+
+pub fn set_enabled(is_enabled: bool) {
+    PROFILER.with(|profiler| profiler.borrow_mut().is_enabled = is_enabled);
+}
+
+pub fn is_enabled() -> bool {
+    PROFILER.with(|profiler| profiler.borrow().is_enabled)
+}
+
+/// Starts a new run of the section called `name`, where the following passes are timed.
+/// Sections can be nested, passes only count in the innermost one
+pub fn begin_section(name: &'static str) {
+    PROFILER.with(|profiler| {
+        let mut profiler = profiler.borrow_mut();
+        if !profiler.is_enabled {
+            profiler.open_sections.push(None);
+            return;
+        }
+
+        let index = match profiler
+            .sections
+            .iter()
+            .position(|section| section.name == name)
+        {
+            Some(index) => index,
+            None => {
+                profiler.sections.push(SectionTimings::new(name));
+                profiler.sections.len() - 1
+            }
+        };
+        profiler.open_sections.push(Some(index));
+    });
+}
+
+/// Ends the innermost section. Its results are read once the GPU has them,
+/// along with the ones of previous runs it has by now
+pub unsafe fn end_section() {
+    PROFILER.with(|profiler| {
+        let mut profiler = profiler.borrow_mut();
+        let Some(Some(index)) = profiler.open_sections.pop() else {
+            return;
+        };
+
+        let (queries, pending_queries) = profiler
+            .pending_queries
+            .drain(..)
+            .partition::<Vec<_>, _>(|query| query.section == index);
+        profiler.pending_queries = pending_queries;
+        profiler.ended_runs.push(EndedRun {
+            section: index,
+            queries,
+        });
+        profiler.read_results(false);
+    });
+}
+
+/// Waits for the GPU to finish every run that ended and reads their results,
+/// for example before writing them
+pub unsafe fn finish() {
+    PROFILER.with(|profiler| profiler.borrow_mut().read_results(true));
+}
+
+/// Times the GPU work of `pass` until the returned timer is dropped.
+/// Does nothing if disabled, outside of a section or inside another scope,
+/// since `GL_TIME_ELAPSED` queries can't be nested
+pub unsafe fn scope(pass: &'static str) -> GpuTimer {
+    PROFILER.with(|profiler| {
+        let mut profiler = profiler.borrow_mut();
+        let Some(&Some(section)) = profiler.open_sections.last() else {
+            return GpuTimer { query: None };
+        };
+        if !profiler.is_enabled || profiler.is_timing {
+            return GpuTimer { query: None };
+        }
+
+        let query = profiler.free_queries.pop().unwrap_or_else(|| {
+            let mut query = 0;
+            gl::GenQueries(1, &mut query);
+            query
+        });
+        gl::BeginQuery(gl::TIME_ELAPSED, query);
+        profiler.is_timing = true;
+
+        GpuTimer {
+            query: Some(PendingQuery {
+                query,
+                section,
+                pass,
+            }),
+        }
+    })
+}
+
+/// Timings of every section that ran while enabled
+pub fn sections() -> Vec<SectionTimings> {
+    PROFILER.with(|profiler| profiler.borrow().sections.clone())
+}
+
+/// Writes the timings of every section to `path` as JSON
+pub fn write_json(path: impl AsRef<Path>) -> io::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, &sections()).map_err(io::Error::from)
+}
+
+/// Writes the timings of every section to `path` as CSV, one row per pass of each section
+pub fn write_csv(path: impl AsRef<Path>) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "section,runs,pass,calls,total_ms,average_ms,last_run_ms"
+    )?;
+    for section in sections().iter() {
+        for pass in section.all_runs.iter() {
+            let last_run_milliseconds = section
+                .last_run
+                .iter()
+                .find(|last_pass| last_pass.name == pass.name)
+                .map_or(0.0, |last_pass| last_pass.milliseconds);
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                section.name,
+                section.runs,
+                pass.name,
+                pass.calls,
+                pass.milliseconds,
+                pass.milliseconds / section.runs as f64,
+                last_run_milliseconds,
+            )?;
+        }
+    }
+    writer.flush()
+}
+
+impl Profiler {
+    /// Adds the results of the ended runs the GPU has finished to their sections, oldest first.
+    /// With `wait`, waits for all of them
+    unsafe fn read_results(&mut self, wait: bool) {
+        while let Some(run) = self.ended_runs.first() {
+            // Queries finish in order, if the last one has its result the others too
+            if let (false, Some(last_query)) = (wait, run.queries.last()) {
+                let mut is_available = 0;
+                gl::GetQueryObjectuiv(
+                    last_query.query,
+                    gl::QUERY_RESULT_AVAILABLE,
+                    &mut is_available,
+                );
+                if is_available == gl::FALSE as GLuint {
+                    return;
+                }
+            }
+
+            let run = self.ended_runs.remove(0);
+            let section = &mut self.sections[run.section];
+            section.runs += 1;
+            section.last_run.clear();
+            for query in run.queries.iter() {
+                let mut nanoseconds: GLuint64 = 0;
+                gl::GetQueryObjectui64v(query.query, gl::QUERY_RESULT, &mut nanoseconds);
+                let milliseconds = nanoseconds as f64 / 1_000_000.0;
+                add_pass(&mut section.last_run, query.pass, milliseconds);
+                add_pass(&mut section.all_runs, query.pass, milliseconds);
+            }
+            self.free_queries
+                .extend(run.queries.into_iter().map(|query| query.query));
+        }
+    }
+}
+
+fn add_pass(passes: &mut Vec<PassTimings>, name: &'static str, milliseconds: f64) {
+    match passes.iter_mut().find(|pass| pass.name == name) {
+        Some(pass) => {
+            pass.calls += 1;
+            pass.milliseconds += milliseconds;
+        }
+        None => passes.push(PassTimings {
+            name,
+            calls: 1,
+            milliseconds,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_pass_adds_up_calls_with_the_same_name() {
+        let mut passes = Vec::new();
+        add_pass(&mut passes, "Mipmap", 1.5);
+        add_pass(&mut passes, "Store photons", 2.0);
+        add_pass(&mut passes, "Mipmap", 0.5);
+
+        assert_eq!(passes.len(), 2);
+        assert_eq!(passes[0].name, "Mipmap");
+        assert_eq!(passes[0].calls, 2);
+        assert_eq!(passes[0].milliseconds, 2.0);
+        assert_eq!(passes[1].name, "Store photons");
+    }
+
+    #[test]
+    fn sections_opened_while_disabled_time_nothing() {
+        set_enabled(false);
+        begin_section("Frame");
+        let timer = unsafe { scope("Cone tracing") };
+        assert!(timer.query.is_none());
+        drop(timer);
+        unsafe { end_section() };
+        assert!(sections().is_empty());
+    }
+
+    #[test]
+    fn average_of_a_section_is_per_run() {
+        let mut section = SectionTimings::new("Frame");
+        section.runs = 2;
+        add_pass(&mut section.all_runs, "Cone tracing", 3.0);
+        add_pass(&mut section.all_runs, "Cone tracing", 5.0);
+        add_pass(&mut section.last_run, "Cone tracing", 5.0);
+
+        assert_eq!(section.average_milliseconds(), 4.0);
+        assert_eq!(section.last_run_milliseconds(), 5.0);
+    }
+}
@@ -2,7 +2,7 @@ use std::{ffi::c_void, mem::size_of};
 
 use gl::types::GLuint;
 
-use super::shader::{Shader, compile_shaders};
+use super::shader::{Shader, ShaderError, compile_shaders};
 
 /// Quad to display textures flat on-screen.
 pub struct Quad {
@@ -15,7 +15,7 @@ pub struct Quad {
 }
 
 impl Quad {
-    pub unsafe fn new() -> Self {
+    pub unsafe fn new() -> Result<Self, ShaderError> {
         let mut vao = 0;
         gl::GenVertexArrays(1, &mut vao);
         gl::BindVertexArray(vao);
@@ -72,13 +72,13 @@ impl Quad {
             (3 * size_of::<f32>()) as *const c_void,
         );
 
-        let shader = compile_shaders!("assets/shaders/renderQuad.glsl");
+        let shader = compile_shaders!("assets/shaders/renderQuad.glsl")?;
 
-        Self {
+        Ok(Self {
             vao,
             indices: indices.to_vec(),
             shader,
-        }
+        })
     }
 
     pub unsafe fn get_vao(&self) -> GLuint {
@@ -96,6 +96,7 @@ impl Quad {
         gl::BindTexture(gl::TEXTURE_2D, texture);
         gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
         gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+        self.shader.validate();
         gl::BindVertexArray(self.vao);
         gl::DrawElements(
             gl::TRIANGLES,
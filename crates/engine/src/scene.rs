@@ -1,10 +1,11 @@
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 
 use crate::prelude::{
-    Object, Light, Material, MaterialProperties, Model, AssetRegistry,
+    Object, Light, Material, MaterialProperties, Model, AssetRegistry, ShaderError,
 };
 
 #[derive(Deserialize)]
+#[serde(from = "SceneFile")]
 pub struct Scene {
     /// Objects in the scene
     pub objects: Vec<Object>,
@@ -12,8 +13,43 @@ pub struct Scene {
     pub models: Vec<ModelInfo>,
     /// Materials to load in the `AssetsRegistry`
     pub materials: Vec<Material>,
-    /// Light of the scene for both direct and indirect illumination
-    pub light: Light,
+    /// Lights of the scene for both direct and indirect illumination
+    pub lights: Vec<Light>,
+}
+
+/// Scene as written in the file.
+/// Older scenes have a single `light` instead of `lights`, both can be used at once
+#[derive(Deserialize)]
+struct SceneFile {
+    objects: Vec<Object>,
+    models: Vec<ModelInfo>,
+    materials: Vec<Material>,
+    #[serde(default)]
+    lights: Vec<Light>,
+    #[serde(default, deserialize_with = "deserialize_single_light")]
+    light: Option<Light>,
+}
+
+/// RON needs `Some(...)` for options, but the old `light` key holds the light directly
+fn deserialize_single_light<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Light>, D::Error> {
+    Light::deserialize(deserializer).map(Some)
+}
+
+impl From<SceneFile> for Scene {
+    fn from(scene_file: SceneFile) -> Self {
+        let mut lights = scene_file.lights;
+        if let Some(light) = scene_file.light {
+            lights.insert(0, light);
+        }
+        Self {
+            objects: scene_file.objects,
+            models: scene_file.models,
+            materials: scene_file.materials,
+            lights,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -22,9 +58,14 @@ pub struct ModelInfo {
     pub path: String,
 }
 
-pub fn process_scene(scene: Scene) -> (Vec<Object>, Light) {
+/// Loads the models and materials of the scene in the `AssetRegistry` and initializes its lights
+pub fn process_scene(scene: Scene) -> Result<(Vec<Object>, Vec<Light>), ShaderError> {
     let mut assets = unsafe { AssetRegistry::initialize(&scene) };
-    (scene.objects, scene.light)
+    let mut lights = scene.lights;
+    for light in lights.iter_mut() {
+        unsafe { light.init()? };
+    }
+    Ok((scene.objects, lights))
 }
 
 #[cfg(test)]
@@ -63,13 +104,13 @@ mod tests {
                     },
                 },
             ],
-            light: Light::default(),
+            lights: vec![Light::default()],
         }
     }
 
     #[test]
     fn scene_deserialization_works() {
-        let (_glfw, _window) = test_utils::init_opengl_context();
+        let _context = test_utils::init_opengl_context();
 
         let previous_path = env::current_dir().unwrap();
         // To go from the crate root to the workspace root
@@ -86,9 +127,35 @@ mod tests {
         env::set_current_dir(previous_path).unwrap();
     }
 
+    #[test]
+    fn single_light_key_is_still_supported() {
+        let _context = test_utils::init_opengl_context();
+
+        let previous_path = env::current_dir().unwrap();
+        // To go from the crate root to the workspace root
+        let mut path = previous_path.clone();
+        path.pop();
+        path.pop();
+        env::set_current_dir(path).unwrap();
+
+        let scene: Scene = ron::de::from_str(
+            "(objects: [], models: [], materials: [], light: Point(()), lights: [Directional(()), Spot(())])",
+        )
+        .unwrap();
+        let directional: Vec<bool> = scene.lights.iter().map(Light::is_directional).collect();
+        assert_eq!(directional, vec![false, true, false]);
+
+        let scene: Scene =
+            ron::de::from_str("(objects: [], models: [], materials: [])").unwrap();
+        assert!(scene.lights.is_empty());
+
+        // Reset dir in the end
+        env::set_current_dir(previous_path).unwrap();
+    }
+
     #[test]
     fn process_scene_works() {
-        let (_glfw, _window) = test_utils::init_opengl_context();
+        let _context = test_utils::init_opengl_context();
 
         let previous_path = env::current_dir().unwrap();
         // To go from the crate root to the workspace root
@@ -100,7 +167,7 @@ mod tests {
         let scene = get_test_scene();
 
         // Process the scene
-        let (objects, _light) = process_scene(scene);
+        let (objects, lights) = process_scene(scene).unwrap();
 
         {
             // Models and materials are now loaded
@@ -112,6 +179,8 @@ mod tests {
         assert_eq!(objects.len(), 1);
         assert_eq!(objects[0].model_handle(), "cube");
         assert_eq!(objects[0].material_handle(), "red");
+        // The lights got the framebuffers of their light view maps
+        assert!(lights[0].light_map_textures().iter().all(|&texture| texture != 0));
 
         // Reset dir in the end
         env::set_current_dir(previous_path).unwrap();
@@ -0,0 +1,97 @@
+use std::{error::Error, fmt, path::PathBuf};
+
+use gl::types::GLenum;
+
+use crate::preprocessor::PreprocessError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Geometry,
+    Compute,
+}
+
+impl ShaderStage {
+    pub fn gl_type(self) -> GLenum {
+        match self {
+            Self::Vertex => gl::VERTEX_SHADER,
+            Self::Fragment => gl::FRAGMENT_SHADER,
+            Self::Geometry => gl::GEOMETRY_SHADER,
+            Self::Compute => gl::COMPUTE_SHADER,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Vertex => "vertex",
+            Self::Fragment => "fragment",
+            Self::Geometry => "geometry",
+            Self::Compute => "compute",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Why a shader couldn't be built
+#[derive(Debug)]
+pub enum ShaderError {
+    /// A file couldn't be read or has wrong directives
+    Preprocess(PreprocessError),
+    /// `#shader <stage>` with a stage that doesn't exist, in a file with every stage
+    UnknownStage { file: PathBuf, stage: String },
+    /// A stage didn't compile. `file` and `line` are where the first error of the log is,
+    /// without a line if the driver's log format isn't known.
+    /// Locations in `log` are already in the original files
+    Compile {
+        stage: ShaderStage,
+        file: PathBuf,
+        line: Option<usize>,
+        log: String,
+    },
+    /// Every stage compiled but they didn't link, `file` is the first file of the program
+    Link { file: PathBuf, log: String },
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Preprocess(error) => write!(f, "Failed to preprocess shader: {error}"),
+            Self::UnknownStage { file, stage } => {
+                write!(f, "{}: unknown shader stage `{stage}`", file.display())
+            }
+            Self::Compile {
+                stage,
+                file,
+                line,
+                log,
+            } => {
+                write!(f, "{}", file.display())?;
+                if let Some(line) = line {
+                    write!(f, ":{line}")?;
+                }
+                write!(f, ": {stage} shader failed to compile:\n{log}")
+            }
+            Self::Link { file, log } => {
+                write!(f, "{}: program failed to link:\n{log}", file.display())
+            }
+        }
+    }
+}
+
+impl Error for ShaderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Preprocess(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<PreprocessError> for ShaderError {
+    fn from(error: PreprocessError) -> Self {
+        Self::Preprocess(error)
+    }
+}
@@ -0,0 +1,306 @@
+//! Compiles shaders again when their files change, to tune them while the program runs.
+//!
+//! Every shader compiled from files is tracked with every file it includes. [`reload_changed`]
+//! compiles again the ones whose files were modified, in place, so every copy of their
+//! [`Shader`] uses the new code. If the new code doesn't compile the shader keeps its previous
+//! program and the error is kept in [`errors`] until the files are fixed.
+//! Nothing is checked until it's enabled with [`set_enabled`].
+
+use std::{
+    cell::RefCell,
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use gl::types::GLuint;
+
+use super::{Shader, ShaderDefines, ShaderError, ShaderFiles, ShaderStage};
+use crate::preprocessor::Source;
+
+thread_local! {
+    static HOT_RELOAD: RefCell<HotReload> = RefCell::new(HotReload::default());
+}
+
+/// Time between checks of the files, reading their metadata every frame isn't free
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Shader that failed to compile again, it keeps running its previous program
+#[derive(Debug, Clone)]
+pub struct ReloadError {
+    /// First file of the shader
+    pub shader: String,
+    /// Compile or link log, with the locations in the original files
+    pub message: String,
+}
+
+#[derive(Default)]
+struct HotReload {
+    is_enabled: bool,
+    last_check: Option<Instant>,
+    shaders: Vec<TrackedShader>,
+    errors: Vec<(GLuint, ReloadError)>,
+}
+
+struct TrackedShader {
+    program: GLuint,
+    files: ShaderFiles,
+    defines: ShaderDefines,
+    is_debug: bool,
+    /// Every file included by any stage the last time it was processed
+    included_files: Vec<PathBuf>,
+    /// Newest modification time of `included_files` the last time it was compiled
+    last_modified: Option<SystemTime>,
+}
+
+impl TrackedShader {
+    /// Compiles the shader again, keeping its program if it fails
+    unsafe fn reload(&mut self) -> Result<(), ShaderError> {
+        let stages = self.files.process(&self.defines, self.is_debug)?;
+        Shader::link_into(self.program, &stages)?;
+        self.included_files = included_files(&stages);
+        Ok(())
+    }
+}
+
+pub fn set_enabled(is_enabled: bool) {
+    HOT_RELOAD.with(|hot_reload| hot_reload.borrow_mut().is_enabled = is_enabled);
+}
+
+pub fn is_enabled() -> bool {
+    HOT_RELOAD.with(|hot_reload| hot_reload.borrow().is_enabled)
+}
+
+/// Number of shaders whose files are checked
+pub fn tracked_shaders() -> usize {
+    HOT_RELOAD.with(|hot_reload| hot_reload.borrow().shaders.len())
+}
+
+/// Shaders whose last change didn't compile, they run their previous program
+pub fn errors() -> Vec<ReloadError> {
+    HOT_RELOAD.with(|hot_reload| {
+        hot_reload
+            .borrow()
+            .errors
+            .iter()
+            .map(|(_, error)| error.clone())
+            .collect()
+    })
+}
+
+/// Compiles again the shaders with files modified since they were last compiled, if it's
+/// enabled and the files weren't checked in the last [`CHECK_INTERVAL`].
+/// Returns how many shaders were compiled again successfully
+pub unsafe fn reload_changed() -> usize {
+    HOT_RELOAD.with(|hot_reload| {
+        let mut hot_reload = hot_reload.borrow_mut();
+        if !hot_reload.is_enabled
+            || hot_reload
+                .last_check
+                .is_some_and(|last_check| last_check.elapsed() < CHECK_INTERVAL)
+        {
+            return 0;
+        }
+        hot_reload.last_check = Some(Instant::now());
+        hot_reload.reload_changed()
+    })
+}
+
+/// Starts checking the files of a shader just compiled from `stages`
+pub(super) fn track(
+    program: GLuint,
+    files: ShaderFiles,
+    defines: ShaderDefines,
+    is_debug: bool,
+    stages: &[(ShaderStage, Source)],
+) {
+    let included_files = included_files(stages);
+    let last_modified = last_modified(&included_files);
+    HOT_RELOAD.with(|hot_reload| {
+        hot_reload.borrow_mut().shaders.push(TrackedShader {
+            program,
+            files,
+            defines,
+            is_debug,
+            included_files,
+            last_modified,
+        })
+    });
+}
+
+/// Stops checking the files of a deleted program
+pub(super) fn untrack(program: GLuint) {
+    HOT_RELOAD.with(|hot_reload| {
+        let mut hot_reload = hot_reload.borrow_mut();
+        hot_reload
+            .shaders
+            .retain(|shader| shader.program != program);
+        hot_reload
+            .errors
+            .retain(|(error_program, _)| *error_program != program);
+    });
+}
+
+impl HotReload {
+    unsafe fn reload_changed(&mut self) -> usize {
+        // Programs deleted since they were compiled, their ids could be reused by new programs
+        self.shaders
+            .retain(|shader| gl::IsProgram(shader.program) == gl::TRUE);
+        let shaders = &self.shaders;
+        self.errors
+            .retain(|(program, _)| shaders.iter().any(|shader| shader.program == *program));
+
+        let mut reloaded = 0;
+        for shader in &mut self.shaders {
+            let last_modified = last_modified(&shader.included_files);
+            if last_modified == shader.last_modified {
+                continue;
+            }
+            // Not compiled again until the files change again if it fails
+            shader.last_modified = last_modified;
+
+            let program = shader.program;
+            self.errors
+                .retain(|(error_program, _)| *error_program != program);
+            match shader.reload() {
+                Ok(()) => {
+                    log::info!("Reloaded shader {}", shader.files.name());
+                    reloaded += 1;
+                }
+                Err(error) => {
+                    log::error!("Failed to reload shader {}: {error}", shader.files.name());
+                    self.errors.push((
+                        program,
+                        ReloadError {
+                            shader: shader.files.name().to_string(),
+                            message: error.to_string(),
+                        },
+                    ));
+                }
+            }
+        }
+        reloaded
+    }
+}
+
+fn included_files(stages: &[(ShaderStage, Source)]) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for (_, source) in stages {
+        for file in source.line_map().files() {
+            if !files.contains(file) {
+                files.push(file.clone());
+            }
+        }
+    }
+    files
+}
+
+/// Newest modification time of `files`, `None` if any of them can't be read
+fn last_modified(files: &[PathBuf]) -> Option<SystemTime> {
+    files
+        .iter()
+        .map(|file| {
+            fs::metadata(file)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .try_fold(SystemTime::UNIX_EPOCH, |newest, modified| {
+            Some(newest.max(modified?))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::test_utils::init_opengl_context;
+
+    const SHADER_CODE: &str = r#"
+#version 460 core
+#include "./_value.glsl"
+layout (local_size_x = 1) in;
+layout (binding = 0, r32ui) uniform uimageBuffer result;
+void main() { imageStore(result, 0, uvec4(VALUE)); }
+"#;
+
+    /// Runs `shader` and returns the value it stores
+    unsafe fn run(shader: Shader) -> u32 {
+        let mut buffer = 0;
+        let mut texture = 0;
+        gl::GenBuffers(1, &mut buffer);
+        gl::BindBuffer(gl::TEXTURE_BUFFER, buffer);
+        gl::BufferData(gl::TEXTURE_BUFFER, 4, std::ptr::null(), gl::STATIC_DRAW);
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_BUFFER, texture);
+        gl::TexBuffer(gl::TEXTURE_BUFFER, gl::R32UI, buffer);
+
+        shader.use_program();
+        gl::BindImageTexture(0, texture, 0, gl::FALSE, 0, gl::WRITE_ONLY, gl::R32UI);
+        shader.dispatch(1);
+        shader.wait_for(gl::BUFFER_UPDATE_BARRIER_BIT);
+
+        let mut value = 0u32;
+        gl::GetBufferSubData(gl::TEXTURE_BUFFER, 0, 4, &mut value as *mut u32 as *mut _);
+        gl::DeleteTextures(1, &texture);
+        gl::DeleteBuffers(1, &buffer);
+        value
+    }
+
+    /// Writes the included file, with a modification time after the previous one
+    fn write_value(directory: &PathBuf, code: &str) {
+        let path = directory.join("_value.glsl");
+        let previous = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        fs::write(&path, code).unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        let modified = previous.map_or(SystemTime::now(), |previous| {
+            previous.max(SystemTime::now()) + Duration::from_secs(1)
+        });
+        file.set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn included_file_changes_are_compiled_in_place() {
+        let _context = init_opengl_context();
+        let directory = env::temp_dir().join("vct_hot_reload_test");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("shader.comp.glsl"), SHADER_CODE).unwrap();
+        write_value(&directory, "#define VALUE 1\n");
+
+        let shader_path = directory.join("shader.comp.glsl");
+        let shader =
+            Shader::new_compute(shader_path.to_str().unwrap(), &ShaderDefines::new(), false)
+                .unwrap();
+        set_enabled(true);
+        unsafe {
+            assert_eq!(run(shader), 1);
+
+            write_value(&directory, "#define VALUE 2\n");
+            HOT_RELOAD.with(|hot_reload| assert_eq!(hot_reload.borrow_mut().reload_changed(), 1));
+            assert_eq!(run(shader), 2, "the same program should run the new code");
+            assert!(errors().is_empty());
+
+            write_value(&directory, "#define VALUE 3 +\n");
+            HOT_RELOAD.with(|hot_reload| assert_eq!(hot_reload.borrow_mut().reload_changed(), 0));
+            assert_eq!(run(shader), 2, "the previous program should be kept");
+            let errors = errors();
+            assert_eq!(errors.len(), 1);
+            assert!(errors[0].shader.ends_with("shader.comp.glsl"));
+            assert!(
+                errors[0].message.contains("shader.comp.glsl:"),
+                "{}",
+                errors[0].message
+            );
+
+            write_value(&directory, "#define VALUE 4\n");
+            HOT_RELOAD.with(|hot_reload| assert_eq!(hot_reload.borrow_mut().reload_changed(), 1));
+            assert_eq!(run(shader), 4);
+            assert!(super::errors().is_empty());
+
+            shader.delete();
+            assert_eq!(tracked_shaders(), 0);
+        }
+    }
+}
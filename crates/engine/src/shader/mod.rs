@@ -0,0 +1,919 @@
+use std::ffi::CStr;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::{ptr, str};
+
+use c_str_macro::c_str;
+use cgmath::{vec3, ElementWise, Matrix, Matrix3, Matrix4, Vector3};
+use gl::types::*;
+use log::trace;
+
+use crate::preprocessor::{Preprocessor, Source};
+
+mod error;
+pub use error::{ShaderError, ShaderStage};
+
+pub mod hot_reload;
+pub mod reflection;
+use reflection::ProgramReflection;
+
+mod uniforms;
+pub use macros::ShaderUniforms;
+pub use uniforms::{half_angle_in_radians, ShaderUniforms};
+
+/// Compiled program, it's compiled again in place if its files change (see `hot_reload`),
+/// so copies of it always use the last code that compiled
+#[derive(Default, Debug, Clone, Copy)]
+pub struct Shader {
+    pub id: u32,
+    is_compute: bool,
+    /// `GL_MAX_COMPUTE_WORK_GROUP_COUNT`, only 65535 in each axis is guaranteed
+    max_work_group_count: [u32; 3],
+}
+
+/// Files a program is compiled from
+#[derive(Debug, Clone)]
+enum ShaderFiles {
+    Stages {
+        vertex: String,
+        fragment: String,
+        geometry: Option<String>,
+    },
+    /// Every stage in one file, see `split_shader_file`
+    Single(String),
+    Compute(String),
+}
+
+/// `#define`s added to every stage of a shader right after its `#version` line,
+/// so constants like the working group size come from Rust instead of being hard-coded in GLSL
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ShaderDefines {
+    defines: Vec<(String, Option<String>)>,
+}
+
+impl ShaderDefines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `#define name value`, replacing the previous value of `name` if there was one
+    pub fn define(self, name: &str, value: impl fmt::Display) -> Self {
+        self.with(name, Some(value.to_string()))
+    }
+
+    /// Adds `#define name` without value, for flags checked with `#ifdef`
+    pub fn flag(self, name: &str) -> Self {
+        self.with(name, None)
+    }
+
+    fn with(mut self, name: &str, value: Option<String>) -> Self {
+        self.defines
+            .retain(|(defined_name, _)| defined_name != name);
+        self.defines.push((name.to_string(), value));
+        self
+    }
+
+    /// Whether `#if name` keeps its code, when `name` is a flag or a value other than 0
+    fn is_set(&self, name: &str) -> bool {
+        self.defines.iter().any(|(defined_name, value)| {
+            defined_name == name && value.as_deref().is_none_or(|value| value != "0")
+        })
+    }
+
+    /// Inserts the defines after every `#version` line, a single file can have several stages
+    fn insert_into(&self, source: &mut Source) {
+        let defines: Vec<String> = self
+            .defines
+            .iter()
+            .map(|(name, value)| match value {
+                Some(value) => format!("#define {name} {value}"),
+                None => format!("#define {name}"),
+            })
+            .collect();
+        source.insert_after_version(&defines);
+    }
+}
+
+impl ShaderFiles {
+    /// The first file, to tell the shader apart in logs
+    fn name(&self) -> &str {
+        match self {
+            Self::Stages { vertex, .. } => vertex,
+            Self::Single(path) | Self::Compute(path) => path,
+        }
+    }
+
+    /// Preprocessed code of each stage
+    fn process(
+        &self,
+        defines: &ShaderDefines,
+        is_debug: bool,
+    ) -> Result<Vec<(ShaderStage, Source)>, ShaderError> {
+        let process = |path: &str| Shader::process_shader_file(path, defines, is_debug);
+        let stages = match self {
+            Self::Stages {
+                vertex,
+                fragment,
+                geometry,
+            } => {
+                let mut stages = vec![
+                    (ShaderStage::Vertex, process(vertex)?),
+                    (ShaderStage::Fragment, process(fragment)?),
+                ];
+                if let Some(geometry) = geometry {
+                    stages.push((ShaderStage::Geometry, process(geometry)?));
+                }
+                stages
+            }
+            Self::Single(path) => {
+                let (vertex_code, fragment_code, geometry_code) =
+                    Shader::split_shader_file(&process(path)?)?;
+                let mut stages = vec![
+                    (ShaderStage::Vertex, vertex_code),
+                    (ShaderStage::Fragment, fragment_code),
+                ];
+                if let Some(geometry_code) = geometry_code {
+                    stages.push((ShaderStage::Geometry, geometry_code));
+                }
+                stages
+            }
+            Self::Compute(path) => vec![(ShaderStage::Compute, process(path)?)],
+        };
+        Ok(stages)
+    }
+}
+
+impl Shader {
+    pub fn new(
+        vertex_path: &str,
+        fragment_path: &str,
+        defines: &ShaderDefines,
+        is_debug: bool,
+    ) -> Result<Self, ShaderError> {
+        let files = ShaderFiles::Stages {
+            vertex: vertex_path.to_string(),
+            fragment: fragment_path.to_string(),
+            geometry: None,
+        };
+        Self::from_files(files, defines, is_debug)
+    }
+
+    pub fn with_geometry_shader(
+        vertex_path: &str,
+        fragment_path: &str,
+        geometry_path: &str,
+        defines: &ShaderDefines,
+        is_debug: bool,
+    ) -> Result<Self, ShaderError> {
+        let files = ShaderFiles::Stages {
+            vertex: vertex_path.to_string(),
+            fragment: fragment_path.to_string(),
+            geometry: Some(geometry_path.to_string()),
+        };
+        Self::from_files(files, defines, is_debug)
+    }
+
+    pub fn new_compute(
+        shader_path: &str,
+        defines: &ShaderDefines,
+        is_debug: bool,
+    ) -> Result<Self, ShaderError> {
+        Self::from_files(
+            ShaderFiles::Compute(shader_path.to_string()),
+            defines,
+            is_debug,
+        )
+    }
+
+    #[cfg(test)]
+    pub fn new_compute_from_string(
+        source_code: &str,
+        defines: &ShaderDefines,
+    ) -> Result<Self, ShaderError> {
+        let mut source = Source::from_string("<string>", source_code);
+        defines.insert_into(&mut source);
+
+        unsafe {
+            let id = gl::CreateProgram();
+            if let Err(error) = Self::link_into(id, &[(ShaderStage::Compute, source)]) {
+                gl::DeleteProgram(id);
+                return Err(error);
+            }
+            Ok(Self {
+                id,
+                is_compute: true,
+                max_work_group_count: max_work_group_count().into(),
+            })
+        }
+    }
+
+    pub fn new_single(
+        shader_path: &str,
+        defines: &ShaderDefines,
+        is_debug: bool,
+    ) -> Result<Self, ShaderError> {
+        Self::from_files(
+            ShaderFiles::Single(shader_path.to_string()),
+            defines,
+            is_debug,
+        )
+    }
+
+    fn from_files(
+        files: ShaderFiles,
+        defines: &ShaderDefines,
+        is_debug: bool,
+    ) -> Result<Self, ShaderError> {
+        let stages = files.process(defines, is_debug)?;
+
+        let name = files.name();
+        let short_name = name.strip_prefix("assets/shaders/").unwrap_or(name);
+        trace!("Compiling shader in path {short_name}");
+        let is_compute = matches!(files, ShaderFiles::Compute(_));
+        unsafe {
+            let id = gl::CreateProgram();
+            if let Err(error) = Self::link_into(id, &stages) {
+                gl::DeleteProgram(id);
+                return Err(error);
+            }
+            reflection::register(id, short_name);
+            hot_reload::track(id, files, defines.clone(), is_debug, &stages);
+
+            Ok(Self {
+                id,
+                is_compute,
+                max_work_group_count: if is_compute {
+                    max_work_group_count().into()
+                } else {
+                    Default::default()
+                },
+            })
+        }
+    }
+
+    /// Deletes the program, copies of the shader can't be used after this
+    pub unsafe fn delete(self) {
+        gl::DeleteProgram(self.id);
+        hot_reload::untrack(self.id);
+        reflection::deleted(self.id);
+    }
+
+    /// Activate the shader
+    pub unsafe fn use_program(&self) {
+        gl::UseProgram(self.id)
+    }
+
+    /// Checks the uniforms before a draw or dispatch, see `reflection`. Undeclared or unset
+    /// uniforms panic in debug builds and are logged in release builds.
+    /// Dispatches call it themselves, draws have to call it before `gl::Draw*`
+    pub unsafe fn validate(&self) {
+        reflection::validate(self.id);
+    }
+
+    pub unsafe fn dispatch_xyz(&self, number_of_groups: Vector3<u32>) {
+        if !self.is_compute {
+            panic!("Can't dispatch a non-compute shader");
+        }
+        self.validate();
+        gl::DispatchCompute(number_of_groups.x, number_of_groups.y, number_of_groups.z);
+    }
+
+    pub unsafe fn dispatch(&self, number_of_groups: u32) {
+        self.dispatch_xyz(vec3(number_of_groups, 1, 1));
+    }
+
+    /// Size of the work groups of a compute shader, `local_size_*` in GLSL.
+    /// Queried from the program, it can change when it's compiled again
+    pub fn work_group_size(&self) -> Vector3<u32> {
+        let mut work_group_size = [0; 3];
+        unsafe {
+            if self.is_compute && Self::is_linked(self.id) {
+                gl::GetProgramiv(
+                    self.id,
+                    gl::COMPUTE_WORK_GROUP_SIZE,
+                    work_group_size.as_mut_ptr(),
+                );
+            }
+        }
+        Vector3::from(work_group_size.map(|size| size as u32))
+    }
+
+    /// Work groups needed for at least `invocations` invocations of the compute shader in each axis
+    pub fn groups_for(&self, invocations: Vector3<u32>) -> Vector3<u32> {
+        let work_group_size = self.work_group_size();
+        assert!(
+            work_group_size.x > 0 && work_group_size.y > 0 && work_group_size.z > 0,
+            "Compute shader {} has no work group size, did it link?",
+            self.id
+        );
+        vec3(
+            invocations.x.div_ceil(work_group_size.x),
+            invocations.y.div_ceil(work_group_size.y),
+            invocations.z.div_ceil(work_group_size.z),
+        )
+    }
+
+    /// Most work groups a single dispatch can have in each axis
+    pub fn max_work_group_count(&self) -> Vector3<u32> {
+        Vector3::from(self.max_work_group_count)
+    }
+
+    /// Dispatches enough work groups for `invocations` invocations in x, like one per node.
+    /// Shaders have to ignore the extra invocations of the last group
+    pub unsafe fn dispatch_invocations(&self, invocations: u32) {
+        self.dispatch_invocations_xyz(vec3(invocations, 1, 1));
+    }
+
+    /// Dispatches enough work groups for `invocations` invocations in each axis, like one per pixel.
+    ///
+    /// Takes several dispatches when there are more groups than `max_work_group_count`.
+    /// Before each one the `uvec3 invocationOffset` uniform is set to its first invocation,
+    /// so shaders have to add it to `gl_GlobalInvocationID` (see `_invocation.glsl`).
+    /// The shader has to be in use
+    pub unsafe fn dispatch_invocations_xyz(&self, invocations: Vector3<u32>) {
+        let work_group_size = self.work_group_size();
+        for (first_group, number_of_groups) in
+            split_groups(self.groups_for(invocations), self.max_work_group_count())
+        {
+            let offset = first_group.mul_element_wise(work_group_size);
+            self.set_invocation_offset(offset);
+            self.dispatch_xyz(number_of_groups);
+        }
+    }
+
+    /// Dispatches the work groups in x, y and z stored as three `u32`s at `offset` bytes of `buffer`,
+    /// usually written by an earlier pass so the CPU doesn't have to read them back.
+    /// It can't be split in batches, so they have to fit in `max_work_group_count`.
+    /// The shader has to be in use
+    pub unsafe fn dispatch_indirect(&self, buffer: GLuint, offset: usize) {
+        if !self.is_compute {
+            panic!("Can't dispatch a non-compute shader");
+        }
+        self.set_invocation_offset(vec3(0, 0, 0));
+        self.validate();
+        gl::BindBuffer(gl::DISPATCH_INDIRECT_BUFFER, buffer);
+        gl::DispatchComputeIndirect(offset as GLintptr);
+        gl::BindBuffer(gl::DISPATCH_INDIRECT_BUFFER, 0);
+    }
+
+    /// Sets `invocationOffset` if the shader uses it, not every shader includes `_invocation.glsl`
+    unsafe fn set_invocation_offset(&self, offset: Vector3<u32>) {
+        let location = reflection::location_if_active(self.id, c_str!("invocationOffset"));
+        if location != -1 {
+            gl::Uniform3ui(location, offset.x, offset.y, offset.z);
+        }
+    }
+
+    pub unsafe fn wait(&self) {
+        gl::MemoryBarrier(gl::ALL_BARRIER_BITS);
+    }
+
+    /// Like `wait`, but only for the accesses in `barriers`, like `gl::SHADER_IMAGE_ACCESS_BARRIER_BIT`
+    /// when the next passes only read the images written by this one
+    pub unsafe fn wait_for(&self, barriers: GLbitfield) {
+        gl::MemoryBarrier(barriers);
+    }
+
+    /// Active uniforms, samplers and images of the program, and the names set from Rust that it
+    /// doesn't have. For debugging, it's queried again after the program is compiled again
+    pub unsafe fn reflection(&self) -> ProgramReflection {
+        reflection::reflection(self.id)
+    }
+
+    /// Whether the code of the shader declares the uniform, even if it's unused. Code shared by
+    /// several shaders, like drawing objects, checks it to skip the uniforms some don't have
+    pub fn declares_uniform(&self, name: &CStr) -> bool {
+        reflection::declares(self.id, name)
+    }
+
+    /// Cached location of the uniform, logs once if the program doesn't declare it
+    unsafe fn uniform_location(&self, name: &CStr) -> GLint {
+        reflection::location(self.id, name)
+    }
+
+    /// Utility uniform functions
+    /// ------------------------------------------------------------------------
+    pub unsafe fn set_bool(&self, name: &CStr, value: bool) {
+        gl::Uniform1i(self.uniform_location(name), value as i32);
+    }
+    /// ------------------------------------------------------------------------
+    pub unsafe fn set_int(&self, name: &CStr, value: i32) {
+        gl::Uniform1i(self.uniform_location(name), value);
+    }
+    /// ------------------------------------------------------------------------
+    pub unsafe fn set_uint(&self, name: &CStr, value: u32) {
+        gl::Uniform1ui(self.uniform_location(name), value);
+    }
+    /// ------------------------------------------------------------------------
+    pub unsafe fn set_float(&self, name: &CStr, value: f32) {
+        gl::Uniform1f(self.uniform_location(name), value);
+    }
+    /// ------------------------------------------------------------------------
+    pub unsafe fn set_vec2(&self, name: &CStr, x: f32, y: f32) {
+        gl::Uniform2f(self.uniform_location(name), x, y);
+    }
+    pub unsafe fn set_vec3(&self, name: &CStr, x: f32, y: f32, z: f32) {
+        gl::Uniform3f(self.uniform_location(name), x, y, z);
+    }
+    pub unsafe fn set_uvec3(&self, name: &CStr, x: u32, y: u32, z: u32) {
+        gl::Uniform3ui(self.uniform_location(name), x, y, z);
+    }
+    /// ------------------------------------------------------------------------
+    pub unsafe fn set_mat3(&self, name: &CStr, mat: &Matrix3<f32>) {
+        gl::UniformMatrix3fv(self.uniform_location(name), 1, gl::FALSE, mat.as_ptr());
+    }
+    pub unsafe fn set_mat4(&self, name: &CStr, mat: &Matrix4<f32>) {
+        gl::UniformMatrix4fv(self.uniform_location(name), 1, gl::FALSE, mat.as_ptr());
+    }
+    pub unsafe fn set_mat4_array(&self, name: &CStr, mats: &[&Matrix4<f32>]) {
+        let expanded_array: Vec<f32> = mats
+            .iter()
+            .flat_map(|matrix| {
+                [
+                    matrix.x.x, matrix.x.y, matrix.x.z, matrix.x.w, matrix.y.x, matrix.y.y,
+                    matrix.y.z, matrix.y.w, matrix.z.x, matrix.z.y, matrix.z.z, matrix.z.w,
+                    matrix.w.x, matrix.w.y, matrix.w.z, matrix.w.w,
+                ]
+            })
+            .collect();
+        gl::UniformMatrix4fv(
+            self.uniform_location(name),
+            mats.len() as i32,
+            gl::FALSE,
+            expanded_array.as_ptr(),
+        );
+    }
+
+    /// Preprocesses the file (see `preprocessor`) and adds the defines.
+    /// `#if debug` keeps its code when `is_debug`, other conditions are set by the defines
+    fn process_shader_file(
+        file_path: &str,
+        defines: &ShaderDefines,
+        is_debug: bool,
+    ) -> Result<Source, ShaderError> {
+        let preprocessor =
+            Preprocessor::new(|name: &str| (name == "debug" && is_debug) || defines.is_set(name));
+        let mut source = preprocessor.process(Path::new(file_path))?;
+        defines.insert_into(&mut source);
+        Ok(source)
+    }
+
+    /// Compiles `stages` and links them into `program`. If it was already linked and they
+    /// don't compile or link it keeps its previous executable, so it can be compiled again
+    /// while it's in use
+    unsafe fn link_into(
+        program: GLuint,
+        stages: &[(ShaderStage, Source)],
+    ) -> Result<(), ShaderError> {
+        let mut shaders = Vec::new();
+        let mut result = Ok(());
+        for (stage, source) in stages {
+            match Self::compile_stage(*stage, source) {
+                Ok(shader) => shaders.push(shader),
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+        }
+
+        if result.is_ok() {
+            let link = |program| {
+                Self::link(program, &shaders).map_err(|log| ShaderError::Link {
+                    file: Self::first_file(&stages[0].1),
+                    log,
+                })
+            };
+            result = if Self::is_linked(program) {
+                // A failed link discards the executable, so the new code is tried in another program first
+                let test_program = gl::CreateProgram();
+                let result = link(test_program);
+                gl::DeleteProgram(test_program);
+                result.and_then(|()| link(program))
+            } else {
+                link(program)
+            };
+        }
+
+        if result.is_ok() {
+            reflection::linked(program, stages);
+        }
+
+        // delete the shaders as they're linked into the program now and no longer necessary
+        for shader in shaders {
+            gl::DeleteShader(shader);
+        }
+        result
+    }
+
+    unsafe fn compile_stage(stage: ShaderStage, source: &Source) -> Result<GLuint, ShaderError> {
+        let shader = gl::CreateShader(stage.gl_type());
+        gl::ShaderSource(shader, 1, &source.to_c_string().as_ptr(), ptr::null());
+        gl::CompileShader(shader);
+
+        let mut success = gl::FALSE as GLint;
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+        if success == gl::TRUE as GLint {
+            return Ok(shader);
+        }
+
+        let info_log = Self::info_log(shader, gl::GetShaderiv, gl::GetShaderInfoLog);
+        gl::DeleteShader(shader);
+        let line_map = source.line_map();
+        let (file, line) = match line_map.error_location(&info_log) {
+            Some((file, line)) => (file.to_path_buf(), Some(line)),
+            None => (Self::first_file(source), None),
+        };
+        Err(ShaderError::Compile {
+            stage,
+            file,
+            line,
+            log: line_map.rewrite_log(&info_log),
+        })
+    }
+
+    /// Links `shaders` into `program` in place of the ones it had, returns the log if it fails
+    unsafe fn link(program: GLuint, shaders: &[GLuint]) -> Result<(), String> {
+        let mut attached_count = 0;
+        gl::GetProgramiv(program, gl::ATTACHED_SHADERS, &mut attached_count);
+        let mut attached = vec![0; attached_count as usize];
+        gl::GetAttachedShaders(
+            program,
+            attached_count,
+            ptr::null_mut(),
+            attached.as_mut_ptr(),
+        );
+        for shader in attached {
+            gl::DetachShader(program, shader);
+        }
+
+        for &shader in shaders {
+            gl::AttachShader(program, shader);
+        }
+        gl::LinkProgram(program);
+        // The executable stays after detaching, which lets the shaders be deleted
+        for &shader in shaders {
+            gl::DetachShader(program, shader);
+        }
+
+        if Self::is_linked(program) {
+            Ok(())
+        } else {
+            Err(Self::info_log(
+                program,
+                gl::GetProgramiv,
+                gl::GetProgramInfoLog,
+            ))
+        }
+    }
+
+    /// File the code of `source` starts in
+    fn first_file(source: &Source) -> PathBuf {
+        source
+            .line_map()
+            .files()
+            .first()
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    unsafe fn is_linked(program: GLuint) -> bool {
+        let mut link_status = gl::FALSE as GLint;
+        gl::GetProgramiv(program, gl::LINK_STATUS, &mut link_status);
+        link_status == gl::TRUE as GLint
+    }
+
+    /// Info log of a shader or a program, with its getters
+    unsafe fn info_log(
+        object: GLuint,
+        get_iv: unsafe fn(GLuint, GLenum, *mut GLint),
+        get_info_log: unsafe fn(GLuint, GLsizei, *mut GLsizei, *mut GLchar),
+    ) -> String {
+        let mut info_log_length = 0;
+        get_iv(object, gl::INFO_LOG_LENGTH, &mut info_log_length);
+        let mut info_log = vec![0u8; info_log_length.max(1) as usize];
+        let mut written = 0;
+        get_info_log(
+            object,
+            info_log.len() as GLsizei,
+            &mut written,
+            info_log.as_mut_ptr() as *mut GLchar,
+        );
+        String::from_utf8_lossy(&info_log[..written as usize]).into_owned()
+    }
+
+    /// Splits a unified shader file into its stages at its `#shader <stage>` lines
+    fn split_shader_file(
+        shader_code: &Source,
+    ) -> Result<(Source, Source, Option<Source>), ShaderError> {
+        let (mut vertex_code, mut fragment_code, mut geometry_code) = Default::default();
+        for (stage, code) in shader_code.split_stages("#shader ", "vertex") {
+            match stage.as_str() {
+                "vertex" => vertex_code = code,
+                "fragment" => fragment_code = code,
+                "geometry" => geometry_code = Some(code),
+                _ => {
+                    return Err(ShaderError::UnknownStage {
+                        file: Self::first_file(shader_code),
+                        stage,
+                    })
+                }
+            }
+        }
+
+        Ok((vertex_code, fragment_code, geometry_code))
+    }
+}
+
+#[macro_export]
+macro_rules! compile_shaders {
+    ($single_path:literal$(,)?) => {
+        Shader::new_single($single_path, &Default::default(), false)
+    };
+    ($vertex_path:literal, $fragment_path:literal$(,)?) => {
+        Shader::new($vertex_path, $fragment_path, &Default::default(), false)
+    };
+    ($vertex_path:literal, $fragment_path:literal, $geometry_path:literal$(,)?) => {
+        Shader::with_geometry_shader(
+            $vertex_path,
+            $fragment_path,
+            $geometry_path,
+            &Default::default(),
+            false,
+        )
+    };
+    ($single_path:literal, debug = $value:expr$(,)?) => {
+        Shader::new_single($single_path, &Default::default(), $value)
+    };
+    ($single_path:literal, defines = $defines:expr$(,)?) => {
+        Shader::new_single($single_path, &$defines, false)
+    };
+    ($single_path:literal, defines = $defines:expr, debug = $value:expr$(,)?) => {
+        Shader::new_single($single_path, &$defines, $value)
+    };
+    ($vertex_path:literal, $fragment_path:literal, $geometry_path:literal, defines = $defines:expr$(,)?) => {
+        Shader::with_geometry_shader(
+            $vertex_path,
+            $fragment_path,
+            $geometry_path,
+            &$defines,
+            false,
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! compile_compute {
+    ($compute_path:expr$(,)?) => {
+        Shader::new_compute($compute_path, &Default::default(), false)
+    };
+    ($compute_path:expr, defines = $defines:expr$(,)?) => {
+        Shader::new_compute($compute_path, &$defines, false)
+    };
+}
+
+pub use compile_compute;
+pub use compile_shaders;
+
+/// `GL_MAX_COMPUTE_WORK_GROUP_COUNT`, the most work groups a single dispatch can have in each axis.
+/// Only 65535 is guaranteed
+pub unsafe fn max_work_group_count() -> Vector3<u32> {
+    let mut max_work_group_count = [0; 3];
+    for (index, count) in max_work_group_count.iter_mut().enumerate() {
+        gl::GetIntegeri_v(gl::MAX_COMPUTE_WORK_GROUP_COUNT, index as u32, count);
+    }
+    Vector3::from(max_work_group_count.map(|count| count as u32))
+}
+
+/// Splits `number_of_groups` into dispatches of at most `max_work_group_count` groups in each axis,
+/// as the first group and number of groups of each of them
+fn split_groups(
+    number_of_groups: Vector3<u32>,
+    max_work_group_count: Vector3<u32>,
+) -> Vec<(Vector3<u32>, Vector3<u32>)> {
+    let ranges = |total: u32, max: u32| -> Vec<(u32, u32)> {
+        (0..total)
+            .step_by(max as usize)
+            .map(|first| (first, max.min(total - first)))
+            .collect()
+    };
+
+    let mut dispatches = Vec::new();
+    for &(first_z, count_z) in &ranges(number_of_groups.z, max_work_group_count.z) {
+        for &(first_y, count_y) in &ranges(number_of_groups.y, max_work_group_count.y) {
+            for &(first_x, count_x) in &ranges(number_of_groups.x, max_work_group_count.x) {
+                dispatches.push((
+                    vec3(first_x, first_y, first_z),
+                    vec3(count_x, count_y, count_z),
+                ));
+            }
+        }
+    }
+    dispatches
+}
+
+pub trait ShaderPass {
+    type Input<'a>;
+
+    /// Run the shader pass.
+    /// Usually involves setting a lot of uniforms and either
+    /// dispatching compute threads or making draw calls.
+    unsafe fn run<'a>(&self, input: Self::Input<'a>);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers;
+    use crate::test_utils::init_opengl_context;
+
+    #[test]
+    fn defines_go_after_every_version() {
+        let defines = ShaderDefines::new()
+            .define("WORKING_GROUP_SIZE", 32)
+            .flag("DEBUG");
+        let mut source = Source::from_string(
+            "shader.glsl",
+            "#shader vertex\n#version 460 core\nvoid main() {}\n#shader fragment\n#version 460 core",
+        );
+        defines.insert_into(&mut source);
+        assert_eq!(
+            source.code(),
+            "#shader vertex\n#version 460 core\n#define WORKING_GROUP_SIZE 32\n#define DEBUG\nvoid main() {}\n\
+             #shader fragment\n#version 460 core\n#define WORKING_GROUP_SIZE 32\n#define DEBUG"
+        );
+    }
+
+    #[test]
+    fn defines_replace_previous_values() {
+        let defines = ShaderDefines::new()
+            .define("BRICK_SIZE", 3)
+            .define("BRICK_SIZE", 5);
+        let mut source = Source::from_string("shader.glsl", "#version 460 core");
+        defines.insert_into(&mut source);
+        assert_eq!(source.code(), "#version 460 core\n#define BRICK_SIZE 5");
+    }
+
+    #[test]
+    fn missing_files_are_errors() {
+        let result = Shader::new_compute(
+            "assets/shaders/missing.comp.glsl",
+            &ShaderDefines::new(),
+            false,
+        );
+        assert!(
+            matches!(result, Err(ShaderError::Preprocess(_))),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn compile_errors_have_their_stage_and_line() {
+        let _context = init_opengl_context();
+
+        let shader_code = "#version 460 core\nlayout (local_size_x = 1) in;\nvoid main() {\n    undeclared = 1;\n}";
+        let error =
+            Shader::new_compute_from_string(shader_code, &ShaderDefines::new().flag("DEBUG"))
+                .unwrap_err();
+        match error {
+            ShaderError::Compile {
+                stage,
+                file,
+                line,
+                log,
+            } => {
+                assert_eq!(stage, ShaderStage::Compute);
+                assert_eq!(file, Path::new("<string>"));
+                assert_eq!(line, Some(4), "{log}");
+                assert!(log.contains("undeclared"), "{log}");
+            }
+            error => panic!("Expected a compile error, got {error}"),
+        }
+    }
+
+    #[test]
+    fn work_group_size_comes_from_defines() {
+        let _context = init_opengl_context();
+
+        // Counts its invocations
+        let shader_code = r#"
+            #version 460 core
+
+            layout (local_size_x = WORKING_GROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
+
+            uniform layout(binding = 0, r32ui) uimageBuffer invocations;
+
+            void main() {
+                imageAtomicAdd(invocations, 0, 1);
+            }
+        "#;
+        let defines = ShaderDefines::new().define("WORKING_GROUP_SIZE", 32);
+        let shader = Shader::new_compute_from_string(shader_code, &defines).unwrap();
+        assert_eq!(shader.work_group_size(), vec3(32, 1, 1));
+        assert_eq!(shader.groups_for(vec3(65, 1, 1)), vec3(3, 1, 1));
+
+        unsafe {
+            shader.use_program();
+            let (texture, texture_buffer) = helpers::generate_texture_buffer(1, gl::R32UI, 0_u32);
+            helpers::bind_image_texture(0, texture, gl::READ_WRITE, gl::R32UI);
+            shader.dispatch_invocations(65);
+            shader.wait();
+            let values = helpers::get_values_from_texture_buffer(texture_buffer, 1, 0_u32);
+            assert_eq!(values[0], 96);
+        }
+    }
+
+    #[test]
+    fn split_groups_covers_every_group_once() {
+        let dispatches = split_groups(vec3(5, 3, 1), vec3(2, 2, 2));
+        assert_eq!(
+            dispatches,
+            vec![
+                (vec3(0, 0, 0), vec3(2, 2, 1)),
+                (vec3(2, 0, 0), vec3(2, 2, 1)),
+                (vec3(4, 0, 0), vec3(1, 2, 1)),
+                (vec3(0, 2, 0), vec3(2, 1, 1)),
+                (vec3(2, 2, 0), vec3(2, 1, 1)),
+                (vec3(4, 2, 0), vec3(1, 1, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_groups_is_a_single_dispatch_within_the_limits() {
+        assert_eq!(
+            split_groups(vec3(65535, 1, 1), vec3(65535, 65535, 65535)),
+            vec![(vec3(0, 0, 0), vec3(65535, 1, 1))]
+        );
+        assert!(split_groups(vec3(0, 1, 1), vec3(65535, 65535, 65535)).is_empty());
+    }
+
+    #[test]
+    fn batched_dispatches_run_every_invocation_once() {
+        let _context = init_opengl_context();
+
+        // Counts how many times each invocation runs
+        let shader_code = r#"
+            #version 460 core
+
+            layout (local_size_x = 4, local_size_y = 1, local_size_z = 1) in;
+
+            uniform uvec3 invocationOffset;
+            uniform layout(binding = 0, r32ui) uimageBuffer invocations;
+
+            void main() {
+                imageAtomicAdd(invocations, int(invocationOffset.x + gl_GlobalInvocationID.x), 1);
+            }
+        "#;
+        let mut shader =
+            Shader::new_compute_from_string(shader_code, &ShaderDefines::new()).unwrap();
+        assert!(shader.max_work_group_count().x >= 65535);
+        // 17 groups in batches of 2
+        shader.max_work_group_count = [2, 1, 1];
+
+        unsafe {
+            shader.use_program();
+            let (texture, texture_buffer) = helpers::generate_texture_buffer(68, gl::R32UI, 0_u32);
+            helpers::bind_image_texture(0, texture, gl::READ_WRITE, gl::R32UI);
+            shader.dispatch_invocations(65);
+            shader.wait();
+            let values = helpers::get_values_from_texture_buffer(texture_buffer, 68, 0_u32);
+            assert!(values.iter().all(|&count| count == 1), "{values:?}");
+        }
+    }
+
+    #[test]
+    fn indirect_dispatch_reads_the_groups_from_the_buffer() {
+        let _context = init_opengl_context();
+
+        // Counts its invocations
+        let shader_code = r#"
+            #version 460 core
+
+            layout (local_size_x = 32, local_size_y = 1, local_size_z = 1) in;
+
+            uniform layout(binding = 0, r32ui) uimageBuffer invocations;
+
+            void main() {
+                imageAtomicAdd(invocations, 0, 1);
+            }
+        "#;
+        let shader = Shader::new_compute_from_string(shader_code, &ShaderDefines::new()).unwrap();
+
+        unsafe {
+            shader.use_program();
+            let (texture, texture_buffer) = helpers::generate_texture_buffer(1, gl::R32UI, 0_u32);
+            helpers::bind_image_texture(0, texture, gl::READ_WRITE, gl::R32UI);
+            // The groups of the dispatch come after an unused one
+            let (_, arguments) = helpers::generate_texture_buffer_with_initial_data(
+                4,
+                gl::R32UI,
+                vec![0_u32, 3, 2, 1],
+            );
+            shader.dispatch_indirect(arguments, std::mem::size_of::<u32>());
+            shader.wait();
+            let values = helpers::get_values_from_texture_buffer(texture_buffer, 1, 0_u32);
+            assert_eq!(values[0], 3 * 2 * 32);
+        }
+    }
+}
@@ -0,0 +1,583 @@
+//! Active uniforms of linked programs, to catch the ones set wrong from Rust.
+//!
+//! The uniforms of a program are queried the first time one of them is set and their locations
+//! are cached from then on. Setting a uniform that isn't declared in the code of the program,
+//! usually misspelled or renamed in GLSL, is logged once per program and name. Uniforms declared
+//! but removed by the compiler because they're unused are fine, shared code sets them for every
+//! shader. Before each draw or dispatch, [`Shader::validate`](super::Shader::validate) checks that
+//! nothing undeclared was set and that every uniform with a value and no initializer was set at
+//! least once. Debug builds panic, release builds log each problem once.
+//! Everything is queried again when the program is linked again, see `hot_reload`.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    ffi::{CStr, CString},
+    fmt,
+};
+
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
+
+use super::ShaderStage;
+use crate::preprocessor::Source;
+
+thread_local! {
+    static PROGRAMS: RefCell<HashMap<GLuint, ProgramUniforms>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniformKind {
+    /// Set from Rust with the `set_*` functions of `Shader`
+    Value,
+    /// Reads the texture bound to the unit in its binding
+    Sampler,
+    /// Reads or writes the image bound to the unit in its binding
+    Image,
+    AtomicCounter,
+}
+
+/// Active uniform of a linked program
+#[derive(Debug, Clone)]
+pub struct Uniform {
+    /// Name as the driver reports it, the first element of arrays ends in `[0]`
+    pub name: String,
+    /// -1 for atomic counters
+    pub location: GLint,
+    pub type_: GLenum,
+    /// Number of elements of arrays, 1 otherwise
+    pub size: GLint,
+    pub kind: UniformKind,
+    /// Texture or image unit of samplers and images
+    pub binding: Option<GLint>,
+    /// Whether it was set from Rust since the program was linked
+    pub is_set: bool,
+    /// Whether GLSL gives it a value, like `uniform float padding = 0.0;`, so it doesn't need to be set
+    pub has_initializer: bool,
+}
+
+impl Uniform {
+    /// Name of its type in GLSL
+    pub fn type_name(&self) -> &'static str {
+        uniform_type(self.type_).map_or("unknown", |(name, _)| name)
+    }
+
+    fn contains(&self, location: GLint) -> bool {
+        self.location != -1 && (self.location..self.location + self.size).contains(&location)
+    }
+}
+
+impl fmt::Display for Uniform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.type_name(), self.name)?;
+        if self.size > 1 {
+            write!(f, " ({} elements)", self.size)?;
+        }
+        match self.binding {
+            Some(binding) => write!(f, ", binding {binding}"),
+            None if self.kind == UniformKind::Value => write!(f, ", location {}", self.location),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Uniforms of a program and how Rust used them, for debugging
+#[derive(Debug, Clone, Default)]
+pub struct ProgramReflection {
+    pub uniforms: Vec<Uniform>,
+    /// Names set from Rust that aren't declared in the code of the program
+    pub unknown_uniforms: Vec<String>,
+}
+
+impl ProgramReflection {
+    /// Uniforms with a value that were never set and have no initializer, they are still 0
+    pub fn unset_uniforms(&self) -> impl Iterator<Item = &Uniform> {
+        self.uniforms.iter().filter(|uniform| {
+            uniform.kind == UniformKind::Value && !uniform.is_set && !uniform.has_initializer
+        })
+    }
+}
+
+#[derive(Default)]
+struct ProgramUniforms {
+    /// First file of the program, to tell it apart in logs
+    name: Option<String>,
+    /// Names of the uniforms declared in its code, active or not
+    declared: HashSet<String>,
+    /// Declared names with an initializer
+    initialized: HashSet<String>,
+    /// `None` until a uniform is set after linking
+    reflection: Option<ProgramReflection>,
+    locations: HashMap<CString, GLint>,
+    /// Unset uniforms already logged
+    reported_unset: HashSet<String>,
+}
+
+impl ProgramUniforms {
+    fn name(&self, program: GLuint) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("program {program}"))
+    }
+
+    unsafe fn reflection(&mut self, program: GLuint) -> &mut ProgramReflection {
+        let initialized = &self.initialized;
+        self.reflection.get_or_insert_with(|| {
+            let mut uniforms = query_uniforms(program);
+            for uniform in uniforms.iter_mut() {
+                uniform.has_initializer = initialized.contains(declared_name(&uniform.name));
+            }
+            ProgramReflection {
+                uniforms,
+                unknown_uniforms: Vec::new(),
+            }
+        })
+    }
+
+    fn declares(&self, name: &str) -> bool {
+        self.declared.contains(declared_name(name))
+    }
+
+    /// Cached location of `name`, -1 if the program doesn't have it
+    unsafe fn location(&mut self, program: GLuint, name: &CStr) -> GLint {
+        if let Some(&location) = self.locations.get(name) {
+            return location;
+        }
+        // Elements of arrays and members of structs aren't all in the reflection, the driver knows them
+        let location = gl::GetUniformLocation(program, name.as_ptr());
+        self.locations.insert(name.to_owned(), location);
+        location
+    }
+}
+
+/// Starts over after `program` is linked from `stages`, its uniforms have new locations and
+/// lost their values. Keeps its name, it's compiled from the same files
+pub(super) fn linked(program: GLuint, stages: &[(ShaderStage, Source)]) {
+    PROGRAMS.with(|programs| {
+        let mut programs = programs.borrow_mut();
+        let uniforms = programs.entry(program).or_default();
+        let declarations: Vec<_> = stages
+            .iter()
+            .flat_map(|(_, source)| declared_uniforms(&source.code()))
+            .collect();
+        *uniforms = ProgramUniforms {
+            name: uniforms.name.take(),
+            declared: declarations.iter().map(|(name, _)| name.clone()).collect(),
+            initialized: declarations
+                .into_iter()
+                .filter_map(|(name, has_initializer)| has_initializer.then_some(name))
+                .collect(),
+            ..Default::default()
+        };
+    });
+}
+
+/// Names the program for logs
+pub(super) fn register(program: GLuint, name: &str) {
+    PROGRAMS.with(|programs| {
+        programs.borrow_mut().entry(program).or_default().name = Some(name.to_string());
+    });
+}
+
+pub(super) fn deleted(program: GLuint) {
+    PROGRAMS.with(|programs| programs.borrow_mut().remove(&program));
+}
+
+/// Location of `name` to set it, -1 if it isn't active. Logs once if it isn't declared either
+pub(super) unsafe fn location(program: GLuint, name: &CStr) -> GLint {
+    let location = location_if_active(program, name);
+    if location == -1 {
+        PROGRAMS.with(|programs| {
+            let mut programs = programs.borrow_mut();
+            let uniforms = programs.entry(program).or_default();
+            let name = name.to_string_lossy().into_owned();
+            if uniforms.declares(&name) {
+                return;
+            }
+            let program_name = uniforms.name(program);
+            let reflection = uniforms.reflection(program);
+            if !reflection.unknown_uniforms.contains(&name) {
+                log::warn!("Shader {program_name} doesn't declare uniform {name}");
+                reflection.unknown_uniforms.push(name);
+            }
+        });
+    }
+    location
+}
+
+/// Location of `name` to set it, -1 without logging anything if the program doesn't have it
+pub(super) unsafe fn location_if_active(program: GLuint, name: &CStr) -> GLint {
+    PROGRAMS.with(|programs| {
+        let mut programs = programs.borrow_mut();
+        let uniforms = programs.entry(program).or_default();
+        let location = uniforms.location(program, name);
+        if location != -1 {
+            let reflection = uniforms.reflection(program);
+            if let Some(uniform) = reflection
+                .uniforms
+                .iter_mut()
+                .find(|uniform| uniform.contains(location))
+            {
+                uniform.is_set = true;
+            }
+        }
+        location
+    })
+}
+
+pub(super) fn declares(program: GLuint, name: &CStr) -> bool {
+    PROGRAMS.with(|programs| {
+        programs
+            .borrow()
+            .get(&program)
+            .is_some_and(|uniforms| uniforms.declares(&name.to_string_lossy()))
+    })
+}
+
+/// Checks the uniforms before running the program. Setting uniforms it doesn't declare or leaving
+/// some unset panics in debug builds, release builds log each unset uniform once. Undeclared ones
+/// were already logged when they were set
+pub(super) unsafe fn validate(program: GLuint) {
+    let problems = PROGRAMS.with(|programs| {
+        let mut programs = programs.borrow_mut();
+        let uniforms = programs.entry(program).or_default();
+        let program_name = uniforms.name(program);
+        let reflection = uniforms.reflection(program);
+        let unknown = reflection.unknown_uniforms.clone();
+        let unset: Vec<String> = reflection
+            .unset_uniforms()
+            .map(|uniform| uniform.name.clone())
+            .collect();
+        if cfg!(debug_assertions) {
+            return (!unknown.is_empty() || !unset.is_empty()).then_some((
+                program_name,
+                unknown,
+                unset,
+            ));
+        }
+        for name in unset {
+            if uniforms.reported_unset.insert(name.clone()) {
+                log::warn!("Shader {program_name} runs without setting uniform {name}");
+            }
+        }
+        None
+    });
+    if let Some((program_name, unknown, unset)) = problems {
+        panic!(
+            "Shader {program_name} sets uniforms it doesn't declare: [{}], and runs without setting: [{}]",
+            unknown.join(", "),
+            unset.join(", ")
+        );
+    }
+}
+
+/// Uniforms of every program compiled from files, with the first file of each, sorted by it
+pub unsafe fn programs() -> Vec<(String, ProgramReflection)> {
+    PROGRAMS.with(|programs| {
+        let mut programs = programs.borrow_mut();
+        let mut reflections: Vec<_> = programs
+            .iter_mut()
+            // Programs can be deleted without `Shader::delete`
+            .filter(|(&program, _)| gl::IsProgram(program) == gl::TRUE)
+            .filter_map(|(&program, uniforms)| {
+                let name = uniforms.name.clone()?;
+                Some((name, uniforms.reflection(program).clone()))
+            })
+            .collect();
+        reflections.sort_by(|(a, _), (b, _)| a.cmp(b));
+        reflections
+    })
+}
+
+pub(super) unsafe fn reflection(program: GLuint) -> ProgramReflection {
+    PROGRAMS.with(|programs| {
+        programs
+            .borrow_mut()
+            .entry(program)
+            .or_default()
+            .reflection(program)
+            .clone()
+    })
+}
+
+/// Name `name` is declared with, members of structs and elements of arrays are declared with
+/// the name of the whole uniform
+fn declared_name(name: &str) -> &str {
+    name.split(['.', '[']).next().unwrap_or_default()
+}
+
+/// Names of the uniforms declared in preprocessed GLSL, like `b` and `c` in
+/// `layout (binding = 0) uniform mat4 b, c[2] = ...;`, and whether they have an initializer.
+/// Uniform blocks add their block name
+fn declared_uniforms(code: &str) -> Vec<(String, bool)> {
+    let is_identifier = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut names = Vec::new();
+    for line in code.lines() {
+        let line = line.split("//").next().unwrap_or_default();
+        let Some(position) = line.find("uniform ") else {
+            continue;
+        };
+        if line[..position].ends_with(is_identifier) {
+            continue;
+        }
+        let declaration = line[position + "uniform ".len()..]
+            .split(';')
+            .next()
+            .unwrap();
+        // Skips qualifiers like `layout (binding = 0, r32ui)`
+        let declaration = match declaration.trim_start().strip_prefix("layout") {
+            Some(qualified) => qualified.split_once(')').map_or("", |(_, rest)| rest),
+            None => declaration,
+        };
+        // Commas inside initializers like `vec3(1, 0, 0)` don't separate declarators
+        let mut depth = 0;
+        let declarators = declaration.split(|c| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            c == ',' && depth == 0
+        });
+        for declarator in declarators {
+            let has_initializer = declarator.contains('=');
+            // The name is the last word before the array size or initializer
+            let declarator = declarator.split(['[', '=']).next().unwrap();
+            if let Some(name) = declarator.split_whitespace().last() {
+                if name.chars().all(is_identifier) {
+                    names.push((name.to_string(), has_initializer));
+                }
+            }
+        }
+    }
+    names
+}
+
+unsafe fn query_uniforms(program: GLuint) -> Vec<Uniform> {
+    let mut count = 0;
+    gl::GetProgramiv(program, gl::ACTIVE_UNIFORMS, &mut count);
+    let mut max_name_length = 0;
+    gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_MAX_LENGTH, &mut max_name_length);
+
+    let mut uniforms = Vec::new();
+    for index in 0..count as GLuint {
+        let mut block_index = -1;
+        gl::GetActiveUniformsiv(
+            program,
+            1,
+            &index,
+            gl::UNIFORM_BLOCK_INDEX,
+            &mut block_index,
+        );
+        if block_index != -1 {
+            // Members of uniform blocks are set with their buffer
+            continue;
+        }
+
+        let mut name = vec![0u8; max_name_length.max(1) as usize];
+        let mut length: GLsizei = 0;
+        let mut size = 0;
+        let mut type_ = 0;
+        gl::GetActiveUniform(
+            program,
+            index,
+            name.len() as GLsizei,
+            &mut length,
+            &mut size,
+            &mut type_,
+            name.as_mut_ptr() as *mut _,
+        );
+        name.truncate(length as usize);
+        let name = String::from_utf8_lossy(&name).into_owned();
+        if name.starts_with("gl_") {
+            continue;
+        }
+
+        let kind = uniform_type(type_).map_or(UniformKind::Value, |(_, kind)| kind);
+        let location = match kind {
+            UniformKind::AtomicCounter => -1,
+            _ => {
+                let c_name = CString::new(name.as_str()).unwrap();
+                gl::GetUniformLocation(program, c_name.as_ptr())
+            }
+        };
+        let binding = match kind {
+            UniformKind::Sampler | UniformKind::Image => {
+                let mut binding = 0;
+                gl::GetUniformiv(program, location, &mut binding);
+                Some(binding)
+            }
+            _ => None,
+        };
+        uniforms.push(Uniform {
+            name,
+            location,
+            type_,
+            size,
+            kind,
+            binding,
+            is_set: false,
+            has_initializer: false,
+        });
+    }
+    uniforms
+}
+
+/// GLSL name and kind of the uniform types used in the shaders, `None` for other types
+fn uniform_type(type_: GLenum) -> Option<(&'static str, UniformKind)> {
+    use UniformKind::*;
+    let uniform_type = match type_ {
+        gl::FLOAT => ("float", Value),
+        gl::FLOAT_VEC2 => ("vec2", Value),
+        gl::FLOAT_VEC3 => ("vec3", Value),
+        gl::FLOAT_VEC4 => ("vec4", Value),
+        gl::INT => ("int", Value),
+        gl::UNSIGNED_INT => ("uint", Value),
+        gl::UNSIGNED_INT_VEC3 => ("uvec3", Value),
+        gl::BOOL => ("bool", Value),
+        gl::FLOAT_MAT3 => ("mat3", Value),
+        gl::FLOAT_MAT4 => ("mat4", Value),
+        gl::SAMPLER_2D => ("sampler2D", Sampler),
+        gl::SAMPLER_3D => ("sampler3D", Sampler),
+        gl::SAMPLER_BUFFER => ("samplerBuffer", Sampler),
+        gl::UNSIGNED_INT_SAMPLER_2D => ("usampler2D", Sampler),
+        gl::UNSIGNED_INT_SAMPLER_3D => ("usampler3D", Sampler),
+        gl::UNSIGNED_INT_SAMPLER_2D_ARRAY => ("usampler2DArray", Sampler),
+        gl::UNSIGNED_INT_SAMPLER_BUFFER => ("usamplerBuffer", Sampler),
+        gl::IMAGE_3D => ("image3D", Image),
+        gl::IMAGE_BUFFER => ("imageBuffer", Image),
+        gl::UNSIGNED_INT_IMAGE_3D => ("uimage3D", Image),
+        gl::UNSIGNED_INT_IMAGE_BUFFER => ("uimageBuffer", Image),
+        gl::UNSIGNED_INT_ATOMIC_COUNTER => ("atomic_uint", AtomicCounter),
+        _ => return None,
+    };
+    Some(uniform_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use c_str_macro::c_str;
+
+    use super::*;
+    use crate::{
+        shader::{Shader, ShaderDefines},
+        test_utils::init_opengl_context,
+    };
+
+    const SHADER_CODE: &str = r#"
+#version 460 core
+layout (local_size_x = 1) in;
+layout (binding = 3, r32ui) uniform uimageBuffer result;
+uniform layout(binding = 4, r32ui) readonly uimageBuffer previous;
+uniform uint value;
+uniform float offsets[2], unused; // uniform float commented;
+void main() { imageStore(result, 0, uvec4(value + uint(offsets[1]))); }
+"#;
+
+    fn uniform<'a>(reflection: &'a ProgramReflection, name: &str) -> &'a Uniform {
+        reflection
+            .uniforms
+            .iter()
+            .find(|uniform| uniform.name == name)
+            .unwrap_or_else(|| panic!("{name} should be active in {reflection:?}"))
+    }
+
+    #[test]
+    fn uniforms_are_declared_after_the_type() {
+        let names: Vec<_> = declared_uniforms(SHADER_CODE)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(
+            names,
+            vec!["result", "previous", "value", "offsets", "unused"]
+        );
+    }
+
+    #[test]
+    fn initialized_uniforms_dont_need_to_be_set() {
+        let code = "uniform float padding = 0.0, scale;\nuniform vec3 color = vec3(1, 0, 0), tint;";
+        assert_eq!(
+            declared_uniforms(code),
+            vec![
+                ("padding".to_string(), true),
+                ("scale".to_string(), false),
+                ("color".to_string(), true),
+                ("tint".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn active_uniforms_are_reflected_with_their_bindings() {
+        let _context = init_opengl_context();
+        let shader = Shader::new_compute_from_string(SHADER_CODE, &ShaderDefines::new()).unwrap();
+        let reflection = unsafe { shader.reflection() };
+
+        let result = uniform(&reflection, "result");
+        assert_eq!(result.kind, UniformKind::Image);
+        assert_eq!(result.type_name(), "uimageBuffer");
+        assert_eq!(result.binding, Some(3));
+        let offsets = uniform(&reflection, "offsets[0]");
+        assert_eq!((offsets.kind, offsets.size), (UniformKind::Value, 2));
+        assert_eq!(uniform(&reflection, "value").type_name(), "uint");
+        assert_eq!(
+            reflection.uniforms.len(),
+            3,
+            "unused uniforms aren't active"
+        );
+        unsafe { shader.delete() };
+    }
+
+    #[test]
+    fn only_undeclared_uniforms_are_unknown() {
+        let _context = init_opengl_context();
+        let shader = Shader::new_compute_from_string(SHADER_CODE, &ShaderDefines::new()).unwrap();
+        unsafe {
+            shader.set_uint(c_str!("value"), 1);
+            shader.set_float(c_str!("unused"), 1.0);
+            shader.set_float(c_str!("valeu"), 1.0);
+            shader.set_float(c_str!("valeu"), 1.0);
+
+            let reflection = shader.reflection();
+            assert_eq!(reflection.unknown_uniforms, vec!["valeu"]);
+            assert!(shader.declares_uniform(c_str!("unused")));
+            assert!(!shader.declares_uniform(c_str!("valeu")));
+            shader.delete();
+        }
+    }
+
+    #[test]
+    fn uniforms_are_set_until_linked_again() {
+        let _context = init_opengl_context();
+        let shader = Shader::new_compute_from_string(SHADER_CODE, &ShaderDefines::new()).unwrap();
+        let unset = |shader: &Shader| -> Vec<String> {
+            let reflection = unsafe { shader.reflection() };
+            reflection
+                .unset_uniforms()
+                .map(|uniform| uniform.name.clone())
+                .collect()
+        };
+        unsafe {
+            assert_eq!(unset(&shader), vec!["value", "offsets[0]"]);
+            shader.set_uint(c_str!("value"), 1);
+            shader.set_float(c_str!("offsets[1]"), 1.0);
+            assert!(unset(&shader).is_empty(), "any element sets the array");
+
+            let source = Source::from_string("<string>", SHADER_CODE);
+            Shader::link_into(shader.id, &[(ShaderStage::Compute, source)]).unwrap();
+            assert_eq!(unset(&shader).len(), 2, "linking again resets the values");
+            shader.delete();
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "runs without setting: [value, offsets[0]]")]
+    fn running_without_setting_uniforms_fails_in_debug_builds() {
+        let _context = init_opengl_context();
+        let shader = Shader::new_compute_from_string(SHADER_CODE, &ShaderDefines::new()).unwrap();
+        unsafe {
+            shader.use_program();
+            shader.dispatch(1);
+        }
+    }
+}
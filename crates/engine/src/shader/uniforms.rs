@@ -0,0 +1,161 @@
+//! Setting Rust values as uniforms by name, structs derive it with `#[derive(ShaderUniforms)]`
+
+use std::ffi::CString;
+
+use cgmath::{Matrix3, Matrix4, Point3, Vector2, Vector3};
+
+use super::Shader;
+
+/// Value that can be set in the uniforms named `prefix`. Structs set each field in
+/// `prefix.fieldName`, arrays each element in `prefix[index]`
+pub trait ShaderUniforms {
+    unsafe fn set_uniforms(&self, prefix: &str, shader: &Shader);
+}
+
+macro_rules! impl_shader_uniforms {
+    ($type:ty, |$value:ident, $name:ident, $shader:ident| $set:expr) => {
+        impl ShaderUniforms for $type {
+            unsafe fn set_uniforms(&self, prefix: &str, $shader: &Shader) {
+                let $value = self;
+                let $name = &CString::new(prefix).unwrap();
+                $set
+            }
+        }
+    };
+}
+
+impl_shader_uniforms!(bool, |value, name, shader| shader.set_bool(name, *value));
+impl_shader_uniforms!(i32, |value, name, shader| shader.set_int(name, *value));
+impl_shader_uniforms!(u32, |value, name, shader| shader.set_uint(name, *value));
+impl_shader_uniforms!(f32, |value, name, shader| shader.set_float(name, *value));
+impl_shader_uniforms!(Vector2<f32>, |value, name, shader| shader
+    .set_vec2(name, value.x, value.y));
+impl_shader_uniforms!(Vector3<f32>, |value, name, shader| {
+    shader.set_vec3(name, value.x, value.y, value.z)
+});
+impl_shader_uniforms!(Point3<f32>, |value, name, shader| {
+    shader.set_vec3(name, value.x, value.y, value.z)
+});
+impl_shader_uniforms!(Vector3<u32>, |value, name, shader| {
+    shader.set_uvec3(name, value.x, value.y, value.z)
+});
+impl_shader_uniforms!(Matrix3<f32>, |value, name, shader| shader
+    .set_mat3(name, value));
+impl_shader_uniforms!(Matrix4<f32>, |value, name, shader| shader
+    .set_mat4(name, value));
+
+impl<T: ShaderUniforms> ShaderUniforms for [T] {
+    unsafe fn set_uniforms(&self, prefix: &str, shader: &Shader) {
+        for (index, element) in self.iter().enumerate() {
+            element.set_uniforms(&format!("{prefix}[{index}]"), shader);
+        }
+    }
+}
+
+impl<T: ShaderUniforms, const N: usize> ShaderUniforms for [T; N] {
+    unsafe fn set_uniforms(&self, prefix: &str, shader: &Shader) {
+        self.as_slice().set_uniforms(prefix, shader);
+    }
+}
+
+impl<T: ShaderUniforms> ShaderUniforms for Vec<T> {
+    unsafe fn set_uniforms(&self, prefix: &str, shader: &Shader) {
+        self.as_slice().set_uniforms(prefix, shader);
+    }
+}
+
+impl<T: ShaderUniforms + ?Sized> ShaderUniforms for &T {
+    unsafe fn set_uniforms(&self, prefix: &str, shader: &Shader) {
+        (**self).set_uniforms(prefix, shader);
+    }
+}
+
+/// Half of an angle in degrees, in radians. Cones are usually set by their whole angle in degrees,
+/// but GLSL uses the angle between their axis and side, like `#[uniform(with = half_angle_in_radians)]`
+pub fn half_angle_in_radians(degrees: &f32) -> f32 {
+    degrees.to_radians() / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use c_str_macro::c_str;
+    use cgmath::vec3;
+
+    use super::*;
+    use crate::{
+        shader::{ShaderDefines, ShaderUniforms},
+        test_utils::init_opengl_context,
+    };
+
+    const SHADER_CODE: &str = r#"
+#version 460 core
+layout (local_size_x = 1) in;
+layout (binding = 0, r32f) uniform imageBuffer result;
+struct Cone {
+    float halfAngle;
+    vec3 axes[2];
+};
+uniform Cone cones[2];
+void main() {
+    for (int i = 0; i < 2; i++) {
+        imageStore(result, i * 3, vec4(cones[i].halfAngle));
+        imageStore(result, i * 3 + 1, vec4(cones[i].axes[0].x));
+        imageStore(result, i * 3 + 2, vec4(cones[i].axes[1].z));
+    }
+}
+"#;
+
+    #[derive(ShaderUniforms)]
+    struct Cone {
+        #[uniform(rename = "halfAngle", with = half_angle_in_radians)]
+        angle_in_degrees: f32,
+        axes: [Vector3<f32>; 2],
+        #[uniform(skip)]
+        _name: String,
+    }
+
+    #[test]
+    fn nested_structs_and_arrays_are_set_by_name() {
+        let _context = init_opengl_context();
+        let shader = Shader::new_compute_from_string(SHADER_CODE, &ShaderDefines::new()).unwrap();
+        let cones = vec![
+            Cone {
+                angle_in_degrees: 90.0,
+                axes: [vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, 2.0)],
+                _name: "first".to_string(),
+            },
+            Cone {
+                angle_in_degrees: 180.0,
+                axes: [vec3(3.0, 0.0, 0.0), vec3(0.0, 0.0, 4.0)],
+                _name: "second".to_string(),
+            },
+        ];
+
+        let mut values = [0f32; 6];
+        unsafe {
+            let mut buffer = 0;
+            let mut texture = 0;
+            gl::GenBuffers(1, &mut buffer);
+            gl::BindBuffer(gl::TEXTURE_BUFFER, buffer);
+            gl::BufferData(gl::TEXTURE_BUFFER, 4 * 6, std::ptr::null(), gl::STATIC_DRAW);
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_BUFFER, texture);
+            gl::TexBuffer(gl::TEXTURE_BUFFER, gl::R32F, buffer);
+
+            shader.use_program();
+            cones.set_uniforms("cones", &shader);
+            assert_eq!(shader.reflection().unset_uniforms().count(), 0);
+            gl::BindImageTexture(0, texture, 0, gl::FALSE, 0, gl::WRITE_ONLY, gl::R32F);
+            shader.dispatch(1);
+            shader.wait_for(gl::BUFFER_UPDATE_BARRIER_BIT);
+            gl::GetBufferSubData(gl::TEXTURE_BUFFER, 0, 4 * 6, values.as_mut_ptr() as *mut _);
+
+            gl::DeleteTextures(1, &texture);
+            gl::DeleteBuffers(1, &buffer);
+            assert!(shader.declares_uniform(c_str!("cones[1].axes[0]")));
+            shader.delete();
+        }
+        let half_pi = std::f32::consts::FRAC_PI_2;
+        assert_eq!(values, [half_pi / 2.0, 1.0, 2.0, half_pi, 3.0, 4.0]);
+    }
+}
@@ -1,33 +1,16 @@
-use egui_glfw_gl::glfw::{self, Context};
+use crate::headless::HeadlessContext;
 
-pub fn init_opengl_context() -> (glfw::Glfw, glfw::Window) {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
-    
-    // No need for window to be visible for tests
-    glfw.window_hint(glfw::WindowHint::Visible(false));
-
-    // OpenGL version hints
-    glfw.window_hint(glfw::WindowHint::ContextVersion(4, 6));
-    glfw.window_hint(glfw::WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Core));
-
-    // Create a windowed mode window and its OpenGL context
-    let (mut window, _) = glfw
-        .create_window(640, 480, "Hidden Window", glfw::WindowMode::Windowed)
-        .expect("Failed to create GLFW window.");
-
-    window.make_current();
-    window.set_key_polling(true);
-
-    gl::load_with(|s| window.get_proc_address(s) as *const _);
-
-    (glfw, window)
+/// Surfaceless EGL context, so tests run without a display server nor GPU.
+/// Keep it alive for the whole test
+pub fn init_opengl_context() -> HeadlessContext {
+    unsafe { HeadlessContext::new(640, 480, false) }
 }
 
 #[test]
 fn compute_shader_works() {
     use crate::prelude::*;
 
-    let (_glfw, _window) = init_opengl_context();
+    let _context = init_opengl_context();
 
     // Test shader code that just reads and writes to a buffer
     let shader_code = r#"
@@ -44,7 +27,7 @@ fn compute_shader_works() {
         }
     "#;
 
-    let shader = Shader::new_compute_from_string(&shader_code);
+    let shader = Shader::new_compute_from_string(&shader_code, &ShaderDefines::default()).unwrap();
 
     unsafe {
         shader.use_program();
@@ -8,7 +8,9 @@ use cgmath::{
 use gl::types::GLuint;
 use serde::{Deserialize, Serialize};
 
-use super::prelude::{compile_shaders, Aabb, Framebuffer, Object, RenderGizmo, Shader};
+use super::prelude::{
+    compile_shaders, Aabb, Framebuffer, Object, RenderGizmo, Shader, ShaderError,
+};
 use super::types::*;
 
 /// Struct that handles `position`, `rotation` and `scale` for an entity
@@ -28,11 +30,12 @@ pub struct Transform {
     right: Vector3<f32>,
     #[serde(default = "default_movement_speed")]
     pub movement_speed: f32,
+    /// The VAO and shaders are only created by `init`
     #[serde(skip)]
     pub vao: GLuint,
-    #[serde(skip, default = "gizmo_shader")]
+    #[serde(skip)]
     shader: Shader,
-    #[serde(skip, default = "default_view_map_shader")]
+    #[serde(skip)]
     view_map_shader: Shader, // TODO: It's kind of ugly to store this here
 }
 
@@ -64,6 +67,8 @@ pub enum Direction {
 }
 use self::Direction::*;
 
+/// Only the position, rotation and scale, without the VAO and shaders of `init`.
+/// Enough for transforms that are never drawn, like the cameras of the voxelization
 impl Default for Transform {
     fn default() -> Self {
         let mut this = Self {
@@ -73,20 +78,17 @@ impl Default for Transform {
             forward: vec3(0.0, 0.0, 1.0),
             up: Vector3::zero(),    // Initialized later
             right: Vector3::zero(), // Initialized later
-            vao: 0,                 // Initialized later
+            vao: 0,                 // Initialized in `init`
             movement_speed: 1.0,
-            shader: gizmo_shader(),
-            view_map_shader: default_view_map_shader(),
+            shader: Shader::default(),
+            view_map_shader: Shader::default(),
         };
-        unsafe {
-            this.setup_vao();
-        }
         this.update_vectors();
         this
     }
 }
 
-fn gizmo_shader() -> Shader {
+fn gizmo_shader() -> Result<Shader, ShaderError> {
     compile_shaders!(
         "assets/shaders/debug/cubicGizmo.vert.glsl",
         "assets/shaders/debug/cubicGizmo.frag.glsl",
@@ -95,19 +97,18 @@ fn gizmo_shader() -> Shader {
 }
 
 // TODO: Weird to have this here
-fn default_view_map_shader() -> Shader {
+fn view_map_shader() -> Result<Shader, ShaderError> {
     compile_shaders!("assets/shaders/octree/viewMap.glsl")
 }
 
 impl RenderGizmo for Transform {
-    unsafe fn draw_gizmo(&self, projection: &Matrix4<f32>, view: &Matrix4<f32>) {
+    unsafe fn draw_gizmo(&self) {
         self.shader.use_program();
 
-        self.shader.set_mat4(c_str!("projection"), projection);
-        self.shader.set_mat4(c_str!("view"), view);
         self.shader
             .set_mat4(c_str!("model"), &self.get_model_matrix());
 
+        self.shader.validate();
         gl::BindVertexArray(self.vao);
         gl::DrawArrays(gl::POINTS, 0, 1);
         gl::BindVertexArray(0);
@@ -115,6 +116,30 @@ impl RenderGizmo for Transform {
 }
 
 impl Transform {
+    /// A default transform that can draw its gizmo and take photos
+    pub unsafe fn new() -> Result<Self, ShaderError> {
+        let mut transform = Self::default();
+        transform.init()?;
+        Ok(transform)
+    }
+
+    /// Compiles the shaders of the gizmo and the view map and creates the VAO of the gizmo.
+    /// Deserialized transforms only have their position, rotation and scale, this has to be called
+    /// once before they draw their gizmo or take photos
+    pub unsafe fn init(&mut self) -> Result<(), ShaderError> {
+        self.shader = gizmo_shader()?;
+        self.view_map_shader = match view_map_shader() {
+            Ok(shader) => shader,
+            Err(error) => {
+                std::mem::take(&mut self.shader).delete();
+                return Err(error);
+            }
+        };
+        self.setup_vao();
+        self.update_vectors();
+        Ok(())
+    }
+
     pub fn get_model_matrix(&self) -> Matrix4<f32> {
         let mut model = Matrix4::<f32>::from_angle_z(Deg(self.rotation.z))
             * Matrix4::<f32>::from_angle_y(Deg(90.0 - self.rotation.y))
@@ -234,22 +259,15 @@ impl Transform {
     }
 
     /// Writes to a framebuffer from the transform's POV of `objects`.
-    /// Used to get geometry buffers
+    /// Used to get geometry buffers. The view and projection come from the `Camera` uniform
+    /// block, which should be updated with the camera of this transform
     pub unsafe fn take_photo<const N: usize>(
         &self,
         objects: &mut [Object],
-        projection: &Matrix4<f32>,
         scene_aabb: &Aabb,
         framebuffer: &Framebuffer<N>,
-        voxel_dimension: u32, // TODO: Find another way. This breaks separation of concerns
     ) -> Textures<N> {
         self.view_map_shader.use_program();
-        self.view_map_shader
-            .set_mat4(c_str!("projection"), &projection);
-        self.view_map_shader
-            .set_mat4(c_str!("view"), &self.get_view_matrix());
-        self.view_map_shader
-            .set_uint(c_str!("voxelDimension"), voxel_dimension);
 
         gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.fbo());
         gl::Enable(gl::DEPTH_TEST);
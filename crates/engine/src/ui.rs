@@ -17,7 +17,8 @@ use once_cell::sync::OnceCell;
 static mut INSTANCE: OnceCell<Ui> = OnceCell::new();
 
 pub struct Ui {
-    painter: egui_backend::Painter,
+    /// There's nothing to paint on without a window
+    painter: Option<egui_backend::Painter>,
     context: egui::Context,
     input_state: egui_backend::EguiInputState,
     modifier_keys: egui::Modifiers,
@@ -64,7 +65,7 @@ impl Ui {
         });
         let modifier_keys = egui::Modifiers::default();
         let ui = Ui {
-            painter,
+            painter: Some(painter),
             context,
             input_state,
             modifier_keys,
@@ -74,6 +75,29 @@ impl Ui {
         unsafe { let _ = INSTANCE.set(ui); }
     }
 
+    /// Without a window the menus still run, so presets apply the same,
+    /// but nothing gets painted
+    pub fn setup_headless(viewport_width: i32, viewport_height: i32) {
+        let native_pixels_per_point = 1.0;
+        let input_state = egui_backend::EguiInputState::new(egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::new(0_f32, 0_f32),
+                egui::vec2(viewport_width as f32, viewport_height as f32),
+            )),
+            pixels_per_point: Some(native_pixels_per_point),
+            ..Default::default()
+        });
+        let ui = Ui {
+            painter: None,
+            context: egui::Context::default(),
+            input_state,
+            modifier_keys: egui::Modifiers::default(),
+            native_pixels_per_point,
+            is_showing: false,
+        };
+        unsafe { let _ = INSTANCE.set(ui); }
+    }
+
     pub fn toggle_showing(&mut self) {
         self.is_showing = !self.is_showing;
     }
@@ -112,24 +136,29 @@ impl Ui {
                 platform_output.copied_text,
             );
         }
+        let Some(painter) = self.painter.as_mut() else {
+            return;
+        };
         let clipped_shapes = self.context.tessellate(shapes);
-        self.painter
-            .paint_and_update_textures(1.0, &clipped_shapes, &textures_delta);
+        painter.paint_and_update_textures(1.0, &clipped_shapes, &textures_delta);
     }
 
+    /// Does nothing without a window
     pub fn set_cursor_mode(mode: glfw::CursorMode) {
         unsafe {
-            let mut binding = WINDOW.borrow_mut();
-            let window = binding.as_mut().unwrap();
-            window.set_cursor_mode(mode);
+            if let Some(window) = WINDOW.borrow_mut().as_mut() {
+                window.set_cursor_mode(mode);
+            }
         }
     }
 
+    /// Always the origin without a window
     pub fn get_cursor_pos() -> (f64, f64) {
         unsafe {
-            let binding = WINDOW.borrow();
-            let window = binding.as_ref().unwrap();
-            window.get_cursor_pos()
+            WINDOW
+                .borrow()
+                .as_ref()
+                .map_or((0.0, 0.0), |window| window.get_cursor_pos())
         }
     }
 
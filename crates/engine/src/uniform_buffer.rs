@@ -0,0 +1,238 @@
+//! Uniform buffers, to share uniforms between shaders instead of setting them in each one.
+//!
+//! A block like `layout (std140, binding = 0) uniform Camera { ... };` is read by every shader
+//! that declares it from the buffer bound to its binding, so it's uploaded and bound once per
+//! frame. The shared blocks are declared in `assets/shaders/_uniformBlocks.glsl`.
+
+use std::marker::PhantomData;
+
+use cgmath::{Matrix4, Point3, Vector3};
+use gl::types::{GLsizeiptr, GLuint};
+
+/// Data of a uniform block, written with the std140 layout
+pub trait UniformBlock {
+    /// `binding` of the block in GLSL
+    const BINDING: GLuint;
+
+    /// Writes the members in the order they are declared in GLSL
+    fn write_std140(&self, buffer: &mut Std140);
+}
+
+/// Bytes of a uniform block with the std140 layout, every value is aligned as GLSL expects.
+/// Scalars take 4 bytes, `vec3` and `vec4` start at multiples of 16 and matrices are
+/// written as columns of `vec4`s
+#[derive(Debug, Default)]
+pub struct Std140 {
+    bytes: Vec<u8>,
+}
+
+impl Std140 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn float(&mut self, value: f32) -> &mut Self {
+        self.push(4, &value.to_ne_bytes())
+    }
+
+    pub fn uint(&mut self, value: u32) -> &mut Self {
+        self.push(4, &value.to_ne_bytes())
+    }
+
+    pub fn int(&mut self, value: i32) -> &mut Self {
+        self.push(4, &value.to_ne_bytes())
+    }
+
+    /// GLSL `bool`s take 4 bytes
+    pub fn bool(&mut self, value: bool) -> &mut Self {
+        self.uint(value as u32)
+    }
+
+    /// The next scalar can go in its fourth component
+    pub fn vec3(&mut self, value: Vector3<f32>) -> &mut Self {
+        self.align(16);
+        self.float(value.x).float(value.y).float(value.z)
+    }
+
+    pub fn point3(&mut self, value: Point3<f32>) -> &mut Self {
+        self.vec3(value.to_homogeneous().truncate())
+    }
+
+    pub fn mat4(&mut self, value: &Matrix4<f32>) -> &mut Self {
+        let columns: &[[f32; 4]; 4] = value.as_ref();
+        for column in columns {
+            self.align(16);
+            for component in column {
+                self.float(*component);
+            }
+        }
+        self
+    }
+
+    /// A struct, or an element of an array. They start and end at multiples of 16
+    pub fn structure(&mut self, write_members: impl FnOnce(&mut Self)) -> &mut Self {
+        self.align(16);
+        write_members(self);
+        self.align(16);
+        self
+    }
+
+    fn push(&mut self, alignment: usize, bytes: &[u8]) -> &mut Self {
+        self.align(alignment);
+        self.bytes.extend_from_slice(bytes);
+        self
+    }
+
+    fn align(&mut self, alignment: usize) {
+        self.bytes
+            .resize(self.bytes.len().next_multiple_of(alignment), 0);
+    }
+}
+
+/// Buffer for a uniform block, its shaders read it after `update` binds it
+pub struct UniformBuffer<T> {
+    buffer: GLuint,
+    _block: PhantomData<T>,
+}
+
+impl<T: UniformBlock> UniformBuffer<T> {
+    pub unsafe fn new() -> Self {
+        let mut buffer = 0;
+        gl::GenBuffers(1, &mut buffer);
+        Self {
+            buffer,
+            _block: PhantomData,
+        }
+    }
+
+    /// Uploads `block` and binds the buffer to its binding, for every shader that declares it
+    pub unsafe fn update(&self, block: &T) {
+        // Padded like a struct, the size of the block in GLSL
+        let mut std140 = Std140::new();
+        std140.structure(|buffer| block.write_std140(buffer));
+        let bytes = std140.bytes();
+        gl::BindBuffer(gl::UNIFORM_BUFFER, self.buffer);
+        // Discards the previous data instead of waiting for the shaders still reading it
+        gl::BufferData(
+            gl::UNIFORM_BUFFER,
+            bytes.len() as GLsizeiptr,
+            bytes.as_ptr() as *const _,
+            gl::DYNAMIC_DRAW,
+        );
+        gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        gl::BindBufferBase(gl::UNIFORM_BUFFER, T::BINDING, self.buffer);
+    }
+
+    pub unsafe fn delete(self) {
+        gl::DeleteBuffers(1, &self.buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::vec3;
+
+    use super::*;
+    use crate::{
+        shader::{Shader, ShaderDefines},
+        test_utils::init_opengl_context,
+    };
+
+    struct Block {
+        scale: f32,
+        offset: Vector3<f32>,
+        count: u32,
+        transform: Matrix4<f32>,
+        items: [(bool, f32); 2],
+    }
+
+    impl UniformBlock for Block {
+        const BINDING: GLuint = 3;
+
+        fn write_std140(&self, buffer: &mut Std140) {
+            buffer
+                .float(self.scale)
+                .vec3(self.offset)
+                .uint(self.count)
+                .mat4(&self.transform);
+            for (is_enabled, value) in self.items {
+                buffer.structure(|item| {
+                    item.bool(is_enabled).float(value);
+                });
+            }
+        }
+    }
+
+    const SHADER_CODE: &str = r#"
+#version 460 core
+layout (local_size_x = 1) in;
+layout (binding = 0, r32f) uniform imageBuffer result;
+struct Item {
+    bool isEnabled;
+    float value;
+};
+layout (std140, binding = 3) uniform Block {
+    float scale;
+    vec3 offset;
+    uint count;
+    mat4 transform;
+    Item items[2];
+};
+void main() {
+    imageStore(result, 0, vec4(scale));
+    imageStore(result, 1, vec4(offset.z));
+    imageStore(result, 2, vec4(count));
+    imageStore(result, 3, vec4(transform[3].x));
+    imageStore(result, 4, vec4(items[1].isEnabled ? items[1].value : -1.0));
+}
+"#;
+
+    #[test]
+    fn offsets_match_std140() {
+        let block = Block {
+            scale: 1.0,
+            offset: vec3(0.0, 0.0, 2.0),
+            count: 3,
+            transform: Matrix4::from_translation(vec3(4.0, 0.0, 0.0)),
+            items: [(false, 0.0), (true, 5.0)],
+        };
+        let mut std140 = Std140::new();
+        block.write_std140(&mut std140);
+        // float, vec3 at 16, uint in its fourth component, mat4 at 32 and the items at 96 and 112
+        assert_eq!(std140.bytes().len(), 128);
+        assert_eq!(&std140.bytes()[28..32], &3u32.to_ne_bytes());
+        assert_eq!(&std140.bytes()[112..116], &1u32.to_ne_bytes());
+
+        let _context = init_opengl_context();
+        let shader = Shader::new_compute_from_string(SHADER_CODE, &ShaderDefines::new()).unwrap();
+        let mut values = [0f32; 5];
+        unsafe {
+            let mut buffer = 0;
+            let mut texture = 0;
+            gl::GenBuffers(1, &mut buffer);
+            gl::BindBuffer(gl::TEXTURE_BUFFER, buffer);
+            gl::BufferData(gl::TEXTURE_BUFFER, 4 * 5, std::ptr::null(), gl::STATIC_DRAW);
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_BUFFER, texture);
+            gl::TexBuffer(gl::TEXTURE_BUFFER, gl::R32F, buffer);
+
+            let uniform_buffer = UniformBuffer::<Block>::new();
+            uniform_buffer.update(&block);
+            shader.use_program();
+            gl::BindImageTexture(0, texture, 0, gl::FALSE, 0, gl::WRITE_ONLY, gl::R32F);
+            shader.dispatch(1);
+            shader.wait_for(gl::BUFFER_UPDATE_BARRIER_BIT);
+            gl::GetBufferSubData(gl::TEXTURE_BUFFER, 0, 4 * 5, values.as_mut_ptr() as *mut _);
+
+            uniform_buffer.delete();
+            gl::DeleteTextures(1, &texture);
+            gl::DeleteBuffers(1, &buffer);
+            shader.delete();
+        }
+        assert_eq!(values, [1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+}
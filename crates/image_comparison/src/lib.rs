@@ -0,0 +1,150 @@
+//! Pixel by pixel comparison of images, used by the visual tests and the benchmarks.
+//! It doesn't need an OpenGL context, so tools that only compare screenshots can use it.
+
+use std::{fs, path::PathBuf};
+
+use image::{ImageBuffer, Pixel, Rgba, RgbaImage};
+
+/// Result of comparing a rendered image against a reference one
+#[derive(Debug)]
+pub struct ImageComparison {
+    /// Number of pixels with at least one channel outside the tolerance
+    pub different_pixels: u32,
+    /// Total number of pixels compared
+    pub total_pixels: u32,
+    /// The reference image darkened, with the differing pixels painted red
+    pub diff_image: RgbaImage,
+}
+
+impl ImageComparison {
+    pub fn images_match(&self) -> bool {
+        self.different_pixels == 0
+    }
+
+    /// Saves the diff image, creating the parent directories if needed
+    pub fn save_diff_image(&self, filepath: &str) {
+        let error_message = format!("Failed to save the diff image, path: {:?}", filepath);
+        let path = PathBuf::from(filepath);
+        let parent_dir = path.parent().expect("No parent");
+        fs::create_dir_all(parent_dir).expect(&error_message);
+        self.diff_image.save(path).expect(&error_message);
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageComparisonError {
+    /// The reference image couldn't be opened
+    MissingReference(image::ImageError),
+    /// The images have different sizes so they can't be compared pixel by pixel
+    DifferentDimensions {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+}
+
+impl std::fmt::Display for ImageComparisonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingReference(error) => write!(f, "Couldn't open reference image: {error}"),
+            Self::DifferentDimensions { expected, actual } => write!(
+                f,
+                "Expected an image of {}x{} but got {}x{}",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+        }
+    }
+}
+
+/// Compares `image` against `reference` pixel by pixel, ignoring alpha.
+/// A pixel is considered different if any of its channels differs by more than `tolerance`.
+pub fn compare_images(
+    image: &RgbaImage,
+    reference: &RgbaImage,
+    tolerance: f32,
+) -> Result<ImageComparison, ImageComparisonError> {
+    if image.dimensions() != reference.dimensions() {
+        return Err(ImageComparisonError::DifferentDimensions {
+            expected: reference.dimensions(),
+            actual: image.dimensions(),
+        });
+    }
+
+    let channel_tolerance = (tolerance.clamp(0.0, 1.0) * 255.0).round() as i16;
+    let (width, height) = image.dimensions();
+    let mut different_pixels = 0;
+
+    let diff_image = ImageBuffer::from_fn(width, height, |x, y| {
+        let pixel = image.get_pixel(x, y).to_rgb();
+        let reference_pixel = reference.get_pixel(x, y).to_rgb();
+        let is_different = (0..3).any(|channel| {
+            (pixel[channel] as i16 - reference_pixel[channel] as i16).abs() > channel_tolerance
+        });
+        if is_different {
+            different_pixels += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            // Darkened so the differences stand out
+            Rgba([
+                reference_pixel[0] / 4,
+                reference_pixel[1] / 4,
+                reference_pixel[2] / 4,
+                255,
+            ])
+        }
+    });
+
+    Ok(ImageComparison {
+        different_pixels,
+        total_pixels: width * height,
+        diff_image,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_images_match() {
+        let image = RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 255]));
+        let comparison = compare_images(&image, &image.clone(), 0.0).unwrap();
+        assert!(comparison.images_match());
+        assert_eq!(comparison.total_pixels, 16);
+    }
+
+    #[test]
+    fn differences_within_tolerance_match() {
+        let image = RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 255]));
+        let reference = RgbaImage::from_pixel(4, 4, Rgba([110, 95, 100, 0]));
+        assert!(compare_images(&image, &reference, 0.05)
+            .unwrap()
+            .images_match());
+        assert!(!compare_images(&image, &reference, 0.02)
+            .unwrap()
+            .images_match());
+    }
+
+    #[test]
+    fn diff_image_marks_different_pixels() {
+        let image = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 255]));
+        let mut reference = image.clone();
+        reference.put_pixel(1, 0, Rgba([255, 255, 255, 255]));
+        let comparison = compare_images(&image, &reference, 0.05).unwrap();
+        assert_eq!(comparison.different_pixels, 1);
+        assert_eq!(
+            *comparison.diff_image.get_pixel(1, 0),
+            Rgba([255, 0, 0, 255])
+        );
+        assert_eq!(*comparison.diff_image.get_pixel(0, 0), Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn different_dimensions_dont_compare() {
+        let image = RgbaImage::new(2, 2);
+        let reference = RgbaImage::new(3, 2);
+        assert!(matches!(
+            compare_images(&image, &reference, 0.05),
+            Err(ImageComparisonError::DifferentDimensions { .. })
+        ));
+    }
+}
@@ -1,6 +1,7 @@
 use proc_macro::TokenStream;
 use syn::{parse_macro_input, DeriveInput};
 
+mod shader_uniforms;
 mod sub_menus;
 
 /// Expects the following to be in scope:
@@ -15,3 +16,24 @@ pub fn simplify_sub_menus(input: TokenStream) -> TokenStream {
         .unwrap_or_else(syn::Error::into_compile_error)
         .into()
 }
+
+/// Implements `ShaderUniforms` for a struct, setting each field in the uniform `prefix.fieldName`,
+/// or `fieldName` if `prefix` is empty. Fields can be anything implementing `ShaderUniforms`,
+/// like other structs deriving it, arrays, numbers or cgmath vectors and matrices.
+///
+/// Fields accept `#[uniform(...)]` with:
+/// - `rename = "name"`: GLSL name, instead of the field in camel case
+/// - `with = path::to::function`: sets `function(&field)` instead of the field
+/// - `flatten`: sets the fields of the field as if they were in this struct
+/// - `skip`: isn't a uniform
+///
+/// Expects the following to be in scope:
+/// - `ShaderUniforms` trait
+/// - `Shader`
+#[proc_macro_derive(ShaderUniforms, attributes(uniform))]
+pub fn shader_uniforms(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    shader_uniforms::shader_uniforms_inner(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
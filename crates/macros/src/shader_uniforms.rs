@@ -0,0 +1,115 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DataStruct, DeriveInput, Error, Field, Fields, FieldsNamed, LitStr, Path, Result};
+
+pub(crate) fn shader_uniforms_inner(input: DeriveInput) -> Result<TokenStream2> {
+    match &input.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(fields),
+            ..
+        }) => generate_for_fields(&input, fields),
+        _ => Err(Error::new_spanned(&input, "Expected a named struct")),
+    }
+}
+
+/// What `#[uniform(...)]` says about a field
+#[derive(Default)]
+struct FieldOptions {
+    rename: Option<LitStr>,
+    with: Option<Path>,
+    skip: bool,
+    flatten: bool,
+}
+
+impl FieldOptions {
+    fn parse(field: &Field) -> Result<Self> {
+        let mut options = Self::default();
+        for attribute in field
+            .attrs
+            .iter()
+            .filter(|attribute| attribute.path().is_ident("uniform"))
+        {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    options.rename = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("with") {
+                    options.with = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("skip") {
+                    options.skip = true;
+                } else if meta.path.is_ident("flatten") {
+                    options.flatten = true;
+                } else {
+                    return Err(meta.error(
+                        "Unknown uniform option, expected `rename`, `with`, `skip` or `flatten`",
+                    ));
+                }
+                Ok(())
+            })?;
+        }
+        if options.flatten && options.rename.is_some() {
+            return Err(Error::new_spanned(
+                field,
+                "A flattened field has no name to rename",
+            ));
+        }
+        Ok(options)
+    }
+}
+
+fn generate_for_fields(input: &DeriveInput, fields: &FieldsNamed) -> Result<TokenStream2> {
+    let mut set_fields = Vec::new();
+    for field in fields.named.iter() {
+        let options = FieldOptions::parse(field)?;
+        if options.skip {
+            continue;
+        }
+        let ident = field.ident.as_ref().unwrap();
+        let value = match &options.with {
+            Some(with) => quote! { &#with(&self.#ident) },
+            None => quote! { &self.#ident },
+        };
+        let name = if options.flatten {
+            quote! { prefix }
+        } else {
+            let glsl_name = options
+                .rename
+                .map(|rename| rename.value())
+                .unwrap_or_else(|| to_camel_case(&ident.to_string()));
+            quote! {
+                &if prefix.is_empty() {
+                    String::from(#glsl_name)
+                } else {
+                    format!("{}.{}", prefix, #glsl_name)
+                }
+            }
+        };
+        set_fields.push(quote! { ShaderUniforms::set_uniforms(#value, #name, shader); });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ShaderUniforms for #name #type_generics #where_clause {
+            unsafe fn set_uniforms(&self, prefix: &str, shader: &Shader) {
+                #( #set_fields )*
+            }
+        }
+    })
+}
+
+/// GLSL names are in camel case, like `half_cone_angle` -> `halfConeAngle`
+fn to_camel_case(name: &str) -> String {
+    let name = name.strip_prefix("r#").unwrap_or(name);
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .enumerate()
+        .map(|(index, word)| {
+            let mut c = word.chars();
+            match c.next() {
+                Some(f) if index > 0 => f.to_uppercase().collect::<String>() + c.as_str(),
+                Some(f) => f.to_string() + c.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,12 @@
+use macros::ShaderUniforms;
+
+struct Shader;
+
+trait ShaderUniforms {
+    unsafe fn set_uniforms(&self, prefix: &str, shader: &Shader);
+}
+
+#[derive(ShaderUniforms)]
+struct Position(f32, f32);
+
+fn main() {}
@@ -0,0 +1,15 @@
+use macros::ShaderUniforms;
+
+struct Shader;
+
+trait ShaderUniforms {
+    unsafe fn set_uniforms(&self, prefix: &str, shader: &Shader);
+}
+
+#[derive(ShaderUniforms)]
+struct Cone {
+    #[uniform(name = "halfAngle")]
+    angle: f32,
+}
+
+fn main() {}
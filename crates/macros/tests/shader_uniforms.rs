@@ -0,0 +1,102 @@
+use std::cell::RefCell;
+
+use macros::ShaderUniforms;
+
+/// Records the uniforms instead of setting them
+#[derive(Default)]
+struct Shader {
+    uniforms: RefCell<Vec<(String, String)>>,
+}
+
+trait ShaderUniforms {
+    unsafe fn set_uniforms(&self, prefix: &str, shader: &Shader);
+}
+
+impl ShaderUniforms for f32 {
+    unsafe fn set_uniforms(&self, prefix: &str, shader: &Shader) {
+        shader
+            .uniforms
+            .borrow_mut()
+            .push((prefix.to_string(), self.to_string()));
+    }
+}
+
+impl<T: ShaderUniforms, const N: usize> ShaderUniforms for [T; N] {
+    unsafe fn set_uniforms(&self, prefix: &str, shader: &Shader) {
+        for (index, element) in self.iter().enumerate() {
+            element.set_uniforms(&format!("{prefix}[{index}]"), shader);
+        }
+    }
+}
+
+impl<T: ShaderUniforms> ShaderUniforms for &T {
+    unsafe fn set_uniforms(&self, prefix: &str, shader: &Shader) {
+        (**self).set_uniforms(prefix, shader);
+    }
+}
+
+fn half_angle(degrees: &f32) -> f32 {
+    degrees / 2.0
+}
+
+#[derive(ShaderUniforms)]
+struct Cone {
+    #[uniform(rename = "halfAngle", with = half_angle)]
+    angle: f32,
+    max_distance: f32,
+}
+
+#[derive(ShaderUniforms)]
+struct Common {
+    exposure: f32,
+}
+
+#[derive(ShaderUniforms)]
+struct Frame<'a> {
+    cones: [Cone; 2],
+    #[uniform(flatten)]
+    common: &'a Common,
+    #[uniform(skip)]
+    _name: String,
+}
+
+fn main() {
+    let shader = Shader::default();
+    let common = Common { exposure: 1.0 };
+    let frame = Frame {
+        cones: [
+            Cone {
+                angle: 90.0,
+                max_distance: 2.0,
+            },
+            Cone {
+                angle: 30.0,
+                max_distance: 3.0,
+            },
+        ],
+        common: &common,
+        _name: String::from("frame"),
+    };
+    unsafe {
+        frame.set_uniforms("", &shader);
+        common.set_uniforms("common", &shader);
+    }
+
+    let uniforms: Vec<String> = shader
+        .uniforms
+        .borrow()
+        .iter()
+        .map(|(name, value)| format!("{name} = {value}"))
+        .collect();
+    assert_eq!(
+        uniforms,
+        vec![
+            "cones[0].halfAngle = 45",
+            "cones[0].maxDistance = 2",
+            "cones[1].halfAngle = 15",
+            "cones[1].maxDistance = 3",
+            "exposure = 1",
+            "common.exposure = 1",
+        ]
+    );
+}
@@ -4,4 +4,7 @@ use trybuild::TestCases;
 fn tests() {
     let t = TestCases::new();
     t.pass("tests/sub_menus.rs");
+    t.pass("tests/shader_uniforms.rs");
+    t.compile_fail("tests/fail/shader_uniforms_tuple_struct.rs");
+    t.compile_fail("tests/fail/shader_uniforms_unknown_option.rs");
 }
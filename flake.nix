@@ -37,6 +37,9 @@
 
           shellHook = ''
             export LD_LIBRARY_PATH=${pkgs.mesa}/lib:${pkgs.libGL}/lib:${pkgs.libGLU}/lib:$LD_LIBRARY_PATH
+            # llvmpipe only advertises OpenGL 4.5, needed by the headless tests
+            export MESA_GL_VERSION_OVERRIDE=4.6
+            export MESA_GLSL_VERSION_OVERRIDE=460
           '';
         };
       }